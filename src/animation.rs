@@ -0,0 +1,64 @@
+//! Values that change over the frames of an animation, see
+//! `Scene::set_frame`.
+
+use crate::sampler::hash;
+
+/// How a light's intensity changes from frame to frame.
+#[derive(Clone, Debug)]
+pub enum LightAnimation {
+    /// `(frame, intensity)` keys, in frame order, blended linearly between
+    /// and held before the first and after the last.
+    Keyframes(Vec<(f32, f32)>),
+    /// `base` intensity wavering by up to `amount` times itself in either
+    /// direction, following smooth noise that changes about `speed` times
+    /// per frame. Lights with different `seed`s flicker independently.
+    Flicker {
+        base: f32,
+        amount: f32,
+        speed: f32,
+        seed: u32,
+    },
+}
+
+impl LightAnimation {
+    pub fn intensity_at(&self, frame: f32) -> f32 {
+        match self {
+            LightAnimation::Keyframes(keys) => {
+                let after = keys.iter().position(|&(key_frame, _)| key_frame > frame);
+                match after {
+                    Some(0) => keys[0].1,
+                    Some(i) => {
+                        let (start, from) = keys[i - 1];
+                        let (end, to) = keys[i];
+                        from + (to - from) * (frame - start) / (end - start)
+                    }
+                    None => keys.last().map_or(0.0, |&(_, intensity)| intensity),
+                }
+            }
+            LightAnimation::Flicker {
+                base,
+                amount,
+                speed,
+                seed,
+            } => {
+                let t = frame * speed;
+                // A faster, weaker second octave adds the jitter of a flame.
+                let noise = 0.7 * noise_1d(t, *seed) + 0.3 * noise_1d(t * 3.1, hash(*seed));
+                (base * (1.0 + amount * noise)).max(0.0)
+            }
+        }
+    }
+}
+
+/// Smooth noise in [-1, 1) with a new random value at every integer `t`.
+fn noise_1d(t: f32, seed: u32) -> f32 {
+    let cell = t.floor();
+    let f = t - cell;
+    let value = |i: i32| {
+        let bits = hash(hash(seed) ^ i as u32);
+        (bits >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    };
+    let smooth = f * f * (3.0 - 2.0 * f);
+    let (a, b) = (value(cell as i32), value(cell as i32 + 1));
+    a + (b - a) * smooth
+}
@@ -0,0 +1,411 @@
+//! Participating media between the camera and the surfaces it sees.
+
+use std::{fmt, sync::Arc};
+use vecmath::{vec3_add, vec3_dot, vec3_scale, vec3_sub};
+
+use crate::{
+    axes::Axes,
+    isosurface::{Grid, ScalarField, TransferFunction},
+    sampler::hash,
+    view::Ray,
+    Color, Vecf,
+};
+
+/// What a medium does to the light along a stretch of ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scattering {
+    /// Share of each color channel from beyond the stretch that gets through.
+    pub transmittance: [f32; 3],
+    /// Light the medium adds along the stretch, on a 0-1 scale.
+    pub inscatter: [f32; 3],
+}
+
+impl Scattering {
+    /// No medium at all.
+    pub const CLEAR: Scattering = Scattering {
+        transmittance: [1.0; 3],
+        inscatter: [0.0; 3],
+    };
+
+    /// This stretch seen in front of `behind`.
+    pub fn then(self, behind: Scattering) -> Scattering {
+        let mut combined = self;
+        for i in 0..3 {
+            combined.transmittance[i] *= behind.transmittance[i];
+            combined.inscatter[i] += self.transmittance[i] * behind.inscatter[i];
+        }
+        combined
+    }
+}
+
+/// Exponential height fog: `density` per meter at `base_height`, thinning
+/// by a factor of e every `1 / falloff` meters above it and thickening
+/// below, converted to the scene's unit. Light travelling through it is
+/// swapped for `color`, the haze lit by the sky.
+#[derive(Clone, Copy, Debug)]
+pub struct HeightFog {
+    color: Color,
+    density: f32,
+    falloff: f32,
+    base_height: f32,
+    extinction: [f32; 3],
+    up: Vecf,
+}
+
+impl HeightFog {
+    pub fn new(color: Color, density: f32, falloff: f32) -> HeightFog {
+        HeightFog {
+            color,
+            density,
+            falloff,
+            base_height: 0.0,
+            extinction: [1.0; 3],
+            up: [0.0, 1.0, 0.0],
+        }
+    }
+
+    /// Height at which the fog has its nominal density; 0 by default.
+    pub fn with_base_height(mut self, base_height: f32) -> HeightFog {
+        self.base_height = base_height;
+        self
+    }
+
+    /// Scales the density per color channel for aerial perspective, e.g.
+    /// `[0.6, 0.8, 1.0]` fades distant surfaces towards the fog's blue
+    /// before its red.
+    pub fn with_extinction(mut self, extinction: [f32; 3]) -> HeightFog {
+        self.extinction = extinction;
+        self
+    }
+
+    /// Measures height along the up axis of `axes`, the convention passed
+    /// to `View::with_axes`; along y by default.
+    pub fn with_axes(mut self, axes: Axes) -> HeightFog {
+        self.up = axes.up_vector();
+        self
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// The same fog with density and falloff per unit `meters` long.
+    pub(crate) fn in_units(mut self, meters: f32) -> HeightFog {
+        self.density *= meters;
+        self.falloff *= meters;
+        self
+    }
+
+    /// Share of each color channel that survives `distance` along `ray`,
+    /// which may be infinite.
+    pub fn transmittance(&self, ray: &Ray, distance: f32) -> [f32; 3] {
+        let depth = self.optical_depth(ray.origin, ray.direction, distance);
+        self.extinction.map(|e| (-depth * e).exp())
+    }
+
+    pub fn scattering(&self, ray: &Ray, distance: f32) -> Scattering {
+        let transmittance = self.transmittance(ray, distance);
+        let mut inscatter = [0.0; 3];
+        for i in 0..3 {
+            inscatter[i] = (1.0 - transmittance[i]) * self.color.0[i] as f32 / 255.0;
+        }
+        Scattering {
+            transmittance,
+            inscatter,
+        }
+    }
+
+    /// Integral of the density over `distance` from `origin` along the unit
+    /// vector `direction`.
+    fn optical_depth(&self, origin: Vecf, direction: Vecf, distance: f32) -> f32 {
+        let height = vec3_dot(origin, self.up) - self.base_height;
+        let start_density = self.density * (-self.falloff * height).exp();
+        let k = self.falloff * vec3_dot(direction, self.up);
+        // Level rays see constant density; the closed form divides by zero.
+        let length = if k.abs() < 1e-5 {
+            distance
+        } else {
+            (1.0 - (-k * distance).exp()) / k
+        };
+        start_density * length
+    }
+}
+
+/// A slab of procedural cloud between the heights `bottom` and `top`,
+/// ray marched through value noise. Lit from above: `color` at the top,
+/// darkening towards `shadow` at the base.
+#[derive(Clone, Copy, Debug)]
+pub struct CloudLayer {
+    bottom: f32,
+    top: f32,
+    color: Color,
+    shadow: Color,
+    density: f32,
+    coverage: f32,
+    scale: f32,
+    steps: u32,
+    max_distance: f32,
+    up: Vecf,
+}
+
+impl CloudLayer {
+    pub fn new(bottom: f32, top: f32, color: Color, shadow: Color) -> CloudLayer {
+        CloudLayer {
+            bottom,
+            top,
+            color,
+            shadow,
+            density: 1.0,
+            coverage: 0.5,
+            scale: 0.1,
+            steps: 32,
+            max_distance: 200.0,
+            up: [0.0, 1.0, 0.0],
+        }
+    }
+
+    /// Extinction per unit length inside the thickest cloud; 1 by default.
+    pub fn with_density(mut self, density: f32) -> CloudLayer {
+        self.density = density;
+        self
+    }
+
+    /// Share of the sky covered, from 0 (clear) to 1 (overcast); 0.5 by
+    /// default.
+    pub fn with_coverage(mut self, coverage: f32) -> CloudLayer {
+        self.coverage = coverage;
+        self
+    }
+
+    /// Noise frequency, roughly one over the size of a cloud; 0.1 by
+    /// default.
+    pub fn with_scale(mut self, scale: f32) -> CloudLayer {
+        self.scale = scale;
+        self
+    }
+
+    /// Samples taken along each ray's path through the slab, and how far
+    /// the path is followed at most, for rays running nearly level inside
+    /// it. 32 and 200 by default.
+    pub fn with_quality(mut self, steps: u32, max_distance: f32) -> CloudLayer {
+        self.steps = steps;
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Measures `bottom` and `top` along the up axis of `axes`, the
+    /// convention passed to `View::with_axes`; along y by default.
+    pub fn with_axes(mut self, axes: Axes) -> CloudLayer {
+        self.up = axes.up_vector();
+        self
+    }
+
+    pub fn scattering(&self, ray: &Ray, distance: f32) -> Scattering {
+        let (start, end) = match self.span(ray, distance) {
+            Some(span) => span,
+            None => return Scattering::CLEAR,
+        };
+        let step = (end - start) / self.steps.max(1) as f32;
+        let mut transmittance = 1.0;
+        let mut inscatter = [0.0; 3];
+        for i in 0..self.steps {
+            let t = start + (i as f32 + 0.5) * step;
+            let point = vec3_add(ray.origin, vec3_scale(ray.direction, t));
+            let density = self.density_at(point);
+            if density <= 0.0 {
+                continue;
+            }
+            let absorbed = 1.0 - (-density * step).exp();
+            let height = self.height(point).clamp(0.0, 1.0);
+            for (c, light) in inscatter.iter_mut().enumerate() {
+                let lit = self.shadow.0[c] as f32
+                    + (self.color.0[c] as f32 - self.shadow.0[c] as f32) * height;
+                *light += transmittance * absorbed * lit / 255.0;
+            }
+            transmittance *= 1.0 - absorbed;
+        }
+        Scattering {
+            transmittance: [transmittance; 3],
+            inscatter,
+        }
+    }
+
+    /// Distances along `ray` between which it is inside the slab, cut off at
+    /// `distance` and `max_distance`.
+    fn span(&self, ray: &Ray, distance: f32) -> Option<(f32, f32)> {
+        let origin = vec3_dot(ray.origin, self.up);
+        let direction = vec3_dot(ray.direction, self.up);
+        let (start, end) = if direction.abs() < 1e-6 {
+            if origin < self.bottom || origin > self.top {
+                return None;
+            }
+            (0.0, f32::INFINITY)
+        } else {
+            let to_bottom = (self.bottom - origin) / direction;
+            let to_top = (self.top - origin) / direction;
+            (to_bottom.min(to_top).max(0.0), to_bottom.max(to_top))
+        };
+        let end = end.min(distance).min(start + self.max_distance);
+        if end <= start {
+            None
+        } else {
+            Some((start, end))
+        }
+    }
+
+    /// Height of `point` within the slab, 0 at `bottom` and 1 at `top`.
+    fn height(&self, point: Vecf) -> f32 {
+        (vec3_dot(point, self.up) - self.bottom) / (self.top - self.bottom)
+    }
+
+    fn density_at(&self, point: Vecf) -> f32 {
+        let height = self.height(point);
+        // Rounds the slab's base and top off so clouds do not end in flat cuts.
+        let profile = (4.0 * height * (1.0 - height)).clamp(0.0, 1.0);
+        let noise = fbm(vec3_scale(point, self.scale));
+        ((noise * profile - (1.0 - self.coverage)) / self.coverage.max(1e-3)).max(0.0)
+            * self.density
+    }
+}
+
+/// A scalar field within a box, such as a CT scan or a simulation's
+/// density grid, rendered by emission-absorption ray marching: every point
+/// glows with the color its value maps to through a `TransferFunction`,
+/// and hides what is behind it by the mapped opacity.
+#[derive(Clone)]
+pub struct Volume {
+    field: Arc<dyn ScalarField>,
+    transfer: TransferFunction,
+    bounds: [Vecf; 2],
+    steps: u32,
+}
+
+impl Volume {
+    /// `field` between the corners `min` and `max`.
+    pub fn new<F: ScalarField + 'static>(
+        field: F,
+        transfer: TransferFunction,
+        min: Vecf,
+        max: Vecf,
+    ) -> Volume {
+        Volume {
+            field: Arc::new(field),
+            transfer,
+            bounds: [min, max],
+            steps: 64,
+        }
+    }
+
+    /// The values of `grid`, within the grid's box.
+    pub fn from_grid(grid: Grid, transfer: TransferFunction) -> Volume {
+        let [min, max] = grid.bounds();
+        Volume::new(grid, transfer, min, max)
+    }
+
+    /// Samples taken along each ray's path through the box; 64 by default.
+    pub fn with_steps(mut self, steps: u32) -> Volume {
+        self.steps = steps;
+        self
+    }
+
+    pub fn scattering(&self, ray: &Ray, distance: f32) -> Scattering {
+        let (start, end) = match self.span(ray, distance) {
+            Some(span) => span,
+            None => return Scattering::CLEAR,
+        };
+        let step = (end - start) / self.steps.max(1) as f32;
+        let mut transmittance = 1.0;
+        let mut inscatter = [0.0; 3];
+        for i in 0..self.steps {
+            let t = start + (i as f32 + 0.5) * step;
+            let value = self
+                .field
+                .value(vec3_add(ray.origin, vec3_scale(ray.direction, t)));
+            let opacity = self.transfer.opacity_at(value);
+            if opacity <= 0.0 {
+                continue;
+            }
+            let absorbed = 1.0 - (-opacity * step).exp();
+            let color = self.transfer.color_at(value);
+            for (c, light) in inscatter.iter_mut().enumerate() {
+                *light += transmittance * absorbed * color.0[c] as f32 / 255.0;
+            }
+            transmittance *= 1.0 - absorbed;
+        }
+        Scattering {
+            transmittance: [transmittance; 3],
+            inscatter,
+        }
+    }
+
+    /// Distances along `ray` between which it is inside the box, cut off
+    /// at `distance`.
+    fn span(&self, ray: &Ray, distance: f32) -> Option<(f32, f32)> {
+        let mut start = 0.0f32;
+        let mut end = distance;
+        for axis in 0..3 {
+            let inv = 1.0 / ray.direction[axis];
+            let t0 = (self.bounds[0][axis] - ray.origin[axis]) * inv;
+            let t1 = (self.bounds[1][axis] - ray.origin[axis]) * inv;
+            start = start.max(t0.min(t1));
+            end = end.min(t0.max(t1));
+        }
+        // Unbounded boxes would spread the steps over an infinite span.
+        if start < end && end.is_finite() {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Volume")
+            .field("transfer", &self.transfer)
+            .field("bounds", &self.bounds)
+            .field("steps", &self.steps)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Four octaves of value noise, in [0, 1).
+fn fbm(point: Vecf) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut point = point;
+    for _ in 0..4 {
+        sum += amplitude * value_noise(point);
+        point = vec3_scale(point, 2.0);
+        amplitude *= 0.5;
+    }
+    sum / 0.9375
+}
+
+/// Smoothly interpolated random values on the integer lattice, in [0, 1).
+fn value_noise(point: Vecf) -> f32 {
+    let cell = point.map(|c| c.floor());
+    let fraction = vec3_sub(point, cell);
+    let smooth = fraction.map(|f| f * f * (3.0 - 2.0 * f));
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        // Wrapping keeps far-off points, whose cells saturate, from overflowing.
+        let [x, y, z] = [
+            (cell[0] as i32).wrapping_add(dx),
+            (cell[1] as i32).wrapping_add(dy),
+            (cell[2] as i32).wrapping_add(dz),
+        ];
+        let bits = hash(hash(hash(x as u32) ^ y as u32) ^ z as u32);
+        (bits >> 8) as f32 / (1u32 << 24) as f32
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let mut layers = [0.0; 2];
+    for (dz, layer) in layers.iter_mut().enumerate() {
+        let dz = dz as i32;
+        *layer = lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), smooth[0]),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), smooth[0]),
+            smooth[1],
+        );
+    }
+    lerp(layers[0], layers[1], smooth[2])
+}
@@ -0,0 +1,98 @@
+//! Conventions for which world axis points up and which way the others
+//! turn, since modelling tools disagree: glTF and Maya are Y-up and
+//! right-handed, Blender and 3ds Max Z-up and right-handed. Pick one for a
+//! scene and pass it to both `View::with_axes` and `Mesh::with_axes`, so
+//! assets from any tool line up without juggling coordinates by hand.
+//! Height fog and cloud layers take it with `HeightFog::with_axes` and
+//! `CloudLayer::with_axes`.
+
+use crate::Vecf;
+
+/// The world axis pointing up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Up {
+    #[default]
+    Y,
+    Z,
+}
+
+/// Which hand the axes follow: with its thumb along +x and index finger
+/// along +y, the middle finger points along +z.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Left,
+    Right,
+}
+
+/// A world axis convention. The default, Y-up and left-handed, is the one
+/// scenes have always been built in: +x right, +y up and +z away from a
+/// camera looking along +z.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Axes {
+    pub up: Up,
+    pub handedness: Handedness,
+}
+
+impl Axes {
+    /// The default convention.
+    pub const Y_UP_LEFT: Axes = Axes::new(Up::Y, Handedness::Left);
+    /// glTF's and Maya's convention.
+    pub const Y_UP_RIGHT: Axes = Axes::new(Up::Y, Handedness::Right);
+    /// Blender's and 3ds Max's convention.
+    pub const Z_UP_RIGHT: Axes = Axes::new(Up::Z, Handedness::Right);
+    /// Unreal Engine's convention.
+    pub const Z_UP_LEFT: Axes = Axes::new(Up::Z, Handedness::Left);
+
+    pub const fn new(up: Up, handedness: Handedness) -> Axes {
+        Axes { up, handedness }
+    }
+
+    /// Unit vector pointing up.
+    pub fn up_vector(self) -> Vecf {
+        self.out_of_default([0.0, 1.0, 0.0])
+    }
+
+    /// Unit vector pointing away from a camera that looks level with +x
+    /// to its right.
+    pub fn forward_vector(self) -> Vecf {
+        self.out_of_default([0.0, 0.0, 1.0])
+    }
+
+    /// `vector`, given in this convention, in the `target` convention:
+    /// the same place or direction with its coordinates swapped and
+    /// negated to match.
+    pub fn convert(self, vector: Vecf, target: Axes) -> Vecf {
+        target.out_of_default(self.to_default(vector))
+    }
+
+    /// Whether converting to `target` mirrors the world, which reverses
+    /// the winding of triangles.
+    pub fn mirrors(self, target: Axes) -> bool {
+        self.handedness != target.handedness
+    }
+
+    /// Whether the axis that is neither x nor up points towards a camera
+    /// looking level with +x to its right, rather than away from it.
+    fn depth_flipped(self) -> bool {
+        // Swapping y and z alone mirrors the world.
+        (self.handedness == Handedness::Right) != (self.up == Up::Z)
+    }
+
+    fn to_default(self, [x, y, z]: Vecf) -> Vecf {
+        let (up, depth) = match self.up {
+            Up::Y => (y, z),
+            Up::Z => (z, y),
+        };
+        let depth = if self.depth_flipped() { -depth } else { depth };
+        [x, up, depth]
+    }
+
+    fn out_of_default(self, [x, up, depth]: Vecf) -> Vecf {
+        let depth = if self.depth_flipped() { -depth } else { depth };
+        match self.up {
+            Up::Y => [x, up, depth],
+            Up::Z => [x, depth, up],
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Baking an object's color over its texture coordinates into an image,
+//! so an expensive procedural texture can be swapped for a cheap
+//! `ImageTexture`, e.g. for the frames of an animation.
+
+use image::{Rgb, RgbImage};
+
+use crate::scene::{Object, Scene};
+
+/// Texels past the edges of the surface filled from their neighbours, so
+/// bilinear filtering there does not blend in black.
+const PADDING: u32 = 2;
+
+/// The color of `object` at the center of each texel of a `width` by
+/// `height` image over its texture coordinates, 0 to 1 both ways, laid out
+/// as `ImageTexture` reads it, with v growing up from the bottom row. As
+/// with rendering, `scene` is there for textures that look at the rest of
+/// the scene. Texels no part of the surface maps to are filled from their
+/// neighbours up to `PADDING` texels out and black beyond; objects
+/// without texture coordinates bake all black.
+pub fn bake(object: &dyn Object, scene: &Scene, width: u32, height: u32) -> RgbImage {
+    let mut image = RgbImage::new(width, height);
+    let mut covered = vec![false; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let uv = [
+                (x as f32 + 0.5) / width as f32,
+                1.0 - (y as f32 + 0.5) / height as f32,
+            ];
+            if let Some(hit) = object.hit_at_uv(uv) {
+                image.put_pixel(x, y, object.get_color_at(&hit, scene));
+                covered[(y * width + x) as usize] = true;
+            }
+        }
+    }
+    for _ in 0..PADDING {
+        pad(&mut image, &mut covered);
+    }
+    image
+}
+
+/// Fills each texel next to a covered one with the mean of its covered
+/// neighbours, marking it covered.
+fn pad(image: &mut RgbImage, covered: &mut [bool]) {
+    let (width, height) = image.dimensions();
+    let before = covered.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            if before[(y * width + x) as usize] {
+                continue;
+            }
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+            for (dx, dy) in neighbours {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                    continue;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                if before[(ny * width + nx) as usize] {
+                    for (s, c) in sum.iter_mut().zip(image.get_pixel(nx, ny).0) {
+                        *s += c as u32;
+                    }
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                image.put_pixel(x, y, Rgb(sum.map(|s| (s / count) as u8)));
+                covered[(y * width + x) as usize] = true;
+            }
+        }
+    }
+}
@@ -1,5 +1,35 @@
 use image::Rgb;
-use raytracer::{scene::*, view::*};
+use raytracer::{
+    bake::bake,
+    dither::Dither,
+    exr::ExrImage,
+    integrator::{AmbientOcclusion, Normals, Whitted},
+    material::{Material, Scatter},
+    metadata::RenderInfo,
+    photon::PhotonMap,
+    post::{BurnIn, LensFlare},
+    report::RenderReport,
+    sampler::Rng,
+    scene::*,
+    texture::{Checker, Filter, ImageTexture, Marble},
+    tonemap::{AutoExposure, Tonemap},
+    view::*,
+};
+use std::{sync::Arc, time::Instant};
+
+/// A material that only gives off light, of the given color, as a custom
+/// `Material` for `--glow`.
+struct Glow([f32; 3]);
+
+impl Material for Glow {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut Rng) -> Scatter {
+        Scatter::Absorb
+    }
+
+    fn get_emission(&self) -> [f32; 3] {
+        self.0
+    }
+}
 
 fn main() {
     let view = View::new(
@@ -12,66 +42,361 @@ fn main() {
         Rgb([50, 100, 200]),
         1e-3,
     );
+    // Clay render for checking geometry: ambient occlusion instead of lights.
+    let view = if std::env::args().any(|arg| arg == "--ao") {
+        view.with_integrator(AmbientOcclusion::new(1.0, 16))
+            .with_samples(4)
+    } else {
+        view
+    };
+    let view = view.with_tonemap(if std::env::args().any(|arg| arg == "--aces") {
+        Tonemap::Aces
+    } else if std::env::args().any(|arg| arg == "--reinhard") {
+        Tonemap::Reinhard
+    } else {
+        Tonemap::Clip
+    });
+    let view = if std::env::args().any(|arg| arg == "--srgb") {
+        view.with_srgb()
+    } else {
+        view
+    };
+    let view = if std::env::args().any(|arg| arg == "--fresnel") {
+        view.with_fresnel()
+    } else {
+        view
+    };
+    let view = if std::env::args().any(|arg| arg == "--dither") {
+        view.with_dither(Dither::BlueNoise)
+    } else {
+        view
+    };
+    let view = view.with_cone_tracing(std::env::args().any(|arg| arg == "--cone"));
+    let view = if std::env::args().any(|arg| arg == "--glass-shadows") {
+        view.with_transparent_shadows()
+    } else {
+        view
+    };
+    // Dispersion follows one color channel per sample, so the prism's
+    // fringes need a few of each to smooth out.
+    let prism = std::env::args().any(|arg| arg == "--prism");
+    let view = if prism { view.with_samples(12) } else { view };
+    #[cfg(feature = "spectral")]
+    let view = if std::env::args().any(|arg| arg == "--spectral") {
+        view.with_spectral()
+    } else {
+        view
+    };
     let mut scene = Scene::default();
-    scene.addLight(Light::new([0.0, 1.0, 7.0], 20.0));
-    scene.addLight(Light::new([2.0, 0.5, 2.0], 40.0));
+    scene.add_light(Light::new([0.0, 1.0, 7.0], 20.0));
+    scene.add_light(Light::new([2.0, 0.5, 2.0], 40.0));
 
-    scene.addObject(Sphere::new(
+    scene.add_object(Sphere::new(
         [0.0, -0.3, 3.0],
         Rgb([255, 0, 0]),
         0.2,
         0.9,
         0.0,
     ));
-    scene.addObject(Sphere::new(
-        [1.0, -0.3, 5.0],
-        Rgb([0, 0, 255]),
-        0.3,
-        0.9,
-        0.3,
-    ));
-    scene.addObject(Plane::new(
+    let shininess = if std::env::args().any(|arg| arg == "--highlights") {
+        40.0
+    } else {
+        0.0
+    };
+    let sphere =
+        Sphere::new([1.0, -0.3, 5.0], Rgb([0, 0, 255]), 0.3, 0.9, 0.3).with_shininess(shininess);
+    let sphere = if std::env::args().any(|arg| arg == "--marble") {
+        sphere.with_texture(Marble::new(Rgb([235, 230, 220]), Rgb([60, 60, 80]), 0.15))
+    } else {
+        sphere
+    };
+    // The marble baked once and looked up from then on.
+    let sphere = if std::env::args().any(|arg| arg == "--bake") {
+        let baked = bake(&sphere, &scene, 1024, 512);
+        sphere.with_texture(ImageTexture::new(baked).with_filter(Filter::Bilinear))
+    } else {
+        sphere
+    };
+    scene.add_object(sphere);
+    let floor = Plane::new(
         Rgb([0, 255, 0]),
         [0.0, -1.0, 0.0],
         [0.0, -1.0, 0.0],
         0.6,
         0.0,
-    ));
-    scene.addObject(Plane::new(
+    );
+    // The classic ray tracer floor, which also shows texture coordinates.
+    let floor = if std::env::args().any(|arg| arg == "--checker") {
+        floor.with_texture(Checker::new(Rgb([0, 255, 0]), Rgb([255; 3]), 0.5))
+    } else {
+        floor
+    };
+    scene.add_object(floor);
+    scene.add_object(Plane::new(
         Rgb([0, 0, 255]),
         [-1.0, 0.0, 0.0],
         [-1.0, 0.0, 0.0],
         0.6,
         0.0,
     ));
-    scene.addObject(Plane::new(
+    scene.add_object(Plane::new(
         Rgb([255; 3]),
         [0.0, 0.0, 1.0],
         [0.0, 0.0, 8.0],
         0.05,
         1.0,
     ));
-    scene.addObject(Plane::new(
+    scene.add_object(Plane::new(
         Rgb([255; 3]),
         [0.0, 0.0, -1.0],
         [0.0, 0.0, -3.0],
         0.05,
         1.0,
     ));
-    scene.addObject(Plane::new(
+    scene.add_object(Plane::new(
         Rgb([100, 0, 100]),
         [1.0, 0.0, 0.0],
         [3.0, 0.0, 0.0],
         0.6,
         0.0,
     ));
-    scene.addObject(Plane::new(
+    scene.add_object(Plane::new(
         Rgb([255; 3]),
         [0.0, 1.0, 0.0],
         [0.0, 2.0, 0.0],
         0.6,
         0.0,
     ));
-    let img = view.render(&scene);
-    img.save("trace.png").unwrap();
+    if std::env::args().any(|arg| arg == "--glass") {
+        scene.add_object(
+            Sphere::new([-0.5, -0.55, 2.5], Rgb([255; 3]), 0.45, 0.0, 0.0)
+                .with_refraction(0.9, 1.5),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--tinted-glass") {
+        scene.add_object(
+            Sphere::new([0.6, -0.6, 2.8], Rgb([255; 3]), 0.4, 0.0, 0.0).with_dielectric(
+                1.5,
+                Rgb([90, 200, 120]),
+                0.5,
+            ),
+        );
+        // An air bubble in the glass.
+        scene.add_object(
+            Sphere::new([0.7, -0.5, 2.6], Rgb([255; 3]), 0.12, 0.0, 0.0).with_dielectric(
+                1.0,
+                Rgb([255; 3]),
+                1.0,
+            ),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--frosted-glass") {
+        scene.add_object(
+            Sphere::new([-0.5, -0.55, 2.5], Rgb([255; 3]), 0.45, 0.0, 0.05)
+                .with_refraction(0.95, 1.5)
+                .with_roughness(0.15),
+        );
+    }
+    if prism {
+        // Far more dispersive than real glass, so the fringes show.
+        scene.add_object(
+            Sphere::new([0.5, -0.6, 2.4], Rgb([255; 3]), 0.4, 0.0, 0.0)
+                .with_dielectric(1.5, Rgb([255; 3]), 1.0)
+                .with_dispersion([1.44, 1.5, 1.58]),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--metal") {
+        scene.add_object(
+            Sphere::new([-0.4, -0.7, 2.2], Rgb([255, 190, 80]), 0.3, 0.0, 0.0).with_metal(0.2),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--pbr") {
+        // Gold, orange plastic and white plastic, from polished to
+        // rough.
+        for (x, color, metallic, roughness) in [
+            (-0.5, Rgb([255, 195, 85]), 1.0, 0.3),
+            (0.0, Rgb([240, 120, 80]), 0.0, 0.2),
+            (0.5, Rgb([220; 3]), 0.0, 0.6),
+        ] {
+            scene.add_object(
+                Sphere::new([x, -0.8, 2.2], color, 0.2, 0.0, 0.0).with_pbr(metallic, roughness),
+            );
+        }
+    }
+    if std::env::args().any(|arg| arg == "--clearcoat") {
+        // Red car paint, a rough metal under a smooth coat, and
+        // lacquered wood, a matte brown under a slightly hazy one.
+        scene.add_object(
+            Sphere::new([-0.3, -0.75, 2.2], Rgb([200, 20, 30]), 0.25, 0.0, 0.0)
+                .with_pbr(1.0, 0.4)
+                .with_glossy_samples(16)
+                .with_clearcoat(1.0, 0.0),
+        );
+        scene.add_object(
+            Sphere::new([0.3, -0.75, 2.2], Rgb([130, 80, 40]), 0.25, 0.9, 0.0)
+                .with_clearcoat(1.0, 0.1),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--water") {
+        scene.add_object(
+            Plane::new(Rgb([255; 3]), [0.0, -1.0, 0.0], [0.0, -0.6, 0.0], 0.0, 0.0)
+                .with_refraction(1.0, 1.33)
+                .with_absorption(Rgb([40, 150, 190]), 1.0),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--glow") {
+        scene.add_object(
+            Sphere::new([0.9, -0.85, 3.5], Rgb([255; 3]), 0.15, 0.0, 0.0)
+                .with_material(Arc::new(Glow([1.0, 0.7, 0.3]))),
+        );
+    }
+    #[cfg(feature = "script")]
+    let mut scene = match std::env::args().skip_while(|arg| arg != "--script").nth(1) {
+        Some(path) => raytracer::script::load(path).unwrap(),
+        None => scene,
+    };
+    let mut photon_time = None;
+    let caustics_only = std::env::args().any(|arg| arg == "--caustics-only");
+    let photon_density = std::env::args().any(|arg| arg == "--photon-density");
+    if caustics_only || photon_density || std::env::args().any(|arg| arg == "--caustics") {
+        let photons = std::env::args()
+            .skip_while(|arg| arg != "--photons")
+            .nth(1)
+            .map_or(1_000_000, |count| count.parse().unwrap());
+        let start = Instant::now();
+        let caustics = PhotonMap::build(&scene, photons, 0).with_caustics_only(caustics_only);
+        photon_time = Some(start.elapsed());
+        scene.set_caustics(caustics);
+    }
+    if std::env::args().any(|arg| arg == "--stats") {
+        eprintln!("{}", scene.summary());
+    }
+    #[cfg(feature = "preview")]
+    {
+        if std::env::args().any(|arg| arg == "--preview") {
+            raytracer::preview::preview(view, &scene).unwrap();
+            return;
+        }
+    }
+    // `--inspect 960,540` prints what went into that pixel instead of
+    // rendering.
+    if let Some(pixel) = std::env::args().skip_while(|arg| arg != "--inspect").nth(1) {
+        let (x, y) = pixel.split_once(',').unwrap();
+        let trace = view.debug_pixel(&scene, x.parse().unwrap(), y.parse().unwrap());
+        println!("{}", trace);
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--bracket") {
+        let images = view.render_bracketed(&scene, &BRACKET_STOPS);
+        for (stop, img) in BRACKET_STOPS.iter().zip(images) {
+            img.save(format!("trace_{:+}ev.png", stop)).unwrap();
+        }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--passes") {
+        let ao = AmbientOcclusion::new(1.0, 16);
+        let outputs = view.render_outputs(&scene, &[&Whitted, &ao, &Normals]);
+        for (name, output) in ["trace.png", "trace_ao.png", "trace_normals.png"]
+            .iter()
+            .zip(outputs)
+        {
+            view.develop(&output).save(name).unwrap();
+        }
+        return;
+    }
+    if photon_density {
+        view.render_photon_density(&scene)
+            .save("trace_photons.png")
+            .unwrap();
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--layout") {
+        view.render_layout(&scene).save("trace_layout.png").unwrap();
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--sheet") {
+        view.render_sheet(&scene, [1.0, 0.0, 4.0], 8.0)
+            .save("trace_sheet.png")
+            .unwrap();
+        return;
+    }
+    let start = Instant::now();
+    let framebuffer = view.render_hdr_with_progress(&scene, |done, total| {
+        let elapsed = start.elapsed().as_secs_f32();
+        let remaining = elapsed / done as f32 * (total - done) as f32;
+        eprint!(
+            "\rrendering {:3}% ({}/{} tiles), {:.0}s left ",
+            done * 100 / total,
+            done,
+            total,
+            remaining
+        );
+    });
+    eprintln!();
+    let render_time = start.elapsed();
+    let view = if std::env::args().any(|arg| arg == "--auto-exposure") {
+        let ev = AutoExposure::new()
+            .with_range(-4.0, 4.0)
+            .exposure_value(&framebuffer);
+        view.with_exposure_value(ev)
+    } else {
+        view
+    };
+    let mut img = view.develop(&framebuffer);
+    for (warning, count) in scene.warnings.iter() {
+        eprintln!("warning: {} {}", count, warning);
+    }
+    if std::env::args().any(|arg| arg == "--flares") {
+        LensFlare::new().apply(&view, &scene, &mut img);
+    }
+    if std::env::args().any(|arg| arg == "--burn-in") {
+        BurnIn::new()
+            .with_scene_name("default")
+            .with_view(&view)
+            .with_render_time(start.elapsed())
+            .with_scale(2)
+            .apply(&mut img);
+    }
+    RenderInfo::new(&view, &scene)
+        .with_entry(
+            "Render time",
+            format!("{:.2}s", start.elapsed().as_secs_f32()),
+        )
+        .save_png(&img, "trace.png")
+        .unwrap();
+    let png16 = std::env::args().any(|arg| arg == "--png16");
+    if png16 {
+        framebuffer.save_png16("trace_16bit.png").unwrap();
+    }
+    let exr = std::env::args().any(|arg| arg == "--exr");
+    if exr {
+        ExrImage::new(&framebuffer)
+            .with_depth(view.render_depth(&scene))
+            .with_normals(&view.render_normals(&scene))
+            .save("trace.exr")
+            .unwrap();
+    }
+    let hdr = std::env::args().any(|arg| arg == "--hdr");
+    if hdr {
+        framebuffer.save_hdr("trace.hdr").unwrap();
+    }
+    if std::env::args().any(|arg| arg == "--report") {
+        let mut report = RenderReport::new(&view, &scene)
+            .with_setting("Scene", "default")
+            .with_timing("render", render_time)
+            .with_output("beauty", "trace.png");
+        if hdr {
+            report = report.with_output("hdr", "trace.hdr");
+        }
+        if exr {
+            report = report.with_output("exr", "trace.exr");
+        }
+        if png16 {
+            report = report.with_output("png16", "trace_16bit.png");
+        }
+        if let Some(time) = photon_time {
+            report = report.with_timing("photons", time);
+        }
+        report.save("trace.json").unwrap();
+    }
 }
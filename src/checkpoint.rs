@@ -0,0 +1,172 @@
+//! Saved state of an unfinished render, so a long render can be stopped
+//! and picked up again later, even in another process.
+
+use crate::framebuffer::Framebuffer;
+use image::RgbImage;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// Identifies checkpoint files and their layout version.
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "could not access checkpoint: {}", err),
+            CheckpointError::Invalid(reason) => write!(f, "invalid checkpoint: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> CheckpointError {
+        CheckpointError::Io(err)
+    }
+}
+
+/// Per-pixel sample counts and running sums of unclamped color, on the
+/// 0-255 scale `View::render_pixel` rounds down from. Filled in sample by
+/// sample by `View::render_checkpointed`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    width: u32,
+    height: u32,
+    samples: Vec<u32>,
+    sums: Vec<[f32; 3]>,
+}
+
+impl Checkpoint {
+    /// An empty checkpoint for a `width` by `height` image. Panics if the
+    /// image has more pixels than `read_from` would accept back.
+    pub fn new(width: u32, height: u32) -> Checkpoint {
+        let len = width
+            .checked_mul(height)
+            .expect("checkpoint image is too large") as usize;
+        Checkpoint {
+            width,
+            height,
+            samples: vec![0; len],
+            sums: vec![[0.0; 3]; len],
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    pub(crate) fn add_sample(&mut self, x: u32, y: u32, color: [f32; 3]) {
+        let i = self.index(x, y);
+        self.samples[i] += 1;
+        for (sum, c) in self.sums[i].iter_mut().zip(color) {
+            *sum += c;
+        }
+    }
+
+    /// The image so far, averaging each pixel's samples; pixels without any
+    /// are black.
+    pub fn image(&self) -> RgbImage {
+        self.framebuffer().to_rgb8()
+    }
+
+    /// Like `image`, unclamped.
+    pub fn framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                if self.samples[i] > 0 {
+                    let n = self.samples[i] as f32;
+                    framebuffer.put_pixel(x, y, self.sums[i].map(|c| c / n));
+                }
+            }
+        }
+        framebuffer
+    }
+
+    /// Writes the checkpoint in a little-endian binary layout.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), CheckpointError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        for (samples, sum) in self.samples.iter().zip(&self.sums) {
+            writer.write_all(&samples.to_le_bytes())?;
+            for c in sum {
+                writer.write_all(&c.to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint written by `write_to`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Checkpoint, CheckpointError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::Invalid("not a checkpoint file"));
+        }
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let len = width
+            .checked_mul(height)
+            .ok_or(CheckpointError::Invalid("image is too large"))?;
+        // Grown as pixels arrive rather than sized up front, so a corrupt
+        // header runs out of data instead of memory.
+        let mut samples = Vec::new();
+        let mut sums = Vec::new();
+        for _ in 0..len {
+            samples.push(read_u32(&mut reader)?);
+            let mut sum = [0.0; 3];
+            for c in sum.iter_mut() {
+                *c = f32::from_bits(read_u32(&mut reader)?);
+            }
+            sums.push(sum);
+        }
+        Ok(Checkpoint {
+            width,
+            height,
+            samples,
+            sums,
+        })
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        self.write_to(BufWriter::new(fs::File::create(path)?))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Checkpoint, CheckpointError> {
+        Checkpoint::read_from(BufReader::new(fs::File::open(path)?))
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, CheckpointError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
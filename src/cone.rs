@@ -0,0 +1,99 @@
+//! Cone tracing against the distance functions of the scene's spheres and
+//! planes, for soft shadows and ambient occlusion from one march instead
+//! of many rays. Approximate: a surface darkens a cone by how close it
+//! comes to the cone's axis, as if it covered the cone up to there.
+//! Enabled with `View::with_cone_tracing`.
+
+use vecmath::{vec3_add, vec3_len, vec3_scale, vec3_sub};
+
+use crate::{
+    scene::{Object, Shape},
+    Vecf,
+};
+
+/// Steps a cone is marched in at most; cones taking more count as open
+/// past the last one.
+const MAX_STEPS: u32 = 64;
+
+/// Distances along the normal ambient occlusion is estimated from.
+const OCCLUSION_STEPS: u32 = 5;
+
+/// The shapes of some objects, each seen from one side: the side a
+/// starting point lies on, so surfaces between it and a light always count
+/// as crossed whichever way their normals face.
+pub(crate) struct DistanceField {
+    shapes: Vec<(Shape, f32)>,
+}
+
+impl DistanceField {
+    /// The shapes of `objects` that have one, seen from `origin`.
+    pub(crate) fn around<'a>(
+        objects: impl Iterator<Item = &'a dyn Object>,
+        origin: Vecf,
+    ) -> DistanceField {
+        let shapes = objects
+            .filter_map(|object| object.shape())
+            .map(|shape| (shape, shape.distance(origin).signum()))
+            .collect();
+        DistanceField { shapes }
+    }
+
+    /// Distance from `point` to the nearest shape, negative past one.
+    fn distance(&self, point: Vecf) -> f32 {
+        self.shapes
+            .iter()
+            .map(|(shape, side)| shape.distance(point) * side)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Share of the cone from `origin` to a disk of `radius` around
+    /// `target` that the shapes leave open: 1 where none comes near its
+    /// axis, a half where one grazes it and 0 where one crosses it. The
+    /// march starts `start` from `origin`.
+    pub(crate) fn visibility(&self, origin: Vecf, target: Vecf, radius: f32, start: f32) -> f32 {
+        let to_target = vec3_sub(target, origin);
+        let length = vec3_len(to_target);
+        if self.shapes.is_empty() || length.is_nan() || length <= 0.0 {
+            return 1.0;
+        }
+        let direction = vec3_scale(to_target, 1.0 / length);
+        let spread = radius / length;
+        let min_step = length / MAX_STEPS as f32;
+        let mut open: f32 = 1.0;
+        let mut t = start;
+        for _ in 0..MAX_STEPS {
+            if t >= length {
+                break;
+            }
+            let distance = self.distance(vec3_add(origin, vec3_scale(direction, t)));
+            let width = (spread * t).max(f32::EPSILON);
+            open = open.min(0.5 + 0.5 * distance / width);
+            if open <= 0.0 {
+                return 0.0;
+            }
+            t += distance.max(min_step);
+        }
+        open.min(1.0)
+    }
+
+    /// Share of the hemisphere above `point`, facing `normal`, that the
+    /// shapes leave open within `distance`, from how much nearer than
+    /// their height they come to a few points along the normal.
+    pub(crate) fn occlusion(&self, point: Vecf, normal: Vecf, distance: f32) -> f32 {
+        if self.shapes.is_empty() || !distance.is_finite() || distance <= 0.0 {
+            return 1.0;
+        }
+        let (mut blocked, mut total, mut weight) = (0.0, 0.0, 1.0);
+        for i in 1..=OCCLUSION_STEPS {
+            let height = distance * i as f32 / OCCLUSION_STEPS as f32;
+            let nearest = self.distance(vec3_add(point, vec3_scale(normal, height)));
+            let covered = (height - nearest) / height;
+            if covered > 0.0 {
+                blocked += weight * covered.min(1.0);
+            }
+            total += weight;
+            weight *= 0.5;
+        }
+        1.0 - blocked / total
+    }
+}
@@ -0,0 +1,142 @@
+//! Patterns that vary the rounding of each pixel when a render is
+//! converted to 8 bits, trading the bands smooth gradients such as the sky
+//! and soft shadows show for fine, even noise.
+
+use crate::sampler::Rng;
+use std::sync::OnceLock;
+
+/// Edge length, in pixels, of the tile `Dither::BlueNoise` repeats.
+const BLUE_NOISE_SIZE: usize = 32;
+
+/// Spread, in pixels, of the Gaussian the void-and-cluster method measures
+/// how crowded a pixel's neighborhood is with.
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// Pattern set with `View::with_dither`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dither {
+    /// An 8x8 Bayer matrix: cheap and regular, with a visible cross-hatch
+    /// in flat areas.
+    Ordered,
+    /// A 32x32 blue noise tile, whose noise has no low frequencies and
+    /// so looks like fine grain rather than a pattern.
+    BlueNoise,
+}
+
+impl Dither {
+    /// Amount in (0, 1) added to pixel `(x, y)` before rounding down.
+    /// Averaged over the pattern it is one half, so colors keep their
+    /// brightness.
+    pub fn threshold(self, x: u32, y: u32) -> f32 {
+        match self {
+            Dither::Ordered => (bayer(x % 8, y % 8) as f32 + 0.5) / 64.0,
+            Dither::BlueNoise => {
+                let (x, y) = (x as usize % BLUE_NOISE_SIZE, y as usize % BLUE_NOISE_SIZE);
+                let rank = blue_noise()[y * BLUE_NOISE_SIZE + x];
+                (rank as f32 + 0.5) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f32
+            }
+        }
+    }
+
+    /// `c`, on the 0-255 scale, rounded up or down to 8 bits by the
+    /// pattern at pixel `(x, y)`.
+    pub fn quantize(self, c: f32, x: u32, y: u32) -> u8 {
+        (c + self.threshold(x, y)).floor() as u8
+    }
+}
+
+/// Rank, 0 to 63, of `(x, y)` in the 8x8 Bayer matrix: the bits of `x ^ y`
+/// and `y` interleaved, in reverse order.
+fn bayer(x: u32, y: u32) -> u32 {
+    let xor = x ^ y;
+    (0..3).fold(0, |rank, bit| {
+        (rank << 2) | ((xor >> bit) & 1) << 1 | ((y >> bit) & 1)
+    })
+}
+
+/// Ranks of the blue noise tile, row by row, built once by Ulichney's
+/// void-and-cluster method.
+fn blue_noise() -> &'static [u32] {
+    static TILE: OnceLock<Vec<u32>> = OnceLock::new();
+    TILE.get_or_init(void_and_cluster)
+}
+
+/// Ranks pixels so that every prefix of the ranking is spread as evenly as
+/// possible: starting from a few random pixels, made even by repeatedly
+/// moving the pixel in the tightest cluster to the largest void, pixels
+/// are removed from the tightest cluster and added to the largest void.
+fn void_and_cluster() -> Vec<u32> {
+    const N: usize = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    // Gaussian of the wrapped distance between pixels, so the tile repeats
+    // seamlessly.
+    let kernel: Vec<f32> = (0..N)
+        .map(|i| {
+            let wrap = |d: usize| d.min(BLUE_NOISE_SIZE - d) as f32;
+            let (dx, dy) = (wrap(i % BLUE_NOISE_SIZE), wrap(i / BLUE_NOISE_SIZE));
+            (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+        })
+        .collect();
+    let offset = |from: usize, to: usize| {
+        let dx =
+            (to % BLUE_NOISE_SIZE + BLUE_NOISE_SIZE - from % BLUE_NOISE_SIZE) % BLUE_NOISE_SIZE;
+        let dy =
+            (to / BLUE_NOISE_SIZE + BLUE_NOISE_SIZE - from / BLUE_NOISE_SIZE) % BLUE_NOISE_SIZE;
+        dy * BLUE_NOISE_SIZE + dx
+    };
+    // How crowded each pixel's neighborhood is with set pixels.
+    let toggle = |set: &mut [bool], energy: &mut [f32], pixel: usize| {
+        set[pixel] = !set[pixel];
+        let sign = if set[pixel] { 1.0 } else { -1.0 };
+        for (i, e) in energy.iter_mut().enumerate() {
+            *e += sign * kernel[offset(pixel, i)];
+        }
+    };
+    let tightest_cluster = |set: &[bool], energy: &[f32]| {
+        (0..N)
+            .filter(|&i| set[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |set: &[bool], energy: &[f32]| {
+        (0..N)
+            .filter(|&i| !set[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    let mut set = vec![false; N];
+    let mut energy = vec![0.0; N];
+    let mut rng = Rng::new(0, 0, 0, 0);
+    let mut initial = 0;
+    while initial < N / 10 {
+        let pixel = ((rng.next_f32() * N as f32) as usize).min(N - 1);
+        if !set[pixel] {
+            toggle(&mut set, &mut energy, pixel);
+            initial += 1;
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&set, &energy);
+        toggle(&mut set, &mut energy, cluster);
+        let void = largest_void(&set, &energy);
+        toggle(&mut set, &mut energy, void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; N];
+    let (start_set, start_energy) = (set.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&set, &energy);
+        toggle(&mut set, &mut energy, cluster);
+        ranks[cluster] = rank as u32;
+    }
+    let (mut set, mut energy) = (start_set, start_energy);
+    for rank in initial..N {
+        let void = largest_void(&set, &energy);
+        toggle(&mut set, &mut energy, void);
+        ranks[void] = rank as u32;
+    }
+    ranks
+}
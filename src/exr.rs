@@ -0,0 +1,151 @@
+//! Minimal OpenEXR writer: uncompressed scanline images of 32-bit float
+//! channels, which compositing tools read with their full range.
+
+use crate::{framebuffer::Framebuffer, Vecf};
+use std::io::{self, Write};
+#[cfg(feature = "fs")]
+use std::{fs, io::BufWriter, path::Path};
+
+/// Starts every OpenEXR file.
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Format version 2, single-part scanline image, short names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+/// An image's channels, ready to write as OpenEXR: the color as "R", "G"
+/// and "B", with 1.0 as white, plus any extra channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrImage {
+    width: u32,
+    height: u32,
+    /// Name and values, row by row from the top left, of every channel.
+    channels: Vec<(String, Vec<f32>)>,
+}
+
+impl ExrImage {
+    pub fn new(framebuffer: &Framebuffer) -> ExrImage {
+        let (width, height) = framebuffer.dimensions();
+        let rgb = framebuffer.to_rgb32f();
+        let channel = |i| rgb.pixels().map(|pixel| pixel.0[i]).collect();
+        ExrImage {
+            width,
+            height,
+            channels: vec![
+                ("R".to_string(), channel(0)),
+                ("G".to_string(), channel(1)),
+                ("B".to_string(), channel(2)),
+            ],
+        }
+    }
+
+    /// Adds channel `name`, or replaces one of that name, with one value per
+    /// pixel, row by row from the top left. Names must be at most 31 bytes.
+    pub fn with_channel(mut self, name: &str, values: Vec<f32>) -> ExrImage {
+        assert_eq!(
+            values.len(),
+            (self.width * self.height) as usize,
+            "channel does not match the image size"
+        );
+        assert!(name.len() <= 31, "channel name is too long");
+        self.channels.retain(|(channel, _)| channel != name);
+        self.channels.push((name.to_string(), values));
+        self
+    }
+
+    /// Adds distances to the first hit, e.g. from `View::render_depth`, as
+    /// the "Z" channel compositing tools use for depth.
+    pub fn with_depth(self, depth: Vec<f32>) -> ExrImage {
+        self.with_channel("Z", depth)
+    }
+
+    /// Adds surface normals, e.g. from `View::render_normals`, as the
+    /// "N.X", "N.Y" and "N.Z" channels.
+    pub fn with_normals(self, normals: &[Vecf]) -> ExrImage {
+        let axis = |i: usize| normals.iter().map(|normal| normal[i]).collect();
+        self.with_channel("N.X", axis(0))
+            .with_channel("N.Y", axis(1))
+            .with_channel("N.Z", axis(2))
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // Readers expect the channels in alphabetical order, in the header
+        // and in every scanline.
+        let mut channels: Vec<_> = self.channels.iter().collect();
+        channels.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION);
+        let mut list = Vec::new();
+        for (name, _) in &channels {
+            list.extend_from_slice(name.as_bytes());
+            list.push(0);
+            list.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+            // Not perceptually linear, three reserved bytes, no subsampling.
+            list.extend_from_slice(&[0; 4]);
+            list.extend_from_slice(&1i32.to_le_bytes());
+            list.extend_from_slice(&1i32.to_le_bytes());
+        }
+        list.push(0);
+        attribute(&mut header, "channels", "chlist", &list);
+        attribute(&mut header, "compression", "compression", &[0]);
+        let mut window = Vec::new();
+        for value in [0, 0, self.width as i32 - 1, self.height as i32 - 1] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        attribute(&mut header, "dataWindow", "box2i", &window);
+        attribute(&mut header, "displayWindow", "box2i", &window);
+        attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+        writer.write_all(&header)?;
+
+        // Every scanline is a chunk of its y, its size and its data, found
+        // through a table of offsets from the start of the file.
+        let line_size = channels.len() * self.width as usize * 4;
+        let chunk_size = 8 + line_size;
+        let table_end = header.len() + self.height as usize * 8;
+        for y in 0..self.height as usize {
+            writer.write_all(&((table_end + y * chunk_size) as u64).to_le_bytes())?;
+        }
+        let width = self.width as usize;
+        let mut line = Vec::with_capacity(line_size);
+        for y in 0..self.height as usize {
+            line.clear();
+            for (_, values) in &channels {
+                for value in &values[y * width..(y + 1) * width] {
+                    line.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            writer.write_all(&(y as i32).to_le_bytes())?;
+            writer.write_all(&(line_size as i32).to_le_bytes())?;
+            writer.write_all(&line)?;
+        }
+        writer.flush()
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(fs::File::create(path)?))
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
@@ -0,0 +1,161 @@
+//! Unclamped floating-point images, the native output of the renderer.
+
+#[cfg(feature = "fs")]
+use crate::exr::ExrImage;
+use crate::tonemap::Tonemap;
+#[cfg(feature = "fs")]
+use image::{hdr::HdrEncoder, png::PngEncoder, ColorType, ImageResult};
+use image::{ImageBuffer, Rgb, RgbImage};
+#[cfg(feature = "fs")]
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Linear color of every pixel on the 0-255 scale of 8-bit images, but
+/// unclamped and unrounded, so highlights above white and detail in the
+/// shadows survive until the image is developed or saved as HDR.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Framebuffer {
+    /// A black `width` by `height` framebuffer.
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![[0.0; 3]; width as usize * height as usize],
+        }
+    }
+
+    /// The pixels of `image` as they are, e.g. to composite renders over it.
+    pub fn from_rgb8(image: &RgbImage) -> Framebuffer {
+        Framebuffer {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.pixels().map(|pixel| pixel.0.map(f32::from)).collect(),
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [f32; 3] {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: [f32; 3]) {
+        let i = self.index(x, y);
+        self.pixels[i] = color;
+    }
+
+    /// All pixels, row by row from the top left.
+    pub fn pixels(&self) -> &[[f32; 3]] {
+        &self.pixels
+    }
+
+    /// Geometric mean of the pixels' luminance, with 1.0 as white, the
+    /// overall brightness auto-exposure aims for; black pixels count as a
+    /// faint gray. `None` if no pixel is finite.
+    pub fn log_average_luminance(&self) -> Option<f32> {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for [r, g, b] in &self.pixels {
+            let luminance = (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0;
+            if luminance.is_finite() {
+                sum += (1e-4 + luminance.max(0.0)).ln();
+                count += 1;
+            }
+        }
+        if count > 0 {
+            Some((sum / count as f32).exp())
+        } else {
+            None
+        }
+    }
+
+    /// The 8-bit image `View::render` returns: values round down and clip
+    /// to 0-255.
+    pub fn to_rgb8(&self) -> RgbImage {
+        self.to_rgb8_exposed(0.0)
+    }
+
+    /// Like `to_rgb8`, developed `stop` exposure values brighter; each stop
+    /// up doubles the brightness.
+    pub fn to_rgb8_exposed(&self, stop: f32) -> RgbImage {
+        let scale = 2f32.powf(stop);
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.get_pixel(x, y).map(|c| (c * scale) as u8))
+        })
+    }
+
+    /// Like `to_rgb8`, bringing colors above white into range by `tonemap`
+    /// instead of clipping them.
+    pub fn to_rgb8_tonemapped(&self, tonemap: Tonemap) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            Rgb(tonemap.apply(self.get_pixel(x, y)).map(|c| c as u8))
+        })
+    }
+
+    /// 16 bits per channel, with 65535 where `to_rgb8` has 255, for smooth
+    /// gradients without banding.
+    pub fn to_rgb16(&self) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.get_pixel(x, y).map(|c| (c * 257.0) as u16))
+        })
+    }
+
+    /// The pixels with 1.0 as white, the scale HDR formats and image
+    /// libraries expect of float images.
+    pub fn to_rgb32f(&self) -> ImageBuffer<Rgb<f32>, Vec<f32>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.get_pixel(x, y).map(|c| c / 255.0))
+        })
+    }
+
+    /// Writes the pixels as a Radiance `.hdr` image, with 1.0 as white.
+    #[cfg(feature = "fs")]
+    pub fn write_hdr<W: Write>(&self, writer: W) -> ImageResult<()> {
+        let pixels: Vec<_> = self.to_rgb32f().pixels().copied().collect();
+        HdrEncoder::new(writer).encode(&pixels, self.width as usize, self.height as usize)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save_hdr<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.write_hdr(BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Writes `to_rgb16` as a PNG.
+    #[cfg(feature = "fs")]
+    pub fn write_png16<W: Write>(&self, writer: W) -> ImageResult<()> {
+        let bytes: Vec<u8> = self
+            .to_rgb16()
+            .into_raw()
+            .iter()
+            .flat_map(|c| c.to_be_bytes())
+            .collect();
+        PngEncoder::new(writer).encode(&bytes, self.width, self.height, ColorType::Rgb16)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save_png16<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.write_png16(BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Writes the pixels as an OpenEXR image, with 1.0 as white. Use
+    /// `ExrImage` to add depth, normals or other channels.
+    #[cfg(feature = "fs")]
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        ExrImage::new(self).save(path)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
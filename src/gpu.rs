@@ -0,0 +1,478 @@
+//! wgpu compute-shader renderer for scenes made of solid-colored spheres,
+//! planes and meshes under point lights. With the default `Whitted`
+//! integrator it produces the same image as `View::render`; with
+//! `PathTracer` it traces paths the same way, converging to the same
+//! image with noise of its own. The CPU path stays the reference
+//! implementation.
+
+use std::{fmt, sync::mpsc};
+
+use bytemuck::{Pod, Zeroable};
+use image::RgbImage;
+use wgpu::util::DeviceExt;
+
+use vecmath::{vec3_neg, vec3_sub};
+
+use crate::{
+    material::Scatter,
+    sampler::Rng,
+    scene::{Object, Scene, Shape},
+    tonemap::Tonemap,
+    view::{Ray, View},
+    Color, Vecf,
+};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+#[derive(Debug)]
+pub enum GpuError {
+    /// No adapter could be found on this machine.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// An object in the scene has no `Primitive` or `PrimitiveMesh` form,
+    /// e.g. a procedural texture, or is transparent, metal, rough, shiny,
+    /// physically based, clear coated, glowing or made of a material with
+    /// its own `Material::scatter`.
+    UnsupportedObject(usize),
+    /// The view composites over a backplate, which the GPU path cannot sample.
+    UnsupportedBackplate,
+    /// The view traces adaptively, or more than one sample per pixel with
+    /// `Whitted`.
+    UnsupportedSamples,
+    /// A light in the scene has a radius.
+    UnsupportedLight(usize),
+    /// The view uses an integrator other than `Whitted` and `PathTracer`.
+    UnsupportedIntegrator,
+    /// The scene has fog, clouds or volumes.
+    UnsupportedAtmosphere,
+    /// The scene has a photon map for caustics.
+    UnsupportedCaustics,
+    /// The view tone maps instead of clipping.
+    UnsupportedTonemap,
+    /// The view has an exposure value other than 0.
+    UnsupportedExposure,
+    /// The view projects orthographically.
+    UnsupportedProjection,
+    /// The view treats colors as sRGB.
+    UnsupportedSrgb,
+    /// The view dithers.
+    UnsupportedDither,
+    /// The view prunes faint reflections.
+    UnsupportedMinContribution,
+    /// The view weights reflections by the Fresnel equations.
+    UnsupportedFresnel,
+    /// The view renders only a region of the image.
+    UnsupportedRegion,
+    /// The view skips lights too far away to matter.
+    UnsupportedLightCutoff,
+    Readback(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpuError::NoAdapter => write!(f, "no GPU adapter available"),
+            GpuError::RequestDevice(err) => write!(f, "could not open GPU device: {}", err),
+            GpuError::UnsupportedObject(index) => {
+                write!(f, "object {} cannot be rendered on the GPU", index)
+            }
+            GpuError::UnsupportedBackplate => {
+                write!(f, "backplates cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedSamples => {
+                write!(f, "this sampling cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedLight(index) => {
+                write!(f, "area light {} cannot be rendered on the GPU", index)
+            }
+            GpuError::UnsupportedIntegrator => {
+                write!(f, "this integrator cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedAtmosphere => {
+                write!(f, "fog, clouds and volumes cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedCaustics => {
+                write!(f, "caustics cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedTonemap => {
+                write!(f, "tone mapping cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedExposure => {
+                write!(f, "camera exposure cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedProjection => {
+                write!(f, "orthographic views cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedSrgb => {
+                write!(f, "sRGB colors cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedDither => {
+                write!(f, "dithering cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedMinContribution => {
+                write!(f, "pruning reflections cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedFresnel => {
+                write!(f, "Fresnel reflections cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedRegion => {
+                write!(f, "image regions cannot be rendered on the GPU")
+            }
+            GpuError::UnsupportedLightCutoff => {
+                write!(f, "light cutoffs cannot be rendered on the GPU")
+            }
+            GpuError::Readback(err) => write!(f, "could not read back image: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl GpuRenderer {
+    pub fn new() -> Result<GpuRenderer, GpuError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok_or(GpuError::NoAdapter)?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .map_err(GpuError::RequestDevice)?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("raytracer"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("raytracer"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Ok(GpuRenderer {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    /// Renders `scene` as `view.render` would, or an error naming the
+    /// first thing in either that the GPU cannot render. Path traced
+    /// pixels draw their random numbers, camera ray offsets included, from
+    /// a hash of their own rather than the view's sampler.
+    pub fn render(&self, view: &View, scene: &Scene) -> Result<RgbImage, GpuError> {
+        if view.backplate.is_some() {
+            return Err(GpuError::UnsupportedBackplate);
+        }
+        let integrator = match view
+            .integrator
+            .as_deref()
+            .map(|integrator| integrator.name())
+        {
+            None | Some("whitted") => INTEGRATOR_WHITTED,
+            Some("path") => INTEGRATOR_PATH,
+            Some(_) => return Err(GpuError::UnsupportedIntegrator),
+        };
+        if view.adaptive.is_some() || (view.samples > 1 && integrator == INTEGRATOR_WHITTED) {
+            return Err(GpuError::UnsupportedSamples);
+        }
+        if let Some(index) = scene.lights.iter().position(|light| light.radius > 0.0) {
+            return Err(GpuError::UnsupportedLight(index));
+        }
+        if scene.fog.is_some() || !scene.clouds.is_empty() || !scene.volumes.is_empty() {
+            return Err(GpuError::UnsupportedAtmosphere);
+        }
+        if scene.caustics.is_some() {
+            return Err(GpuError::UnsupportedCaustics);
+        }
+        if view.tonemap != Tonemap::Clip {
+            return Err(GpuError::UnsupportedTonemap);
+        }
+        if view.exposure_value != 0.0 {
+            return Err(GpuError::UnsupportedExposure);
+        }
+        if view.orthographic.is_some() {
+            return Err(GpuError::UnsupportedProjection);
+        }
+        if view.srgb {
+            return Err(GpuError::UnsupportedSrgb);
+        }
+        if view.dither.is_some() {
+            return Err(GpuError::UnsupportedDither);
+        }
+        if view.min_contribution > 0.0 {
+            return Err(GpuError::UnsupportedMinContribution);
+        }
+        if view.fresnel {
+            return Err(GpuError::UnsupportedFresnel);
+        }
+        if view.region.is_some() {
+            return Err(GpuError::UnsupportedRegion);
+        }
+        if view.light_cutoff > 0.0 {
+            return Err(GpuError::UnsupportedLightCutoff);
+        }
+        let (width, height) = (view.image_width, view.image_height);
+        let objects = gpu_objects(scene)?;
+        let lights: Vec<GpuLight> = scene
+            .lights
+            .iter()
+            .map(|light| GpuLight {
+                position: vec4(light.position, light.intensity * scene.intensity_scale()),
+            })
+            .collect();
+        let frame = view.camera_frame();
+        let background = view.background.0;
+        let params = Params {
+            cam_position: vec4(view.cam_position, 0.0),
+            direction: vec4(view.direction, 0.0),
+            cam_right: vec4(frame.cam_right, 0.0),
+            cam_up: vec4(frame.cam_up, 0.0),
+            background: [
+                background[0] as f32,
+                background[1] as f32,
+                background[2] as f32,
+                0.0,
+            ],
+            cam_half_width: frame.cam_half_width,
+            cam_half_height: frame.cam_half_height,
+            pixel_width: frame.pixel_width,
+            pixel_height: frame.pixel_height,
+            image_width: width,
+            image_height: height,
+            max_depth: view.max_depth,
+            shadow_bias: view.shadow_bias,
+            object_count: objects.len() as u32,
+            light_count: lights.len() as u32,
+            integrator,
+            samples: view.samples.max(1),
+            seed: view.frame_seed(),
+            _pad: [0; 3],
+        };
+
+        let params_buffer = self.buffer_init(&[params], wgpu::BufferUsages::UNIFORM);
+        // Zero-sized bindings are invalid, so empty lists upload one dummy entry.
+        let objects_buffer = self.buffer_init(&non_empty(objects), wgpu::BufferUsages::STORAGE);
+        let lights_buffer = self.buffer_init(&non_empty(lights), wgpu::BufferUsages::STORAGE);
+        let pixels_size = (width.max(1) * height.max(1) * 4) as wgpu::BufferAddress;
+        let pixels_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pixels"),
+            size: pixels_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: pixels_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                binding(0, &params_buffer),
+                binding(1, &objects_buffer),
+                binding(2, &lights_buffer),
+                binding(3, &pixels_buffer),
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&pixels_buffer, 0, &readback_buffer, 0, pixels_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("map_async callback dropped")
+            .map_err(GpuError::Readback)?;
+
+        let mut img_buffer = RgbImage::new(width, height);
+        {
+            let mapped = slice.get_mapped_range();
+            let pixels: &[u32] = bytemuck::cast_slice(&mapped);
+            for (pixel, &packed) in img_buffer.pixels_mut().zip(pixels) {
+                pixel.0 = [packed as u8, (packed >> 8) as u8, (packed >> 16) as u8];
+            }
+        }
+        readback_buffer.unmap();
+        Ok(img_buffer)
+    }
+
+    fn buffer_init<T: Pod>(&self, contents: &[T], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(contents),
+                usage,
+            })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    cam_position: [f32; 4],
+    direction: [f32; 4],
+    cam_right: [f32; 4],
+    cam_up: [f32; 4],
+    background: [f32; 4],
+    cam_half_width: f32,
+    cam_half_height: f32,
+    pixel_width: f32,
+    pixel_height: f32,
+    image_width: u32,
+    image_height: u32,
+    max_depth: u32,
+    shadow_bias: f32,
+    object_count: u32,
+    light_count: u32,
+    integrator: u32,
+    samples: u32,
+    seed: u32,
+    _pad: [u32; 3],
+}
+
+const INTEGRATOR_WHITTED: u32 = 0;
+const INTEGRATOR_PATH: u32 = 1;
+
+const KIND_SPHERE: u32 = 0;
+const KIND_PLANE: u32 = 1;
+const KIND_TRIANGLE: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, Pod, Zeroable)]
+struct GpuObject {
+    a: [f32; 4],
+    b: [f32; 4],
+    c: [f32; 4],
+    color: [f32; 4],
+    /// Lambert, specular, squared radius, unused.
+    material: [f32; 4],
+    kind: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, Pod, Zeroable)]
+struct GpuLight {
+    position: [f32; 4],
+}
+
+/// The scene's objects as the shader reads them, each mesh as one entry
+/// per triangle.
+fn gpu_objects(scene: &Scene) -> Result<Vec<GpuObject>, GpuError> {
+    let mut gpu_objects = Vec::new();
+    for (index, object) in scene.objects.iter().enumerate() {
+        let plain = object.get_transparency() <= 0.0
+            && !object.is_metal()
+            && object.get_roughness() <= 0.0
+            && object.get_shininess() <= 0.0
+            && object.get_pbr().is_none()
+            && object.get_clearcoat().is_none()
+            && !object
+                .material()
+                .is_some_and(|material| material.get_emission() != [0.0; 3]);
+        if !plain {
+            return Err(GpuError::UnsupportedObject(index));
+        }
+        if let Some(mesh) = object.primitive_mesh() {
+            let material = [mesh.lambert, mesh.specular, 0.0, 0.0];
+            gpu_objects.extend(mesh.triangles.iter().map(|&[a, b, c]| GpuObject {
+                a: vec4(a, 0.0),
+                b: vec4(b, 0.0),
+                c: vec4(c, 0.0),
+                color: unit_color(mesh.color),
+                material,
+                kind: KIND_TRIANGLE,
+                _pad: [0; 3],
+            }));
+            continue;
+        }
+        let primitive = object
+            .primitive()
+            .filter(|primitive| !shades_itself(object.as_ref(), primitive.shape))
+            .ok_or(GpuError::UnsupportedObject(index))?;
+        let (kind, a, b, sq_radius) = match primitive.shape {
+            Shape::Sphere { center, radius } => (KIND_SPHERE, center, [0.0; 3], radius * radius),
+            Shape::Plane { point, normal } => (KIND_PLANE, point, normal, 0.0),
+        };
+        gpu_objects.push(GpuObject {
+            a: vec4(a, 0.0),
+            b: vec4(b, 0.0),
+            c: [0.0; 4],
+            color: unit_color(primitive.color),
+            material: [primitive.lambert, primitive.specular, sq_radius, 0.0],
+            kind,
+            _pad: [0; 3],
+        });
+    }
+    Ok(gpu_objects)
+}
+
+/// `color` on the 0-1 scale, with an unused fourth channel.
+fn unit_color(color: Color) -> [f32; 4] {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    [r, g, b, 0.0]
+}
+
+/// Whether the material of `object` scatters or gives off light by its
+/// own rules at a point on `shape`, which the GPU cannot call back into.
+fn shades_itself(object: &dyn Object, shape: Shape) -> bool {
+    let material = match object.material() {
+        Some(material) => material,
+        None => return false,
+    };
+    let (point, direction) = match shape {
+        Shape::Sphere { center, radius } => (vec3_sub(center, [0.0, 0.0, radius]), [0.0, 0.0, 1.0]),
+        Shape::Plane { point, normal } => (point, vec3_neg(normal)),
+    };
+    let ray = Ray::new(vec3_sub(point, direction), direction);
+    let hit = object.hit_at(&ray, point);
+    !matches!(
+        material.scatter(&ray, &hit, &mut Rng::new(0, 0, 0, 0)),
+        Scatter::Standard
+    ) || material.emitted(&hit) != [0.0; 3]
+}
+
+fn non_empty<T: Default>(mut items: Vec<T>) -> Vec<T> {
+    if items.is_empty() {
+        items.push(T::default());
+    }
+    items
+}
+
+fn binding(index: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding: index,
+        resource: buffer.as_entire_binding(),
+    }
+}
+
+fn vec4(v: Vecf, w: f32) -> [f32; 4] {
+    [v[0], v[1], v[2], w]
+}
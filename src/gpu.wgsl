@@ -0,0 +1,345 @@
+// Compute-shader ports of the `Whitted` and `PathTracer` integrators. Every
+// step mirrors the CPU path, so Whitted images agree pixel for pixel up to
+// float rounding and path traced ones converge to the same image.
+
+const PI: f32 = 3.14159265;
+const INF: f32 = 3.4028235e38;
+const SPHERE: u32 = 0u;
+const PLANE: u32 = 1u;
+const PATH: u32 = 1u;
+
+// `background` is in 0-255, object colors in 0-1, as on the CPU side.
+// `integrator` is 0 for `Whitted` and 1 for `PathTracer`.
+struct Params {
+    cam_position: vec4<f32>,
+    direction: vec4<f32>,
+    cam_right: vec4<f32>,
+    cam_up: vec4<f32>,
+    background: vec4<f32>,
+    cam_half_width: f32,
+    cam_half_height: f32,
+    pixel_width: f32,
+    pixel_height: f32,
+    image_width: u32,
+    image_height: u32,
+    max_depth: u32,
+    shadow_bias: f32,
+    object_count: u32,
+    light_count: u32,
+    integrator: u32,
+    samples: u32,
+    seed: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+// `a` is the sphere center, a point on the plane or a triangle's first
+// corner, `b.xyz` the plane normal or the second corner and `c.xyz` the
+// third. `material` is (lambert, specular, squared radius, unused).
+struct Object {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    color: vec4<f32>,
+    material: vec4<f32>,
+    kind: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+// `w` holds the intensity.
+struct Light {
+    position: vec4<f32>,
+}
+
+struct Closest {
+    distance: f32,
+    index: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> objects: array<Object>;
+@group(0) @binding(2) var<storage, read> lights: array<Light>;
+@group(0) @binding(3) var<storage, read_write> pixels: array<u32>;
+
+var<private> rng_state: u32;
+
+// PCG hash.
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Uniform in [0, 1).
+fn next_f32() -> f32 {
+    rng_state = hash(rng_state);
+    return f32(rng_state >> 8u) / 16777216.0;
+}
+
+// The watertight test of `Triangle::crossing`, in single precision
+// throughout.
+fn intersect_triangle(object: Object, origin: vec3<f32>, direction: vec3<f32>) -> f32 {
+    let size = abs(direction);
+    var kz = 0u;
+    if size[1] >= size[kz] {
+        kz = 1u;
+    }
+    if size[2] >= size[kz] {
+        kz = 2u;
+    }
+    var kx = (kz + 1u) % 3u;
+    var ky = (kz + 2u) % 3u;
+    if direction[kz] < 0.0 {
+        let swap = kx;
+        kx = ky;
+        ky = swap;
+    }
+    let shear = vec2<f32>(direction[kx] / direction[kz], direction[ky] / direction[kz]);
+    let la = object.a.xyz - origin;
+    let lb = object.b.xyz - origin;
+    let lc = object.c.xyz - origin;
+    let a = vec3<f32>(la[kx] - shear.x * la[kz], la[ky] - shear.y * la[kz], la[kz] / direction[kz]);
+    let b = vec3<f32>(lb[kx] - shear.x * lb[kz], lb[ky] - shear.y * lb[kz], lb[kz] / direction[kz]);
+    let c = vec3<f32>(lc[kx] - shear.x * lc[kz], lc[ky] - shear.y * lc[kz], lc[kz] / direction[kz]);
+    let u = c.x * b.y - c.y * b.x;
+    let v = a.x * c.y - a.y * c.x;
+    let w = b.x * a.y - b.y * a.x;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return INF;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return INF;
+    }
+    let distance = (u * a.z + v * b.z + w * c.z) / det;
+    if distance > 0.0 {
+        return distance;
+    }
+    return INF;
+}
+
+fn intersect(object: Object, origin: vec3<f32>, direction: vec3<f32>) -> f32 {
+    if object.kind == SPHERE {
+        let from_ray_origin = object.a.xyz - origin;
+        let on_ray_midpoint = dot(from_ray_origin, direction);
+        if on_ray_midpoint > 0.0 {
+            let c_center_to_midpoint = dot(from_ray_origin, from_ray_origin)
+                - on_ray_midpoint * on_ray_midpoint;
+            if c_center_to_midpoint < object.material.z {
+                return on_ray_midpoint - sqrt(object.material.z - c_center_to_midpoint);
+            }
+        }
+        return INF;
+    }
+    if object.kind != PLANE {
+        return intersect_triangle(object, origin, direction);
+    }
+    let norm_ray_dot = dot(direction, object.b.xyz);
+    if norm_ray_dot > 1e-6 {
+        let distance = dot(object.a.xyz - origin, object.b.xyz) / norm_ray_dot;
+        if distance > 0.0 {
+            return distance;
+        }
+    }
+    return INF;
+}
+
+fn closest_hit(origin: vec3<f32>, direction: vec3<f32>) -> Closest {
+    var closest = Closest(INF, 0u);
+    for (var i = 0u; i < params.object_count; i++) {
+        let distance = intersect(objects[i], origin, direction);
+        if distance < closest.distance && distance > 0.0 {
+            closest = Closest(distance, i);
+        }
+    }
+    return closest;
+}
+
+// Whether an object lies between `point` and `distance` along
+// `direction`, as `View::blocked` decides it.
+fn blocked(point: vec3<f32>, direction: vec3<f32>, distance: f32) -> bool {
+    let shadow_point = point + direction * params.shadow_bias;
+    for (var i = 0u; i < params.object_count; i++) {
+        let hit = intersect(objects[i], shadow_point, direction);
+        if hit > 0.0 && hit < INF && hit < distance {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Matches `Object::normal_to` for a ray leaving `point` along `direction`.
+fn normal_to(object: Object, point: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    if object.kind == SPHERE {
+        return normalize(point - object.a.xyz);
+    }
+    var normal = object.b.xyz;
+    if object.kind != PLANE {
+        normal = normalize(cross(object.b.xyz - object.a.xyz, object.c.xyz - object.a.xyz));
+    }
+    if dot(direction, normal) < 0.0 {
+        return normal;
+    }
+    return -normal;
+}
+
+fn lambert_shade(object: Object, point: vec3<f32>) -> f32 {
+    var lambert_amount = 0.0;
+    for (var l = 0u; l < params.light_count; l++) {
+        let light = lights[l];
+        let to_light = light.position.xyz - point;
+        let dir_to_light = normalize(to_light);
+        let dist_to_light = length(to_light);
+        if !blocked(point, dir_to_light, dist_to_light) {
+            let contribution = dot(dir_to_light, normal_to(object, point, -dir_to_light));
+            if contribution > 0.0 {
+                lambert_amount += contribution
+                    * (light.position.w / (4.0 * PI * dist_to_light * dist_to_light));
+            }
+        }
+    }
+    return min(lambert_amount, 1.0);
+}
+
+// `render_pixel` of the `Whitted` integrator, on the 0-255 scale.
+fn whitted(origin_in: vec3<f32>, direction_in: vec3<f32>) -> vec3<f32> {
+    var origin = origin_in;
+    var direction = direction_in;
+    var pixel_color = vec3<f32>(0.0);
+    var reflection_coef = 1.0;
+    var depth = 0u;
+    while depth < params.max_depth && reflection_coef > 0.0 {
+        let hit = closest_hit(origin, direction);
+        if hit.distance == INF {
+            break;
+        }
+        let object = objects[hit.index];
+        let point = origin + direction * hit.distance;
+        let light = lambert_shade(object, point);
+        let normal = normal_to(object, point, direction);
+        direction = normalize(direction - normal * (2.0 * dot(direction, normal)));
+        origin = point;
+        pixel_color += object.color.rgb * light * object.material.x * reflection_coef;
+        reflection_coef *= object.material.y;
+        depth++;
+    }
+    if depth == 0u {
+        return params.background.rgb;
+    }
+    return clamp(pixel_color, vec3<f32>(0.0), vec3<f32>(1.0)) * 255.0;
+}
+
+// Light from one light at `point`, facing `normal`, picked in proportion
+// to intensity out of `total` and divided by the chance of picking it, as
+// `sample_light` does for point lights.
+fn sample_light(point: vec3<f32>, normal: vec3<f32>, total: f32) -> f32 {
+    var pick = next_f32() * total;
+    var chosen = params.light_count - 1u;
+    for (var l = 0u; l < params.light_count; l++) {
+        pick -= lights[l].position.w;
+        if pick < 0.0 {
+            chosen = l;
+            break;
+        }
+    }
+    let light = lights[chosen];
+    let to_light = light.position.xyz - point;
+    let distance = length(to_light);
+    let direction = to_light / distance;
+    let cos = dot(direction, normal);
+    if cos <= 0.0 || blocked(point, direction, distance) {
+        return 0.0;
+    }
+    let pick_chance = light.position.w / total;
+    return cos * light.position.w / (4.0 * PI * distance * distance) / pick_chance;
+}
+
+// A cosine-weighted direction around `normal`, as `disk_to_hemisphere`
+// lifts the point `r`, `phi` of the unit disk.
+fn disk_to_hemisphere(normal: vec3<f32>, r: f32, phi: f32) -> vec3<f32> {
+    var helper = vec3<f32>(1.0, 0.0, 0.0);
+    if abs(normal.x) > 0.9 {
+        helper = vec3<f32>(0.0, 1.0, 0.0);
+    }
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+    let z = sqrt(max(1.0 - r * r, 0.0));
+    return tangent * (r * cos(phi)) + bitangent * (r * sin(phi)) + normal * z;
+}
+
+// One path of the `PathTracer` integrator, on the 0-255 scale.
+fn path(origin_in: vec3<f32>, direction_in: vec3<f32>) -> vec3<f32> {
+    var origin = origin_in;
+    var direction = direction_in;
+    var color = vec3<f32>(0.0);
+    var throughput = vec3<f32>(1.0);
+    var total_intensity = 0.0;
+    for (var l = 0u; l < params.light_count; l++) {
+        total_intensity += lights[l].position.w;
+    }
+    for (var depth = 0u; depth < params.max_depth; depth++) {
+        let hit = closest_hit(origin, direction);
+        if hit.distance == INF {
+            color += throughput * params.background.rgb;
+            break;
+        }
+        let object = objects[hit.index];
+        let point = origin + direction * hit.distance;
+        let lambert = object.material.x;
+        let specular = object.material.y;
+        let total = lambert + specular;
+        if total <= 0.0 {
+            break;
+        }
+        let normal = normal_to(object, point, direction);
+        if lambert > 0.0 && total_intensity > 0.0 {
+            let direct = sample_light(point, normal, total_intensity) * lambert;
+            color += throughput * object.color.rgb * 255.0 * direct;
+        }
+        // Choosing each lobe in proportion to its weight leaves `total`
+        // as the estimator's weight for either.
+        if next_f32() * total < specular {
+            direction = normalize(direction - normal * (2.0 * dot(direction, normal)));
+            throughput *= total;
+        } else {
+            throughput *= object.color.rgb * total;
+            let r = sqrt(next_f32());
+            let phi = 2.0 * PI * next_f32();
+            direction = disk_to_hemisphere(normal, r, phi);
+        }
+        origin = point + normal * params.shadow_bias;
+    }
+    return color;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.image_width || id.y >= params.image_height {
+        return;
+    }
+    rng_state = hash(hash(id.y * params.image_width + id.x) ^ params.seed);
+    var sum = vec3<f32>(0.0);
+    for (var sample = 0u; sample < params.samples; sample++) {
+        // A single sample goes through the pixel's corner, as on the CPU.
+        var offset = vec2<f32>(0.0);
+        if params.samples > 1u {
+            offset = vec2<f32>(next_f32(), next_f32());
+        }
+        let x = f32(id.x) + offset.x;
+        let y = f32(id.y) + offset.y;
+        let translate = params.cam_right.xyz * (params.pixel_width * x - params.cam_half_width)
+            + params.cam_up.xyz * (params.pixel_height * y - params.cam_half_height);
+        let origin = params.cam_position.xyz;
+        let direction = normalize(normalize(params.direction.xyz + translate));
+        if params.integrator == PATH {
+            sum += path(origin, direction);
+        } else {
+            sum += whitted(origin, direction);
+        }
+    }
+    let rgb = vec3<u32>(clamp(sum / f32(params.samples), vec3<f32>(0.0), vec3<f32>(255.0)));
+    pixels[id.y * params.image_width + id.x] = rgb.r | (rgb.g << 8u) | (rgb.b << 16u);
+}
@@ -0,0 +1,258 @@
+//! Everything that goes into one pixel, from `View::debug_pixel`, for
+//! finding out why a single pixel renders wrong.
+
+use std::fmt;
+
+use crate::{
+    scene::{same_object, Object, Part, Scene},
+    summary::Material,
+    view::{Ray, View},
+    Color, Vecf,
+};
+
+/// Returned by `View::debug_pixel`; `Display` lays it out for the
+/// terminal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelTrace {
+    pub x: u32,
+    pub y: u32,
+    /// The pixel as the view renders it, on the 0-255 scale and before
+    /// tone mapping.
+    pub color: [f32; 3],
+    /// The rays the view's integrator follows for the pixel's first
+    /// sample, in the order it follows them, as it records them: for
+    /// `Whitted` every reflected, refracted and clear coat ray it branches
+    /// into, for the path tracers the one path they pick. Integrators that
+    /// record nothing show just the camera ray and its first hit.
+    pub bounces: Vec<Bounce>,
+}
+
+/// How the ray of a `Bounce` came about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BounceKind {
+    Camera,
+    Reflection,
+    Refraction,
+    /// Mirrored by a clear coat, see `material::Layered`.
+    Clearcoat,
+    /// Picked by the material's own `Material::scatter`.
+    Scatter,
+    /// A diffuse bounce of a path tracer.
+    Diffuse,
+}
+
+/// One ray followed for a `PixelTrace`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bounce {
+    /// Index in `PixelTrace::bounces` of the bounce whose hit the ray
+    /// leaves; `None` for the camera ray.
+    pub parent: Option<usize>,
+    pub kind: BounceKind,
+    /// Surfaces met before this ray's, 0 for the camera ray.
+    pub depth: u32,
+    pub origin: Vecf,
+    pub direction: Vecf,
+    /// Share of each channel of the light coming back along the ray that
+    /// reaches the pixel.
+    pub weight: [f32; 3],
+    /// What the ray hits; `None` where it leaves the scene.
+    pub hit: Option<SurfaceHit>,
+}
+
+/// Where a `Bounce` meets a surface, and how the surface is lit and shaded
+/// there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurfaceHit {
+    /// Index of the object in `Scene::objects`.
+    pub object: usize,
+    /// `Object::kind` of the object.
+    pub kind: String,
+    pub point: Vecf,
+    pub distance: f32,
+    /// Surface normal, facing the ray.
+    pub normal: Vecf,
+    pub uv: Option<[f32; 2]>,
+    /// The object's color at the hit.
+    pub color: Color,
+    pub material: Material,
+    /// Light from each of the scene's lights, in order.
+    pub lights: Vec<LightSample>,
+    /// Light the surface sends back along the ray by Lambert shading and
+    /// highlights, on the 0-255 scale; the pixel gets it times the
+    /// bounce's `weight`.
+    pub shaded: [f32; 3],
+    /// Light the surface gives off, on the same scale.
+    pub emitted: [f32; 3],
+    /// Shares of the light arriving along the ray the surface reflects,
+    /// refracts and mirrors off a clear coat, see the child bounces.
+    pub reflect: f32,
+    pub refract: f32,
+    pub coat: f32,
+}
+
+/// Light from one of the scene's lights at a `SurfaceHit`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightSample {
+    /// Index of the light in `Scene::lights`.
+    pub light: usize,
+    /// Light arriving in each channel, after shadows.
+    pub arriving: [f32; 3],
+    /// Light that would arrive without shadows.
+    pub unshadowed: [f32; 3],
+}
+
+/// Collects the `Bounce`s of a `PixelTrace` as an integrator follows
+/// them, see `Integrator::li`.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    bounces: Vec<Bounce>,
+}
+
+impl Recorder {
+    /// Adds `ray`, leaving the hit of bounce `parent`, with the share
+    /// `weight` of its light that reaches the pixel. Returns the new
+    /// bounce's index.
+    pub fn ray(
+        &mut self,
+        parent: Option<usize>,
+        kind: BounceKind,
+        depth: u32,
+        ray: &Ray,
+        weight: [f32; 3],
+    ) -> usize {
+        self.bounces.push(Bounce {
+            parent,
+            kind,
+            depth,
+            origin: ray.origin,
+            direction: ray.direction,
+            weight,
+            hit: None,
+        });
+        self.bounces.len() - 1
+    }
+
+    /// Records that the ray of bounce `index` meets `part` of `object` at
+    /// `point`, `distance` along, with the light each of the scene's lights
+    /// sends there. The shading, emission and shares of the light going on
+    /// are left for the integrator to fill in.
+    #[allow(clippy::too_many_arguments)]
+    pub fn hit(
+        &mut self,
+        index: usize,
+        view: &View,
+        scene: &Scene,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        distance: f32,
+        part: Option<Part>,
+    ) -> &mut SurfaceHit {
+        let hit = view.hit_at(scene, object, ray, point, part);
+        let lights = scene
+            .lights
+            .iter()
+            .enumerate()
+            .map(|(light, source)| LightSample {
+                light,
+                arriving: view.light_contribution(scene, object, point, part, source, true),
+                unshadowed: view.light_contribution(scene, object, point, part, source, false),
+            })
+            .collect();
+        self.bounces[index].hit.insert(SurfaceHit {
+            object: scene
+                .objects
+                .iter()
+                .position(|o| same_object(o.as_ref(), object))
+                .unwrap_or_default(),
+            kind: object.kind().to_string(),
+            point,
+            distance,
+            normal: hit.normal,
+            uv: hit.uv,
+            color: object.get_color_at(&hit, scene),
+            material: Material::of(object),
+            lights,
+            shaded: [0.0; 3],
+            emitted: [0.0; 3],
+            reflect: 0.0,
+            refract: 0.0,
+            coat: 0.0,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounces.is_empty()
+    }
+
+    pub fn into_bounces(self) -> Vec<Bounce> {
+        self.bounces
+    }
+}
+
+impl fmt::Display for PixelTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.color;
+        writeln!(
+            f,
+            "pixel {} {}: ({:.2}, {:.2}, {:.2})",
+            self.x, self.y, r, g, b
+        )?;
+        for bounce in &self.bounces {
+            let indent = "  ".repeat(bounce.depth as usize);
+            let [x, y, z] = bounce.direction;
+            let [r, g, b] = bounce.weight;
+            writeln!(
+                f,
+                "{}{} along ({:.3}, {:.3}, {:.3}), weight ({:.3}, {:.3}, {:.3})",
+                indent,
+                format!("{:?}", bounce.kind).to_lowercase(),
+                x,
+                y,
+                z,
+                r,
+                g,
+                b
+            )?;
+            let hit = match &bounce.hit {
+                Some(hit) => hit,
+                None => {
+                    writeln!(f, "{}  missed", indent)?;
+                    continue;
+                }
+            };
+            let [x, y, z] = hit.point;
+            writeln!(
+                f,
+                "{}  hit {} {} at ({:.3}, {:.3}, {:.3}), distance {:.3}",
+                indent, hit.kind, hit.object, x, y, z, hit.distance
+            )?;
+            writeln!(f, "{}  {}", indent, hit.material)?;
+            if hit.material.color.is_none() {
+                let [r, g, b] = hit.color.0;
+                writeln!(f, "{}  texture rgb({}, {}, {})", indent, r, g, b)?;
+            }
+            for light in &hit.lights {
+                let [r, g, b] = light.arriving;
+                let [ur, ug, ub] = light.unshadowed;
+                writeln!(
+                    f,
+                    "{}  light {}: ({:.3}, {:.3}, {:.3}) of ({:.3}, {:.3}, {:.3})",
+                    indent, light.light, r, g, b, ur, ug, ub
+                )?;
+            }
+            let [r, g, b] = hit.shaded;
+            writeln!(f, "{}  shaded ({:.2}, {:.2}, {:.2})", indent, r, g, b)?;
+            if hit.emitted != [0.0; 3] {
+                let [r, g, b] = hit.emitted;
+                writeln!(f, "{}  emitted ({:.2}, {:.2}, {:.2})", indent, r, g, b)?;
+            }
+            writeln!(
+                f,
+                "{}  reflect {:.3}, refract {:.3}, coat {:.3}",
+                indent, hit.reflect, hit.refract, hit.coat
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1705 @@
+//! Ways of turning a camera ray into a color, see `View::with_integrator`.
+
+use std::f32::consts::PI;
+
+use vecmath::{vec3_add, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub};
+
+use crate::{
+    cone::DistanceField,
+    inspect::{BounceKind, Recorder},
+    material::{AlphaMode, Scatter},
+    sampler::Rng,
+    scene::{same_object, transmittance, Light, Media, Object, Part, RayMask, Scene},
+    texture::{disk_to_hemisphere, tangent_frame},
+    view::{Band, Ray, View, MAX_CUTOUT_LAYERS},
+    warning::RenderWarning,
+    Vecf,
+};
+
+/// Computes the light reaching the camera along one ray.
+pub trait Integrator: Send + Sync {
+    /// Light arriving along the camera ray `ray`, on the unclamped 0-255
+    /// scale. `background` is the view's background or backplate color
+    /// behind the pixel; `rng` supplies this sample's random numbers.
+    /// `recorder`, given by `View::debug_pixel`, takes the rays the
+    /// integrator follows and what they hit; integrators may leave it
+    /// empty.
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        recorder: Option<&mut Recorder>,
+    ) -> [f32; 3];
+
+    /// Like `li`, for a camera ray whose first hit is already known, so
+    /// integrators rendered together share it, see `View::render_outputs`.
+    /// `hit` is the point, distance, object and `Part` of it the ray meets
+    /// first, shadow catchers included, or `None` for a miss. By default the ray
+    /// is traced again, for integrators that see the first hit their own
+    /// way.
+    fn li_from_hit(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        hit: Option<(Vecf, f32, &dyn Object, Option<Part>)>,
+        background: [f32; 3],
+        rng: &mut Rng,
+    ) -> [f32; 3] {
+        let _ = hit;
+        self.li(view, scene, ray, background, rng, None)
+    }
+
+    /// Short name recorded in render metadata, see
+    /// `metadata::RenderInfo`.
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+/// The default: Lambert shading from every light with hard shadows, plus
+/// mirror reflections and refraction up to the view's `max_depth`, both
+/// followed where a surface reflects and refracts, with light absorbed on
+/// its way through absorbing objects. Deterministic, except that rough
+/// reflections follow one random ray around the mirror direction per
+/// sample, and dispersive objects one color channel per sample. Materials
+/// with their own `Material::scatter` follow the one ray it picks per
+/// sample instead, and add the light they emit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Whitted;
+
+/// A ray `Whitted` has yet to follow.
+struct Branch<'a> {
+    ray: Ray,
+    /// The part of the surface the ray leaves, which it passes over, see
+    /// `View::trace`.
+    from: Option<(&'a dyn Object, Part)>,
+    depth: u32,
+    /// Share of the light coming back along the ray that reaches the
+    /// camera.
+    coef: f32,
+    /// Share of that light the fog, clouds and absorbing objects before it
+    /// let through.
+    medium_coef: [f32; 3],
+    /// Transparent objects the ray is inside.
+    media: Media,
+    /// Rays a rough surface split the ray, or one it came from, into; 1 if
+    /// none. Split rays are not split again, and count together against
+    /// the view's minimum contribution.
+    split: u32,
+    /// Blended cutouts the ray carries on a ray past. Camera rays carried
+    /// on add what they see to the pixel instead of being all of it.
+    layers: u32,
+    /// The light the ray carries alone, once a dispersive object bent it
+    /// apart from the rest.
+    band: Option<Band>,
+    /// The recorded bounce whose hit the ray leaves, and how, see
+    /// `inspect::Recorder`.
+    parent: Option<usize>,
+    kind: BounceKind,
+}
+
+impl Integrator for Whitted {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        mut recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        // The background as seen through fog and clouds, for camera rays
+        // that miss.
+        let missed = |ray: &Ray| match scene.scattering(ray, f32::INFINITY) {
+            Some(scattering) => {
+                let mut color = background;
+                for i in 0..3 {
+                    color[i] = background[i] * scattering.transmittance[i]
+                        + scattering.inscatter[i] * 255.0;
+                }
+                color
+            }
+            None => background,
+        };
+        if view.max_depth == 0 {
+            return missed(ray);
+        }
+        let mut pixel_color: [f32; 3] = [0.0; 3];
+        // Rays still to follow, depth first, each with the share of its
+        // light that reaches the camera.
+        let mut branches = vec![Branch {
+            ray: Ray {
+                origin: ray.origin,
+                direction: ray.direction,
+            },
+            from: None,
+            depth: 0,
+            coef: 1.0,
+            medium_coef: [1.0; 3],
+            media: Media::default(),
+            split: 1,
+            layers: 0,
+            band: None,
+            parent: None,
+            kind: BounceKind::Camera,
+        }];
+        while let Some(mut branch) = branches.pop() {
+            let Branch {
+                ray, depth, coef, ..
+            } = branch;
+            let absorption = branch.media.absorption();
+            // The share of the camera's light the ray still carries, after
+            // what media on the way absorbed. Camera rays are always
+            // followed.
+            let contribution = coef * branch.medium_coef.iter().fold(0.0, |a, &b| f32::max(a, b));
+            let faint = depth > 0 && contribution * branch.split as f32 <= view.min_contribution;
+            let followed = depth < view.max_depth && coef > 0.0 && !faint;
+            if !followed {
+                continue;
+            }
+            // Only camera rays see shadow catchers.
+            let mask = if depth == 0 {
+                RayMask::CAMERA
+            } else {
+                RayMask::REFLECTION
+            };
+            let (parent, kind) = (branch.parent, branch.kind);
+            let weight = branch.medium_coef.map(|m| coef * m);
+            let index = recorder
+                .as_deref_mut()
+                .map(|recorder| recorder.ray(parent, kind, depth, &ray, weight));
+            let (hit_point, dist, hit_object, part) =
+                match view.trace_layers(scene, &ray, branch.from, mask, depth == 0) {
+                    Some(hit) => hit,
+                    None if depth == 0 && branch.layers == 0 => return missed(&ray),
+                    None if depth == 0 => {
+                        let seen = missed(&ray);
+                        for ((c, s), m) in pixel_color.iter_mut().zip(seen).zip(branch.medium_coef)
+                        {
+                            *c += s / 255.0 * coef * m;
+                        }
+                        continue;
+                    }
+                    None => {
+                        let absorbed = transmittance(absorption, f32::INFINITY);
+                        for (m, a) in branch.medium_coef.iter_mut().zip(absorbed) {
+                            *m *= a;
+                        }
+                        let haze = view.medium_segment(
+                            scene,
+                            &ray,
+                            f32::INFINITY,
+                            coef,
+                            &mut branch.medium_coef,
+                            [0.0; 3],
+                        );
+                        for (c, h) in pixel_color.iter_mut().zip(haze) {
+                            *c += h;
+                        }
+                        continue;
+                    }
+                };
+            let mut recorded = match (recorder.as_deref_mut(), index) {
+                (Some(recorder), Some(index)) => {
+                    Some(recorder.hit(index, view, scene, hit_object, &ray, hit_point, dist, part))
+                }
+                _ => None,
+            };
+            if hit_object.is_shadow_catcher() {
+                let lit = view.light_amount(scene, hit_object, hit_point, part, true);
+                let unoccluded = view.light_amount(scene, hit_object, hit_point, part, false);
+                let mut seen = background;
+                for ((s, l), u) in seen.iter_mut().zip(lit).zip(unoccluded) {
+                    let shadow = if u > 0.0 { 1.0 - l / u } else { 0.0 };
+                    *s *= 1.0 - shadow;
+                }
+                if let Some(recorded) = recorded {
+                    recorded.shaded = seen;
+                }
+                if branch.layers == 0 {
+                    return seen;
+                }
+                for ((c, s), m) in pixel_color.iter_mut().zip(seen).zip(branch.medium_coef) {
+                    *c += s / 255.0 * coef * m;
+                }
+                continue;
+            }
+            // Blended cutouts show the surface by their coverage, and what
+            // is behind them by the rest.
+            let alpha = match hit_object.get_cutout() {
+                Some(cutout) if cutout.mode == AlphaMode::Blend => cutout
+                    .mask
+                    .coverage(hit_object.hit_on(&ray, hit_point, part).uv),
+                _ => 1.0,
+            };
+            let (scatter, emitted) = if alpha > 0.0 {
+                view.scatter(hit_object, &ray, hit_point, rng)
+            } else {
+                (Scatter::Absorb, [0.0; 3])
+            };
+            let mut surface = match scatter {
+                Scatter::Standard => {
+                    view.color_trace(scene, hit_point, hit_object, part, &ray, coef * alpha)
+                }
+                _ => [0.0; 3],
+            };
+            for (c, e) in surface.iter_mut().zip(emitted) {
+                *c += e * coef * alpha;
+            }
+            if let Some(recorded) = recorded.as_deref_mut() {
+                if let Scatter::Standard = scatter {
+                    recorded.shaded = view
+                        .color_trace(scene, hit_point, hit_object, part, &ray, alpha)
+                        .map(|c| c * 255.0);
+                }
+                recorded.emitted = emitted.map(|e| e * alpha * 255.0);
+            }
+            let from = part.map(|part| (hit_object, part));
+            let mut medium_coef = branch.medium_coef;
+            for (m, a) in medium_coef.iter_mut().zip(transmittance(absorption, dist)) {
+                *m *= a;
+            }
+            let seen = view.medium_segment(scene, &ray, dist, coef, &mut medium_coef, surface);
+            for (c, s) in pixel_color.iter_mut().zip(seen) {
+                *c += s;
+            }
+            if alpha < 1.0 && branch.layers < MAX_CUTOUT_LAYERS {
+                branches.push(Branch {
+                    ray: Ray::new(
+                        vec3_add(hit_point, vec3_scale(ray.direction, view.shadow_bias)),
+                        ray.direction,
+                    ),
+                    from,
+                    depth,
+                    coef: coef * (1.0 - alpha),
+                    medium_coef,
+                    media: branch.media.clone(),
+                    split: branch.split,
+                    layers: branch.layers + 1,
+                    band: branch.band,
+                    parent: index,
+                    kind: branch.kind,
+                });
+            }
+            let coef = coef * alpha;
+            match scatter {
+                Scatter::Standard => {}
+                Scatter::Continue(next, share) => {
+                    for (m, s) in medium_coef.iter_mut().zip(share) {
+                        *m *= s;
+                    }
+                    branches.push(Branch {
+                        ray: next,
+                        from,
+                        depth: depth + 1,
+                        coef,
+                        medium_coef,
+                        media: branch.media,
+                        split: branch.split,
+                        layers: 0,
+                        band: branch.band,
+                        parent: index,
+                        kind: BounceKind::Scatter,
+                    });
+                    continue;
+                }
+                Scatter::Absorb => continue,
+            }
+            let outside = branch.media.around(hit_object, &ray, hit_point);
+            let split = view.split(hit_object, &ray, hit_point, part, outside);
+            if let Some(recorded) = recorded {
+                (recorded.reflect, recorded.refract, recorded.coat) =
+                    (split.reflect, split.refract, split.coat);
+            }
+            // Rough surfaces share the ray's light between several rays in
+            // their own random directions.
+            let samples = if branch.split > 1 {
+                1
+            } else {
+                view.glossy_samples(hit_object)
+            };
+            let coef = coef / samples as f32;
+            for _ in 0..samples {
+                if let Some(refracted) = &split.refracted {
+                    let refracted = Ray::new(refracted.origin, refracted.direction);
+                    let dispersed = view.disperse(
+                        hit_object,
+                        &ray,
+                        hit_point,
+                        outside,
+                        refracted,
+                        branch.band,
+                        rng,
+                    );
+                    for (refracted, band, weight) in dispersed {
+                        let refracted = view.roughen(hit_object, &ray, hit_point, refracted, rng);
+                        let mut medium_coef = medium_coef;
+                        for (m, w) in medium_coef.iter_mut().zip(weight) {
+                            *m *= w;
+                        }
+                        branches.push(Branch {
+                            media: branch
+                                .media
+                                .crossed(hit_object, &ray, hit_point, &refracted),
+                            ray: refracted,
+                            from,
+                            depth: depth + 1,
+                            coef: coef * split.refract,
+                            medium_coef,
+                            split: branch.split.max(samples),
+                            layers: 0,
+                            band,
+                            parent: index,
+                            kind: BounceKind::Refraction,
+                        });
+                    }
+                }
+                if split.coat > 0.0 {
+                    branches.push(Branch {
+                        ray: view.coat_reflect(hit_object, &ray, hit_point, rng),
+                        from,
+                        depth: depth + 1,
+                        coef: coef * split.coat,
+                        medium_coef,
+                        media: branch.media.clone(),
+                        split: branch.split.max(samples),
+                        layers: 0,
+                        band: branch.band,
+                        parent: index,
+                        kind: BounceKind::Clearcoat,
+                    });
+                }
+                let (reflected, tint) = view.reflect(scene, hit_object, &ray, hit_point, part, rng);
+                let mut medium_coef = medium_coef;
+                for (m, t) in medium_coef.iter_mut().zip(tint) {
+                    *m *= t;
+                }
+                branches.push(Branch {
+                    ray: reflected,
+                    from,
+                    depth: depth + 1,
+                    coef: coef * split.reflect,
+                    medium_coef,
+                    media: branch.media.clone(),
+                    split: branch.split.max(samples),
+                    layers: 0,
+                    band: branch.band,
+                    parent: index,
+                    kind: BounceKind::Reflection,
+                });
+            }
+        }
+        pixel_color.map(|c| c * 255.0)
+    }
+
+    fn name(&self) -> &str {
+        "whitted"
+    }
+}
+
+/// Unidirectional path tracer: each bounce picks a mirror, refracted,
+/// clear coat or cosine-weighted diffuse direction in proportion to the
+/// surface's specular, transparency, coat and lambert coefficients, for up
+/// to the view's `max_depth` bounces. Paths escaping the scene pick up the sky, the view's background
+/// color. At every hit one light, chosen in proportion to its intensity,
+/// is sampled directly with a shadow ray, so small lights light the scene
+/// without needing to be hit by chance. Lights with a radius can also be
+/// hit by bounced rays, as the disk they show the ray's origin; the two
+/// ways of finding them are combined with the power heuristic, so both
+/// large and small lights converge without fireflies. The camera itself
+/// never sees lights. Objects that give off light are sampled the same
+/// way, as lights shaped like the object, so a glowing ball lights its
+/// surroundings without noise from rays that happen to bounce into it.
+/// Light travelling through an absorbing object is absorbed along the
+/// way, and split into colors by a dispersive one. Materials with their
+/// own `Material::scatter` continue paths as it picks, and add the light
+/// they emit. Shadow catchers are ignored.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathTracer;
+
+/// How the ray a path tracer is following was chosen.
+#[derive(Clone, Copy, Debug)]
+enum Bounce {
+    Camera,
+    Specular,
+    /// A diffuse bounce, with the chance density of its direction.
+    Diffuse(f32),
+}
+
+impl Integrator for PathTracer {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        mut recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let sky = view.linear(view.background);
+        let mut ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction,
+        };
+        let mut color = [0.0; 3];
+        let mut throughput = [1.0; 3];
+        let mut media = Media::default();
+        let mut bounce = Bounce::Camera;
+        // The light the path carries alone, once a dispersive object bent
+        // it apart from the rest.
+        let mut band = None;
+        // The part of the surface the path last bounced off, which the
+        // next ray passes over.
+        let mut from = None;
+        // The recorded bounce the path last left, and how.
+        let (mut parent, mut kind) = (None, BounceKind::Camera);
+        let emitters = Emitters::new(scene);
+        for depth in 0..view.max_depth {
+            let index = recorder
+                .as_deref_mut()
+                .map(|recorder| recorder.ray(parent, kind, depth, &ray, throughput));
+            let mask = match bounce {
+                Bounce::Camera => RayMask::CAMERA,
+                _ => RayMask::REFLECTION,
+            };
+            let hit = view.trace(scene, &ray, from, mask, false);
+            let distance = hit.as_ref().map_or(f32::INFINITY, |(_, dist, _, _)| *dist);
+            for (t, a) in throughput
+                .iter_mut()
+                .zip(transmittance(media.absorption(), distance))
+            {
+                *t *= a;
+            }
+            if let Some(scattering) = scene.scattering(&ray, distance) {
+                for i in 0..3 {
+                    color[i] += throughput[i] * scattering.inscatter[i] * 255.0;
+                    throughput[i] *= scattering.transmittance[i];
+                }
+            }
+            if !matches!(bounce, Bounce::Camera) {
+                let emitted = hit_lights(scene, &ray, distance, bounce);
+                for i in 0..3 {
+                    color[i] += throughput[i] * emitted;
+                }
+            }
+            let (point, _, object, part) = match hit {
+                Some(hit) => hit,
+                None => {
+                    let escaped = match bounce {
+                        Bounce::Camera => background,
+                        _ => sky,
+                    };
+                    for i in 0..3 {
+                        color[i] += throughput[i] * escaped[i];
+                    }
+                    break;
+                }
+            };
+            from = part.map(|part| (object, part));
+            let mut recorded = match (recorder.as_deref_mut(), index) {
+                (Some(recorder), Some(index)) => {
+                    Some(recorder.hit(index, view, scene, object, &ray, point, distance, part))
+                }
+                _ => None,
+            };
+            parent = index;
+            let (scatter, emitted) = view.scatter(object, &ray, point, rng);
+            let weight = match bounce {
+                Bounce::Diffuse(bounce_pdf) => {
+                    let light_pdf = emitters.pdf(object, &ray, point);
+                    power_heuristic(bounce_pdf, light_pdf)
+                }
+                _ => 1.0,
+            };
+            for i in 0..3 {
+                color[i] += throughput[i] * emitted[i] * 255.0 * weight;
+            }
+            if let Some(recorded) = recorded.as_deref_mut() {
+                recorded.emitted = emitted.map(|e| e * 255.0 * weight);
+            }
+            match scatter {
+                Scatter::Standard => {}
+                Scatter::Continue(next, share) => {
+                    for (t, s) in throughput.iter_mut().zip(share) {
+                        *t *= s;
+                    }
+                    ray = next;
+                    bounce = Bounce::Specular;
+                    kind = BounceKind::Scatter;
+                    continue;
+                }
+                Scatter::Absorb => break,
+            }
+            let outside = media.around(object, &ray, point);
+            let split = view.split(object, &ray, point, part, outside);
+            if let Some(recorded) = recorded.as_deref_mut() {
+                (recorded.reflect, recorded.refract, recorded.coat) =
+                    (split.reflect, split.refract, split.coat);
+            }
+            let lambert = object.get_lambert() * (1.0 - split.coat);
+            let (specular, transparency) = (split.reflect, split.refract);
+            let total = lambert + specular + transparency + split.coat;
+            if total <= 0.0 {
+                break;
+            }
+            let hit = view.hit_at(scene, object, &ray, point, part);
+            let albedo = view.linear(object.get_color_at(&hit, scene));
+            if lambert > 0.0 {
+                let diffuse_chance = lambert / total;
+                let (normal, chance) = (hit.normal, diffuse_chance);
+                let direct =
+                    sample_light(view, scene, object, point, part, normal, chance, rng) * lambert;
+                let glow = emitters.sample(view, scene, object, point, part, normal, chance, rng);
+                for i in 0..3 {
+                    color[i] += throughput[i] * albedo[i] * (direct + glow[i] * lambert);
+                }
+                if let Some(recorded) = recorded {
+                    recorded.shaded = [0, 1, 2].map(|i| albedo[i] * (direct + glow[i] * lambert));
+                }
+            }
+            // Choosing each lobe in proportion to its weight leaves `total`
+            // as the estimator's weight for any.
+            let pick = rng.next_f32() * total;
+            if pick < specular + transparency {
+                ray = match split.refracted {
+                    Some(refracted) if pick >= specular => {
+                        let mut dispersed =
+                            view.disperse(object, &ray, point, outside, refracted, band, rng);
+                        // Follow one of the rays, weighted by their number.
+                        let count = dispersed.len();
+                        let pick = match count {
+                            1 => 0,
+                            _ => ((rng.next_f32() * count as f32) as usize).min(count - 1),
+                        };
+                        let (refracted, picked, weight) = dispersed.swap_remove(pick);
+                        for (t, w) in throughput.iter_mut().zip(weight) {
+                            *t *= w * count as f32;
+                        }
+                        band = picked;
+                        let refracted = view.roughen(object, &ray, point, refracted, rng);
+                        media = media.crossed(object, &ray, point, &refracted);
+                        kind = BounceKind::Refraction;
+                        refracted
+                    }
+                    _ => {
+                        let (reflected, tint) = view.reflect(scene, object, &ray, point, part, rng);
+                        for (t, c) in throughput.iter_mut().zip(tint) {
+                            *t *= c;
+                        }
+                        kind = BounceKind::Reflection;
+                        reflected
+                    }
+                };
+                throughput = throughput.map(|t| t * total);
+                bounce = Bounce::Specular;
+            } else if pick < specular + transparency + split.coat {
+                ray = view.coat_reflect(object, &ray, point, rng);
+                throughput = throughput.map(|t| t * total);
+                bounce = Bounce::Specular;
+                kind = BounceKind::Clearcoat;
+            } else {
+                for i in 0..3 {
+                    throughput[i] *= albedo[i] / 255.0 * total;
+                }
+                let r = rng.next_f32().sqrt();
+                let phi = 2.0 * PI * rng.next_f32();
+                let origin = vec3_add(point, vec3_scale(hit.normal, view.shadow_bias));
+                ray = Ray::new(origin, disk_to_hemisphere(hit.normal, r, phi));
+                // Cosine-weighted: the cosine is the disk radius' complement.
+                let cos = (1.0 - r * r).max(0.0).sqrt();
+                bounce = Bounce::Diffuse(lambert / total * cos / PI);
+                kind = BounceKind::Diffuse;
+            }
+        }
+        color
+    }
+
+    fn name(&self) -> &str {
+        "path"
+    }
+}
+
+/// Light reaching `point` on `part` of `object`, facing `normal`, from one
+/// light, picked in proportion to intensity and divided by the chance of
+/// picking it. Measured like `View::light_amount`, from a random point on
+/// the disk a light with a radius shows towards `point`, and weighted
+/// against finding the same point by a diffuse bounce, chosen with
+/// `diffuse_chance`.
+#[allow(clippy::too_many_arguments)]
+fn sample_light(
+    view: &View,
+    scene: &Scene,
+    object: &dyn Object,
+    point: Vecf,
+    part: Option<Part>,
+    normal: Vecf,
+    diffuse_chance: f32,
+    rng: &mut Rng,
+) -> f32 {
+    let total: f32 = scene.lights.iter().map(|light| light.intensity).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let mut pick = rng.next_f32() * total;
+    let light = scene
+        .lights
+        .iter()
+        .find(|light| {
+            pick -= light.intensity;
+            pick < 0.0
+        })
+        .unwrap_or(&scene.lights[scene.lights.len() - 1]);
+    if view.out_of_reach(scene, light, point) {
+        return 0.0;
+    }
+    let pick_chance = light.intensity / total;
+    let mut target = light.position;
+    let facing = vec3_normalized(vec3_sub(light.position, point));
+    if light.radius > 0.0 {
+        let r = light.radius * rng.next_f32().sqrt();
+        let phi = 2.0 * PI * rng.next_f32();
+        let (tangent, bitangent) = tangent_frame(facing);
+        target = vec3_add(
+            target,
+            vec3_add(
+                vec3_scale(tangent, r * phi.cos()),
+                vec3_scale(bitangent, r * phi.sin()),
+            ),
+        );
+    }
+    let to_light = vec3_sub(target, point);
+    let distance = vec3_len(to_light);
+    let direction = vec3_scale(to_light, 1.0 / distance);
+    let cos = vec3_dot(direction, normal);
+    let mask = light.shadow_mask;
+    if cos <= 0.0 || view.blocked(scene, point, target, Some(object), part, mask) {
+        return 0.0;
+    }
+    let intensity = light.intensity * scene.intensity_scale();
+    let direct = cos * intensity / (4.0 * PI * distance * distance) / pick_chance;
+    if light.radius <= 0.0 {
+        // Bounced rays can never hit a point light.
+        return direct;
+    }
+    let light_cos = vec3_dot(direction, facing);
+    let light_pdf = pick_chance * disk_pdf(light.radius, distance, light_cos);
+    let bounce_pdf = diffuse_chance * cos / PI;
+    direct * light_cos * power_heuristic(light_pdf, bounce_pdf)
+}
+
+/// Light from every light with a radius that `ray`, chosen by `bounce`,
+/// hits before `distance`, on the 0-255 scale. Diffuse bounces are weighted
+/// against `sample_light` finding the same light.
+fn hit_lights(scene: &Scene, ray: &Ray, distance: f32, bounce: Bounce) -> f32 {
+    let total: f32 = scene.lights.iter().map(|light| light.intensity).sum();
+    let mut emitted = 0.0;
+    for light in &scene.lights {
+        if light.radius <= 0.0 || light.intensity <= 0.0 {
+            continue;
+        }
+        let facing = vec3_sub(light.position, ray.origin);
+        let center_distance = vec3_len(facing);
+        let light_cos = vec3_dot(ray.direction, facing) / center_distance;
+        if light_cos <= 0.0 {
+            continue;
+        }
+        // Distance to the plane of the disk the light shows the origin.
+        let t = center_distance / light_cos;
+        let offset = vec3_sub(
+            vec3_add(ray.origin, vec3_scale(ray.direction, t)),
+            light.position,
+        );
+        if t >= distance || vec3_dot(offset, offset) > light.radius * light.radius {
+            continue;
+        }
+        // Matches `sample_light`: a disk of this radiance lights a diffuse
+        // surface as much as a point light of the same intensity.
+        let radiance = 255.0 * light.intensity * scene.intensity_scale()
+            / (4.0 * PI * light.radius * light.radius);
+        let weight = match bounce {
+            Bounce::Diffuse(bounce_pdf) => {
+                let light_pdf = light.intensity / total * disk_pdf(light.radius, t, light_cos);
+                power_heuristic(bounce_pdf, light_pdf)
+            }
+            _ => 1.0,
+        };
+        emitted += radiance * weight;
+    }
+    emitted
+}
+
+/// Objects whose material gives off light, each picked in proportion to
+/// the light it gives off in all, for aiming rays at.
+struct Emitters<'a> {
+    objects: Vec<(&'a dyn Object, f32)>,
+    total: f32,
+}
+
+impl<'a> Emitters<'a> {
+    /// The bounded objects of `scene` that bounced rays can hit and whose
+    /// material gives off light.
+    fn new(scene: &'a Scene) -> Emitters<'a> {
+        let objects: Vec<(&dyn Object, f32)> = scene
+            .objects
+            .iter()
+            .filter(|object| object.visibility().intersects(RayMask::REFLECTION))
+            .filter_map(|object| {
+                let emission = object.material()?.get_emission();
+                let power = emission.iter().sum::<f32>() * object.area()?;
+                (power > 0.0 && power.is_finite()).then_some((object.as_ref(), power))
+            })
+            .collect();
+        let total = objects.iter().map(|(_, power)| power).sum();
+        Emitters { objects, total }
+    }
+
+    fn pick_chance(&self, object: &dyn Object) -> f32 {
+        self.objects
+            .iter()
+            .find(|(emitter, _)| same_object(*emitter, object))
+            .map_or(0.0, |(_, power)| power / self.total)
+    }
+
+    /// Light reaching `point` on `part` of `object`, facing `normal`, from a
+    /// random point on one emitter, as a share of full light per channel. Like
+    /// `sample_light`, divided by the chance of the pick and weighted
+    /// against finding the same point by a diffuse bounce. Objects never
+    /// light themselves, as convex ones cannot.
+    #[allow(clippy::too_many_arguments)]
+    fn sample(
+        &self,
+        view: &View,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        normal: Vecf,
+        diffuse_chance: f32,
+        rng: &mut Rng,
+    ) -> [f32; 3] {
+        if self.objects.is_empty() {
+            return [0.0; 3];
+        }
+        let mut pick = rng.next_f32() * self.total;
+        let (emitter, power) = *self
+            .objects
+            .iter()
+            .find(|(_, power)| {
+                pick -= power;
+                pick < 0.0
+            })
+            .unwrap_or(&self.objects[self.objects.len() - 1]);
+        if same_object(emitter, object) {
+            return [0.0; 3];
+        }
+        let target = emitter.point_on_surface(rng.next_f32(), rng.next_f32());
+        let to_target = vec3_sub(target, point);
+        let distance = vec3_len(to_target);
+        let direction = vec3_scale(to_target, 1.0 / distance);
+        let cos = vec3_dot(direction, normal);
+        // Stop short of the emitter, which would otherwise block itself;
+        // shadow rays start `shadow_bias` along too.
+        let short = vec3_sub(target, vec3_scale(direction, 2.0 * view.shadow_bias));
+        if distance <= 3.0 * view.shadow_bias
+            || cos <= 0.0
+            || view.blocked(scene, point, short, Some(object), part, RayMask::REFLECTION)
+        {
+            return [0.0; 3];
+        }
+        let ray = Ray::new(point, direction);
+        let hit = view.hit_at(scene, emitter, &ray, target, None);
+        let light_cos = -vec3_dot(direction, hit.normal);
+        let light_pdf = solid_angle_pdf(emitter, power / self.total, distance, light_cos);
+        if !(light_pdf > 0.0 && light_pdf.is_finite()) {
+            return [0.0; 3];
+        }
+        let bounce_pdf = diffuse_chance * cos / PI;
+        let weight = power_heuristic(light_pdf, bounce_pdf) * cos / PI / light_pdf;
+        let emitted = emitter.material().map_or([0.0; 3], |m| m.emitted(&hit));
+        emitted.map(|e| e * weight)
+    }
+
+    /// Chance density per solid angle of `sample` aiming `ray` at `point`
+    /// on `object`, 0 if it never picks the object.
+    fn pdf(&self, object: &dyn Object, ray: &Ray, point: Vecf) -> f32 {
+        let pick_chance = self.pick_chance(object);
+        if pick_chance <= 0.0 {
+            return 0.0;
+        }
+        // `View::scatter` has already recorded any degenerate normal.
+        let normal = object.hit_at(ray, point).normal;
+        let distance = vec3_len(vec3_sub(point, ray.origin));
+        solid_angle_pdf(
+            object,
+            pick_chance,
+            distance,
+            -vec3_dot(ray.direction, normal),
+        )
+    }
+}
+
+/// Chance density per solid angle of picking, with `pick_chance`, a
+/// random point `distance` away on `emitter`, at `light_cos` to its normal.
+fn solid_angle_pdf(emitter: &dyn Object, pick_chance: f32, distance: f32, light_cos: f32) -> f32 {
+    let area = emitter.area().unwrap_or(0.0);
+    pick_chance * distance * distance / (area * light_cos)
+}
+
+/// Chance density per solid angle of picking a direction `distance` away
+/// through a uniformly sampled disk of `radius`, at `cos` to its normal.
+fn disk_pdf(radius: f32, distance: f32, cos: f32) -> f32 {
+    distance * distance / (PI * radius * radius * cos)
+}
+
+/// Weight of a sample drawn with density `pdf` that another technique
+/// could also have drawn with density `other`.
+fn power_heuristic(pdf: f32, other: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Bidirectional path tracer: every sample traces a path from the camera
+/// and one from a light, picked in proportion to intensity, bouncing like
+/// the `PathTracer` for up to the view's `max_depth` bounces, then joins
+/// each diffuse vertex of the camera path to each vertex of the light path
+/// with a shadow ray. Every complete path can be built in several of these
+/// ways; their results are weighted with the power heuristic. Light that
+/// reaches the visible surfaces through small openings or after several
+/// diffuse bounces is found far sooner than by the `PathTracer`, as are
+/// caustics of point lights, which it cannot find at all. Light
+/// paths are not joined to the camera itself, so the camera never sees
+/// lights, as with the other integrators. Lights with a radius are glowing
+/// spheres, slightly brighter up close than the disks the `PathTracer`
+/// sees. Fog, clouds, absorption and shadow catchers are ignored. Paths
+/// end at materials with their own `Material::scatter`, which come out
+/// black, and materials that give off light are shaded without it; both
+/// record a `RenderWarning::UnsupportedMaterial`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bidirectional;
+
+impl Integrator for Bidirectional {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let max_depth = view.max_depth as usize;
+        let sky = view.linear(view.background);
+        let light_path = light_path(view, scene, max_depth, rng);
+        let mut color = [0.0; 3];
+        let mut camera_path = vec![Vertex {
+            kind: VertexKind::Camera,
+            point: ray.origin,
+            normal: [0.0; 3],
+            beta: [1.0; 3],
+            delta: false,
+            pdf_fwd: 1.0,
+            pdf_rev: 0.0,
+        }];
+        let camera_ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction,
+        };
+        // One surface more than a light path joins to, so lights hit just
+        // past the last surface joined to count too.
+        let escaped = walk(
+            view,
+            scene,
+            &mut camera_path,
+            camera_ray,
+            [1.0; 3],
+            1.0,
+            max_depth + 2,
+            rng,
+            recorder,
+            |path, ray, distance, beta, pdf| {
+                // Paths that hit a light on the way to the next surface.
+                if path.len() < 2 {
+                    return;
+                }
+                for (index, light) in scene.lights.iter().enumerate() {
+                    let t = match hit_light_sphere(light, ray) {
+                        Some(t) if t < distance => t,
+                        _ => continue,
+                    };
+                    let point = vec3_add(ray.origin, vec3_scale(ray.direction, t));
+                    let normal = vec3_normalized(vec3_sub(point, light.position));
+                    let emitted = emitted(scene, light, normal, vec3_scale(ray.direction, -1.0));
+                    if emitted <= 0.0 {
+                        continue;
+                    }
+                    let mut path = path.to_vec();
+                    let mut vertex = Vertex {
+                        kind: VertexKind::Light(index),
+                        point,
+                        normal,
+                        beta,
+                        delta: false,
+                        pdf_fwd: 0.0,
+                        pdf_rev: 0.0,
+                    };
+                    vertex.pdf_fwd = path[path.len() - 1].area_pdf(pdf, &vertex);
+                    path.push(vertex);
+                    let weight = mis_weight(scene, &path, &[]);
+                    for i in 0..3 {
+                        color[i] += beta[i] * emitted * weight;
+                    }
+                }
+            },
+        );
+        if let Some(beta) = escaped {
+            let escaped = if camera_path.len() == 1 {
+                background
+            } else {
+                sky
+            };
+            for i in 0..3 {
+                color[i] += beta[i] * escaped[i];
+            }
+        }
+        for t in 2..=camera_path.len() {
+            for s in 1..=light_path.len() {
+                if s + t - 2 > max_depth {
+                    break;
+                }
+                let joined = connect(view, scene, &camera_path[..t], &light_path[..s]);
+                for i in 0..3 {
+                    color[i] += joined[i];
+                }
+            }
+        }
+        color
+    }
+
+    fn name(&self) -> &str {
+        "bidirectional"
+    }
+}
+
+#[derive(Clone, Copy)]
+enum VertexKind<'a> {
+    Camera,
+    /// On the sphere of the light with this index, or at its center for a
+    /// point light.
+    Light(usize),
+    Surface {
+        object: &'a dyn Object,
+        part: Option<Part>,
+        /// Surface color on a 0-1 scale.
+        albedo: [f32; 3],
+        lambert: f32,
+        specular: f32,
+    },
+}
+
+/// A point on a camera or light path of the `Bidirectional` integrator.
+#[derive(Clone, Copy)]
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    point: Vecf,
+    /// Facing the path's previous vertex on surfaces and outwards on lights
+    /// with a radius; zero on the camera and point lights.
+    normal: Vecf,
+    /// What the path carries to this vertex divided by the chance density
+    /// of the path so far: light on light paths, importance on camera paths.
+    beta: [f32; 3],
+    /// Whether the path left this vertex by a mirror bounce.
+    delta: bool,
+    /// Chance density per area of reaching this vertex from the path's
+    /// previous vertex, and of reaching it from the next one going the
+    /// other way. 0 after and before a mirror bounce.
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl Vertex<'_> {
+    fn on_surface(&self) -> bool {
+        self.normal != [0.0; 3]
+    }
+
+    /// Density per area at `next` of a density `pdf` per solid angle of
+    /// leaving this vertex towards it.
+    fn area_pdf(&self, pdf: f32, next: &Vertex) -> f32 {
+        let to_next = vec3_sub(next.point, self.point);
+        let distance2 = vec3_dot(to_next, to_next);
+        let mut pdf = pdf / distance2;
+        if next.on_surface() {
+            pdf *= vec3_dot(next.normal, to_next).abs() / distance2.sqrt();
+        }
+        pdf
+    }
+
+    /// Chance density per area of a path going on from this vertex to
+    /// `next` by a diffuse bounce or light emission.
+    fn pdf(&self, scene: &Scene, next: &Vertex) -> f32 {
+        let direction = vec3_normalized(vec3_sub(next.point, self.point));
+        let pdf = match self.kind {
+            VertexKind::Camera => 0.0,
+            VertexKind::Light(index) => emission_pdf(&scene.lights[index], self.normal, direction),
+            VertexKind::Surface {
+                lambert, specular, ..
+            } => lambert / (lambert + specular) * vec3_dot(self.normal, direction).max(0.0) / PI,
+        };
+        self.area_pdf(pdf, next)
+    }
+
+    /// Diffuse reflectance towards `direction`, zero for all but surfaces
+    /// and for directions behind the surface.
+    fn f(&self, direction: Vecf) -> [f32; 3] {
+        match self.kind {
+            VertexKind::Surface {
+                albedo, lambert, ..
+            } if vec3_dot(self.normal, direction) > 0.0 => albedo.map(|a| a * lambert / PI),
+            _ => [0.0; 3],
+        }
+    }
+}
+
+/// Starts a light path at a light picked in proportion to intensity: a
+/// uniform point on its sphere, sending light out cosine-weighted around
+/// the sphere's normal, or its center sending light out in all directions
+/// alike. Then bounces it for up to `max_vertices` vertices in all.
+fn light_path<'a>(
+    view: &View,
+    scene: &'a Scene,
+    max_vertices: usize,
+    rng: &mut Rng,
+) -> Vec<Vertex<'a>> {
+    let total: f32 = scene.lights.iter().map(|light| light.intensity).sum();
+    if total <= 0.0 || max_vertices == 0 {
+        return Vec::new();
+    }
+    let mut pick = rng.next_f32() * total;
+    let index = scene
+        .lights
+        .iter()
+        .position(|light| {
+            pick -= light.intensity;
+            pick < 0.0
+        })
+        .unwrap_or(scene.lights.len() - 1);
+    let light = &scene.lights[index];
+    let (point, normal, direction, pdf_dir) = if light.radius > 0.0 {
+        let normal = uniform_sphere(rng);
+        let point = vec3_add(light.position, vec3_scale(normal, light.radius));
+        let r = rng.next_f32().sqrt();
+        let phi = 2.0 * PI * rng.next_f32();
+        let direction = disk_to_hemisphere(normal, r, phi);
+        (point, normal, direction, (1.0 - r * r).max(0.0).sqrt() / PI)
+    } else {
+        (
+            light.position,
+            [0.0; 3],
+            uniform_sphere(rng),
+            1.0 / (4.0 * PI),
+        )
+    };
+    let origin_pdf = light_origin_pdf(scene, index);
+    let cos = if light.radius > 0.0 {
+        vec3_dot(normal, direction)
+    } else {
+        1.0
+    };
+    let beta = emitted(scene, light, normal, direction) * cos / (origin_pdf * pdf_dir);
+    let mut path = vec![Vertex {
+        kind: VertexKind::Light(index),
+        point,
+        normal,
+        beta: [1.0 / origin_pdf; 3],
+        delta: false,
+        pdf_fwd: origin_pdf,
+        pdf_rev: 0.0,
+    }];
+    walk(
+        view,
+        scene,
+        &mut path,
+        Ray::new(point, direction),
+        [beta; 3],
+        pdf_dir,
+        max_vertices,
+        rng,
+        None,
+        |_, _, _, _, _| {},
+    );
+    path
+}
+
+/// Extends `path` from its last vertex along `ray`, chosen with density
+/// `pdf` per solid angle, until it has `max_vertices` vertices or is
+/// absorbed, bouncing like the `PathTracer`. `beta` is what the path
+/// carries along `ray`. `segment` sees every stretch of ray travelled:
+/// the path so far, the ray, the distance to the surface it hits (infinite
+/// for none), and the `beta` and `pdf` it was taken with. `recorder`
+/// takes the rays of a camera path. Returns the `beta` of a path that left
+/// the scene.
+#[allow(clippy::too_many_arguments)]
+fn walk<'a>(
+    view: &View,
+    scene: &'a Scene,
+    path: &mut Vec<Vertex<'a>>,
+    mut ray: Ray,
+    mut beta: [f32; 3],
+    mut pdf: f32,
+    max_vertices: usize,
+    rng: &mut Rng,
+    mut recorder: Option<&mut Recorder>,
+    mut segment: impl FnMut(&[Vertex], &Ray, f32, [f32; 3], f32),
+) -> Option<[f32; 3]> {
+    let mut media = Media::default();
+    // The recorded bounce the path last left, and how.
+    let (mut parent, mut kind) = (None, BounceKind::Camera);
+    while path.len() < max_vertices {
+        let depth = path.len() as u32 - 1;
+        let index = recorder
+            .as_deref_mut()
+            .map(|recorder| recorder.ray(parent, kind, depth, &ray, beta));
+        let (mask, from) = match path.last().map(|vertex| vertex.kind) {
+            Some(VertexKind::Camera) => (RayMask::CAMERA, None),
+            Some(VertexKind::Surface { object, part, .. }) => {
+                (RayMask::REFLECTION, part.map(|part| (object, part)))
+            }
+            _ => (RayMask::REFLECTION, None),
+        };
+        let hit = view.trace(scene, &ray, from, mask, false);
+        let distance = hit.as_ref().map_or(f32::INFINITY, |(_, dist, _, _)| *dist);
+        segment(path, &ray, distance, beta, pdf);
+        let (point, _, object, part) = match hit {
+            Some(hit) => hit,
+            None => return Some(beta),
+        };
+        let recorded = match (recorder.as_deref_mut(), index) {
+            (Some(recorder), Some(index)) => {
+                Some(recorder.hit(index, view, scene, object, &ray, point, distance, part))
+            }
+            _ => None,
+        };
+        parent = index;
+        // Light a material sends its own way cannot be weighed against the
+        // other ways of building the path.
+        let (scatter, emitted) = view.scatter(object, &ray, point, rng);
+        let custom = !matches!(scatter, Scatter::Standard);
+        if custom || emitted != [0.0; 3] {
+            scene.warnings.record(RenderWarning::UnsupportedMaterial);
+        }
+        if custom {
+            break;
+        }
+        let hit = view.hit_at(scene, object, &ray, point, part);
+        let outside = media.around(object, &ray, point);
+        let split = view.split(object, &ray, point, part, outside);
+        if let Some(recorded) = recorded {
+            (recorded.reflect, recorded.refract, recorded.coat) =
+                (split.reflect, split.refract, split.coat);
+        }
+        let lambert = object.get_lambert() * (1.0 - split.coat);
+        // Mirrors, refraction and clear coats are all delta lobes, told
+        // apart below.
+        let specular = split.reflect + split.refract + split.coat;
+        let albedo = view
+            .linear(object.get_color_at(&hit, scene))
+            .map(|c| c / 255.0);
+        let previous = path.len() - 1;
+        let mut vertex = Vertex {
+            kind: VertexKind::Surface {
+                object,
+                part,
+                albedo,
+                lambert,
+                specular,
+            },
+            point,
+            normal: hit.normal,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        vertex.pdf_fwd = path[previous].area_pdf(pdf, &vertex);
+        path.push(vertex);
+        let total = lambert + specular;
+        if total <= 0.0 {
+            break;
+        }
+        let current = previous + 1;
+        let pick = rng.next_f32() * total;
+        if pick < specular {
+            ray = match split.refracted {
+                _ if pick >= split.reflect + split.refract => {
+                    kind = BounceKind::Clearcoat;
+                    view.coat_reflect(object, &ray, point, rng)
+                }
+                Some(refracted) if pick >= split.reflect => {
+                    let refracted = view.roughen(object, &ray, point, refracted, rng);
+                    media = media.crossed(object, &ray, point, &refracted);
+                    kind = BounceKind::Refraction;
+                    refracted
+                }
+                _ => {
+                    let (reflected, tint) = view.reflect(scene, object, &ray, point, part, rng);
+                    for (b, t) in beta.iter_mut().zip(tint) {
+                        *b *= t;
+                    }
+                    kind = BounceKind::Reflection;
+                    reflected
+                }
+            };
+            beta = beta.map(|b| b * total);
+            pdf = 0.0;
+            path[current].delta = true;
+            path[previous].pdf_rev = 0.0;
+        } else {
+            for i in 0..3 {
+                beta[i] *= albedo[i] * total;
+            }
+            let r = rng.next_f32().sqrt();
+            let phi = 2.0 * PI * rng.next_f32();
+            let origin = vec3_add(point, vec3_scale(hit.normal, view.shadow_bias));
+            ray = Ray::new(origin, disk_to_hemisphere(hit.normal, r, phi));
+            pdf = lambert / total * (1.0 - r * r).max(0.0).sqrt() / PI;
+            kind = BounceKind::Diffuse;
+            path[previous].pdf_rev = path[current].pdf(scene, &path[previous]);
+        }
+    }
+    None
+}
+
+/// Light that the light path `light` brings to the camera through the
+/// camera path `camera` by a shadow ray between their last vertices,
+/// weighted against the other ways of building the same path.
+fn connect(view: &View, scene: &Scene, camera: &[Vertex], light: &[Vertex]) -> [f32; 3] {
+    let z = &camera[camera.len() - 1];
+    let y = &light[light.len() - 1];
+    let to_light = vec3_sub(y.point, z.point);
+    let distance2 = vec3_dot(to_light, to_light);
+    let direction = vec3_scale(to_light, 1.0 / distance2.sqrt());
+    let back = vec3_scale(direction, -1.0);
+    let camera_f = z.f(direction);
+    let light_f = match y.kind {
+        VertexKind::Light(index) => [emitted(scene, &scene.lights[index], y.normal, back); 3],
+        _ => y.f(back),
+    };
+    let mut joined = [0.0; 3];
+    for i in 0..3 {
+        joined[i] = z.beta[i] * camera_f[i] * light_f[i] * y.beta[i];
+    }
+    if joined == [0.0; 3] {
+        return joined;
+    }
+    let light_cos = if y.on_surface() {
+        vec3_dot(y.normal, back).abs()
+    } else {
+        1.0
+    };
+    let geometry = vec3_dot(z.normal, direction) * light_cos / distance2;
+    // Shadow rays start `shadow_bias` along; stopping as far short of `y`
+    // keeps the surface under it from blocking its own light.
+    let target = vec3_sub(y.point, vec3_scale(direction, 2.0 * view.shadow_bias));
+    let (receiver, part) = match z.kind {
+        VertexKind::Surface { object, part, .. } => (Some(object), part),
+        _ => (None, None),
+    };
+    // Joining two surfaces stands in for a bounce between them.
+    let mask = match y.kind {
+        VertexKind::Light(index) => scene.lights[index].shadow_mask,
+        _ => RayMask::REFLECTION,
+    };
+    if geometry <= 0.0 || view.blocked(scene, z.point, target, receiver, part, mask) {
+        return [0.0; 3];
+    }
+    let weight = mis_weight(scene, camera, light) * geometry;
+    joined.map(|c| c * weight)
+}
+
+/// Power heuristic weight of building the path `camera` + `light` by
+/// joining their last vertices, among all ways of building it from a
+/// camera path of at least two vertices and a light path. `light` may be
+/// empty for camera paths that end on a light.
+fn mis_weight(scene: &Scene, camera: &[Vertex], light: &[Vertex]) -> f32 {
+    let mut camera = camera.to_vec();
+    let mut light = light.to_vec();
+    let z = camera.len() - 1;
+    // Densities at and around the join, which the walks could not know.
+    if let Some(y) = light.len().checked_sub(1) {
+        camera[z].pdf_rev = light[y].pdf(scene, &camera[z]);
+        camera[z - 1].pdf_rev = camera[z].pdf(scene, &camera[z - 1]);
+        light[y].pdf_rev = camera[z].pdf(scene, &light[y]);
+        if y > 0 {
+            light[y - 1].pdf_rev = light[y].pdf(scene, &light[y - 1]);
+        }
+        camera[z].delta = false;
+        light[y].delta = false;
+    } else if let VertexKind::Light(index) = camera[z].kind {
+        camera[z].pdf_rev = light_origin_pdf(scene, index);
+        camera[z - 1].pdf_rev = camera[z].pdf(scene, &camera[z - 1]);
+    }
+    // Mirror bounces leave densities of 0, which do not change the ratios.
+    let remap = |pdf: f32| if pdf == 0.0 { 1.0 } else { pdf };
+    let mut others = 0.0;
+    let mut ratio = 1.0;
+    for i in (1..camera.len()).rev() {
+        ratio *= remap(camera[i].pdf_rev) / remap(camera[i].pdf_fwd);
+        // Light paths are never joined to the camera vertex itself.
+        if i > 1 && !camera[i].delta && !camera[i - 1].delta {
+            others += ratio * ratio;
+        }
+    }
+    ratio = 1.0;
+    for i in (0..light.len()).rev() {
+        ratio *= remap(light[i].pdf_rev) / remap(light[i].pdf_fwd);
+        let previous_delta = match i {
+            0 => {
+                matches!(light[0].kind, VertexKind::Light(index) if scene.lights[index].radius <= 0.0)
+            }
+            _ => light[i - 1].delta,
+        };
+        if !light[i].delta && !previous_delta {
+            others += ratio * ratio;
+        }
+    }
+    1.0 / (1.0 + others)
+}
+
+/// Chance density per area of a light path starting where it does on the
+/// light with `index`: the chance of picking the light times the density
+/// on its sphere, or times 1 for a point light.
+fn light_origin_pdf(scene: &Scene, index: usize) -> f32 {
+    let total: f32 = scene.lights.iter().map(|light| light.intensity).sum();
+    let light = &scene.lights[index];
+    let pick_chance = light.intensity / total;
+    if light.radius > 0.0 {
+        pick_chance / (4.0 * PI * light.radius * light.radius)
+    } else {
+        pick_chance
+    }
+}
+
+/// Chance density per solid angle of a light path leaving `light` in
+/// `direction`, from a point with `normal` on its sphere.
+fn emission_pdf(light: &Light, normal: Vecf, direction: Vecf) -> f32 {
+    if light.radius > 0.0 {
+        vec3_dot(normal, direction).max(0.0) / PI
+    } else {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// Light `light` sends out in `direction`, on the 0-255 scale: radiance
+/// from a point with `normal` on its sphere, or intensity for a point
+/// light. Both match the `PathTracer`'s lights.
+fn emitted(scene: &Scene, light: &Light, normal: Vecf, direction: Vecf) -> f32 {
+    let intensity = light.intensity * scene.intensity_scale();
+    if light.radius > 0.0 {
+        if vec3_dot(normal, direction) > 0.0 {
+            255.0 * intensity / (4.0 * PI * light.radius * light.radius)
+        } else {
+            0.0
+        }
+    } else {
+        255.0 * intensity / 4.0
+    }
+}
+
+/// Distance along `ray` to where it enters the sphere of `light`, if it
+/// does; rays starting inside never hit it.
+fn hit_light_sphere(light: &Light, ray: &Ray) -> Option<f32> {
+    if light.radius <= 0.0 {
+        return None;
+    }
+    let to_origin = vec3_sub(ray.origin, light.position);
+    let b = vec3_dot(to_origin, ray.direction);
+    let c = vec3_dot(to_origin, to_origin) - light.radius * light.radius;
+    let discriminant = b * b - c;
+    if c <= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = -b - discriminant.sqrt();
+    if t > 0.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn uniform_sphere(rng: &mut Rng) -> Vecf {
+    let z = 1.0 - 2.0 * rng.next_f32();
+    let phi = 2.0 * PI * rng.next_f32();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    [r * phi.cos(), r * phi.sin(), z]
+}
+
+/// Ambient occlusion pass: white where the hemisphere around the first hit
+/// is open within `distance`, darker the more of it other objects block,
+/// estimated from `samples` random cosine-weighted rays, or by cones with
+/// `View::with_cone_tracing`. Misses are white.
+#[derive(Clone, Copy, Debug)]
+pub struct AmbientOcclusion {
+    distance: f32,
+    samples: u32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32, samples: u32) -> AmbientOcclusion {
+        AmbientOcclusion { distance, samples }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        _background: [f32; 3],
+        rng: &mut Rng,
+        _recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let hit = view.trace(scene, ray, None, RayMask::CAMERA, false);
+        self.occlusion(view, scene, ray, hit, rng)
+    }
+
+    fn li_from_hit(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        hit: Option<(Vecf, f32, &dyn Object, Option<Part>)>,
+        background: [f32; 3],
+        rng: &mut Rng,
+    ) -> [f32; 3] {
+        // Shadow catchers are seen through.
+        match hit {
+            Some((_, _, object, _)) if object.is_shadow_catcher() => {
+                self.li(view, scene, ray, background, rng, None)
+            }
+            _ => self.occlusion(view, scene, ray, hit, rng),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "ambient occlusion"
+    }
+}
+
+impl AmbientOcclusion {
+    /// The pass's value for camera ray `ray` first hitting `hit`.
+    fn occlusion(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        hit: Option<(Vecf, f32, &dyn Object, Option<Part>)>,
+        rng: &mut Rng,
+    ) -> [f32; 3] {
+        let (point, _, object, part) = match hit {
+            Some(hit) => hit,
+            None => return [255.0; 3],
+        };
+        if self.samples == 0 {
+            return [255.0; 3];
+        }
+        let normal = view.hit_at(scene, object, ray, point, part).normal;
+        let origin = vec3_add(point, vec3_scale(normal, view.shadow_bias));
+        let casts = |other: &dyn Object| other.visibility().intersects(RayMask::SHADOW);
+        let mut cone_open = 1.0;
+        if view.cone_tracing {
+            let others = scene
+                .objects
+                .iter()
+                .map(|other| other.as_ref())
+                .filter(|other| casts(*other) && !same_object(*other, object));
+            cone_open =
+                DistanceField::around(others, origin).occlusion(origin, normal, self.distance);
+            if scene.objects.iter().all(|other| other.shape().is_some()) {
+                return [255.0 * cone_open; 3];
+            }
+        }
+        // Rays only need to find what the cone could not.
+        let sampled =
+            |other: &dyn Object| casts(other) && !(view.cone_tracing && other.shape().is_some());
+        let mut open = 0;
+        for _ in 0..self.samples {
+            let r = rng.next_f32().sqrt();
+            let phi = 2.0 * PI * rng.next_f32();
+            let direction = disk_to_hemisphere(normal, r, phi);
+            let ray = Ray::new(origin, direction);
+            let from = part.map(|part| (object, part));
+            if !scene.occluded_from(&ray, self.distance, from, |_, other| sampled(other)) {
+                open += 1;
+            }
+        }
+        [255.0 * cone_open * open as f32 / self.samples as f32; 3]
+    }
+}
+
+/// Shows every surface along camera rays as a translucent layer of
+/// `opacity`, composited front to back over the background, e.g. to see
+/// nested shells inside each other. Layers are found by tracing on past
+/// each hit, up to `max_layers` per ray, and lit by diffuse light without
+/// shadows, so outer shells do not darken inner ones. Reflections, fog,
+/// clouds and shadow catchers are ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct DepthPeeling {
+    opacity: f32,
+    max_layers: u32,
+}
+
+impl DepthPeeling {
+    pub fn new(opacity: f32, max_layers: u32) -> DepthPeeling {
+        DepthPeeling {
+            opacity,
+            max_layers,
+        }
+    }
+}
+
+impl Integrator for DepthPeeling {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        _rng: &mut Rng,
+        _recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let mut ray = Ray::new(ray.origin, ray.direction);
+        let mut color = [0.0; 3];
+        // Share of the layers behind that still shows through.
+        let mut transmittance = 1.0;
+        let mut from = None;
+        for _ in 0..self.max_layers {
+            let (point, _, object, part) =
+                match view.trace(scene, &ray, from, RayMask::CAMERA, false) {
+                    Some(hit) => hit,
+                    None => break,
+                };
+            let hit = view.hit_at(scene, object, &ray, point, part);
+            let albedo = view.linear(object.get_color_at(&hit, scene));
+            let light = view.light_amount(scene, object, point, part, false);
+            for i in 0..3 {
+                let weight =
+                    transmittance * self.opacity * light[i].min(1.0) * object.get_lambert();
+                color[i] += weight * albedo[i];
+            }
+            transmittance *= 1.0 - self.opacity;
+            if transmittance <= 0.0 {
+                return color;
+            }
+            ray.origin = vec3_add(point, vec3_scale(ray.direction, view.shadow_bias));
+            from = part.map(|part| (object, part));
+        }
+        for i in 0..3 {
+            color[i] += transmittance * background[i];
+        }
+        color
+    }
+
+    fn name(&self) -> &str {
+        "depth peeling"
+    }
+}
+
+/// Debug view of the surface normal at the first hit, facing the camera,
+/// with each axis mapped from -1..1 to 0..255. Misses are black.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        _recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let hit = view.trace(scene, ray, None, RayMask::CAMERA, true);
+        self.li_from_hit(view, scene, ray, hit, background, rng)
+    }
+
+    fn li_from_hit(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        hit: Option<(Vecf, f32, &dyn Object, Option<Part>)>,
+        _background: [f32; 3],
+        _rng: &mut Rng,
+    ) -> [f32; 3] {
+        match hit {
+            Some((point, _, object, part)) => {
+                let normal: Vecf = view.hit_at(scene, object, ray, point, part).normal;
+                normal.map(|n| (n * 0.5 + 0.5) * 255.0)
+            }
+            None => [0.0; 3],
+        }
+    }
+
+    fn name(&self) -> &str {
+        "normals"
+    }
+}
+
+/// Debug view of the distance to the first hit: white at the camera,
+/// fading to black at `max_distance` and beyond. Misses are black.
+#[derive(Clone, Copy, Debug)]
+pub struct Depth {
+    max_distance: f32,
+}
+
+impl Depth {
+    pub fn new(max_distance: f32) -> Depth {
+        Depth { max_distance }
+    }
+}
+
+impl Integrator for Depth {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        _recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        let hit = view.trace(scene, ray, None, RayMask::CAMERA, true);
+        self.li_from_hit(view, scene, ray, hit, background, rng)
+    }
+
+    fn li_from_hit(
+        &self,
+        _view: &View,
+        _scene: &Scene,
+        ray: &Ray,
+        hit: Option<(Vecf, f32, &dyn Object, Option<Part>)>,
+        _background: [f32; 3],
+        _rng: &mut Rng,
+    ) -> [f32; 3] {
+        match hit {
+            Some((point, _, _, _)) => {
+                let distance = vec3_len(vec3_sub(point, ray.origin));
+                [255.0 * (1.0 - distance / self.max_distance).max(0.0); 3]
+            }
+            None => [0.0; 3],
+        }
+    }
+
+    fn name(&self) -> &str {
+        "depth"
+    }
+}
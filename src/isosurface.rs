@@ -0,0 +1,483 @@
+//! 3D scalar fields, e.g. simulation data, shown as isosurfaces found by
+//! ray marching the field directly instead of meshing it first, and as
+//! slices colored by the field's value.
+
+use image::Rgb;
+use std::sync::Arc;
+use vecmath::{vec3_add, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub};
+
+use crate::{
+    scene::{texture_color, Hit, Object, Scene},
+    texture::{mix, Texture},
+    view::Ray,
+    Color, Vecf,
+};
+
+/// Most samples taken along a ray, however small the step.
+const MAX_STEPS: usize = 1024;
+/// Samples along the diagonal of the bounds with the default step.
+const DEFAULT_STEPS: f32 = 128.0;
+/// Halvings of the step a crossing is narrowed down by.
+const BISECTIONS: usize = 16;
+/// Share of a step rays travel before their first sample, so rays leaving
+/// the surface do not hit it again straight away.
+const LEAVE_DISTANCE: f32 = 1e-2;
+/// Hits closer than this to the ray origin are ignored, so rays leaving a
+/// slice do not hit it again.
+const MIN_DISTANCE: f32 = 1e-5;
+
+/// A value at every point in space.
+pub trait ScalarField: Send + Sync {
+    fn value(&self, point: Vecf) -> f32;
+}
+
+/// Closures over the point work as fields, e.g.
+/// `|p: Vecf| p[0] * p[0] + p[1] * p[1] + p[2] * p[2]` for a ball.
+impl<F> ScalarField for F
+where
+    F: Fn(Vecf) -> f32 + Send + Sync,
+{
+    fn value(&self, point: Vecf) -> f32 {
+        self(point)
+    }
+}
+
+/// Values sampled on a regular grid spanning a box, such as a simulation's
+/// output, interpolated trilinearly between samples. Points outside the
+/// box take the value at the nearest point on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    min: Vecf,
+    max: Vecf,
+    size: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl Grid {
+    /// `size` samples along x, y and z, from `min` to `max`, with `values`
+    /// running along x first, then y, then z. Panics unless there are
+    /// at least two samples along every axis and one value for each.
+    pub fn new(min: Vecf, max: Vecf, size: [usize; 3], values: Vec<f32>) -> Grid {
+        assert!(
+            size.iter().all(|&n| n >= 2),
+            "grid needs two samples along every axis"
+        );
+        assert_eq!(
+            values.len(),
+            size[0] * size[1] * size[2],
+            "values do not match the grid size"
+        );
+        Grid {
+            min,
+            max,
+            size,
+            values,
+        }
+    }
+
+    /// The corners of the box the grid spans.
+    pub fn bounds(&self) -> [Vecf; 2] {
+        [self.min, self.max]
+    }
+
+    fn sample(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[(z * self.size[1] + y) * self.size[0] + x]
+    }
+}
+
+impl ScalarField for Grid {
+    fn value(&self, point: Vecf) -> f32 {
+        let mut cell = [0; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let last = (self.size[axis] - 1) as f32;
+            let at = ((point[axis] - self.min[axis]) / (self.max[axis] - self.min[axis]) * last)
+                .clamp(0.0, last);
+            cell[axis] = (at as usize).min(self.size[axis] - 2);
+            weight[axis] = at - cell[axis] as f32;
+        }
+        let [x, y, z] = cell;
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let along_x = |y, z| lerp(self.sample(x, y, z), self.sample(x + 1, y, z), weight[0]);
+        let along_y = |z| lerp(along_x(y, z), along_x(y + 1, z), weight[1]);
+        lerp(along_y(z), along_y(z + 1), weight[2])
+    }
+}
+
+/// Colors for field values, blended linearly between stops. Values below
+/// the first stop or above the last take its color; without stops, every
+/// value is black.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Color)>,
+}
+
+impl ColorRamp {
+    pub fn new() -> ColorRamp {
+        ColorRamp::default()
+    }
+
+    /// Adds a stop giving `value` the color `color`.
+    pub fn with_stop(mut self, value: f32, color: Color) -> ColorRamp {
+        self.stops.push((value, color));
+        self.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn color_at(&self, value: f32) -> Color {
+        blend(&self.stops, value, mix).unwrap_or(Rgb([0; 3]))
+    }
+}
+
+/// Color and opacity for field values, for rendering a `Volume`: each
+/// point glows with its color and hides what is behind it by its opacity,
+/// the share of light absorbed per unit length. Both blend linearly
+/// between stops like a `ColorRamp`; without stops, every value is clear.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TransferFunction {
+    colors: ColorRamp,
+    opacities: Vec<(f32, f32)>,
+}
+
+impl TransferFunction {
+    pub fn new() -> TransferFunction {
+        TransferFunction::default()
+    }
+
+    /// Adds a stop giving `value` the color `color` and opacity `opacity`,
+    /// e.g. a high opacity for bone and a low one for soft tissue.
+    pub fn with_stop(mut self, value: f32, color: Color, opacity: f32) -> TransferFunction {
+        self.colors = self.colors.with_stop(value, color);
+        self.opacities.push((value, opacity));
+        self.opacities.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn color_at(&self, value: f32) -> Color {
+        self.colors.color_at(value)
+    }
+
+    pub fn opacity_at(&self, value: f32) -> f32 {
+        blend(&self.opacities, value, |a, b, t| a + (b - a) * t).unwrap_or(0.0)
+    }
+}
+
+/// The value of sorted `stops` at `value` by `lerp` between the stops
+/// around it, or `None` without stops.
+fn blend<T: Copy>(stops: &[(f32, T)], value: f32, lerp: impl Fn(T, T, f32) -> T) -> Option<T> {
+    let above = stops.iter().position(|&(stop, _)| stop > value);
+    match above {
+        Some(0) => Some(stops[0].1),
+        Some(i) => {
+            let (low, low_value) = stops[i - 1];
+            let (high, high_value) = stops[i];
+            Some(lerp(low_value, high_value, (value - low) / (high - low)))
+        }
+        None => stops.last().map(|&(_, last)| last),
+    }
+}
+
+/// The surface where a field takes `iso_value`, within a box. Rays march
+/// through the box in fixed steps, so features thinner than a step may be
+/// missed; normals follow the field's gradient.
+#[derive(Clone)]
+pub struct Isosurface {
+    field: Arc<dyn ScalarField>,
+    iso_value: f32,
+    bounds: [Vecf; 2],
+    step: f32,
+    /// Point on and normal of the plane set with `with_clip`.
+    clip: Option<(Vecf, Vecf)>,
+    texture: Arc<dyn Texture>,
+    lambert: f32,
+    specular: f32,
+}
+
+impl Isosurface {
+    /// Where `field` takes `iso_value` between the corners `min` and
+    /// `max`, marched in 1/128 of the box's diagonal per step.
+    pub fn new<F: ScalarField + 'static>(
+        field: F,
+        iso_value: f32,
+        min: Vecf,
+        max: Vecf,
+        color: Color,
+        lambert: f32,
+        specular: f32,
+    ) -> Isosurface {
+        Isosurface {
+            field: Arc::new(field),
+            iso_value,
+            bounds: [min, max],
+            step: vec3_len(vec3_sub(max, min)) / DEFAULT_STEPS,
+            clip: None,
+            texture: Arc::new(color),
+            lambert,
+            specular,
+        }
+    }
+
+    /// Where the values of `grid` reach `iso_value`, within the grid's box.
+    pub fn from_grid(
+        grid: Grid,
+        iso_value: f32,
+        color: Color,
+        lambert: f32,
+        specular: f32,
+    ) -> Isosurface {
+        let [min, max] = grid.bounds();
+        Isosurface::new(grid, iso_value, min, max, color, lambert, specular)
+    }
+
+    /// Marches rays `step` at a time; smaller steps find thinner features
+    /// but sample the field more often.
+    pub fn with_step(mut self, step: f32) -> Isosurface {
+        self.step = step;
+        self
+    }
+
+    /// Cuts the surface away on the side of the plane through `point` that
+    /// `normal` points to, opening it up, e.g. to show a `Slice` through
+    /// the same plane inside it.
+    pub fn with_clip(mut self, point: Vecf, normal: Vecf) -> Isosurface {
+        self.clip = Some((point, normal));
+        self
+    }
+
+    pub fn with_texture<T: Texture + 'static>(mut self, texture: T) -> Isosurface {
+        self.texture = Arc::new(texture);
+        self
+    }
+
+    /// How far above `iso_value` the field is at `point`.
+    fn excess(&self, point: Vecf) -> f32 {
+        self.field.value(point) - self.iso_value
+    }
+
+    /// Distances along `ray` where it enters and leaves the bounds, less
+    /// the part cut away by `with_clip`.
+    fn clip(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inv = 1.0 / ray.direction[axis];
+            let t0 = (self.bounds[0][axis] - ray.origin[axis]) * inv;
+            let t1 = (self.bounds[1][axis] - ray.origin[axis]) * inv;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        if let Some((point, normal)) = self.clip {
+            let height = vec3_dot(vec3_sub(ray.origin, point), normal);
+            let rate = vec3_dot(ray.direction, normal);
+            let crossing = -height / rate;
+            if height > 0.0 {
+                if rate >= 0.0 {
+                    return None;
+                }
+                near = near.max(crossing);
+            } else if rate > 0.0 {
+                far = far.min(crossing);
+            }
+        }
+        if near <= far {
+            Some((near, far))
+        } else {
+            None
+        }
+    }
+
+    fn march(&self, ray: &Ray) -> Option<f32> {
+        let (near, far) = self.clip(ray)?;
+        let at = |t: f32| vec3_add(ray.origin, vec3_scale(ray.direction, t));
+        let mut t = near + self.step * LEAVE_DISTANCE;
+        let mut previous = self.excess(at(t));
+        let steps = (((far - t) / self.step).ceil() as usize).min(MAX_STEPS);
+        for _ in 0..steps {
+            let next_t = (t + self.step).min(far);
+            let next = self.excess(at(next_t));
+            if (previous < 0.0) != (next < 0.0) && !next.is_nan() && !previous.is_nan() {
+                let (mut low, mut high) = (t, next_t);
+                for _ in 0..BISECTIONS {
+                    let middle = 0.5 * (low + high);
+                    if (self.excess(at(middle)) < 0.0) == (previous < 0.0) {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                return Some(high);
+            }
+            t = next_t;
+            previous = next;
+        }
+        None
+    }
+}
+
+impl Object for Isosurface {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        let distance = self.march(ray).unwrap_or(f32::INFINITY);
+        let hit_position = vec3_add(ray.origin, vec3_scale(ray.direction, distance));
+        (distance, hit_position)
+    }
+
+    fn get_position(&self) -> Vecf {
+        vec3_scale(vec3_add(self.bounds[0], self.bounds[1]), 0.5)
+    }
+
+    fn get_color(&self) -> Color {
+        texture_color(self.texture.as_ref(), self.get_position())
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.texture.shade(hit, scene)
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        // Central differences over a tenth of a step.
+        let h = 0.1 * self.step;
+        let mut gradient = [0.0; 3];
+        for (axis, slope) in gradient.iter_mut().enumerate() {
+            let mut offset = [0.0; 3];
+            offset[axis] = h;
+            *slope = self.excess(vec3_add(hit_ray.origin, offset))
+                - self.excess(vec3_sub(hit_ray.origin, offset));
+        }
+        let normal = vec3_normalized(gradient);
+        if vec3_dot(hit_ray.direction, normal) < 0.0 {
+            normal
+        } else {
+            vecmath::vec3_neg(normal)
+        }
+    }
+
+    fn get_lambert(&self) -> f32 {
+        self.lambert
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.specular
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        let normal = self.normal_to(&Ray::new(point, ray.direction));
+        let reflection = 2.0 * vec3_dot(ray.direction, normal);
+        let reflected_ray = vec3_sub(ray.direction, vec3_scale(normal, reflection));
+        Ray::new(point, reflected_ray)
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        Some(self.bounds)
+    }
+
+    fn kind(&self) -> &str {
+        "isosurface"
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        self.texture.solid_color()
+    }
+}
+
+/// A flat cut through a field within a box, colored by the field's value
+/// through a `ColorRamp`, e.g. to read a simulation's values on a plane.
+/// Seen from both sides, and lit as a matte surface.
+#[derive(Clone)]
+pub struct Slice {
+    field: Arc<dyn ScalarField>,
+    ramp: ColorRamp,
+    point: Vecf,
+    normal: Vecf,
+    bounds: [Vecf; 2],
+}
+
+impl Slice {
+    /// The part of the plane through `point` facing `normal` between the
+    /// corners `min` and `max`.
+    pub fn new<F: ScalarField + 'static>(
+        field: F,
+        ramp: ColorRamp,
+        point: Vecf,
+        normal: Vecf,
+        min: Vecf,
+        max: Vecf,
+    ) -> Slice {
+        Slice {
+            field: Arc::new(field),
+            ramp,
+            point,
+            normal: vec3_normalized(normal),
+            bounds: [min, max],
+        }
+    }
+
+    /// A slice through `grid`, within the grid's box.
+    pub fn from_grid(grid: Grid, ramp: ColorRamp, point: Vecf, normal: Vecf) -> Slice {
+        let [min, max] = grid.bounds();
+        Slice::new(grid, ramp, point, normal, min, max)
+    }
+
+    fn contains(&self, point: Vecf) -> bool {
+        // Slices along a face of the box must not lose points to rounding.
+        let slack = 1e-4 * vec3_len(vec3_sub(self.bounds[1], self.bounds[0]));
+        (0..3).all(|axis| {
+            let (low, high) = (self.bounds[0][axis], self.bounds[1][axis]);
+            point[axis] >= low.min(high) - slack && point[axis] <= low.max(high) + slack
+        })
+    }
+}
+
+impl Object for Slice {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        let rate = vec3_dot(ray.direction, self.normal);
+        let mut distance = vec3_dot(vec3_sub(self.point, ray.origin), self.normal) / rate;
+        let hit_position = vec3_add(ray.origin, vec3_scale(ray.direction, distance));
+        if !(distance > MIN_DISTANCE && self.contains(hit_position)) {
+            distance = f32::INFINITY;
+        }
+        (distance, hit_position)
+    }
+
+    fn get_position(&self) -> Vecf {
+        self.point
+    }
+
+    fn get_color(&self) -> Color {
+        self.ramp.color_at(self.field.value(self.point))
+    }
+
+    fn get_color_at(&self, hit: &Hit, _scene: &Scene) -> Color {
+        self.ramp.color_at(self.field.value(hit.point))
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        if vec3_dot(hit_ray.direction, self.normal) < 0.0 {
+            self.normal
+        } else {
+            vecmath::vec3_neg(self.normal)
+        }
+    }
+
+    fn get_lambert(&self) -> f32 {
+        1.0
+    }
+
+    fn get_specular(&self) -> f32 {
+        0.0
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        let normal = self.normal_to(&Ray::new(point, ray.direction));
+        let reflection = 2.0 * vec3_dot(ray.direction, normal);
+        let reflected_ray = vec3_sub(ray.direction, vec3_scale(normal, reflection));
+        Ray::new(point, reflected_ray)
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        Some(self.bounds)
+    }
+
+    fn kind(&self) -> &str {
+        "slice"
+    }
+}
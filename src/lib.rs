@@ -6,5 +6,42 @@ use vecmath::Vector3;
 
 pub type Vecf = Vector3<f32>;
 pub type Color = Rgb<u8>;
+pub mod animation;
+pub mod atmosphere;
+pub mod axes;
+pub mod bake;
+pub mod checkpoint;
+pub mod cone;
+pub mod dither;
+pub mod exr;
+pub mod framebuffer;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod inspect;
+pub mod integrator;
+pub mod isosurface;
+pub mod material;
+pub mod mesh;
+pub mod metadata;
+pub mod noise;
+pub mod pbr;
+pub mod photon;
+pub mod placement;
+pub mod post;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod report;
+pub mod sampler;
 pub mod scene;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod shadow;
+#[cfg(feature = "spectral")]
+pub mod spectral;
+pub mod srgb;
+pub mod summary;
+pub mod texture;
+pub mod tonemap;
+pub mod units;
 pub mod view;
+pub mod warning;
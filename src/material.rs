@@ -0,0 +1,541 @@
+//! How surfaces respond to light, kept apart from the shapes that use them
+//! so that many objects can share one material and new shading models only
+//! need a new `Material` instead of changes to every primitive.
+
+use std::sync::Arc;
+
+use crate::{
+    pbr::{Pbr, DIELECTRIC_F0},
+    sampler::Rng,
+    scene::{schlick, Hit},
+    texture::AlphaMask,
+    view::Ray,
+    Color,
+};
+
+/// What becomes of a ray meeting a surface, from `Material::scatter`.
+pub enum Scatter {
+    /// The integrator shades the surface from the material's parameters,
+    /// with its own Lambert, mirror and refraction rules, which also sample
+    /// lights directly.
+    Standard,
+    /// The ray continues as the given one, bringing back the given share
+    /// of each channel of the light arriving along it.
+    Continue(Ray, [f32; 3]),
+    /// The surface absorbs the ray.
+    Absorb,
+}
+
+/// How rays treat the partly covered parts of a cutout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// The default `Whitted` integrator shades the surface in proportion
+    /// to its coverage and traces on through it for the rest, which is
+    /// smooth but follows a ray through every layer, e.g. of a whole tree's
+    /// leaves. Other integrators treat it as `Stochastic`.
+    #[default]
+    Blend,
+    /// Each ray either stops at the surface or passes it, at random by its
+    /// coverage, so dense layers cost one hit and their noise averages out
+    /// over the view's samples.
+    Stochastic,
+}
+
+/// The parts of a surface cut away, see `Standard::with_cutout`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cutout {
+    pub mask: AlphaMask,
+    pub mode: AlphaMode,
+}
+
+/// A thin clear layer over a surface, such as the lacquer on wood or the
+/// clear coat of car paint, as glTF's clearcoat extension describes it:
+/// a dielectric with an index of refraction of 1.5 mirroring some of the
+/// light by the Fresnel equations and letting the rest through to the
+/// surface below. Put over a material with `Layered`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clearcoat {
+    /// How much of the surface the coat covers, from 0 to 1.
+    pub strength: f32,
+    /// Perceptual roughness of the coat, as `Pbr::roughness`.
+    pub roughness: f32,
+}
+
+impl Clearcoat {
+    /// Share of the light arriving at `cos` to the normal the coat mirrors.
+    pub fn reflectance(&self, cos: f32) -> f32 {
+        self.strength * schlick(cos, DIELECTRIC_F0)
+    }
+
+    /// The coat's own highlights and blurred reflections, shaded as a
+    /// smooth dielectric.
+    pub fn pbr(&self) -> Pbr {
+        Pbr {
+            metallic: 0.0,
+            roughness: self.roughness,
+            anisotropy: None,
+        }
+    }
+}
+
+/// Shading parameters of a surface; everything but its color, which the
+/// object's texture gives. Set on `Sphere` and `Plane` with
+/// `with_material`, shared through an `Arc`.
+pub trait Material: Send + Sync {
+    /// Share of the object's color lit by Lambert's cosine law.
+    fn get_lambert(&self) -> f32 {
+        0.0
+    }
+
+    /// Share of light mirrored.
+    fn get_specular(&self) -> f32 {
+        0.0
+    }
+
+    /// Share of light passing through the surface.
+    fn get_transparency(&self) -> f32 {
+        0.0
+    }
+
+    /// Index of refraction of the material behind the surface.
+    fn get_ior(&self) -> f32 {
+        1.0
+    }
+
+    /// Index of refraction for each color channel, where the material
+    /// bends them apart, see `Standard::with_dispersion`.
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        None
+    }
+
+    /// Share of each channel absorbed per unit of distance travelled
+    /// inside, see `Standard::with_absorption`.
+    fn get_absorption(&self) -> [f32; 3] {
+        [0.0; 3]
+    }
+
+    /// Whether reflection and refraction follow the Fresnel equations
+    /// whatever the view, see `Standard::with_dielectric`.
+    fn is_dielectric(&self) -> bool {
+        false
+    }
+
+    /// Whether reflections are tinted by the surface's color.
+    fn is_metal(&self) -> bool {
+        false
+    }
+
+    /// How much reflected and refracted rays spread, from 0 for a mirror
+    /// to 1.
+    fn get_roughness(&self) -> f32 {
+        0.0
+    }
+
+    /// Rays a ray meeting the rough surface splits into, see
+    /// `Standard::with_glossy_samples`.
+    fn get_glossy_samples(&self) -> u32 {
+        1
+    }
+
+    /// Blinn-Phong exponent of highlights from lights; 0 for none.
+    fn get_shininess(&self) -> f32 {
+        0.0
+    }
+
+    /// The parts of the surface cut away, if any.
+    fn get_cutout(&self) -> Option<&Cutout> {
+        None
+    }
+
+    /// Physically based parameters, see `Standard::with_pbr`.
+    fn get_pbr(&self) -> Option<Pbr> {
+        None
+    }
+
+    /// The clear layer over the surface, see `Layered`.
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        None
+    }
+
+    /// What becomes of `ray` meeting the surface at `hit`, for shading
+    /// models the parameters above cannot describe. Continued rays may
+    /// start at `hit.point`; integrators move them off the surface. `rng`
+    /// supplies random numbers for picking directions. Custom materials
+    /// carry their own colors, since the object's color only feeds the
+    /// standard shading. `Whitted` and `PathTracer` follow it. The
+    /// `Bidirectional` integrator renders such materials black and the
+    /// caustics let them absorb photons, recording a
+    /// `RenderWarning::UnsupportedMaterial`; the GPU renderer refuses them.
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut Rng) -> Scatter {
+        Scatter::Standard
+    }
+
+    /// Light the surface gives off by itself, per channel, where 1 is as
+    /// bright as a white surface in full light. The `PathTracer` aims rays
+    /// at bounded objects whose material gives off light, such as spheres,
+    /// so that they light the scene like lights do; see
+    /// `Standard::with_emission`.
+    fn get_emission(&self) -> [f32; 3] {
+        [0.0; 3]
+    }
+
+    /// Light the surface gives off at `hit`, `get_emission` unless it
+    /// varies over the surface. Seen by `Whitted` and `PathTracer` only;
+    /// `Whitted` does not light other surfaces with it, and the
+    /// `Bidirectional` integrator records a
+    /// `RenderWarning::UnsupportedMaterial` instead.
+    fn emitted(&self, _hit: &Hit) -> [f32; 3] {
+        self.get_emission()
+    }
+}
+
+/// The material primitives are made of unless given another: Lambert
+/// shading, mirror reflection and optionally refraction, metal, highlights
+/// or physically based shading.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standard {
+    lambert: f32,
+    specular: f32,
+    transparency: f32,
+    ior: f32,
+    dispersion: Option<[f32; 3]>,
+    absorption: [f32; 3],
+    dielectric: bool,
+    metal: bool,
+    roughness: f32,
+    glossy_samples: u32,
+    shininess: f32,
+    pbr: Option<Pbr>,
+    emission: [f32; 3],
+    cutout: Option<Cutout>,
+}
+
+impl Standard {
+    pub fn new(lambert: f32, specular: f32) -> Standard {
+        Standard {
+            lambert,
+            specular,
+            transparency: 0.0,
+            ior: 1.0,
+            dispersion: None,
+            absorption: [0.0; 3],
+            dielectric: false,
+            metal: false,
+            roughness: 0.0,
+            glossy_samples: 1,
+            shininess: 0.0,
+            pbr: None,
+            emission: [0.0; 3],
+            cutout: None,
+        }
+    }
+
+    /// A copy of what `material` reports, to change some of it with the
+    /// `with_*` methods.
+    pub fn from_material(material: &dyn Material) -> Standard {
+        Standard {
+            lambert: material.get_lambert(),
+            specular: material.get_specular(),
+            transparency: material.get_transparency(),
+            ior: material.get_ior(),
+            dispersion: material.get_dispersion(),
+            absorption: material.get_absorption(),
+            dielectric: material.is_dielectric(),
+            metal: material.is_metal(),
+            roughness: material.get_roughness(),
+            glossy_samples: material.get_glossy_samples(),
+            shininess: material.get_shininess(),
+            pbr: material.get_pbr(),
+            emission: material.get_emission(),
+            cutout: material.get_cutout().cloned(),
+        }
+    }
+
+    /// Lets `transparency` of the light through, bent as by a material
+    /// with index of refraction `ior`, e.g. 1.5 for glass. Lower `lambert`
+    /// and `specular` to match.
+    pub fn with_refraction(mut self, transparency: f32, ior: f32) -> Standard {
+        self.transparency = transparency;
+        self.ior = ior;
+        self
+    }
+
+    /// Bends red, green and blue light by their own indices of refraction
+    /// `iors`, e.g. `[1.51, 1.52, 1.53]` for crown glass, so edges split
+    /// white light into colored fringes. Each sample follows one channel
+    /// through, so the fringes need several samples per pixel to smooth
+    /// out. Reflections use the green index. Only matters with
+    /// `with_refraction` or `with_dielectric`; set it after them, as they
+    /// set the index of refraction.
+    pub fn with_dispersion(mut self, iors: [f32; 3]) -> Standard {
+        self.ior = iors[1];
+        self.dispersion = Some(iors);
+        self
+    }
+
+    /// Makes light refracted into the material fade towards `color` the
+    /// further it travels inside: white light comes out as `color` after
+    /// `1 / density` units, so thick parts look darker and more saturated
+    /// than thin ones. Only matters with `with_refraction`.
+    pub fn with_absorption(mut self, color: Color, density: f32) -> Standard {
+        self.absorption = color.0.map(|c| -(c as f32 / 255.0).ln() * density);
+        self
+    }
+
+    /// Makes the material glass or another clear dielectric with index of
+    /// refraction `ior`: it reflects and refracts by the Fresnel
+    /// equations, whatever the view, and white light that travels
+    /// `distance` through it comes out as `tint`, as with
+    /// `with_absorption`. Lambert shading and `specular` are turned off.
+    pub fn with_dielectric(mut self, ior: f32, tint: Color, distance: f32) -> Standard {
+        self.lambert = 0.0;
+        self.specular = 0.0;
+        self.transparency = 1.0;
+        self.ior = ior;
+        self.dielectric = true;
+        self.with_absorption(tint, 1.0 / distance)
+    }
+
+    /// Makes the material a metal: it reflects all light, tinted by the
+    /// object's color, blurred by `roughness` as for `with_roughness`, from
+    /// 0 for polished metal to 1 for a matte one. Lambert shading is turned
+    /// off.
+    pub fn with_metal(mut self, roughness: f32) -> Standard {
+        self.lambert = 0.0;
+        self.specular = 1.0;
+        self.metal = true;
+        self.with_roughness(roughness)
+    }
+
+    /// Blurs reflections and, with `with_refraction`, what is seen
+    /// through the surface, as for frosted glass: each ray follows one
+    /// random direction around the mirror or refracted one per sample,
+    /// spread wider the closer `roughness` is to 1.
+    pub fn with_roughness(mut self, roughness: f32) -> Standard {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Splits rays meeting the surface into `samples` reflected and
+    /// refracted ones, each in its own random direction, instead of one per
+    /// pixel sample, so that a rough mirror renders smoothly without
+    /// raising `View::with_samples` for the whole frame. Rays split once
+    /// at most: later rough surfaces they meet take one direction each.
+    /// `View::with_max_glossy_samples` caps it; only `Whitted` splits rays.
+    pub fn with_glossy_samples(mut self, samples: u32) -> Standard {
+        self.glossy_samples = samples;
+        self
+    }
+
+    /// Adds the highlights lights leave on shiny surfaces, which mirror
+    /// reflections miss since rays never hit point lights: white spots as
+    /// bright as `specular` and the smaller the higher `shininess` is, e.g.
+    /// 10 for rubber or 100 for polished plastic. Only the default
+    /// `Whitted` integrator draws them.
+    pub fn with_shininess(mut self, shininess: f32) -> Standard {
+        self.shininess = shininess;
+        self
+    }
+
+    /// Shades the material as physically based, with the object's color
+    /// as base color, so that assets authored for PBR pipelines such as
+    /// glTF look as intended: `metallic` from 0 for plastic to 1 for metal
+    /// and `roughness` from 0 for a mirror finish to 1. Lights leave
+    /// Cook-Torrance highlights, which only the default `Whitted`
+    /// integrator draws, in place of those of `with_shininess`; reflections
+    /// are tinted by Schlick's Fresnel term and blurred by the same GGX
+    /// distribution, rather than as by `with_roughness`. Sets `lambert` and
+    /// `specular` to match.
+    pub fn with_pbr(mut self, metallic: f32, roughness: f32) -> Standard {
+        self.lambert = 1.0 - metallic;
+        self.specular = 1.0;
+        self.pbr = Some(Pbr {
+            metallic,
+            roughness,
+            anisotropy: None,
+        });
+        self.with_roughness(roughness)
+    }
+
+    /// Gives the physically based material of `with_pbr` a roughness of
+    /// `roughness_u` along the surface's tangent, the direction its u
+    /// texture coordinate grows in, and `roughness_v` across it, so
+    /// highlights and reflections streak as on brushed metal; the
+    /// roughness of `with_pbr` becomes their mean, for surfaces without a
+    /// tangent. Does nothing without `with_pbr`.
+    pub fn with_anisotropy(mut self, roughness_u: f32, roughness_v: f32) -> Standard {
+        let roughness = (roughness_u + roughness_v) / 2.0;
+        match &mut self.pbr {
+            Some(pbr) => {
+                pbr.roughness = roughness;
+                pbr.anisotropy = Some([roughness_u, roughness_v]);
+            }
+            None => return self,
+        }
+        self.with_roughness(roughness)
+    }
+
+    /// Makes the surface give off light of `color`, `strength` times as
+    /// bright as a white surface in full light, turning the object into a
+    /// light source such as a glowing ball or a lamp shade. Light it is
+    /// given is still shaded as usual on top; lower `lambert` and
+    /// `specular` for a surface that only glows.
+    pub fn with_emission(mut self, color: Color, strength: f32) -> Standard {
+        self.emission = color.0.map(|c| c as f32 / 255.0 * strength);
+        self
+    }
+
+    /// Cuts away the parts of the surface `mask` leaves uncovered, as for
+    /// leaves or a chain-link fence drawn on a plane, with partly covered
+    /// parts treated as `mode` says. Shadows fall through the cut-away
+    /// parts, picked at random by coverage in either mode. The GPU
+    /// renderer and caustics draw cutouts solid.
+    pub fn with_cutout(mut self, mask: AlphaMask, mode: AlphaMode) -> Standard {
+        self.cutout = Some(Cutout { mask, mode });
+        self
+    }
+}
+
+impl Material for Standard {
+    fn get_lambert(&self) -> f32 {
+        self.lambert
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.specular
+    }
+
+    fn get_transparency(&self) -> f32 {
+        self.transparency
+    }
+
+    fn get_ior(&self) -> f32 {
+        self.ior
+    }
+
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.dispersion
+    }
+
+    fn get_absorption(&self) -> [f32; 3] {
+        self.absorption
+    }
+
+    fn is_dielectric(&self) -> bool {
+        self.dielectric
+    }
+
+    fn is_metal(&self) -> bool {
+        self.metal
+    }
+
+    fn get_roughness(&self) -> f32 {
+        self.roughness
+    }
+
+    fn get_glossy_samples(&self) -> u32 {
+        self.glossy_samples
+    }
+
+    fn get_shininess(&self) -> f32 {
+        self.shininess
+    }
+
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.pbr
+    }
+
+    fn get_emission(&self) -> [f32; 3] {
+        self.emission
+    }
+
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.cutout.as_ref()
+    }
+}
+
+/// `base` under a `Clearcoat`: shaded as `base` is, but dimmed by the
+/// light the coat mirrors away, which integrators add as a reflection of
+/// its own. Lacquered wood is a coat over a Lambert material, car paint
+/// one over a rough metal.
+#[derive(Clone)]
+pub struct Layered {
+    base: Arc<dyn Material>,
+    coat: Clearcoat,
+}
+
+impl Layered {
+    pub fn new(base: Arc<dyn Material>, coat: Clearcoat) -> Layered {
+        Layered { base, coat }
+    }
+}
+
+impl Material for Layered {
+    fn get_lambert(&self) -> f32 {
+        self.base.get_lambert()
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.base.get_specular()
+    }
+
+    fn get_transparency(&self) -> f32 {
+        self.base.get_transparency()
+    }
+
+    fn get_ior(&self) -> f32 {
+        self.base.get_ior()
+    }
+
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.base.get_dispersion()
+    }
+
+    fn get_absorption(&self) -> [f32; 3] {
+        self.base.get_absorption()
+    }
+
+    fn is_dielectric(&self) -> bool {
+        self.base.is_dielectric()
+    }
+
+    fn is_metal(&self) -> bool {
+        self.base.is_metal()
+    }
+
+    fn get_roughness(&self) -> f32 {
+        self.base.get_roughness()
+    }
+
+    fn get_glossy_samples(&self) -> u32 {
+        self.base.get_glossy_samples()
+    }
+
+    fn get_shininess(&self) -> f32 {
+        self.base.get_shininess()
+    }
+
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.base.get_cutout()
+    }
+
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.base.get_pbr()
+    }
+
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        Some(self.coat)
+    }
+
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut Rng) -> Scatter {
+        self.base.scatter(ray, hit, rng)
+    }
+
+    fn get_emission(&self) -> [f32; 3] {
+        self.base.get_emission()
+    }
+
+    fn emitted(&self, hit: &Hit) -> [f32; 3] {
+        self.base.emitted(hit)
+    }
+}
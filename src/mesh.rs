@@ -0,0 +1,758 @@
+use image::Rgb;
+use std::{fmt, io, sync::Arc};
+#[cfg(feature = "fs")]
+use std::{fs, io::BufReader, path::Path};
+#[cfg(feature = "fs")]
+use vecmath::{col_mat4_mul, col_mat4_transform, mat4_id, Matrix4};
+use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_normalized, vec3_scale, vec3_sub};
+
+use crate::{
+    axes::Axes,
+    scene::{texture_color, Hit, Object, Part, PrimitiveMesh, Scene},
+    texture::{NormalMap, Texture},
+    units::Unit,
+    view::Ray,
+    Color, Vecf,
+};
+
+#[cfg(feature = "fs")]
+mod ply;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    #[cfg(feature = "fs")]
+    Obj(tobj::LoadError),
+    #[cfg(feature = "fs")]
+    Gltf(gltf::Error),
+    /// The file parsed but describes something a `Mesh` cannot hold.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Io(err) => write!(f, "could not read mesh: {}", err),
+            #[cfg(feature = "fs")]
+            MeshError::Obj(err) => write!(f, "could not load OBJ: {}", err),
+            #[cfg(feature = "fs")]
+            MeshError::Gltf(err) => write!(f, "could not load glTF: {}", err),
+            MeshError::Invalid(reason) => write!(f, "invalid mesh: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+impl From<io::Error> for MeshError {
+    fn from(err: io::Error) -> MeshError {
+        MeshError::Io(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<tobj::LoadError> for MeshError {
+    fn from(err: tobj::LoadError) -> MeshError {
+        MeshError::Obj(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<gltf::Error> for MeshError {
+    fn from(err: gltf::Error) -> MeshError {
+        MeshError::Gltf(err)
+    }
+}
+
+/// Scale, then counter-clockwise rotation in radians, then offset, applied
+/// to a mesh's texture coordinates before they reach its texture.
+#[derive(Clone, Copy, Debug)]
+pub struct UvTransform {
+    pub scale: [f32; 2],
+    pub rotation: f32,
+    pub offset: [f32; 2],
+}
+
+impl Default for UvTransform {
+    fn default() -> UvTransform {
+        UvTransform {
+            scale: [1.0, 1.0],
+            rotation: 0.0,
+            offset: [0.0, 0.0],
+        }
+    }
+}
+
+impl UvTransform {
+    pub fn apply(&self, uv: [f32; 2]) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let u = uv[0] * self.scale[0];
+        let v = uv[1] * self.scale[1];
+        [
+            u * cos - v * sin + self.offset[0],
+            u * sin + v * cos + self.offset[1],
+        ]
+    }
+}
+
+/// One triangle of a `Mesh`. UVs follow the OBJ convention of `v` pointing
+/// up from the bottom of the image; vertex colors are RGB in 0-1.
+#[derive(Clone, Debug)]
+pub struct Triangle {
+    pub vertices: [Vecf; 3],
+    pub uvs: Option<[[f32; 2]; 3]>,
+    pub colors: Option<[[f32; 3]; 3]>,
+}
+
+impl Triangle {
+    fn normal(&self) -> Vecf {
+        let [a, b, c] = self.vertices;
+        vec3_normalized(vec3_cross(vec3_sub(b, a), vec3_sub(c, a)))
+    }
+
+    /// Watertight intersection (Woop, Benthin and Wald), returning the
+    /// distance along `ray` and the barycentric weights of the hit. Edges
+    /// shared by two triangles are tested the same way from both, so rays
+    /// cannot slip between them.
+    fn intersect(&self, ray: &Ray) -> Option<(f32, [f32; 3])> {
+        self.crossing(ray).filter(|&(distance, _)| distance > 0.0)
+    }
+
+    /// Where the line through `ray` crosses the triangle, as `intersect`
+    /// finds it but also behind the origin, with a negative distance.
+    fn crossing(&self, ray: &Ray) -> Option<(f32, [f32; 3])> {
+        let direction = ray.direction;
+        let kz = (0..3)
+            .max_by(|&i, &j| direction[i].abs().total_cmp(&direction[j].abs()))
+            .unwrap_or(2);
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if direction[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+        let shear = [direction[kx] / direction[kz], direction[ky] / direction[kz]];
+        let [a, b, c] = self.vertices.map(|vertex| {
+            let local = vec3_sub(vertex, ray.origin);
+            [
+                local[kx] - shear[0] * local[kz],
+                local[ky] - shear[1] * local[kz],
+                local[kz] / direction[kz],
+            ]
+        });
+        let mut edges = [
+            c[0] * b[1] - c[1] * b[0],
+            a[0] * c[1] - a[1] * c[0],
+            b[0] * a[1] - b[1] * a[0],
+        ];
+        // Exactly on an edge in single precision; decide it in double so
+        // the neighbouring triangle gets the opposite answer.
+        if edges.contains(&0.0) {
+            let edge = |p: [f32; 3], q: [f32; 3]| {
+                (p[0] as f64 * q[1] as f64 - p[1] as f64 * q[0] as f64) as f32
+            };
+            edges = [edge(c, b), edge(a, c), edge(b, a)];
+        }
+        let [u, v, w] = edges;
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+        let det = u + v + w;
+        if det == 0.0 {
+            return None;
+        }
+        let distance = (u * a[2] + v * b[2] + w * c[2]) / det;
+        Some((distance, [u / det, v / det, w / det]))
+    }
+
+    /// The point with barycentric `weights`, exactly on the triangle.
+    fn point_at(&self, weights: [f32; 3]) -> Vecf {
+        let [a, b, c] = self.vertices;
+        vec3_add(
+            vec3_add(vec3_scale(a, weights[0]), vec3_scale(b, weights[1])),
+            vec3_scale(c, weights[2]),
+        )
+    }
+
+    /// Directions in which u and v grow across the triangle, for texture
+    /// coordinates `uvs` at its corners; `None` if they do not span it.
+    fn uv_directions(&self, uvs: [[f32; 2]; 3]) -> Option<(Vecf, Vecf)> {
+        let [a, b, c] = self.vertices;
+        let (edge1, edge2) = (vec3_sub(b, a), vec3_sub(c, a));
+        let (du1, dv1) = (uvs[1][0] - uvs[0][0], uvs[1][1] - uvs[0][1]);
+        let (du2, dv2) = (uvs[2][0] - uvs[0][0], uvs[2][1] - uvs[0][1]);
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let tangent = vec3_scale(
+            vec3_sub(vec3_scale(edge1, dv2), vec3_scale(edge2, dv1)),
+            1.0 / determinant,
+        );
+        let bitangent = vec3_scale(
+            vec3_sub(vec3_scale(edge2, du1), vec3_scale(edge1, du2)),
+            1.0 / determinant,
+        );
+        Some((tangent, bitangent))
+    }
+}
+
+/// Triangle mesh loaded from OBJ, glTF or PLY, with optional texture
+/// coordinates and vertex colors.
+#[derive(Clone)]
+pub struct Mesh {
+    triangles: Arc<Vec<Triangle>>,
+    position: Vecf,
+    bounds: [Vecf; 2],
+    texture: Arc<dyn Texture>,
+    uv_transform: UvTransform,
+    lambert: f32,
+    specular: f32,
+    normal_map: Option<Arc<NormalMap>>,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>, color: Color, lambert: f32, specular: f32) -> Mesh {
+        let bounds = bounding_box(&triangles);
+        Mesh {
+            triangles: Arc::new(triangles),
+            position: vec3_scale(vec3_add(bounds[0], bounds[1]), 0.5),
+            bounds,
+            texture: Arc::new(color),
+            uv_transform: UvTransform::default(),
+            lambert,
+            specular,
+            normal_map: None,
+        }
+    }
+
+    /// Loads every model in a Wavefront OBJ file into one mesh.
+    #[cfg(feature = "fs")]
+    pub fn from_obj<P: AsRef<Path>>(
+        path: P,
+        color: Color,
+        lambert: f32,
+        specular: f32,
+    ) -> Result<Mesh, MeshError> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, _materials) = tobj::load_obj(path.as_ref(), &options)?;
+        let mut triangles = Vec::new();
+        for model in models {
+            let mesh = model.mesh;
+            let positions: Vec<Vecf> = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect();
+            let uvs: Vec<[f32; 2]> = mesh
+                .texcoords
+                .chunks_exact(2)
+                .map(|t| [t[0], t[1]])
+                .collect();
+            let colors: Vec<[f32; 3]> = mesh
+                .vertex_color
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect();
+            push_triangles(
+                &mut triangles,
+                &positions,
+                non_empty(&uvs),
+                non_empty(&colors),
+                &mesh.indices,
+            )?;
+        }
+        Ok(Mesh::new(triangles, color, lambert, specular))
+    }
+
+    /// Loads the triangles of a glTF file's default scene, with node
+    /// transforms applied. Buffers must be in the binary chunk or in files
+    /// next to `path`.
+    #[cfg(feature = "fs")]
+    pub fn from_gltf<P: AsRef<Path>>(
+        path: P,
+        color: Color,
+        lambert: f32,
+        specular: f32,
+    ) -> Result<Mesh, MeshError> {
+        let path = path.as_ref();
+        let gltf = gltf::Gltf::open(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut buffers = Vec::new();
+        for buffer in gltf.buffers() {
+            buffers.push(match buffer.source() {
+                gltf::buffer::Source::Bin => gltf
+                    .blob
+                    .clone()
+                    .ok_or(MeshError::Invalid("missing binary chunk"))?,
+                gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                    return Err(MeshError::Invalid("embedded data URIs are not supported"))
+                }
+                gltf::buffer::Source::Uri(uri) => fs::read(dir.join(uri))?,
+            });
+        }
+        let mut triangles = Vec::new();
+        if let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            for node in scene.nodes() {
+                add_gltf_node(&node, mat4_id(), &buffers, &mut triangles)?;
+            }
+        }
+        Ok(Mesh::new(triangles, color, lambert, specular))
+    }
+
+    /// Loads a Stanford PLY file, ASCII or binary, including `red`/`green`/
+    /// `blue` vertex colors and `s`/`t` texture coordinates if present.
+    #[cfg(feature = "fs")]
+    pub fn from_ply<P: AsRef<Path>>(
+        path: P,
+        color: Color,
+        lambert: f32,
+        specular: f32,
+    ) -> Result<Mesh, MeshError> {
+        let data = ply::read(BufReader::new(fs::File::open(path)?))?;
+        let mut triangles = Vec::new();
+        push_triangles(
+            &mut triangles,
+            &data.positions,
+            data.uvs.as_deref(),
+            data.colors.as_deref(),
+            &data.indices,
+        )?;
+        Ok(Mesh::new(triangles, color, lambert, specular))
+    }
+
+    pub fn with_texture<T: Texture + 'static>(mut self, texture: T) -> Mesh {
+        self.texture = Arc::new(texture);
+        self
+    }
+
+    pub fn with_uv_transform(mut self, uv_transform: UvTransform) -> Mesh {
+        self.uv_transform = uv_transform;
+        self
+    }
+
+    /// Bends the mesh's shading normals by `normal_map`, laid out by the
+    /// triangles' texture coordinates after the UV transform. Triangles
+    /// without texture coordinates keep their flat normals.
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Mesh {
+        self.normal_map = Some(Arc::new(normal_map));
+        self
+    }
+
+    /// Converts the mesh from the `source` axis convention, that of the
+    /// tool that wrote it, to the scene's `target` one; see `axes`. glTF
+    /// files are always `Axes::Y_UP_RIGHT`.
+    pub fn with_axes(mut self, source: Axes, target: Axes) -> Mesh {
+        let mirrored = source.mirrors(target);
+        let triangles: Vec<Triangle> = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let mut triangle = triangle.clone();
+                triangle.vertices = triangle.vertices.map(|v| source.convert(v, target));
+                // Mirroring turns the winding around; turn it back.
+                if mirrored {
+                    triangle.vertices.swap(1, 2);
+                    if let Some(uvs) = &mut triangle.uvs {
+                        uvs.swap(1, 2);
+                    }
+                    if let Some(colors) = &mut triangle.colors {
+                        colors.swap(1, 2);
+                    }
+                }
+                triangle
+            })
+            .collect();
+        self.bounds = bounding_box(&triangles);
+        self.position = vec3_scale(vec3_add(self.bounds[0], self.bounds[1]), 0.5);
+        self.triangles = Arc::new(triangles);
+        self
+    }
+
+    /// Scales the mesh from the `source` unit it was modelled in to the
+    /// scene's `target` one, about the origin; see `units`. glTF files are
+    /// always in meters.
+    pub fn with_unit(mut self, source: Unit, target: Unit) -> Mesh {
+        let scale = source.scale_to(target);
+        let triangles: Vec<Triangle> = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let mut triangle = triangle.clone();
+                triangle.vertices = triangle.vertices.map(|v| vec3_scale(v, scale));
+                triangle
+            })
+            .collect();
+        self.bounds = bounding_box(&triangles);
+        self.position = vec3_scale(vec3_add(self.bounds[0], self.bounds[1]), 0.5);
+        self.triangles = Arc::new(triangles);
+        self
+    }
+
+    fn hits_bounds(&self, ray: &Ray) -> bool {
+        let mut near = f32::NEG_INFINITY;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inv = 1.0 / ray.direction[axis];
+            let t0 = (self.bounds[0][axis] - ray.origin[axis]) * inv;
+            let t1 = (self.bounds[1][axis] - ray.origin[axis]) * inv;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        near <= far && far > 0.0
+    }
+
+    /// The triangle the origin of `ray` lies on, for callers that only
+    /// know the point: the one the line through it along the ray crosses
+    /// closest to it. The watertight test gives an edge shared by two
+    /// triangles to exactly one of them, so no tolerance is needed.
+    fn part_through(&self, ray: &Ray) -> Option<Part> {
+        let mut closest: Option<(f32, Part)> = None;
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            if let Some((distance, weights)) = triangle.crossing(ray) {
+                if closest.is_none_or(|(best, _)| distance.abs() < best) {
+                    closest = Some((distance.abs(), Part { index, weights }));
+                }
+            }
+        }
+        closest.map(|(_, part)| part)
+    }
+
+    /// Normal of the triangle of `part`, facing against `direction` and
+    /// bent by the normal map if there is one.
+    fn normal_on(&self, direction: Vecf, part: Part) -> Vecf {
+        let triangle = &self.triangles[part.index];
+        let normal = triangle.normal();
+        let normal = if vec3_dot(direction, normal) < 0.0 {
+            normal
+        } else {
+            vecmath::vec3_neg(normal)
+        };
+        let (map, uvs) = match (&self.normal_map, triangle.uvs) {
+            (Some(map), Some(uvs)) => (map, uvs.map(|uv| self.uv_transform.apply(uv))),
+            _ => return normal,
+        };
+        match triangle.uv_directions(uvs) {
+            Some((tangent, bitangent)) => {
+                map.perturb(normal, tangent, bitangent, interpolate(uvs, part.weights))
+            }
+            None => normal,
+        }
+    }
+}
+
+/// The texture coordinates with barycentric `weights` in the triangle with
+/// `uvs` at its corners.
+fn interpolate(uvs: [[f32; 2]; 3], weights: [f32; 3]) -> [f32; 2] {
+    let mut uv = [0.0; 2];
+    for (corner, weight) in uvs.iter().zip(weights.iter()) {
+        uv[0] += corner[0] * weight;
+        uv[1] += corner[1] * weight;
+    }
+    uv
+}
+
+/// Barycentric weights of `uv` in the triangle with texture coordinates
+/// `uvs` at its corners; `None` if it lies outside or they span nothing.
+fn uv_weights(uvs: [[f32; 2]; 3], uv: [f32; 2]) -> Option<[f32; 3]> {
+    let [a, b, c] = uvs;
+    let area = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+    if area.abs() < 1e-12 {
+        return None;
+    }
+    let edge = |p: [f32; 2], q: [f32; 2]| {
+        ((q[0] - p[0]) * (uv[1] - p[1]) - (uv[0] - p[0]) * (q[1] - p[1])) / area
+    };
+    let weights = [edge(b, c), edge(c, a), edge(a, b)];
+    if weights.iter().all(|&w| w >= 0.0) {
+        Some(weights)
+    } else {
+        None
+    }
+}
+
+/// Corners of the box around all vertices; inverted and infinite for no
+/// triangles.
+fn bounding_box(triangles: &[Triangle]) -> [Vecf; 2] {
+    let mut bounds = [[f32::INFINITY; 3], [f32::NEG_INFINITY; 3]];
+    for vertex in triangles
+        .iter()
+        .flat_map(|triangle| triangle.vertices.iter())
+    {
+        for axis in 0..3 {
+            bounds[0][axis] = bounds[0][axis].min(vertex[axis]);
+            bounds[1][axis] = bounds[1][axis].max(vertex[axis]);
+        }
+    }
+    bounds
+}
+
+impl Object for Mesh {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        let (distance, point, _) = self.intersect_part(ray, None);
+        (distance, point)
+    }
+
+    fn intersect_part(&self, ray: &Ray, skip: Option<usize>) -> (f32, Vecf, Option<Part>) {
+        let mut closest: Option<(f32, Part)> = None;
+        if self.hits_bounds(ray) {
+            for (index, triangle) in self.triangles.iter().enumerate() {
+                if skip == Some(index) {
+                    continue;
+                }
+                if let Some((distance, weights)) = triangle.intersect(ray) {
+                    if closest.is_none_or(|(best, _)| distance < best) {
+                        closest = Some((distance, Part { index, weights }));
+                    }
+                }
+            }
+        }
+        match closest {
+            // Rebuilt from the vertices rather than stepped along the ray,
+            // so the point lies on the triangle however far away it is.
+            Some((distance, part)) => (
+                distance,
+                self.triangles[part.index].point_at(part.weights),
+                Some(part),
+            ),
+            None => {
+                let distance = f32::INFINITY;
+                (
+                    distance,
+                    vec3_add(ray.origin, vec3_scale(ray.direction, distance)),
+                    None,
+                )
+            }
+        }
+    }
+
+    fn get_position(&self) -> Vecf {
+        self.position
+    }
+
+    fn get_color(&self) -> Color {
+        texture_color(self.texture.as_ref(), self.get_position())
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.texture.shade(hit, scene)
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        match self.part_through(hit_ray) {
+            Some(part) => self.normal_on(hit_ray.direction, part),
+            None => vecmath::vec3_neg(hit_ray.direction),
+        }
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        let through = Ray {
+            origin: point,
+            direction: ray.direction,
+        };
+        self.hit_on(ray, point, self.part_through(&through))
+    }
+
+    fn hit_on(&self, ray: &Ray, point: Vecf, part: Option<Part>) -> Hit {
+        let part = match part.filter(|part| part.index < self.triangles.len()) {
+            Some(part) => part,
+            None => {
+                return Hit {
+                    point,
+                    normal: vecmath::vec3_neg(ray.direction),
+                    local_point: vec3_sub(point, self.position),
+                    uv: None,
+                    vertex_color: None,
+                    tangent: None,
+                }
+            }
+        };
+        let triangle = &self.triangles[part.index];
+        let uvs = triangle
+            .uvs
+            .map(|uvs| uvs.map(|uv| self.uv_transform.apply(uv)));
+        let vertex_color = triangle.colors.map(|colors| {
+            let mut rgb = [0.0; 3];
+            for (corner, weight) in colors.iter().zip(part.weights.iter()) {
+                for (channel, value) in rgb.iter_mut().zip(corner.iter()) {
+                    *channel += value * weight;
+                }
+            }
+            Rgb(rgb.map(|value: f32| (value.clamp(0.0, 1.0) * 255.0) as u8))
+        });
+        let tangent = uvs.and_then(|uvs| {
+            triangle
+                .uv_directions(uvs)
+                .map(|(tangent, _)| vec3_normalized(tangent))
+        });
+        let point = triangle.point_at(part.weights);
+        Hit {
+            point,
+            normal: self.normal_on(ray.direction, part),
+            local_point: vec3_sub(point, self.position),
+            uv: uvs.map(|uvs| interpolate(uvs, part.weights)),
+            vertex_color,
+            tangent,
+        }
+    }
+
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        let part = self
+            .triangles
+            .iter()
+            .enumerate()
+            .find_map(|(index, triangle)| {
+                let uvs = triangle.uvs?.map(|uv| self.uv_transform.apply(uv));
+                let weights = uv_weights(uvs, uv)?;
+                Some(Part { index, weights })
+            })?;
+        let triangle = &self.triangles[part.index];
+        let point = triangle.point_at(part.weights);
+        let towards = Ray {
+            origin: point,
+            direction: vecmath::vec3_neg(triangle.normal()),
+        };
+        Some(self.hit_on(&towards, point, Some(part)))
+    }
+
+    fn get_lambert(&self) -> f32 {
+        self.lambert
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.specular
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        let normal = self.normal_to(&Ray::new(point, ray.direction));
+        let reflection = 2.0 * vec3_dot(ray.direction, normal);
+        let reflected_ray = vec3_sub(ray.direction, vec3_scale(normal, reflection));
+        Ray::new(point, reflected_ray)
+    }
+
+    fn kind(&self) -> &str {
+        "mesh"
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        // A mesh without triangles has an inverted, infinite box.
+        Some(self.bounds).filter(|[min, max]| (0..3).all(|axis| min[axis] <= max[axis]))
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        self.texture.solid_color()
+    }
+
+    fn primitive_mesh(&self) -> Option<PrimitiveMesh> {
+        if self.normal_map.is_some() {
+            return None;
+        }
+        Some(PrimitiveMesh {
+            triangles: self
+                .triangles
+                .iter()
+                .map(|triangle| triangle.vertices)
+                .collect(),
+            color: self.solid_color()?,
+            lambert: self.lambert,
+            specular: self.specular,
+        })
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self) + self.triangles.len() * std::mem::size_of::<Triangle>()
+    }
+}
+
+#[cfg(feature = "fs")]
+fn push_triangles(
+    triangles: &mut Vec<Triangle>,
+    positions: &[Vecf],
+    uvs: Option<&[[f32; 2]]>,
+    colors: Option<&[[f32; 3]]>,
+    indices: &[u32],
+) -> Result<(), MeshError> {
+    if uvs.is_some_and(|uvs| uvs.len() != positions.len()) {
+        return Err(MeshError::Invalid("UV count does not match vertex count"));
+    }
+    if colors.is_some_and(|colors| colors.len() != positions.len()) {
+        return Err(MeshError::Invalid(
+            "color count does not match vertex count",
+        ));
+    }
+    for face in indices.chunks_exact(3) {
+        let corners = [face[0] as usize, face[1] as usize, face[2] as usize];
+        if corners.iter().any(|&i| i >= positions.len()) {
+            return Err(MeshError::Invalid("vertex index out of range"));
+        }
+        triangles.push(Triangle {
+            vertices: corners.map(|i| positions[i]),
+            uvs: uvs.map(|uvs| corners.map(|i| uvs[i])),
+            colors: colors.map(|colors| corners.map(|i| colors[i])),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+fn add_gltf_node(
+    node: &gltf::Node,
+    parent: Matrix4<f32>,
+    buffers: &[Vec<u8>],
+    triangles: &mut Vec<Triangle>,
+) -> Result<(), MeshError> {
+    let transform = col_mat4_mul(parent, node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let positions: Vec<Vecf> = reader
+                .read_positions()
+                .ok_or(MeshError::Invalid("primitive without positions"))?
+                .map(|[x, y, z]| {
+                    let p = col_mat4_transform(transform, [x, y, z, 1.0]);
+                    [p[0], p[1], p[2]]
+                })
+                .collect();
+            // glTF puts the UV origin at the top of the image, OBJ at the bottom.
+            let uvs: Option<Vec<[f32; 2]>> = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().map(|[u, v]| [u, 1.0 - v]).collect());
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let colors: Option<Vec<[f32; 3]>> = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgb_f32().collect());
+            push_triangles(
+                triangles,
+                &positions,
+                uvs.as_deref(),
+                colors.as_deref(),
+                &indices,
+            )?;
+        }
+    }
+    for child in node.children() {
+        add_gltf_node(&child, transform, buffers, triangles)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+fn non_empty<T>(items: &[T]) -> Option<&[T]> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
@@ -0,0 +1,309 @@
+//! Minimal Stanford PLY reader: vertex positions, optional texture
+//! coordinates and colors, and polygon faces, in ASCII or binary form.
+
+use std::io::{BufRead, Read};
+
+use super::MeshError;
+use crate::Vecf;
+
+pub(super) struct PlyData {
+    pub positions: Vec<Vecf>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 3]>>,
+    /// Faces fan-triangulated into index triples.
+    pub indices: Vec<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, MeshError> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(MeshError::Invalid("unknown PLY property type")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Scale that maps the type's color range onto 0-1.
+    fn color_scale(self) -> f32 {
+        match self {
+            Scalar::U8 => 1.0 / 255.0,
+            Scalar::U16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+pub(super) fn read<R: BufRead>(mut reader: R) -> Result<PlyData, MeshError> {
+    let (format, elements) = read_header(&mut reader)?;
+    let mut body = Body::new(reader, format)?;
+    let mut data = PlyData {
+        positions: Vec::new(),
+        uvs: None,
+        colors: None,
+        indices: Vec::new(),
+    };
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut data)?,
+            "face" => read_faces(&mut body, element, &mut data)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        body.skip(property)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(data)
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<(Format, Vec<Element>), MeshError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim() != "ply" {
+        return Err(MeshError::Invalid("missing PLY magic number"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(MeshError::Invalid("PLY header is not terminated"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(MeshError::Invalid("unknown PLY format")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| MeshError::Invalid("bad PLY element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or(MeshError::Invalid("PLY property outside an element"))?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count_type)?,
+                    Scalar::parse(item_type)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or(MeshError::Invalid("PLY property outside an element"))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(ty)?)),
+            _ => {}
+        }
+    }
+    let format = format.ok_or(MeshError::Invalid("PLY header has no format"))?;
+    Ok((format, elements))
+}
+
+fn read_vertices<R: Read>(
+    body: &mut Body<R>,
+    element: &Element,
+    data: &mut PlyData,
+) -> Result<(), MeshError> {
+    let has = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .any(|p| matches!(p, Property::Scalar(name, _) if names.contains(&name.as_str())))
+    };
+    let mut uvs = if has(&["s", "u", "texture_u"]) && has(&["t", "v", "texture_v"]) {
+        Some(Vec::with_capacity(element.count))
+    } else {
+        None
+    };
+    let mut colors = if has(&["red", "r"]) && has(&["green", "g"]) && has(&["blue", "b"]) {
+        Some(Vec::with_capacity(element.count))
+    } else {
+        None
+    };
+    for _ in 0..element.count {
+        let mut position = [0.0; 3];
+        let mut uv = [0.0; 2];
+        let mut color = [0.0; 3];
+        for property in &element.properties {
+            let (name, ty) = match property {
+                Property::Scalar(name, ty) => (name.as_str(), *ty),
+                Property::List(..) => {
+                    body.skip(property)?;
+                    continue;
+                }
+            };
+            let value = body.value(ty)?;
+            match name {
+                "x" => position[0] = value as f32,
+                "y" => position[1] = value as f32,
+                "z" => position[2] = value as f32,
+                "s" | "u" | "texture_u" => uv[0] = value as f32,
+                "t" | "v" | "texture_v" => uv[1] = value as f32,
+                "red" | "r" => color[0] = value as f32 * ty.color_scale(),
+                "green" | "g" => color[1] = value as f32 * ty.color_scale(),
+                "blue" | "b" => color[2] = value as f32 * ty.color_scale(),
+                _ => {}
+            }
+        }
+        data.positions.push(position);
+        if let Some(uvs) = uvs.as_mut() {
+            uvs.push(uv);
+        }
+        if let Some(colors) = colors.as_mut() {
+            colors.push(color);
+        }
+    }
+    data.uvs = uvs;
+    data.colors = colors;
+    Ok(())
+}
+
+fn read_faces<R: Read>(
+    body: &mut Body<R>,
+    element: &Element,
+    data: &mut PlyData,
+) -> Result<(), MeshError> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::List(name, count_type, item_type)
+                    if name == "vertex_indices" || name == "vertex_index" =>
+                {
+                    let count = body.value(*count_type)? as usize;
+                    let mut polygon = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        polygon.push(body.value(*item_type)? as u32);
+                    }
+                    for i in 1..count.saturating_sub(1) {
+                        data.indices
+                            .extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                }
+                _ => body.skip(property)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The data section of a PLY file, read one value at a time.
+struct Body<R> {
+    reader: R,
+    format: Format,
+    /// Remaining ASCII tokens; unused for binary files.
+    tokens: std::vec::IntoIter<String>,
+}
+
+impl<R: Read> Body<R> {
+    fn new(mut reader: R, format: Format) -> Result<Body<R>, MeshError> {
+        let mut tokens = Vec::new();
+        if format == Format::Ascii {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            tokens = text.split_whitespace().map(String::from).collect();
+        }
+        Ok(Body {
+            reader,
+            format,
+            tokens: tokens.into_iter(),
+        })
+    }
+
+    fn value(&mut self, ty: Scalar) -> Result<f64, MeshError> {
+        if self.format == Format::Ascii {
+            return self
+                .tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or(MeshError::Invalid("bad or missing PLY value"));
+        }
+        let mut bytes = [0; 8];
+        let bytes = &mut bytes[..ty.size()];
+        self.reader.read_exact(bytes)?;
+        if self.format == Format::BigEndian {
+            bytes.reverse();
+        }
+        let mut le = [0; 8];
+        le[..bytes.len()].copy_from_slice(bytes);
+        Ok(match ty {
+            Scalar::I8 => le[0] as i8 as f64,
+            Scalar::U8 => le[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([le[0], le[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([le[0], le[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(le),
+        })
+    }
+
+    fn skip(&mut self, property: &Property) -> Result<(), MeshError> {
+        match property {
+            Property::Scalar(_, ty) => {
+                self.value(*ty)?;
+            }
+            Property::List(_, count_type, item_type) => {
+                for _ in 0..self.value(*count_type)? as usize {
+                    self.value(*item_type)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Settings a render was made with, stored in the image file itself so
+//! any render can be traced back to how it was produced.
+
+use image::ImageError;
+#[cfg(feature = "fs")]
+use image::{png::PngEncoder, ColorType, RgbImage};
+use std::{
+    fmt,
+    io::{self, Read},
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{scene::Scene, units::Unit, view::View};
+
+/// Starts every PNG file.
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(io::Error),
+    Encode(ImageError),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataError::Io(err) => write!(f, "could not access image: {}", err),
+            MetadataError::Encode(err) => write!(f, "could not encode image: {}", err),
+            MetadataError::Invalid(reason) => write!(f, "invalid PNG file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl From<io::Error> for MetadataError {
+    fn from(err: io::Error) -> MetadataError {
+        MetadataError::Io(err)
+    }
+}
+
+/// Named text entries describing a render, written as PNG `tEXt` chunks
+/// that image viewers and tools like `exiftool` show.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderInfo {
+    entries: Vec<(String, String)>,
+}
+
+impl RenderInfo {
+    /// The camera, resolution, exposure, sampling, integrator, tone map,
+    /// color space, dithering and seed of `view`, the
+    /// crate version, and `scene`'s unit and a hash of its objects, lights
+    /// and media.
+    pub fn new(view: &View, scene: &Scene) -> RenderInfo {
+        let mut info = RenderInfo::default()
+            .with_entry(
+                "Software",
+                format!("raytracer {}", env!("CARGO_PKG_VERSION")),
+            )
+            .with_entry(
+                "Resolution",
+                format!("{}x{}", view.image_width, view.image_height),
+            )
+            .with_entry("Camera position", format!("{:?}", view.cam_position))
+            .with_entry("Camera direction", format!("{:?}", view.direction))
+            .with_entry("Field of view", format!("{:?}", view.fov_rad.to_degrees()))
+            .with_entry("Max depth", view.max_depth.to_string())
+            .with_entry("Exposure value", format!("{:?}", view.exposure_value))
+            .with_entry("Samples", view.samples.to_string());
+        if let Some(width) = view.orthographic {
+            info = info.with_entry("Orthographic width", format!("{:?}", width));
+        }
+        if scene.unit != Unit::Meter {
+            info = info.with_entry("Unit", format!("{:?}", scene.unit));
+        }
+        if view.fresnel {
+            info = info.with_entry("Reflections", "Fresnel");
+        }
+        if view.min_contribution != 0.0 {
+            info = info.with_entry("Min contribution", format!("{:?}", view.min_contribution));
+        }
+        if view.light_cutoff != 0.0 {
+            info = info.with_entry("Light cutoff", format!("{:?}", view.light_cutoff));
+        }
+        if view.max_glossy_samples != u32::MAX {
+            info = info.with_entry("Max glossy samples", view.max_glossy_samples.to_string());
+        }
+        if view.cone_tracing {
+            info = info.with_entry("Shadows", "Cone traced");
+        }
+        if let Some(dither) = view.dither {
+            info = info.with_entry("Dither", format!("{:?}", dither));
+        }
+        if let Some(adaptive) = view.adaptive {
+            info = info.with_entry(
+                "Adaptive sampling",
+                format!(
+                    "threshold {:?}, up to {} samples",
+                    adaptive.threshold, adaptive.max_samples
+                ),
+            );
+        }
+        let integrator = view
+            .integrator
+            .as_ref()
+            .map_or("whitted", |integrator| integrator.name());
+        if view.frame != 0 && !view.fixed_noise {
+            info = info.with_entry("Frame", view.frame.to_string());
+        }
+        info.with_entry("Integrator", integrator)
+            .with_entry("Tone map", format!("{:?}", view.tonemap))
+            .with_entry("Color space", if view.srgb { "sRGB" } else { "linear" })
+            .with_entry("Seed", view.seed.to_string())
+            .with_entry("Scene hash", format!("{:016x}", scene_hash(scene)))
+    }
+
+    /// Adds an entry, e.g. the scene's name or the render time. `key` must
+    /// be 1 to 79 printable ASCII characters; characters outside Latin-1 in
+    /// `value` are written as `?`.
+    pub fn with_entry(mut self, key: impl Into<String>, value: impl Into<String>) -> RenderInfo {
+        let key = key.into();
+        assert!(
+            (1..=79).contains(&key.len()) && key.bytes().all(|b| (32..127).contains(&b)),
+            "key must be 1 to 79 printable ASCII characters"
+        );
+        self.entries.push((key, value.into()));
+        self
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    /// Value of the first entry named `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Encodes `image` as a PNG file carrying the entries.
+    #[cfg(feature = "fs")]
+    pub fn write_png<W: Write>(
+        &self,
+        image: &RgbImage,
+        mut writer: W,
+    ) -> Result<(), MetadataError> {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .encode(image, image.width(), image.height(), ColorType::Rgb8)
+            .map_err(MetadataError::Encode)?;
+        // The signature and the IHDR chunk must come first; text may follow.
+        let (head, rest) = png.split_at(PNG_SIGNATURE.len() + 8 + 13 + 4);
+        writer.write_all(head)?;
+        for (key, value) in &self.entries {
+            let mut data = key.as_bytes().to_vec();
+            data.push(0);
+            data.extend(
+                value
+                    .chars()
+                    .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }),
+            );
+            write_chunk(&mut writer, b"tEXt", &data)?;
+        }
+        writer.write_all(rest)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the entries of a PNG file's `tEXt` chunks, such as those
+    /// written by `write_png`.
+    pub fn read_png<R: Read>(mut reader: R) -> Result<RenderInfo, MetadataError> {
+        let mut signature = [0; 8];
+        reader.read_exact(&mut signature)?;
+        if &signature != PNG_SIGNATURE {
+            return Err(MetadataError::Invalid("not a PNG file"));
+        }
+        let mut info = RenderInfo::default();
+        loop {
+            let mut header = [0; 8];
+            reader.read_exact(&mut header)?;
+            let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let mut data = vec![0; len as usize + 4];
+            reader.read_exact(&mut data)?;
+            data.truncate(len as usize);
+            match &header[4..] {
+                b"tEXt" => {
+                    let nul = data
+                        .iter()
+                        .position(|&b| b == 0)
+                        .ok_or(MetadataError::Invalid("text chunk without a keyword"))?;
+                    let latin1 =
+                        |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect::<String>();
+                    info.entries
+                        .push((latin1(&data[..nul]), latin1(&data[nul + 1..])));
+                }
+                b"IEND" => return Ok(info),
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save_png<P: AsRef<Path>>(&self, image: &RgbImage, path: P) -> Result<(), MetadataError> {
+        self.write_png(image, BufWriter::new(fs::File::create(path)?))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn open_png<P: AsRef<Path>>(path: P) -> Result<RenderInfo, MetadataError> {
+        RenderInfo::read_png(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+#[cfg(feature = "fs")]
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// The CRC-32 PNG chunks end with, computed bit by bit; metadata chunks are
+/// small enough not to need a table.
+#[cfg(feature = "fs")]
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// FNV-1a hash of everything in `scene` that can be written out: object
+/// geometry and materials, lights and media. Objects without a
+/// `Primitive` contribute their position and material coefficients only.
+/// Refraction is only hashed for transparent objects, absorption for
+/// dielectrics and absorbing objects, roughness for metals and rough
+/// objects, shininess for shiny objects, PBR materials for objects that
+/// have one and emission for glowing objects, keeping the hashes of
+/// earlier scenes.
+fn scene_hash(scene: &Scene) -> u64 {
+    let mut description = String::new();
+    for object in &scene.objects {
+        match object.primitive() {
+            Some(primitive) => description += &format!("{:?}\n", primitive),
+            None => {
+                description += &format!(
+                    "object {:?} {:?} {:?}\n",
+                    object.get_position(),
+                    object.get_lambert(),
+                    object.get_specular()
+                )
+            }
+        }
+        if object.get_transparency() != 0.0 {
+            description += &format!(
+                "refraction {:?} {:?}\n",
+                object.get_transparency(),
+                object.get_ior()
+            );
+        }
+        if object.is_dielectric() {
+            description += &format!("dielectric {:?}\n", object.get_absorption());
+        } else if object.get_absorption() != [0.0; 3] {
+            description += &format!("absorption {:?}\n", object.get_absorption());
+        }
+        if object.is_metal() {
+            description += &format!("metal {:?}\n", object.get_roughness());
+        } else if object.get_roughness() != 0.0 {
+            description += &format!("roughness {:?}\n", object.get_roughness());
+        }
+        if object.get_shininess() > 0.0 {
+            description += &format!("shininess {:?}\n", object.get_shininess());
+        }
+        if let Some(pbr) = object.get_pbr() {
+            description += &format!("{:?}\n", pbr);
+        }
+        if let Some(coat) = object.get_clearcoat() {
+            description += &format!("{:?}\n", coat);
+        }
+        let emission = object.material().map_or([0.0; 3], |m| m.get_emission());
+        if emission != [0.0; 3] {
+            description += &format!("emission {:?}\n", emission);
+        }
+    }
+    for light in &scene.lights {
+        description += &format!(
+            "light {:?} {:?} {:?} {}\n",
+            light.position, light.intensity, light.radius, light.shadow_samples
+        );
+    }
+    description += &format!("{:?} {:?} {:?}", scene.fog, scene.clouds, scene.volumes);
+    description
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
@@ -0,0 +1,86 @@
+//! Gradient noise for procedural textures such as `texture::Marble`, so
+//! natural-looking surfaces need no image files. All functions are smooth
+//! and the same for the same point, with features about one unit across.
+
+use vecmath::{vec3_dot, vec3_scale, vec3_sub};
+
+use crate::{sampler::hash, Vecf};
+
+/// The middles of a cube's edges, which give Perlin noise its gradients.
+const GRADIENTS: [Vecf; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Ken Perlin's improved gradient noise, from about -1 to 1 and 0 on the
+/// integer lattice.
+pub fn perlin(point: Vecf) -> f32 {
+    let cell = point.map(|c| c.floor());
+    let fraction = vec3_sub(point, cell);
+    let fade = fraction.map(|f| f * f * f * (f * (f * 6.0 - 15.0) + 10.0));
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        // Wrapping keeps far-off points, whose cells saturate, from overflowing.
+        let [x, y, z] = [
+            (cell[0] as i32).wrapping_add(dx),
+            (cell[1] as i32).wrapping_add(dy),
+            (cell[2] as i32).wrapping_add(dz),
+        ];
+        let gradient = GRADIENTS[(hash(hash(hash(x as u32) ^ y as u32) ^ z as u32) % 12) as usize];
+        vec3_dot(
+            gradient,
+            vec3_sub(fraction, [dx as f32, dy as f32, dz as f32]),
+        )
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let mut layers = [0.0; 2];
+    for (dz, layer) in layers.iter_mut().enumerate() {
+        let dz = dz as i32;
+        *layer = lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), fade[0]),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), fade[0]),
+            fade[1],
+        );
+    }
+    lerp(layers[0], layers[1], fade[2])
+}
+
+/// Fractal Brownian motion: `octaves` layers of `perlin` noise, each at
+/// twice the frequency and half the amplitude of the last, from about -1
+/// to 1. More octaves add finer detail.
+pub fn fbm(point: Vecf, octaves: u32) -> f32 {
+    octaves_sum(point, octaves, perlin)
+}
+
+/// Like `fbm`, but summing the noise's magnitude, from 0 to about 1, which
+/// gives the sharp creases of marble veins and billowing smoke.
+pub fn turbulence(point: Vecf, octaves: u32) -> f32 {
+    octaves_sum(point, octaves, |point| perlin(point).abs())
+}
+
+/// Sum of `noise` over `octaves` octaves, divided by the sum of their
+/// amplitudes.
+fn octaves_sum(point: Vecf, octaves: u32, noise: impl Fn(Vecf) -> f32) -> f32 {
+    let (mut sum, mut total, mut amplitude) = (0.0, 0.0, 1.0);
+    let mut point = point;
+    for _ in 0..octaves {
+        sum += amplitude * noise(point);
+        total += amplitude;
+        point = vec3_scale(point, 2.0);
+        amplitude *= 0.5;
+    }
+    if total > 0.0 {
+        sum / total
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,157 @@
+//! Physically based materials as PBR pipelines such as glTF author them: a
+//! base color, how metallic a surface is and how rough, shaded with the
+//! Cook-Torrance microfacet model.
+
+use std::f32::consts::PI;
+
+use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub};
+
+use crate::{scene::schlick, texture::tangent_frame, Vecf};
+
+/// Share of light non-metals reflect head on, as glTF assumes.
+pub(crate) const DIELECTRIC_F0: f32 = 0.04;
+
+/// Narrowest GGX distribution shaded, so that perfectly smooth surfaces
+/// still show a pinpoint highlight instead of none.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Set with `Standard::with_pbr`; the base color is the object's color or
+/// texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pbr {
+    /// 0 for dielectrics such as plastic or wood, 1 for metals.
+    pub metallic: f32,
+    /// Perceptual roughness, from 0 for a mirror finish to 1; its square
+    /// is the width of the GGX distribution.
+    pub roughness: f32,
+    /// Perceptual roughness along and across the surface's tangent, see
+    /// `Hit::tangent`, in place of `roughness` where there is one, for
+    /// brushed metal; `None` for the same roughness every way.
+    pub anisotropy: Option<[f32; 2]>,
+}
+
+impl Pbr {
+    /// Share of each channel reflected head on: 4% for dielectrics, the
+    /// `base` color, on the 0-1 scale, for metals.
+    pub fn f0(&self, base: [f32; 3]) -> [f32; 3] {
+        base.map(|b| DIELECTRIC_F0 + (b - DIELECTRIC_F0) * self.metallic)
+    }
+
+    /// Cook-Torrance specular reflectance for light arriving from
+    /// `to_light` and leaving towards `to_eye`, unit vectors pointing away
+    /// from the surface with unit `normal`: the GGX distribution of
+    /// microfacet normals, Smith's masking and shadowing and Schlick's
+    /// Fresnel term.
+    pub fn specular(&self, base: [f32; 3], normal: Vecf, to_light: Vecf, to_eye: Vecf) -> [f32; 3] {
+        let cos_light = vec3_dot(normal, to_light);
+        let cos_eye = vec3_dot(normal, to_eye);
+        if cos_light <= 0.0 || cos_eye <= 0.0 {
+            return [0.0; 3];
+        }
+        let halfway = vec3_normalized(vec3_add(to_light, to_eye));
+        let alpha = (self.roughness * self.roughness).max(MIN_ALPHA);
+        let distribution = ggx(vec3_dot(normal, halfway).max(0.0), alpha);
+        let masking = smith_g1(cos_light, alpha) * smith_g1(cos_eye, alpha);
+        let cos_halfway = vec3_dot(to_eye, halfway).max(0.0);
+        self.f0(base).map(|f0| {
+            schlick(cos_halfway, f0) * distribution * masking / (4.0 * cos_light * cos_eye)
+        })
+    }
+
+    /// Like `specular`, but stretched along `tangent`, a direction along
+    /// the surface, for anisotropic materials.
+    pub fn specular_along(
+        &self,
+        base: [f32; 3],
+        normal: Vecf,
+        tangent: Option<Vecf>,
+        to_light: Vecf,
+        to_eye: Vecf,
+    ) -> [f32; 3] {
+        let ([alpha_t, alpha_b], tangent, bitangent) = match self.frame(normal, tangent) {
+            Some(frame) => frame,
+            None => return self.specular(base, normal, to_light, to_eye),
+        };
+        let cos_light = vec3_dot(normal, to_light);
+        let cos_eye = vec3_dot(normal, to_eye);
+        if cos_light <= 0.0 || cos_eye <= 0.0 {
+            return [0.0; 3];
+        }
+        let halfway = vec3_normalized(vec3_add(to_light, to_eye));
+        let [t, b, n] = [tangent, bitangent, normal].map(|axis| vec3_dot(halfway, axis));
+        let d = (t / alpha_t).powi(2) + (b / alpha_b).powi(2) + n.max(0.0).powi(2);
+        let distribution = 1.0 / (PI * alpha_t * alpha_b * d * d);
+        let g1 = |direction: Vecf, cos: f32| {
+            let t = vec3_dot(direction, tangent) * alpha_t;
+            let b = vec3_dot(direction, bitangent) * alpha_b;
+            2.0 / (1.0 + (1.0 + (t * t + b * b) / (cos * cos)).sqrt())
+        };
+        let masking = g1(to_light, cos_light) * g1(to_eye, cos_eye);
+        let cos_halfway = vec3_dot(to_eye, halfway).max(0.0);
+        self.f0(base).map(|f0| {
+            schlick(cos_halfway, f0) * distribution * masking / (4.0 * cos_light * cos_eye)
+        })
+    }
+
+    /// GGX widths along and across `tangent`, and the tangent and
+    /// bitangent made perpendicular to the unit `normal`; `None` unless
+    /// the material is anisotropic and the tangent usable.
+    fn frame(&self, normal: Vecf, tangent: Option<Vecf>) -> Option<([f32; 2], Vecf, Vecf)> {
+        let anisotropy = self.anisotropy?;
+        let tangent = tangent?;
+        let tangent = vec3_sub(tangent, vec3_scale(normal, vec3_dot(tangent, normal)));
+        let length = vec3_len(tangent);
+        if length.is_nan() || length <= 1e-6 {
+            return None;
+        }
+        let tangent = vec3_normalized(tangent);
+        let alphas = anisotropy.map(|roughness| (roughness * roughness).max(MIN_ALPHA));
+        Some((alphas, tangent, vec3_cross(normal, tangent)))
+    }
+
+    /// A microfacet normal around the unit `normal`, picked from the GGX
+    /// distribution by `u` and `v` from 0 to 1, for reflections blurred as
+    /// much as the highlights. Anisotropic materials stretch it along
+    /// `tangent`.
+    pub fn sample_microfacet(&self, normal: Vecf, tangent: Option<Vecf>, u: f32, v: f32) -> Vecf {
+        let phi = 2.0 * PI * v;
+        if let Some(([alpha_t, alpha_b], tangent, bitangent)) = self.frame(normal, tangent) {
+            // Slopes of the unit-width distribution, stretched by the
+            // widths along each direction.
+            let slope = (u / (1.0 - u)).sqrt();
+            return vec3_normalized(vec3_add(
+                normal,
+                vec3_add(
+                    vec3_scale(tangent, alpha_t * slope * phi.cos()),
+                    vec3_scale(bitangent, alpha_b * slope * phi.sin()),
+                ),
+            ));
+        }
+        let alpha = (self.roughness * self.roughness).max(MIN_ALPHA);
+        let cos_squared = (1.0 - u) / (1.0 + (alpha * alpha - 1.0) * u);
+        let (cos, sin) = (cos_squared.sqrt(), (1.0 - cos_squared).max(0.0).sqrt());
+        let (tangent, bitangent) = tangent_frame(normal);
+        vec3_normalized(vec3_add(
+            vec3_scale(normal, cos),
+            vec3_add(
+                vec3_scale(tangent, sin * phi.cos()),
+                vec3_scale(bitangent, sin * phi.sin()),
+            ),
+        ))
+    }
+}
+
+/// Density of microfacets facing the halfway vector, at cosine `cos` to
+/// the normal, in the GGX (Trowbridge-Reitz) distribution of width `alpha`.
+fn ggx(cos: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = cos * cos * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Share of microfacets seen from a direction at cosine `cos` to the
+/// normal that no other microfacet hides, by Smith's GGX form.
+fn smith_g1(cos: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+}
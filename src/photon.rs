@@ -0,0 +1,303 @@
+//! Caustics: light that reaches diffuse surfaces by way of mirrors, which
+//! direct shading from the lights cannot find. See `Scene::set_caustics`.
+
+use std::{collections::BinaryHeap, f32::consts::PI};
+
+use vecmath::{vec3_add, vec3_dot, vec3_scale, vec3_sub};
+
+use crate::{
+    material::Scatter,
+    sampler::Rng,
+    scene::{Media, Object, Part, RayMask, Scene},
+    view::Ray,
+    warning::RenderWarning,
+    Vecf,
+};
+
+/// Bounces a photon is followed for before it is given up on.
+const MAX_BOUNCES: u32 = 8;
+
+/// How far a reflected photon starts off the surface it left, so it does
+/// not hit that surface again.
+const SURFACE_OFFSET: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug)]
+struct Photon {
+    position: Vecf,
+    /// Direction the photon was travelling in when it landed.
+    direction: Vecf,
+    power: f32,
+    /// Axis the kd-tree splits on at this photon.
+    axis: u8,
+}
+
+/// Photons shot from the scene's lights that reached a diffuse surface
+/// after one or more mirror bounces, in a kd-tree for finding the ones
+/// near a shading point. The default `Whitted` integrator adds the light
+/// they carry to its direct shading; the `PathTracer` finds such paths on
+/// its own and ignores the map.
+#[derive(Clone, Debug)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    gather: usize,
+    max_radius: f32,
+    caustics_only: bool,
+}
+
+impl PhotonMap {
+    /// Shoots `count` photons from `scene`'s lights, in proportion to their
+    /// intensity and evenly in all directions. Most never touch a mirror,
+    /// so sharp caustics take hundreds of thousands. Different `seed`s
+    /// give different photons.
+    pub fn build(scene: &Scene, count: u32, seed: u32) -> PhotonMap {
+        let total: f32 = scene.lights.iter().map(|light| light.intensity).sum();
+        let mut photons = Vec::new();
+        if total > 0.0 && count > 0 {
+            let power = total * scene.intensity_scale() / count as f32;
+            for i in 0..count {
+                let mut rng = Rng::new(i, 0, 0, seed);
+                let mut pick = rng.next_f32() * total;
+                let light = scene
+                    .lights
+                    .iter()
+                    .find(|light| {
+                        pick -= light.intensity;
+                        pick < 0.0
+                    })
+                    .unwrap_or(&scene.lights[scene.lights.len() - 1]);
+                let z = 1.0 - 2.0 * rng.next_f32();
+                let phi = 2.0 * PI * rng.next_f32();
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let ray = Ray::new(light.position, [r * phi.cos(), r * phi.sin(), z]);
+                trace_photon(scene, ray, power, &mut rng, &mut photons);
+            }
+        }
+        build_tree(&mut photons);
+        PhotonMap {
+            photons,
+            gather: 50,
+            max_radius: 0.25,
+            caustics_only: false,
+        }
+    }
+
+    /// Estimates light at each point from the `count` photons nearest to
+    /// it within `max_radius`. More photons give smoother but blurrier
+    /// caustics. 50 and 0.25 by default.
+    pub fn with_gather(mut self, count: usize, max_radius: f32) -> PhotonMap {
+        assert!(count > 0, "must gather at least one photon");
+        self.gather = count;
+        self.max_radius = max_radius;
+        self
+    }
+
+    /// Lights diffuse surfaces with the photons' light alone, leaving out
+    /// direct light and highlights, for tuning the photon count and
+    /// `with_gather` against the caustics by themselves. Off by default.
+    pub fn with_caustics_only(mut self, caustics_only: bool) -> PhotonMap {
+        self.caustics_only = caustics_only;
+        self
+    }
+
+    pub fn caustics_only(&self) -> bool {
+        self.caustics_only
+    }
+
+    /// Number of photons stored.
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Rough number of bytes the map takes.
+    pub(crate) fn memory_size(&self) -> usize {
+        std::mem::size_of::<PhotonMap>() + self.photons.len() * std::mem::size_of::<Photon>()
+    }
+
+    /// Light the photons bring to `point` on a surface facing `normal`, on
+    /// the scale of `View::light_amount`.
+    pub fn irradiance(&self, point: Vecf, normal: Vecf) -> f32 {
+        let (nearest, radius2) = self.gather_at(point);
+        let power: f32 = nearest
+            .iter()
+            .map(|&(_, i)| &self.photons[i])
+            .filter(|photon| vec3_dot(photon.direction, normal) < 0.0)
+            .map(|photon| photon.power)
+            .sum();
+        // Photons piled on `point` itself, or a `max_radius` of 0, leave no
+        // area to spread their power over.
+        if radius2.is_nan() || radius2 <= 0.0 {
+            return 0.0;
+        }
+        power / (PI * radius2)
+    }
+
+    /// Photons landed per unit of area around `point`, counting those
+    /// `irradiance` would gather there whichever way they arrived. See
+    /// `View::render_photon_density`.
+    pub fn density(&self, point: Vecf) -> f32 {
+        let (nearest, radius2) = self.gather_at(point);
+        if radius2.is_nan() || radius2 <= 0.0 {
+            return 0.0;
+        }
+        nearest.len() as f32 / (PI * radius2)
+    }
+
+    /// The photons `irradiance` gathers at `point`, and the square of the
+    /// radius they were found within.
+    fn gather_at(&self, point: Vecf) -> (BinaryHeap<(u32, usize)>, f32) {
+        let mut nearest = BinaryHeap::with_capacity(self.gather + 1);
+        let mut radius2 = self.max_radius * self.max_radius;
+        if !self.photons.is_empty() {
+            self.nearest(0, self.photons.len(), point, &mut nearest, &mut radius2);
+        }
+        (nearest, radius2)
+    }
+
+    /// Collects the photons of the subtree in `start..end` within the
+    /// square root of `radius2` of `point` into `nearest`, keeping at most
+    /// `gather` of them by shrinking `radius2` to the furthest once full.
+    fn nearest(
+        &self,
+        start: usize,
+        end: usize,
+        point: Vecf,
+        nearest: &mut BinaryHeap<(u32, usize)>,
+        radius2: &mut f32,
+    ) {
+        if start >= end {
+            return;
+        }
+        let mid = (start + end) / 2;
+        let photon = &self.photons[mid];
+        let axis = photon.axis as usize;
+        let offset = point[axis] - photon.position[axis];
+        let (near, far) = if offset < 0.0 {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.nearest(near.0, near.1, point, nearest, radius2);
+        let to_photon = vec3_sub(photon.position, point);
+        let distance2 = vec3_dot(to_photon, to_photon);
+        if distance2 < *radius2 {
+            // Bits of non-negative floats sort like the floats themselves.
+            nearest.push((distance2.to_bits(), mid));
+            if nearest.len() > self.gather {
+                nearest.pop();
+            }
+            if nearest.len() == self.gather {
+                *radius2 = f32::from_bits(nearest.peek().map_or(0, |&(bits, _)| bits));
+            }
+        }
+        if offset * offset < *radius2 {
+            self.nearest(far.0, far.1, point, nearest, radius2);
+        }
+    }
+}
+
+/// Follows a photon through mirror bounces and refraction, storing it
+/// wherever it lands on a diffuse surface after the first. Each surface
+/// passes it on with a chance of its specular and transparency
+/// coefficients, which keeps the power unchanged. Metals reflect photons
+/// untinted, as polished mirrors. Materials with their own
+/// `Material::scatter` absorb them, recording a
+/// `RenderWarning::UnsupportedMaterial`.
+fn trace_photon(
+    scene: &Scene,
+    mut ray: Ray,
+    mut power: f32,
+    rng: &mut Rng,
+    photons: &mut Vec<Photon>,
+) {
+    let mut media = Media::default();
+    let mut from = None;
+    for bounce in 0..MAX_BOUNCES {
+        let (point, object, part) = match closest_hit(scene, &ray, from) {
+            Some(hit) => hit,
+            None => return,
+        };
+        from = part.map(|part| (object, part));
+        if let Some(material) = object.material() {
+            let hit = object.hit_on(&ray, point, part);
+            if !matches!(material.scatter(&ray, &hit, rng), Scatter::Standard) {
+                scene.warnings.record(RenderWarning::UnsupportedMaterial);
+                return;
+            }
+        }
+        if bounce > 0 && object.get_lambert() > 0.0 {
+            photons.push(Photon {
+                position: point,
+                direction: ray.direction,
+                power,
+                axis: 0,
+            });
+        }
+        let (specular, transparency) = (object.get_specular(), object.get_transparency());
+        let total = specular + transparency;
+        let survive = total.min(1.0);
+        let pick = rng.next_f32();
+        if survive <= 0.0 || pick >= survive {
+            return;
+        }
+        power *= total / survive;
+        let next = if pick * total < specular * survive {
+            object.reflect_ray(&ray, point)
+        } else {
+            let refracted = object.refract_ray(&ray, point, media.around(object, &ray, point));
+            media = media.crossed(object, &ray, point, &refracted);
+            refracted
+        };
+        ray = Ray {
+            origin: vec3_add(next.origin, vec3_scale(next.direction, SURFACE_OFFSET)),
+            direction: next.direction,
+        };
+    }
+}
+
+/// Nearest object visible to reflection rays that `ray`, leaving the part
+/// `from`, hits, and the part of it. Shadow catchers are invisible to
+/// light.
+fn closest_hit<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    from: Option<(&dyn Object, Part)>,
+) -> Option<(Vecf, &'a dyn Object, Option<Part>)> {
+    let hit = scene.raycast_from(ray, from, |_, object| {
+        object.visibility().intersects(RayMask::REFLECTION) && !object.is_shadow_catcher()
+    })?;
+    Some((hit.point, scene.objects[hit.index].as_ref(), hit.part))
+}
+
+/// Arranges `photons` as a balanced kd-tree: the median along the axis of
+/// widest spread sits in the middle, with the smaller half before it and
+/// the larger after, each arranged the same way.
+fn build_tree(photons: &mut [Photon]) {
+    if photons.len() <= 1 {
+        if let Some(photon) = photons.first_mut() {
+            photon.axis = 0;
+        }
+        return;
+    }
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for photon in photons.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(photon.position[i]);
+            max[i] = max[i].max(photon.position[i]);
+        }
+    }
+    let spread = vec3_sub(max, min);
+    let axis = (0..3)
+        .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+        .unwrap_or(0);
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.position[axis].total_cmp(&b.position[axis]));
+    photons[mid].axis = axis as u8;
+    let (before, rest) = photons.split_at_mut(mid);
+    build_tree(before);
+    build_tree(&mut rest[1..]);
+}
@@ -0,0 +1,75 @@
+//! Points to place many objects at, so that demo and stress scenes with
+//! hundreds of objects take a few lines: lay out a `Region`, take a grid or
+//! a random scatter of points from it, optionally `jitter` them, and add an
+//! object at each with `Scene::add_at`.
+
+use vecmath::{vec3_add, vec3_scale, vec3_sub};
+
+use crate::{sampler::Rng, Vecf};
+
+/// A rectangle given by three corners, as for `Plane::from_points` and
+/// `Scene::add_area_light_from_quad`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub top_right: Vecf,
+    pub bottom_right: Vecf,
+    pub bottom_left: Vecf,
+}
+
+impl Region {
+    pub fn new(top_right: Vecf, bottom_right: Vecf, bottom_left: Vecf) -> Region {
+        Region {
+            top_right,
+            bottom_right,
+            bottom_left,
+        }
+    }
+
+    /// The point `across` of the way from the left edge to the right and
+    /// `up` of the way from the bottom edge to the top.
+    pub fn point(&self, across: f32, up: f32) -> Vecf {
+        let height_vec = vec3_sub(self.top_right, self.bottom_right);
+        let width_vec = vec3_sub(self.bottom_right, self.bottom_left);
+        vec3_add(
+            self.bottom_left,
+            vec3_add(vec3_scale(width_vec, across), vec3_scale(height_vec, up)),
+        )
+    }
+
+    /// The middles of the cells of a grid of `columns` by `rows` over the
+    /// region, row by row from the bottom left.
+    pub fn grid(&self, columns: u32, rows: u32) -> Vec<Vecf> {
+        let mut points = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let across = (column as f32 + 0.5) / columns as f32;
+                let up = (row as f32 + 0.5) / rows as f32;
+                points.push(self.point(across, up));
+            }
+        }
+        points
+    }
+
+    /// `count` points spread at random over the region, the same for the
+    /// same `seed`.
+    pub fn scatter(&self, count: u32, seed: u32) -> Vec<Vecf> {
+        let mut rng = Rng::new(0, 0, 0, seed);
+        (0..count)
+            .map(|_| {
+                let across = rng.next_f32();
+                self.point(across, rng.next_f32())
+            })
+            .collect()
+    }
+}
+
+/// Moves each of `points` by up to `amount` along each axis at random, the
+/// same for the same `seed`, e.g. to break up the regularity of a grid.
+pub fn jitter(points: &mut [Vecf], amount: f32, seed: u32) {
+    let mut rng = Rng::new(0, 0, 1, seed);
+    for point in points {
+        for coordinate in point.iter_mut() {
+            *coordinate += (2.0 * rng.next_f32() - 1.0) * amount;
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Effects applied to a finished render.
+
+use std::time::Duration;
+
+use image::RgbImage;
+use vecmath::{vec3_len, vec3_sub};
+
+use crate::{
+    scene::{RayMask, Scene},
+    view::View,
+};
+
+/// Tints of the ghost reflections `LensFlare` scatters along the line from
+/// a light through the image center, cycled through in order.
+const GHOST_TINTS: [[f32; 3]; 4] = [
+    [0.4, 0.6, 1.0],
+    [1.0, 0.6, 0.3],
+    [0.5, 1.0, 0.6],
+    [1.0, 0.4, 0.8],
+];
+
+/// Procedural lens flares over every light the camera can see: a glow,
+/// a horizontal streak and a row of ghosts mirrored through the image
+/// center. Sizes are fractions of the image width.
+#[derive(Clone, Copy, Debug)]
+pub struct LensFlare {
+    strength: f32,
+    glow: f32,
+    streak: f32,
+    ghosts: u32,
+}
+
+impl Default for LensFlare {
+    fn default() -> LensFlare {
+        LensFlare {
+            strength: 1.0,
+            glow: 0.05,
+            streak: 0.3,
+            ghosts: 4,
+        }
+    }
+}
+
+impl LensFlare {
+    pub fn new() -> LensFlare {
+        LensFlare::default()
+    }
+
+    /// Scales the brightness of every part; 1 by default.
+    pub fn with_strength(mut self, strength: f32) -> LensFlare {
+        self.strength = strength;
+        self
+    }
+
+    /// Radius of the glow around the light; 0.05 by default.
+    pub fn with_glow(mut self, glow: f32) -> LensFlare {
+        self.glow = glow;
+        self
+    }
+
+    /// Half length of the horizontal streak, 0 for none; 0.3 by default.
+    pub fn with_streak(mut self, streak: f32) -> LensFlare {
+        self.streak = streak;
+        self
+    }
+
+    /// Number of ghosts, 0 for none; 4 by default.
+    pub fn with_ghosts(mut self, ghosts: u32) -> LensFlare {
+        self.ghosts = ghosts;
+        self
+    }
+
+    /// Adds the flares of `scene`'s lights to `image`, a render of it
+    /// through `view`. Lights behind the camera, outside the image or
+    /// hidden behind objects get none; brighter and closer lights get
+    /// stronger ones.
+    pub fn apply(&self, view: &View, scene: &Scene, image: &mut RgbImage) {
+        let (width, height) = image.dimensions();
+        assert_eq!(
+            (width, height),
+            (view.image_width, view.image_height),
+            "image does not match the view size"
+        );
+        let scale = width as f32;
+        let center = [width as f32 / 2.0, height as f32 / 2.0];
+        let mut flare = vec![[0.0f32; 3]; (width * height) as usize];
+        for light in &scene.lights {
+            let [lx, ly] = match view.project(light.position) {
+                Some(position) => position,
+                None => continue,
+            };
+            if lx < 0.0 || ly < 0.0 || lx >= width as f32 || ly >= height as f32 {
+                continue;
+            }
+            if view.blocked(
+                scene,
+                view.cam_position,
+                light.position,
+                None,
+                None,
+                RayMask::CAMERA,
+            ) {
+                continue;
+            }
+            let distance = vec3_len(vec3_sub(light.position, view.cam_position));
+            let intensity = light.intensity * scene.intensity_scale();
+            let brightness = self.strength
+                * (intensity / (4.0 * std::f32::consts::PI * distance * distance)).min(1.0);
+            // Ghosts sit along the line through the center, on both sides.
+            let ghosts: Vec<([f32; 2], f32, [f32; 3])> = (0..self.ghosts)
+                .map(|i| {
+                    let t = -0.6 + 1.9 * (i as f32 + 1.0) / (self.ghosts as f32 + 1.0);
+                    let position = [
+                        lx + (center[0] - lx) * (1.0 + t),
+                        ly + (center[1] - ly) * (1.0 + t),
+                    ];
+                    let radius = scale * (0.02 + 0.03 * ((i * 7) % 5) as f32 / 4.0);
+                    (
+                        position,
+                        radius,
+                        GHOST_TINTS[i as usize % GHOST_TINTS.len()],
+                    )
+                })
+                .collect();
+            for y in 0..height {
+                for x in 0..width {
+                    let (dx, dy) = (x as f32 - lx, y as f32 - ly);
+                    let glow_radius = self.glow * scale;
+                    let mut amount = (-(dx * dx + dy * dy) / (glow_radius * glow_radius)).exp();
+                    if self.streak > 0.0 {
+                        amount += (-dx.abs() / (self.streak * scale)).exp()
+                            * (-dy.abs() / (0.003 * scale)).exp()
+                            * 0.5;
+                    }
+                    let pixel = &mut flare[(y * width + x) as usize];
+                    for c in pixel.iter_mut() {
+                        *c += amount * brightness;
+                    }
+                    for (position, radius, tint) in &ghosts {
+                        let r = ((x as f32 - position[0]).powi(2)
+                            + (y as f32 - position[1]).powi(2))
+                        .sqrt()
+                            / radius;
+                        if r < 1.0 {
+                            // Soft-edged discs, brightest at the rim like real ghosts.
+                            let ghost =
+                                0.15 * brightness * (0.4 + 0.6 * r * r) * (1.0 - r).min(0.1) * 10.0;
+                            for (c, t) in pixel.iter_mut().zip(tint) {
+                                *c += ghost * t;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (pixel, add) in image.pixels_mut().zip(&flare) {
+            for (c, a) in pixel.0.iter_mut().zip(add) {
+                *c = (*c as f32 + a * 255.0).min(255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Corner of the image a `BurnIn` is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Lines of text burnt into a corner of a render for review: frame number,
+/// scene name, camera settings, sample count, render time, or anything
+/// else. Drawn in white capitals from a 5x7 bitmap font over a darkened
+/// box, one line per piece of information in the order added.
+#[derive(Clone, Debug)]
+pub struct BurnIn {
+    lines: Vec<String>,
+    corner: Corner,
+    scale: u32,
+}
+
+impl Default for BurnIn {
+    fn default() -> BurnIn {
+        BurnIn {
+            lines: Vec::new(),
+            corner: Corner::BottomLeft,
+            scale: 1,
+        }
+    }
+}
+
+impl BurnIn {
+    pub fn new() -> BurnIn {
+        BurnIn::default()
+    }
+
+    /// Bottom left by default.
+    pub fn with_corner(mut self, corner: Corner) -> BurnIn {
+        self.corner = corner;
+        self
+    }
+
+    /// Size of each font pixel in image pixels; 1 by default.
+    pub fn with_scale(mut self, scale: u32) -> BurnIn {
+        assert!(scale > 0, "scale must be at least 1");
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_line(mut self, line: impl Into<String>) -> BurnIn {
+        self.lines.push(line.into());
+        self
+    }
+
+    pub fn with_frame(self, frame: f32) -> BurnIn {
+        self.with_line(format!("frame {}", frame))
+    }
+
+    pub fn with_scene_name(self, name: &str) -> BurnIn {
+        self.with_line(format!("scene {}", name))
+    }
+
+    /// The camera position, direction and field of view, and the samples
+    /// per pixel of `view`.
+    pub fn with_view(self, view: &View) -> BurnIn {
+        let [x, y, z] = view.cam_position;
+        let [dx, dy, dz] = view.direction;
+        self.with_line(format!(
+            "cam ({:.2}, {:.2}, {:.2}) dir ({:.2}, {:.2}, {:.2}) fov {:.0}",
+            x,
+            y,
+            z,
+            dx,
+            dy,
+            dz,
+            view.fov_rad.to_degrees()
+        ))
+        .with_line(format!("samples {}", view.samples))
+    }
+
+    pub fn with_render_time(self, time: Duration) -> BurnIn {
+        self.with_line(format!("time {:.1}s", time.as_secs_f32()))
+    }
+
+    /// Draws the text into `image`, cut off where it does not fit.
+    pub fn apply(&self, image: &mut RgbImage) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let (width, height) = image.dimensions();
+        // Glyphs are 5x7 font pixels, with one pixel of spacing between
+        // characters and lines and around the box.
+        let columns = self
+            .lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0) as u32;
+        let box_width = (columns * 6 + 1) * self.scale;
+        let box_height = (self.lines.len() as u32 * 8 + 1) * self.scale;
+        let margin = 4 * self.scale;
+        let left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin as i64,
+            Corner::TopRight | Corner::BottomRight => {
+                width as i64 - margin as i64 - box_width as i64
+            }
+        };
+        let top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => margin as i64,
+            Corner::BottomLeft | Corner::BottomRight => {
+                height as i64 - margin as i64 - box_height as i64
+            }
+        };
+        let mut set = |x: i64, y: i64, text: bool| {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            pixel.0 = if text {
+                [255; 3]
+            } else {
+                pixel.0.map(|c| c / 2)
+            };
+        };
+        for y in 0..box_height as i64 {
+            for x in 0..box_width as i64 {
+                set(left + x, top + y, false);
+            }
+        }
+        for (row, line) in self.lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let glyph = glyph(c);
+                for (gy, bits) in glyph.iter().enumerate() {
+                    for gx in 0..5 {
+                        if bits & (0x10 >> gx) == 0 {
+                            continue;
+                        }
+                        let x = left + ((column as i64 * 6 + 1 + gx) * self.scale as i64);
+                        let y = top + ((row as i64 * 8 + 1 + gy as i64) * self.scale as i64);
+                        for sy in 0..self.scale as i64 {
+                            for sx in 0..self.scale as i64 {
+                                set(x + sx, y + sy, true);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of the 5x7 font's glyph for `c`, top first, with the leftmost
+/// pixel in bit 4. Lowercase letters are drawn as capitals and characters
+/// the font lacks as `?`.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
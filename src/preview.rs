@@ -0,0 +1,122 @@
+//! Interactive preview window. WASD moves the camera and dragging with the
+//! left mouse button turns it; every move shows a blocky low-resolution
+//! frame at once, with cone traced soft shadows, then refines it tile by
+//! tile. Escape closes the window.
+
+use minifb::{Key, MouseButton, MouseMode, Window, WindowOptions};
+use vecmath::{vec3_add, vec3_normalized, vec3_scale};
+
+use crate::{
+    scene::Scene,
+    view::{View, TILE_SIZE},
+    Color,
+};
+
+/// Block size of the low-resolution frame shown right after a move.
+const PREVIEW_BLOCK: u32 = 8;
+/// Camera movement per frame a key is held, in scene units.
+const MOVE_SPEED: f32 = 0.05;
+/// Camera rotation per pixel of mouse drag, in radians.
+const LOOK_SPEED: f32 = 0.005;
+
+/// Opens a window showing `scene` through `view` until it is closed.
+pub fn preview(mut view: View, scene: &Scene) -> minifb::Result<()> {
+    let (width, height) = (view.image_width, view.image_height);
+    let mut window = Window::new(
+        "raytracer",
+        width as usize,
+        height as usize,
+        WindowOptions::default(),
+    )?;
+    window.set_target_fps(60);
+    let mut buffer = vec![0; (width * height) as usize];
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tile_count = tiles_x * height.div_ceil(TILE_SIZE);
+    let mut next_tile = 0;
+    let mut dirty = true;
+    let mut last_mouse = None;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if move_camera(&window, &mut view, &mut last_mouse) {
+            dirty = true;
+        }
+        let frame = view.begin_render(scene);
+        if dirty {
+            let quick = view.clone().with_cone_tracing(true);
+            for y in (0..height).step_by(PREVIEW_BLOCK as usize) {
+                for x in (0..width).step_by(PREVIEW_BLOCK as usize) {
+                    let color = quick.render_pixel(scene, &frame, x, y);
+                    fill(&mut buffer, width, height, x, y, PREVIEW_BLOCK, color);
+                }
+            }
+            next_tile = 0;
+            dirty = false;
+        } else if next_tile < tile_count {
+            let tile_x = next_tile % tiles_x * TILE_SIZE;
+            let tile_y = next_tile / tiles_x * TILE_SIZE;
+            for y in tile_y..(tile_y + TILE_SIZE).min(height) {
+                for x in tile_x..(tile_x + TILE_SIZE).min(width) {
+                    let color = view.render_pixel(scene, &frame, x, y);
+                    fill(&mut buffer, width, height, x, y, 1, color);
+                }
+            }
+            next_tile += 1;
+        }
+        window.update_with_buffer(&buffer, width as usize, height as usize)?;
+    }
+    Ok(())
+}
+
+/// Applies this frame's key and mouse input to the camera, returning
+/// whether it moved.
+fn move_camera(window: &Window, view: &mut View, last_mouse: &mut Option<(f32, f32)>) -> bool {
+    let frame = view.camera_frame();
+    let mut moved = false;
+    for (key, axis, sign) in [
+        (Key::W, view.direction, 1.0),
+        (Key::S, view.direction, -1.0),
+        (Key::D, frame.cam_right, 1.0),
+        (Key::A, frame.cam_right, -1.0),
+    ] {
+        if window.is_key_down(key) {
+            view.cam_position = vec3_add(view.cam_position, vec3_scale(axis, sign * MOVE_SPEED));
+            moved = true;
+        }
+    }
+
+    let mouse = window.get_mouse_pos(MouseMode::Pass);
+    if window.get_mouse_down(MouseButton::Left) {
+        if let (Some((x, y)), Some((last_x, last_y))) = (mouse, *last_mouse) {
+            let (dx, dy) = (x - last_x, y - last_y);
+            if dx != 0.0 || dy != 0.0 {
+                // `cam_up` points down the screen, so dragging down looks down.
+                let turned = vec3_normalized(vec3_add(
+                    view.direction,
+                    vec3_add(
+                        vec3_scale(frame.cam_right, dx * LOOK_SPEED),
+                        vec3_scale(frame.cam_up, dy * LOOK_SPEED),
+                    ),
+                ));
+                // Looking straight up or down would leave no right vector.
+                if turned[1].abs() < 0.99 {
+                    view.direction = turned;
+                    moved = true;
+                }
+            }
+        }
+        *last_mouse = mouse;
+    } else {
+        *last_mouse = None;
+    }
+    moved
+}
+
+fn fill(buffer: &mut [u32], width: u32, height: u32, x: u32, y: u32, block: u32, color: Color) {
+    let [r, g, b] = color.0;
+    let pixel = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+    for by in y..(y + block).min(height) {
+        for bx in x..(x + block).min(width) {
+            buffer[(by * width + bx) as usize] = pixel;
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! A JSON summary of a render, written next to the image for pipeline tools
+//! that track and check renders.
+
+#[cfg(feature = "fs")]
+use std::{fs, io::BufWriter, path::Path};
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{metadata::RenderInfo, scene::Scene, view::View, warning::RenderWarning};
+
+/// Settings, timings, statistics, warnings and output files of one render.
+/// Entries keep the order they were added in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderReport {
+    settings: RenderInfo,
+    timings: Vec<(String, f64)>,
+    stats: Vec<(String, f64)>,
+    warnings: Vec<String>,
+    outputs: Vec<(String, String)>,
+}
+
+impl RenderReport {
+    /// The settings of `RenderInfo::new`, counts of pixels, objects, lights,
+    /// photons and the scene's `RenderWarning`s, and warnings about settings
+    /// that are likely mistakes and problems worked around while rendering.
+    pub fn new(view: &View, scene: &Scene) -> RenderReport {
+        let mut report = RenderReport {
+            settings: RenderInfo::new(view, scene),
+            timings: Vec::new(),
+            stats: Vec::new(),
+            warnings: Vec::new(),
+            outputs: Vec::new(),
+        }
+        .with_stat("pixels", view.image_width as f64 * view.image_height as f64)
+        .with_stat("objects", scene.objects.len() as f64)
+        .with_stat("lights", scene.lights.len() as f64);
+        if let Some(caustics) = &scene.caustics {
+            report = report.with_stat("photons", caustics.len() as f64);
+        }
+        for warning in RenderWarning::ALL {
+            report = report.with_stat(warning.name(), scene.warnings.count(warning) as f64);
+        }
+        let integrator = report.settings.get("Integrator").unwrap_or("whitted");
+        let deterministic = ["whitted", "normals", "depth"].contains(&integrator);
+        let mut warnings = Vec::new();
+        if scene.objects.is_empty() {
+            warnings.push("the scene has no objects".to_string());
+        }
+        if scene.lights.is_empty() && integrator != "ambient occlusion" {
+            warnings.push("the scene has no lights".to_string());
+        }
+        if !deterministic && view.samples == 1 && view.adaptive.is_none() {
+            warnings.push(format!(
+                "the {} integrator with one sample per pixel is noisy",
+                integrator
+            ));
+        }
+        if scene.caustics.is_some() && integrator != "whitted" {
+            warnings.push(format!(
+                "the {} integrator ignores the caustics photon map",
+                integrator
+            ));
+        }
+        if let Some(index) = scene
+            .lights
+            .iter()
+            .position(|light| light.radius > 0.0 && light.shadow_samples == 0)
+        {
+            warnings.push(format!("area light {} has no shadow samples", index));
+        }
+        for (warning, count) in scene.warnings.iter() {
+            warnings.push(format!("{} {}", count, warning));
+        }
+        for warning in warnings {
+            report = report.with_warning(warning);
+        }
+        report
+    }
+
+    /// Adds a setting beyond those of `RenderInfo::new`, e.g. the scene's
+    /// name.
+    pub fn with_setting(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> RenderReport {
+        self.settings = self.settings.with_entry(key, value);
+        self
+    }
+
+    /// Adds how long a stage of the render took, e.g. "render" or "photons".
+    pub fn with_timing(mut self, stage: impl Into<String>, time: Duration) -> RenderReport {
+        self.timings.push((stage.into(), time.as_secs_f64()));
+        self
+    }
+
+    pub fn with_stat(mut self, name: impl Into<String>, value: f64) -> RenderReport {
+        self.stats.push((name.into(), value));
+        self
+    }
+
+    pub fn with_warning(mut self, warning: impl Into<String>) -> RenderReport {
+        self.warnings.push(warning.into());
+        self
+    }
+
+    /// Adds a file the render wrote, e.g. "beauty" or a pass such as
+    /// "depth", and its path.
+    pub fn with_output(mut self, name: impl Into<String>, path: impl Into<String>) -> RenderReport {
+        self.outputs.push((name.into(), path.into()));
+        self
+    }
+
+    pub fn settings(&self) -> &RenderInfo {
+        &self.settings
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Writes the report as a JSON object with the members `settings`
+    /// (strings), `timings` (seconds), `stats` (numbers), `warnings` (an
+    /// array of strings) and `outputs` (paths).
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{{")?;
+        write_object(&mut writer, "settings", self.settings.entries(), |value| {
+            json_string(value)
+        })?;
+        writeln!(writer, ",")?;
+        write_object(&mut writer, "timings", &self.timings, |&value| {
+            json_number(value)
+        })?;
+        writeln!(writer, ",")?;
+        write_object(&mut writer, "stats", &self.stats, |&value| {
+            json_number(value)
+        })?;
+        writeln!(writer, ",")?;
+        write!(writer, "  \"warnings\": [")?;
+        for (i, warning) in self.warnings.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(writer, "{}\n    {}", separator, json_string(warning))?;
+        }
+        if !self.warnings.is_empty() {
+            write!(writer, "\n  ")?;
+        }
+        writeln!(writer, "],")?;
+        write_object(&mut writer, "outputs", &self.outputs, |value| {
+            json_string(value)
+        })?;
+        writeln!(writer, "\n}}")?;
+        writer.flush()
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_json(BufWriter::new(fs::File::create(path)?))
+    }
+}
+
+/// Writes `"name": { ... }` at the top level, one member per entry.
+fn write_object<W: Write, T>(
+    writer: &mut W,
+    name: &str,
+    entries: &[(String, T)],
+    value: impl Fn(&T) -> String,
+) -> io::Result<()> {
+    write!(writer, "  {}: {{", json_string(name))?;
+    for (i, (key, v)) in entries.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        write!(
+            writer,
+            "{}\n    {}: {}",
+            separator,
+            json_string(key),
+            value(v)
+        )?;
+    }
+    if !entries.is_empty() {
+        write!(writer, "\n  ")?;
+    }
+    write!(writer, "}}")
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// JSON has no infinities or NaN; they are written as `null`.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "null".to_string()
+    }
+}
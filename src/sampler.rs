@@ -0,0 +1,150 @@
+//! Patterns for placing a pixel's samples, see `View::with_sampler`. All
+//! samplers are deterministic functions of the view's seed, so renders
+//! with the same seed are identical down to the bit.
+
+/// Places sample `index` of `count` in pixel `(x, y)` at an offset in
+/// [0, 1)² from the pixel's corner. Adaptive sampling keeps asking for
+/// more, so `index` can run past `count`. Different `seed`s should give
+/// unrelated patterns.
+pub trait Sampler: Send + Sync {
+    fn sample(&self, x: u32, y: u32, index: u32, count: u32, seed: u32) -> [f32; 2];
+}
+
+/// Independent pseudo-random offsets. Cheap, but samples can clump and
+/// leave parts of the pixel uncovered.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Uniform;
+
+impl Sampler for Uniform {
+    fn sample(&self, x: u32, y: u32, index: u32, _count: u32, seed: u32) -> [f32; 2] {
+        let seed = hash(pixel_seed(x, y, seed) ^ index);
+        [seed, hash(seed)].map(unit)
+    }
+}
+
+/// One random offset in each cell of a grid covering the pixel, as square
+/// as `count` allows. Samples past `count` start over on a fresh grid.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stratified;
+
+impl Sampler for Stratified {
+    fn sample(&self, x: u32, y: u32, index: u32, count: u32, seed: u32) -> [f32; 2] {
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        let cell = index % count;
+        let [jitter_x, jitter_y] = Uniform.sample(x, y, index, count, seed);
+        [
+            ((cell % columns) as f32 + jitter_x) / columns as f32,
+            ((cell / columns) as f32 + jitter_y) / rows as f32,
+        ]
+    }
+}
+
+/// The base 2 and 3 Halton sequence, shifted by a random offset per pixel
+/// so neighbouring pixels do not repeat the same pattern.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Halton;
+
+impl Sampler for Halton {
+    fn sample(&self, x: u32, y: u32, index: u32, _count: u32, seed: u32) -> [f32; 2] {
+        let seed = pixel_seed(x, y, seed);
+        [
+            (radical_inverse(index, 2) + unit(seed)).fract(),
+            (radical_inverse(index, 3) + unit(hash(seed))).fract(),
+        ]
+    }
+}
+
+/// The first two dimensions of the Sobol sequence, with their digits
+/// scrambled per pixel. Evenly spread at every power-of-two `count`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sobol;
+
+impl Sampler for Sobol {
+    fn sample(&self, x: u32, y: u32, index: u32, _count: u32, seed: u32) -> [f32; 2] {
+        let seed = pixel_seed(x, y, seed);
+        [
+            unit(index.reverse_bits() ^ seed),
+            unit(sobol_second(index) ^ hash(seed)),
+        ]
+    }
+}
+
+/// Stream of pseudo-random numbers in [0, 1) for one sample of one pixel,
+/// for integrators that make random choices along a path.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u32,
+    /// The sample's index, offset at random per pixel, see `stratum`.
+    stratum: u32,
+}
+
+impl Rng {
+    /// The stream for sample `index` of pixel `(x, y)` under `seed`.
+    pub fn new(x: u32, y: u32, index: u32, seed: u32) -> Rng {
+        let pixel = pixel_seed(x, y, seed);
+        Rng {
+            state: hash(pixel ^ index),
+            stratum: hash(pixel).wrapping_add(index),
+        }
+    }
+
+    /// Which of `count` choices the sample makes, taking turns through
+    /// them sample by sample from a random one per pixel, so a pixel's
+    /// samples share them out evenly.
+    pub(crate) fn stratum(&self, count: u32) -> u32 {
+        self.stratum % count
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        // Weyl sequence through the hash: consecutive states stay unrelated.
+        self.state = self.state.wrapping_add(0x9e37_79b9);
+        unit(hash(self.state))
+    }
+}
+
+/// The second Sobol dimension as 32 fixed-point fraction bits.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// `index` with its base-`base` digits mirrored around the radix point.
+fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let inverse_base = 1.0 / base as f32;
+    let mut scale = inverse_base;
+    let mut result = 0.0;
+    while index != 0 {
+        result += (index % base) as f32 * scale;
+        index /= base;
+        scale *= inverse_base;
+    }
+    result
+}
+
+fn pixel_seed(x: u32, y: u32, seed: u32) -> u32 {
+    hash(hash(hash(seed) ^ x) ^ y)
+}
+
+/// Maps the top 24 bits of `bits` to [0, 1).
+pub(crate) fn unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// The lowbias32 integer hash from Chris Wellons' hash-prospector.
+pub(crate) fn hash(mut v: u32) -> u32 {
+    v ^= v >> 16;
+    v = v.wrapping_mul(0x7feb_352d);
+    v ^= v >> 15;
+    v = v.wrapping_mul(0x846c_a68b);
+    v ^= v >> 16;
+    v
+}
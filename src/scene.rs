@@ -1,80 +1,1345 @@
+use std::{
+    cmp::Ordering,
+    f32::consts::PI,
+    ops::{BitAnd, BitOr},
+    sync::Arc,
+};
 use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub};
 
-use crate::{view::Ray, Color, Vecf};
+use crate::{
+    animation::LightAnimation,
+    atmosphere::{CloudLayer, HeightFog, Scattering, Volume},
+    material::{AlphaMode, Clearcoat, Cutout, Layered, Material, Standard},
+    pbr::Pbr,
+    photon::PhotonMap,
+    placement::Region,
+    shadow::ShadowCache,
+    summary::SceneSummary,
+    texture::{tangent_frame, AlphaMask, NormalMap, Texture},
+    units::Unit,
+    view::Ray,
+    warning::RenderWarnings,
+    Color, Vecf,
+};
 
 #[derive(Default)]
 pub struct Scene {
     pub objects: Vec<Box<dyn Object>>,
     pub lights: Vec<Light>,
+    pub fog: Option<HeightFog>,
+    pub clouds: Vec<CloudLayer>,
+    pub volumes: Vec<Volume>,
+    pub caustics: Option<PhotonMap>,
+    /// Length of one unit of the scene's coordinates.
+    pub unit: Unit,
+    /// Problems worked around while rendering the scene. Counts add up
+    /// over renders until reset.
+    pub warnings: RenderWarnings,
+    pub shadow_cache: Option<ShadowCache>,
 }
 
 impl Scene {
-    pub fn addObject<T: Object + 'static>(&mut self, object: T) {
+    pub fn add_object<T: Object + 'static>(&mut self, object: T) {
         self.objects.push(Box::new(object));
     }
 
-    pub fn addLight(&mut self, light: Light) {
-        self.lights.push(light);
+    /// Adds the object `make` builds at each of `points`, given the point
+    /// and its index to vary the objects by, e.g. at the points of
+    /// `Region::grid` or `Region::scatter`.
+    pub fn add_at<T: Object + 'static>(
+        &mut self,
+        points: &[Vecf],
+        mut make: impl FnMut(Vecf, usize) -> T,
+    ) {
+        for (index, &point) in points.iter().enumerate() {
+            self.add_object(make(point, index));
+        }
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    #[deprecated(note = "renamed to `add_object`")]
+    #[allow(non_snake_case)]
+    pub fn addObject<T: Object + 'static>(&mut self, object: T) {
+        self.add_object(object);
+    }
+
+    #[deprecated(note = "renamed to `add_light`")]
+    #[allow(non_snake_case)]
+    pub fn addLight(&mut self, light: Light) {
+        self.add_light(light);
+    }
+
+    /// Lights the scene from the rectangle `Plane::from_points` would
+    /// build from the same corners, e.g. a softbox, as `columns` by `rows`
+    /// point lights evenly spread over it and sharing `intensity`. More
+    /// lights give smoother shadows at the cost of a shadow ray each. The
+    /// rectangle itself is not an object, and shines to both sides.
+    pub fn add_area_light_from_quad(
+        &mut self,
+        top_right: Vecf,
+        bottom_right: Vecf,
+        bottom_left: Vecf,
+        intensity: f32,
+        columns: u32,
+        rows: u32,
+    ) {
+        let count = columns * rows;
+        // Each light sits in the middle of its cell of the grid.
+        for position in Region::new(top_right, bottom_right, bottom_left).grid(columns, rows) {
+            self.add_light(Light::new(position, intensity / count as f32));
+        }
+    }
+
+    /// Fills the space between camera and surfaces with `fog`, replacing any
+    /// earlier fog.
+    pub fn set_fog(&mut self, fog: HeightFog) {
+        self.fog = Some(fog);
+    }
+
+    /// Measures the scene in `unit`: light intensities and fog densities,
+    /// which are given per meter, are converted, so a light 100 units
+    /// away in a scene in centimeters lights as much as one 1 unit away in
+    /// meters. Meters by default.
+    pub fn set_unit(&mut self, unit: Unit) {
+        self.unit = unit;
+    }
+
+    /// Factor on light intensities for distances in the scene's unit
+    /// rather than meters, by the inverse square law.
+    pub(crate) fn intensity_scale(&self) -> f32 {
+        self.unit.meters().powi(-2)
+    }
+
+    /// Lights diffuse surfaces with the light `caustics` found bouncing
+    /// off mirrors, replacing any earlier map. Build it once the scene's
+    /// objects and lights are in place: it does not follow later changes.
+    pub fn set_caustics(&mut self, caustics: PhotonMap) {
+        self.caustics = Some(caustics);
+    }
+
+    /// Moves animated lights to `frame`, which may be fractional for motion
+    /// blur or slowed-down sequences.
+    pub fn set_frame(&mut self, frame: f32) {
+        for light in &mut self.lights {
+            if let Some(animation) = &light.animation {
+                light.intensity = animation.intensity_at(frame);
+            }
+        }
+    }
+
+    /// Remembers which lights are visible from which points across renders,
+    /// in cells `cell_size` wide, replacing any earlier cache. Speeds up
+    /// animations where only the camera moves; results are forgotten
+    /// whenever an object moves, and apply only to direct light in the
+    /// default integrator.
+    pub fn set_shadow_cache(&mut self, cell_size: f32) {
+        self.shadow_cache = Some(ShadowCache::new(cell_size));
+    }
+
+    pub fn add_clouds(&mut self, clouds: CloudLayer) {
+        self.clouds.push(clouds);
+    }
+
+    pub fn add_volume(&mut self, volume: Volume) {
+        self.volumes.push(volume);
+    }
+
+    /// Counts of the scene's objects, triangles, lights and media, its
+    /// materials and an estimate of its memory use, to check before a
+    /// long render.
+    pub fn summary(&self) -> SceneSummary {
+        SceneSummary::new(self)
+    }
+
+    /// What the fog, clouds and volumes do to light along the first
+    /// `distance` of `ray`, or `None` if the scene has none of them.
+    pub fn scattering(&self, ray: &Ray, distance: f32) -> Option<Scattering> {
+        if self.fog.is_none() && self.clouds.is_empty() && self.volumes.is_empty() {
+            return None;
+        }
+        let fog = self.fog.map_or(Scattering::CLEAR, |fog| {
+            fog.in_units(self.unit.meters()).scattering(ray, distance)
+        });
+        // Layers are few and rarely overlap, so their order barely matters.
+        let clouds = self.clouds.iter().fold(Scattering::CLEAR, |seen, layer| {
+            seen.then(layer.scattering(ray, distance))
+        });
+        let volumes = self.volumes.iter().fold(Scattering::CLEAR, |seen, volume| {
+            seen.then(volume.scattering(ray, distance))
+        });
+        Some(fog.then(clouds).then(volumes))
+    }
+
+    /// Whether anything blocks `ray` closer than `max_distance`. Shadow
+    /// catchers and objects hidden from shadow rays never block.
+    pub fn occluded(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.occluded_filtered(ray, max_distance, |_, object| {
+            object.visibility().intersects(RayMask::SHADOW)
+        })
+    }
+
+    /// Like `occluded`, but only objects `filter` accepts, given their
+    /// index in `objects`, can block.
+    pub fn occluded_filtered<F>(&self, ray: &Ray, max_distance: f32, filter: F) -> bool
+    where
+        F: Fn(usize, &dyn Object) -> bool,
+    {
+        self.occluded_from(ray, max_distance, None, filter)
+    }
+
+    /// Like `occluded_filtered`, for a ray leaving the `Part` of an object
+    /// in `from`, which cannot block it, see `Object::intersect_part`.
+    pub fn occluded_from<F>(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        from: Option<(&dyn Object, Part)>,
+        filter: F,
+    ) -> bool
+    where
+        F: Fn(usize, &dyn Object) -> bool,
+    {
+        self.objects.iter().enumerate().any(|(index, object)| {
+            if object.is_shadow_catcher() || !filter(index, object.as_ref()) {
+                return false;
+            }
+            let (distance, _, _) = object.intersect_part(ray, skip(from, object.as_ref()));
+            distance > 0.0 && distance < max_distance
+        })
+    }
+
+    /// Closest object `ray` hits, shadow catchers included. Depth biases
+    /// decide which hit is closest, see `DepthBias`. The result does not
+    /// depend on the order objects were added in: of hits at exactly the
+    /// same distance, the object first in `tie_order` wins.
+    pub fn raycast(&self, ray: &Ray) -> Option<RaycastHit> {
+        self.raycast_filtered(ray, |_, _| true)
+    }
+
+    /// Like `raycast`, but only objects visible to one of the categories in
+    /// `mask` can be hit.
+    pub fn raycast_masked(&self, ray: &Ray, mask: RayMask) -> Option<RaycastHit> {
+        self.raycast_filtered(ray, |_, object| object.visibility().intersects(mask))
+    }
+
+    /// Like `raycast`, but only objects `filter` accepts, given their index
+    /// in `objects`, can be hit, e.g. to skip the object a ray starts on or
+    /// a category of objects the caller tells apart by index.
+    pub fn raycast_filtered<F>(&self, ray: &Ray, filter: F) -> Option<RaycastHit>
+    where
+        F: Fn(usize, &dyn Object) -> bool,
+    {
+        self.raycast_from(ray, None, filter)
+    }
+
+    /// Like `raycast_filtered`, for a ray leaving the `Part` of an object
+    /// in `from`, which it cannot hit again, see `Object::intersect_part`.
+    pub fn raycast_from<F>(
+        &self,
+        ray: &Ray,
+        from: Option<(&dyn Object, Part)>,
+        filter: F,
+    ) -> Option<RaycastHit>
+    where
+        F: Fn(usize, &dyn Object) -> bool,
+    {
+        let mut min_dist = f32::INFINITY;
+        let mut closest = None;
+        for (index, object) in self.objects.iter().enumerate() {
+            if !filter(index, object.as_ref()) {
+                continue;
+            }
+            let (distance, point, part) = object.intersect_part(ray, skip(from, object.as_ref()));
+            let biased = distance - object.depth_bias();
+            let closer = biased < min_dist
+                || biased == min_dist
+                    && closest.is_some_and(|hit: RaycastHit| {
+                        tie_order(object.as_ref(), self.objects[hit.index].as_ref())
+                            == Ordering::Less
+                    });
+            if closer && distance > 0.0 {
+                min_dist = biased;
+                closest = Some(RaycastHit {
+                    index,
+                    distance,
+                    point,
+                    part,
+                });
+            }
+        }
+        closest
+    }
+}
+
+/// The part of `object` a ray leaving `from` passes over: the one it
+/// starts on, if that is on `object`.
+fn skip(from: Option<(&dyn Object, Part)>, object: &dyn Object) -> Option<usize> {
+    from.filter(|&(from, _)| same_object(from, object))
+        .map(|(_, part)| part.index)
+}
+
+/// Order deciding which of two objects hit at exactly the same distance,
+/// such as coincident planes, `Scene::raycast` returns: by kind, position,
+/// shading coefficients, color and material parameters. Objects equal in
+/// all of these are told apart by insertion order, which only shows if
+/// they are textured differently or shaded by their own `Material`.
+fn tie_order(a: &dyn Object, b: &dyn Object) -> Ordering {
+    let (position_a, position_b) = (a.get_position(), b.get_position());
+    a.kind()
+        .cmp(b.kind())
+        .then_with(|| {
+            (0..3).fold(Ordering::Equal, |order, axis| {
+                order.then_with(|| position_a[axis].total_cmp(&position_b[axis]))
+            })
+        })
+        .then_with(|| a.get_lambert().total_cmp(&b.get_lambert()))
+        .then_with(|| a.get_specular().total_cmp(&b.get_specular()))
+        .then_with(|| {
+            let color = |object: &dyn Object| object.solid_color().map(|color| color.0);
+            color(a).cmp(&color(b))
+        })
+        .then_with(|| {
+            let (key_a, key_b) = (material_key(a), material_key(b));
+            key_a
+                .iter()
+                .zip(&key_b)
+                .fold(Ordering::Equal, |order, (a, b)| {
+                    order.then_with(|| a.total_cmp(b))
+                })
+        })
+}
+
+/// The material parameters `tie_order` compares, with 1 or 0 for flags and
+/// for whether optional parameters are present.
+fn material_key(object: &dyn Object) -> [f32; 28] {
+    let flag = |set: bool| if set { 1.0 } else { 0.0 };
+    let dispersion = object.get_dispersion();
+    let [d0, d1, d2] = dispersion.unwrap_or_default();
+    let [a0, a1, a2] = object.get_absorption();
+    let pbr = object.get_pbr();
+    let anisotropy = pbr.and_then(|pbr| pbr.anisotropy);
+    let [u, v] = anisotropy.unwrap_or_default();
+    let coat = object.get_clearcoat();
+    let [e0, e1, e2] = object.material().map_or([0.0; 3], |m| m.get_emission());
+    [
+        object.get_transparency(),
+        object.get_ior(),
+        flag(dispersion.is_some()),
+        d0,
+        d1,
+        d2,
+        a0,
+        a1,
+        a2,
+        flag(object.is_metal()),
+        object.get_roughness(),
+        object.get_glossy_samples() as f32,
+        object.get_shininess(),
+        flag(pbr.is_some()),
+        pbr.map_or(0.0, |pbr| pbr.metallic),
+        pbr.map_or(0.0, |pbr| pbr.roughness),
+        flag(anisotropy.is_some()),
+        u,
+        v,
+        flag(coat.is_some()),
+        coat.map_or(0.0, |coat| coat.strength),
+        coat.map_or(0.0, |coat| coat.roughness),
+        flag(object.is_dielectric()),
+        flag(object.is_shadow_catcher()),
+        object.depth_bias(),
+        e0,
+        e1,
+        e2,
+    ]
+}
+
+#[derive(Clone)]
+pub struct Light {
+    pub position: Vecf,
+    pub intensity: f32,
+    /// Radius of the glowing sphere; 0 for a point light with hard shadows.
+    pub radius: f32,
+    /// Shadow rays per shaded point for lights with a radius.
+    pub shadow_samples: u32,
+    /// Sets `intensity` on every `Scene::set_frame`.
+    pub animation: Option<LightAnimation>,
+    /// Categories of the shadow rays cast towards the light.
+    pub shadow_mask: RayMask,
+}
+
+impl Light {
+    pub fn new(position: Vecf, intensity: f32) -> Light {
+        Light {
+            position,
+            intensity,
+            radius: 0.0,
+            shadow_samples: 1,
+            animation: None,
+            shadow_mask: RayMask::SHADOW,
+        }
+    }
+
+    /// Makes the light a sphere of `radius`, casting soft shadows estimated
+    /// from `shadow_samples` rays spread over it by the view's sampler, see
+    /// `View::with_sampler`.
+    pub fn with_radius(mut self, radius: f32, shadow_samples: u32) -> Light {
+        self.radius = radius;
+        self.shadow_samples = shadow_samples;
+        self
+    }
+
+    /// Drives the intensity by `animation`, starting at frame 0.
+    pub fn with_animation(mut self, animation: LightAnimation) -> Light {
+        self.intensity = animation.intensity_at(0.0);
+        self.animation = Some(animation);
+        self
+    }
+
+    /// Lets only objects visible to one of the categories in `mask` cast
+    /// the light's shadows, e.g. `RayMask::user(0)` for a light whose
+    /// shadows only come from objects tagged with that category.
+    pub fn with_shadow_mask(mut self, mask: RayMask) -> Light {
+        self.shadow_mask = mask;
+        self
+    }
+}
+
+/// Categories of rays, as bit flags. Objects are hit only by rays of the
+/// categories they are visible to, see `Object::visibility`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RayMask(pub u32);
+
+impl RayMask {
+    pub const NONE: RayMask = RayMask(0);
+    /// Rays from the camera to the first surface.
+    pub const CAMERA: RayMask = RayMask(1);
+    /// Rays from surfaces towards lights, and occlusion rays.
+    pub const SHADOW: RayMask = RayMask(1 << 1);
+    /// Rays bounced off surfaces, and light leaving lights for photon maps
+    /// and bidirectional light paths.
+    pub const REFLECTION: RayMask = RayMask(1 << 2);
+    pub const ALL: RayMask = RayMask(!0);
+
+    /// Category `index`, below 29, left for applications to define, e.g.
+    /// to tag the objects a light casts shadows from.
+    pub const fn user(index: u32) -> RayMask {
+        RayMask(1 << (3 + index))
+    }
+
+    /// Whether the masks share a category.
+    pub fn intersects(self, other: RayMask) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for RayMask {
+    type Output = RayMask;
+
+    fn bitor(self, other: RayMask) -> RayMask {
+        RayMask(self.0 | other.0)
+    }
+}
+
+impl BitAnd for RayMask {
+    type Output = RayMask;
+
+    fn bitand(self, other: RayMask) -> RayMask {
+        RayMask(self.0 & other.0)
+    }
+}
+
+/// Where a ray meets the closest object, see `Scene::raycast`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    /// Index of the object in `Scene::objects`.
+    pub index: usize,
+    /// Distance along the ray.
+    pub distance: f32,
+    pub point: Vecf,
+    /// The part of the object hit, for objects built from several, see
+    /// `Object::intersect_part`.
+    pub part: Option<Part>,
+}
+
+/// One of the parts an object is built from, such as a triangle of a
+/// `Mesh`, where a ray meets it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Part {
+    /// Index of the part in the object.
+    pub index: usize,
+    /// Barycentric weights of the hit on the part.
+    pub weights: [f32; 3],
+}
+
+/// Surface information at a ray hit, handed to textures so they can vary
+/// over world space as well as over the object itself.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    /// Hit position in world space.
+    pub point: Vecf,
+    /// Surface normal at the hit, facing the incoming ray.
+    pub normal: Vecf,
+    /// Hit position relative to the object's position.
+    pub local_point: Vecf,
+    /// Texture coordinates at the hit, for objects that carry them.
+    pub uv: Option<[f32; 2]>,
+    /// Interpolated vertex color at the hit, for meshes that carry one.
+    pub vertex_color: Option<Color>,
+    /// Direction along the surface the u texture coordinate grows in, for
+    /// objects that have one; anisotropic materials are brushed along it.
+    pub tangent: Option<Vecf>,
+}
+
+pub trait Object: CloneObject {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf);
+
+    fn get_position(&self) -> Vecf;
+
+    /// The object's color where nothing is known about the hit.
+    fn get_color(&self) -> Color;
+
+    /// The object's color at `hit`, for textures that vary over the
+    /// surface or look at the rest of `scene`; `get_color` unless
+    /// implemented.
+    fn get_color_at(&self, _hit: &Hit, _scene: &Scene) -> Color {
+        self.get_color()
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf;
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        let normal = self.normal_to(&Ray::new(point, ray.direction));
+        Hit {
+            point,
+            normal,
+            local_point: vec3_sub(point, self.get_position()),
+            uv: None,
+            vertex_color: None,
+            tangent: None,
+        }
+    }
+
+    /// Like `intersect`, but also telling which `Part` the ray meets, for
+    /// objects built from several, and passing over part `skip`. Rays
+    /// leaving an object skip the flat part they start on, which they
+    /// cannot meet again, rather than relying on a minimum distance.
+    fn intersect_part(&self, ray: &Ray, _skip: Option<usize>) -> (f32, Vecf, Option<Part>) {
+        let (distance, point) = self.intersect(ray);
+        (distance, point, None)
+    }
+
+    /// Like `hit_at`, for a hit on `part` as `intersect_part` found it,
+    /// which saves objects built from several finding it again.
+    fn hit_on(&self, ray: &Ray, point: Vecf, _part: Option<Part>) -> Hit {
+        self.hit_at(ray, point)
+    }
+
+    /// The hit on the front of the surface where its texture coordinates
+    /// are `uv`, for baking its color into an image with `bake::bake`.
+    /// `None` where no part of the surface has them, and for objects
+    /// without texture coordinates.
+    fn hit_at_uv(&self, _uv: [f32; 2]) -> Option<Hit> {
+        None
+    }
+
+    fn get_lambert(&self) -> f32;
+
+    fn get_specular(&self) -> f32;
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray;
+
+    /// The material the object is shaded with, if it has one; the shading
+    /// getters below read it unless the object overrides them.
+    fn material(&self) -> Option<&dyn Material> {
+        None
+    }
+
+    /// Share of light passing through the surface, bent by `get_ior`; 0
+    /// for opaque objects.
+    fn get_transparency(&self) -> f32 {
+        self.material().map_or(0.0, |m| m.get_transparency())
+    }
+
+    /// Index of refraction inside the object, e.g. 1.5 for glass or 1.33
+    /// for water.
+    fn get_ior(&self) -> f32 {
+        self.material().map_or(1.0, |m| m.get_ior())
+    }
+
+    /// Index of refraction inside the object for each color channel,
+    /// where they differ, see `Sphere::with_dispersion`.
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.material().and_then(|m| m.get_dispersion())
+    }
+
+    /// Share of each color channel of the light inside the object that is
+    /// absorbed per unit of distance, see `Sphere::with_absorption`.
+    fn get_absorption(&self) -> [f32; 3] {
+        self.material().map_or([0.0; 3], |m| m.get_absorption())
+    }
+
+    /// Whether reflections take on the object's color, as for
+    /// `Sphere::with_metal`.
+    fn is_metal(&self) -> bool {
+        self.material().is_some_and(|m| m.is_metal())
+    }
+
+    /// How blurred reflections and refractions are, from 0 for a perfectly
+    /// smooth surface to 1, see `Sphere::with_roughness`.
+    fn get_roughness(&self) -> f32 {
+        self.material().map_or(0.0, |m| m.get_roughness())
+    }
+
+    /// The parts of the surface cut away, see `Sphere::with_cutout`.
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.material().and_then(|m| m.get_cutout())
+    }
+
+    /// Rays a ray meeting the object splits into where it is rough, see
+    /// `Sphere::with_glossy_samples`.
+    fn get_glossy_samples(&self) -> u32 {
+        self.material().map_or(1, |m| m.get_glossy_samples())
+    }
+
+    /// Blinn-Phong exponent of the highlights lights leave on the object,
+    /// see `Sphere::with_shininess`; 0 for none.
+    fn get_shininess(&self) -> f32 {
+        self.material().map_or(0.0, |m| m.get_shininess())
+    }
+
+    /// The physically based material the object is shaded with, see
+    /// `Sphere::with_pbr`.
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.material().and_then(|m| m.get_pbr())
+    }
+
+    /// The clear layer over the object's surface, see
+    /// `Sphere::with_clearcoat`.
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        self.material().and_then(|m| m.get_clearcoat())
+    }
+
+    /// Whether reflection and refraction always follow the Fresnel
+    /// equations, as for `Sphere::with_dielectric`, not only with
+    /// `View::with_fresnel`.
+    fn is_dielectric(&self) -> bool {
+        self.material().is_some_and(|m| m.is_dielectric())
+    }
+
+    /// Whether `ray`, meeting the surface at `point`, passes into the
+    /// object rather than out of it when refracted.
+    fn enters(&self, _ray: &Ray, _point: Vecf) -> bool {
+        true
+    }
+
+    /// The ray `ray` continues as after passing through the surface at
+    /// `point`, or after total internal reflection when it meets the
+    /// surface from inside at too shallow an angle. `outside` is the index
+    /// of refraction of what surrounds the object there: 1 for air, or
+    /// that of an object it is inside of. Only called on transparent
+    /// objects.
+    fn refract_ray(&self, ray: &Ray, point: Vecf, _outside: f32) -> Ray {
+        self.reflect_ray(ray, point)
+    }
+
+    /// `refract_ray` for light the object bends by index of refraction
+    /// `ior` instead of `get_ior`, one of the indices of a dispersive
+    /// object, see `get_dispersion`.
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, _ior: f32) -> Ray {
+        self.refract_ray(ray, point, outside)
+    }
+
+    /// The object as plain data, for render backends that cannot call back
+    /// into `Object`. `None` if it needs more than a solid color.
+    fn primitive(&self) -> Option<Primitive> {
+        None
+    }
+
+    /// The object's triangles as plain data, as `primitive` gives other
+    /// objects. `None` if it is not made of triangles or needs more than a
+    /// solid color.
+    fn primitive_mesh(&self) -> Option<PrimitiveMesh> {
+        None
+    }
+
+    /// The object's surface as a shape with a distance function, whatever
+    /// its color, for `View::with_cone_tracing`. `None` if it has no such
+    /// shape or parts of it are cut away.
+    fn shape(&self) -> Option<Shape> {
+        None
+    }
+
+    /// Whether the object only shows the shadows falling on it, see
+    /// `ShadowCatcher`.
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
+
+    /// Distance the object's hits are moved towards the ray origin when
+    /// picking the closest one, see `DepthBias`.
+    fn depth_bias(&self) -> f32 {
+        0.0
+    }
+
+    /// Whether rays leaving the surface outwards can never hit the object
+    /// again, as for spheres and planes. Shadow rays then skip the object
+    /// they start on instead of relying on the view's shadow bias alone.
+    fn is_convex(&self) -> bool {
+        false
+    }
+
+    /// Categories of rays that can hit the object, see `Visibility`.
+    fn visibility(&self) -> RayMask {
+        RayMask::ALL
+    }
+
+    /// What kind of object this is, e.g. "sphere", for `Scene::summary`.
+    fn kind(&self) -> &str {
+        "object"
+    }
+
+    /// Number of triangles the object is made of; 0 unless it is a mesh.
+    fn triangle_count(&self) -> usize {
+        0
+    }
+
+    /// Corners of a box around the object, `None` if it is unbounded as
+    /// planes are.
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        None
+    }
+
+    /// Area of the surface, for objects `point_on_surface` can pick points
+    /// on; `None` for the others, such as planes.
+    fn area(&self) -> Option<f32> {
+        None
+    }
+
+    /// A point on the surface, spread evenly over it as `u` and `v` run
+    /// over 0 to 1, for aiming rays at objects that give off light.
+    fn point_on_surface(&self, _u: f32, _v: f32) -> Vecf {
+        self.get_position()
+    }
+
+    /// The single color the object is shaded with, `None` if it is
+    /// textured.
+    fn solid_color(&self) -> Option<Color> {
+        self.primitive().map(|primitive| primitive.color)
+    }
+
+    /// Rough number of bytes the object takes, including data it holds on
+    /// the heap. Data shared between clones is counted for each.
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// Whether `a` and `b` are the same object, not merely equal ones.
+pub(crate) fn same_object(a: &dyn Object, b: &dyn Object) -> bool {
+    std::ptr::eq(
+        a as *const dyn Object as *const u8,
+        b as *const dyn Object as *const u8,
+    )
+}
+
+/// The color of `texture` at `point`, for `Object::get_color` of objects
+/// given one: its solid color, or what it shows at `point` without
+/// texture coordinates.
+pub(crate) fn texture_color(texture: &dyn Texture, point: Vecf) -> Color {
+    texture.solid_color().unwrap_or_else(|| {
+        texture.color_at(&Hit {
+            point,
+            normal: [0.0, 0.0, 1.0],
+            local_point: [0.0; 3],
+            uv: None,
+            vertex_color: None,
+            tangent: None,
+        })
+    })
+}
+
+/// Plain-data description of an object's geometry and material.
+#[derive(Clone, Copy, Debug)]
+pub struct Primitive {
+    pub shape: Shape,
+    pub color: Color,
+    pub lambert: f32,
+    pub specular: f32,
+}
+
+/// Plain-data description of a mesh: the corners of its triangles and
+/// its material.
+#[derive(Clone, Debug)]
+pub struct PrimitiveMesh {
+    pub triangles: Vec<[Vecf; 3]>,
+    pub color: Color,
+    pub lambert: f32,
+    pub specular: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    Sphere { center: Vecf, radius: f32 },
+    Plane { point: Vecf, normal: Vecf },
+}
+
+impl Shape {
+    /// Signed distance from `point` to the surface: negative inside a
+    /// sphere, and behind a plane, on the side away from its normal.
+    pub fn distance(&self, point: Vecf) -> f32 {
+        match *self {
+            Shape::Sphere { center, radius } => vec3_len(vec3_sub(point, center)) - radius,
+            Shape::Plane {
+                point: on_plane,
+                normal,
+            } => vec3_dot(vec3_sub(point, on_plane), normal),
+        }
+    }
+}
+
+pub trait CloneObject {
+    fn clone_object(&self) -> Box<dyn Object>;
+}
+
+impl<T> CloneObject for T
+where
+    T: Object + Clone + 'static,
+{
+    fn clone_object(&self) -> Box<dyn Object> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Object> {
+    fn clone(&self) -> Self {
+        self.clone_object()
+    }
+}
+
+/// Wraps an object so it only shows the shadows cast onto it: camera rays
+/// that hit it see the view's background or backplate, darkened by the
+/// share of light other objects block there. All other rays pass through.
+#[derive(Clone)]
+pub struct ShadowCatcher<T>(pub T);
+
+impl<T: Object + Clone + 'static> Object for ShadowCatcher<T> {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        self.0.intersect(ray)
+    }
+
+    fn intersect_part(&self, ray: &Ray, skip: Option<usize>) -> (f32, Vecf, Option<Part>) {
+        self.0.intersect_part(ray, skip)
+    }
+
+    fn get_position(&self) -> Vecf {
+        self.0.get_position()
+    }
+
+    fn get_color(&self) -> Color {
+        self.0.get_color()
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.0.get_color_at(hit, scene)
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        self.0.normal_to(hit_ray)
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        self.0.hit_at(ray, point)
+    }
+
+    fn hit_on(&self, ray: &Ray, point: Vecf, part: Option<Part>) -> Hit {
+        self.0.hit_on(ray, point, part)
+    }
+
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        self.0.hit_at_uv(uv)
+    }
+
+    fn get_lambert(&self) -> f32 {
+        self.0.get_lambert()
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.0.get_specular()
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        self.0.reflect_ray(ray, point)
+    }
+
+    fn material(&self) -> Option<&dyn Material> {
+        self.0.material()
+    }
+
+    fn get_transparency(&self) -> f32 {
+        self.0.get_transparency()
+    }
+
+    fn get_ior(&self) -> f32 {
+        self.0.get_ior()
+    }
+
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.0.get_dispersion()
+    }
+
+    fn refract_ray(&self, ray: &Ray, point: Vecf, outside: f32) -> Ray {
+        self.0.refract_ray(ray, point, outside)
+    }
+
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        self.0.disperse_ray(ray, point, outside, ior)
+    }
+
+    fn get_absorption(&self) -> [f32; 3] {
+        self.0.get_absorption()
+    }
+
+    fn is_metal(&self) -> bool {
+        self.0.is_metal()
+    }
+
+    fn get_roughness(&self) -> f32 {
+        self.0.get_roughness()
+    }
+
+    fn get_glossy_samples(&self) -> u32 {
+        self.0.get_glossy_samples()
+    }
+
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.0.get_cutout()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        self.0.shape()
+    }
+
+    fn get_shininess(&self) -> f32 {
+        self.0.get_shininess()
+    }
+
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.0.get_pbr()
+    }
+
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        self.0.get_clearcoat()
+    }
+
+    fn is_dielectric(&self) -> bool {
+        self.0.is_dielectric()
+    }
+
+    fn enters(&self, ray: &Ray, point: Vecf) -> bool {
+        self.0.enters(ray, point)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
+    }
+
+    fn depth_bias(&self) -> f32 {
+        self.0.depth_bias()
+    }
+
+    fn is_convex(&self) -> bool {
+        self.0.is_convex()
+    }
+
+    fn visibility(&self) -> RayMask {
+        self.0.visibility()
+    }
+
+    fn kind(&self) -> &str {
+        self.0.kind()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.0.triangle_count()
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        self.0.bounds()
+    }
+
+    fn area(&self) -> Option<f32> {
+        self.0.area()
+    }
+
+    fn point_on_surface(&self, u: f32, v: f32) -> Vecf {
+        self.0.point_on_surface(u, v)
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        self.0.solid_color()
+    }
+
+    fn memory_size(&self) -> usize {
+        self.0.memory_size()
+    }
+}
+
+/// Wraps an object so it wins against surfaces up to `bias` behind it when
+/// rays pick the closest hit, e.g. a decal plane lying on a wall, which
+/// would otherwise speckle where rounding makes either surface the closer.
+/// Hit points are unaffected.
+#[derive(Clone)]
+pub struct DepthBias<T>(pub T, pub f32);
+
+impl<T: Object + Clone + 'static> Object for DepthBias<T> {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        self.0.intersect(ray)
+    }
+
+    fn intersect_part(&self, ray: &Ray, skip: Option<usize>) -> (f32, Vecf, Option<Part>) {
+        self.0.intersect_part(ray, skip)
+    }
+
+    fn get_position(&self) -> Vecf {
+        self.0.get_position()
+    }
+
+    fn get_color(&self) -> Color {
+        self.0.get_color()
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.0.get_color_at(hit, scene)
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        self.0.normal_to(hit_ray)
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        self.0.hit_at(ray, point)
+    }
+
+    fn hit_on(&self, ray: &Ray, point: Vecf, part: Option<Part>) -> Hit {
+        self.0.hit_on(ray, point, part)
+    }
+
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        self.0.hit_at_uv(uv)
+    }
+
+    fn get_lambert(&self) -> f32 {
+        self.0.get_lambert()
+    }
+
+    fn get_specular(&self) -> f32 {
+        self.0.get_specular()
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        self.0.reflect_ray(ray, point)
+    }
+
+    fn material(&self) -> Option<&dyn Material> {
+        self.0.material()
+    }
+
+    fn get_transparency(&self) -> f32 {
+        self.0.get_transparency()
+    }
+
+    fn get_ior(&self) -> f32 {
+        self.0.get_ior()
+    }
+
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.0.get_dispersion()
+    }
+
+    fn refract_ray(&self, ray: &Ray, point: Vecf, outside: f32) -> Ray {
+        self.0.refract_ray(ray, point, outside)
+    }
+
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        self.0.disperse_ray(ray, point, outside, ior)
+    }
+
+    fn get_absorption(&self) -> [f32; 3] {
+        self.0.get_absorption()
+    }
+
+    fn is_metal(&self) -> bool {
+        self.0.is_metal()
+    }
+
+    fn get_roughness(&self) -> f32 {
+        self.0.get_roughness()
+    }
+
+    fn get_glossy_samples(&self) -> u32 {
+        self.0.get_glossy_samples()
+    }
+
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.0.get_cutout()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        self.0.shape()
+    }
+
+    fn get_shininess(&self) -> f32 {
+        self.0.get_shininess()
+    }
+
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.0.get_pbr()
+    }
+
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        self.0.get_clearcoat()
+    }
+
+    fn is_dielectric(&self) -> bool {
+        self.0.is_dielectric()
+    }
+
+    fn enters(&self, ray: &Ray, point: Vecf) -> bool {
+        self.0.enters(ray, point)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.0.is_shadow_catcher()
+    }
+
+    fn depth_bias(&self) -> f32 {
+        self.0.depth_bias() + self.1
+    }
+
+    fn is_convex(&self) -> bool {
+        self.0.is_convex()
+    }
+
+    fn visibility(&self) -> RayMask {
+        self.0.visibility()
+    }
+
+    fn kind(&self) -> &str {
+        self.0.kind()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.0.triangle_count()
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        self.0.bounds()
+    }
+
+    fn area(&self) -> Option<f32> {
+        self.0.area()
+    }
+
+    fn point_on_surface(&self, u: f32, v: f32) -> Vecf {
+        self.0.point_on_surface(u, v)
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        self.0.solid_color()
+    }
+
+    fn memory_size(&self) -> usize {
+        self.0.memory_size()
+    }
+}
+
+/// Wraps an object so only rays of the categories in the mask hit it, e.g.
+/// `RayMask::SHADOW | RayMask::REFLECTION` for an object that casts shadows
+/// and shows in mirrors but is hidden from the camera.
+#[derive(Clone)]
+pub struct Visibility<T>(pub T, pub RayMask);
+
+impl<T: Object + Clone + 'static> Object for Visibility<T> {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        self.0.intersect(ray)
+    }
+
+    fn intersect_part(&self, ray: &Ray, skip: Option<usize>) -> (f32, Vecf, Option<Part>) {
+        self.0.intersect_part(ray, skip)
+    }
+
+    fn get_position(&self) -> Vecf {
+        self.0.get_position()
+    }
+
+    fn get_color(&self) -> Color {
+        self.0.get_color()
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.0.get_color_at(hit, scene)
+    }
+
+    fn normal_to(&self, hit_ray: &Ray) -> Vecf {
+        self.0.normal_to(hit_ray)
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        self.0.hit_at(ray, point)
+    }
+
+    fn hit_on(&self, ray: &Ray, point: Vecf, part: Option<Part>) -> Hit {
+        self.0.hit_on(ray, point, part)
+    }
+
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        self.0.hit_at_uv(uv)
     }
-}
 
-pub struct Light {
-    pub position: Vecf,
-    pub intensity: f32,
-}
+    fn get_lambert(&self) -> f32 {
+        self.0.get_lambert()
+    }
 
-impl Light {
-    pub fn new(position: Vecf, intensity: f32) -> Light {
-        Light {
-            position,
-            intensity,
-        }
+    fn get_specular(&self) -> f32 {
+        self.0.get_specular()
     }
-}
 
-pub trait Object: CloneObject {
-    fn intersect(&self, ray: &Ray) -> (f32, Vecf);
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        self.0.reflect_ray(ray, point)
+    }
 
-    fn get_position(&self) -> Vecf;
+    fn material(&self) -> Option<&dyn Material> {
+        self.0.material()
+    }
 
-    fn get_color(&self) -> Color;
+    fn get_transparency(&self) -> f32 {
+        self.0.get_transparency()
+    }
 
-    fn normal_to(&self, hit_ray: &Ray) -> Vecf;
+    fn get_ior(&self) -> f32 {
+        self.0.get_ior()
+    }
 
-    fn get_lambert(&self) -> f32;
+    fn get_dispersion(&self) -> Option<[f32; 3]> {
+        self.0.get_dispersion()
+    }
 
-    fn get_specular(&self) -> f32;
+    fn refract_ray(&self, ray: &Ray, point: Vecf, outside: f32) -> Ray {
+        self.0.refract_ray(ray, point, outside)
+    }
 
-    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray;
-}
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        self.0.disperse_ray(ray, point, outside, ior)
+    }
 
-pub trait CloneObject {
-    fn clone_object(&self) -> Box<dyn Object>;
-}
+    fn get_absorption(&self) -> [f32; 3] {
+        self.0.get_absorption()
+    }
 
-impl<T> CloneObject for T
-where
-    T: Object + Clone + 'static,
-{
-    fn clone_object(&self) -> Box<dyn Object> {
-        Box::new(self.clone())
+    fn is_metal(&self) -> bool {
+        self.0.is_metal()
     }
-}
 
-impl Clone for Box<dyn Object> {
-    fn clone(&self) -> Self {
-        self.clone_object()
+    fn get_roughness(&self) -> f32 {
+        self.0.get_roughness()
+    }
+
+    fn get_glossy_samples(&self) -> u32 {
+        self.0.get_glossy_samples()
+    }
+
+    fn get_cutout(&self) -> Option<&Cutout> {
+        self.0.get_cutout()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        self.0.shape()
+    }
+
+    fn get_shininess(&self) -> f32 {
+        self.0.get_shininess()
+    }
+
+    fn get_pbr(&self) -> Option<Pbr> {
+        self.0.get_pbr()
+    }
+
+    fn get_clearcoat(&self) -> Option<Clearcoat> {
+        self.0.get_clearcoat()
+    }
+
+    fn is_dielectric(&self) -> bool {
+        self.0.is_dielectric()
+    }
+
+    fn enters(&self, ray: &Ray, point: Vecf) -> bool {
+        self.0.enters(ray, point)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.0.is_shadow_catcher()
+    }
+
+    fn depth_bias(&self) -> f32 {
+        self.0.depth_bias()
+    }
+
+    fn is_convex(&self) -> bool {
+        self.0.is_convex()
+    }
+
+    fn visibility(&self) -> RayMask {
+        self.0.visibility() & self.1
+    }
+
+    fn kind(&self) -> &str {
+        self.0.kind()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.0.triangle_count()
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        self.0.bounds()
+    }
+
+    fn area(&self) -> Option<f32> {
+        self.0.area()
+    }
+
+    fn point_on_surface(&self, u: f32, v: f32) -> Vecf {
+        self.0.point_on_surface(u, v)
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        self.0.solid_color()
+    }
+
+    fn memory_size(&self) -> usize {
+        self.0.memory_size()
     }
 }
 
+/// Depth below a sphere's surface, as a share of its radius, from which
+/// rays count as starting inside it and hit its far side.
+const INSIDE_DEPTH: f32 = 1e-4;
+
+/// Distance past a plane rays refracted through it start at, so they do
+/// not hit it again by rounding.
+const PLANE_OFFSET: f32 = 1e-4;
+
 #[derive(Clone)]
 pub struct Sphere {
     position: Vecf,
-    color: Color,
+    texture: Arc<dyn Texture>,
     radius: f32,
     sq_radius: f32,
-    lambert: f32,
-    specular: f32,
+    material: Arc<dyn Material>,
+    normal_map: Option<Arc<NormalMap>>,
 }
 
 impl Sphere {
@@ -82,13 +1347,174 @@ impl Sphere {
         let sq_radius = radius * radius;
         Sphere {
             position,
-            color,
+            texture: Arc::new(color),
             radius,
             sq_radius,
-            lambert,
-            specular,
+            material: Arc::new(Standard::new(lambert, specular)),
+            normal_map: None,
+        }
+    }
+
+    /// Colors the sphere with `texture`, wrapped around it by longitude and
+    /// latitude: u runs once around the vertical axis, from 0.5 on the side
+    /// facing -z, and v from 0 at the bottom pole to 1 at the top.
+    pub fn with_texture<T: Texture + 'static>(mut self, texture: T) -> Sphere {
+        self.texture = Arc::new(texture);
+        self
+    }
+
+    /// Bends the sphere's shading normals by `normal_map`, laid out by the
+    /// same texture coordinates as `with_texture`.
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Sphere {
+        self.normal_map = Some(Arc::new(normal_map));
+        self
+    }
+
+    /// Texture coordinates where the outward normal is `normal`.
+    fn uv(normal: Vecf) -> [f32; 2] {
+        let [x, y, z] = normal;
+        [
+            0.5 + x.atan2(-z) / (2.0 * PI),
+            0.5 + y.clamp(-1.0, 1.0).asin() / PI,
+        ]
+    }
+
+    /// Direction u grows in at `offset` from the center, around the
+    /// sphere's vertical axis; `None` at the poles.
+    fn tangent(offset: Vecf) -> Option<Vecf> {
+        let [x, _, z] = offset;
+        let tangent = [-z, 0.0, x];
+        if vec3_len(tangent) > 0.0 {
+            Some(vec3_normalized(tangent))
+        } else {
+            None
         }
     }
+
+    /// Shades the sphere with `material`, which other objects may share.
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Sphere {
+        self.material = material;
+        self
+    }
+
+    /// Gives the sphere a `Standard` material changed by `change` from a
+    /// copy of its current one, so it no longer shares it, under the same
+    /// clear coat if it has one.
+    fn with_standard<F: FnOnce(Standard) -> Standard>(self, change: F) -> Sphere {
+        let material = change(Standard::from_material(&*self.material));
+        let material: Arc<dyn Material> = match self.material.get_clearcoat() {
+            Some(coat) => Arc::new(Layered::new(Arc::new(material), coat)),
+            None => Arc::new(material),
+        };
+        self.with_material(material)
+    }
+
+    /// Makes the sphere let `transparency` of the light through, bent as
+    /// by a solid ball of a material with index of refraction `ior`, as
+    /// `Standard::with_refraction` does.
+    pub fn with_refraction(self, transparency: f32, ior: f32) -> Sphere {
+        self.with_standard(|m| m.with_refraction(transparency, ior))
+    }
+
+    /// Makes the sphere bend each color channel by its own index of
+    /// refraction, as `Standard::with_dispersion` does, for prisms and
+    /// gemstones.
+    pub fn with_dispersion(self, iors: [f32; 3]) -> Sphere {
+        self.with_standard(|m| m.with_dispersion(iors))
+    }
+
+    /// Makes the light refracted into the sphere fade towards `color`, as
+    /// `Standard::with_absorption` does.
+    pub fn with_absorption(self, color: Color, density: f32) -> Sphere {
+        self.with_standard(|m| m.with_absorption(color, density))
+    }
+
+    /// Makes the sphere solid glass or another clear material, as
+    /// `Standard::with_dielectric` does.
+    pub fn with_dielectric(self, ior: f32, tint: Color, distance: f32) -> Sphere {
+        self.with_standard(|m| m.with_dielectric(ior, tint, distance))
+    }
+
+    /// Makes the sphere a metal, as `Standard::with_metal` does.
+    pub fn with_metal(self, roughness: f32) -> Sphere {
+        self.with_standard(|m| m.with_metal(roughness))
+    }
+
+    /// Blurs reflections and refractions, as `Standard::with_roughness`
+    /// does.
+    pub fn with_roughness(self, roughness: f32) -> Sphere {
+        self.with_standard(|m| m.with_roughness(roughness))
+    }
+
+    /// Cuts away the parts of the sphere `mask` leaves uncovered, as
+    /// `Standard::with_cutout` does.
+    pub fn with_cutout(self, mask: AlphaMask, mode: AlphaMode) -> Sphere {
+        self.with_standard(|m| m.with_cutout(mask, mode))
+    }
+
+    /// Splits rays meeting the rough sphere into `samples`, as
+    /// `Standard::with_glossy_samples` does.
+    pub fn with_glossy_samples(self, samples: u32) -> Sphere {
+        self.with_standard(|m| m.with_glossy_samples(samples))
+    }
+
+    /// Adds highlights from lights, as `Standard::with_shininess` does.
+    pub fn with_shininess(self, shininess: f32) -> Sphere {
+        self.with_standard(|m| m.with_shininess(shininess))
+    }
+
+    /// Shades the sphere as a physically based material, as
+    /// `Standard::with_pbr` does.
+    pub fn with_pbr(self, metallic: f32, roughness: f32) -> Sphere {
+        self.with_standard(|m| m.with_pbr(metallic, roughness))
+    }
+
+    /// Gives the sphere's physically based material different roughness
+    /// around and along its axis, as `Standard::with_anisotropy` does.
+    pub fn with_anisotropy(self, roughness_u: f32, roughness_v: f32) -> Sphere {
+        self.with_standard(|m| m.with_anisotropy(roughness_u, roughness_v))
+    }
+
+    /// Makes the sphere give off light, as `Standard::with_emission` does.
+    pub fn with_emission(self, color: Color, strength: f32) -> Sphere {
+        self.with_standard(|m| m.with_emission(color, strength))
+    }
+
+    /// Lays a clear coat of `strength`, from 0 to 1, and perceptual
+    /// `roughness` over the sphere's material, see `Layered`, for
+    /// lacquered wood or car paint.
+    pub fn with_clearcoat(self, strength: f32, roughness: f32) -> Sphere {
+        let coat = Clearcoat {
+            strength,
+            roughness,
+        };
+        let material = Layered::new(self.material.clone(), coat);
+        self.with_material(Arc::new(material))
+    }
+
+    /// `Object::refract_ray` for a ball of index of refraction `ior`.
+    fn refract_through(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        let outward = self.normal_to(&Ray::new(point, ray.direction));
+        let (normal, from, to) = if vec3_dot(ray.direction, outward) < 0.0 {
+            (outward, outside, ior)
+        } else {
+            (vecmath::vec3_neg(outward), ior, outside)
+        };
+        let direction = refract(ray.direction, normal, from, to)
+            .unwrap_or_else(|| self.reflect_ray(ray, point).direction);
+        // Start clearly on the side the ray heads to, so `intersect` tells
+        // whether it starts inside.
+        let side = if vec3_dot(direction, outward) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let origin = vec3_add(
+            point,
+            vec3_scale(outward, side * 2.0 * INSIDE_DEPTH * self.radius),
+        );
+        Ray::new(origin, direction)
+    }
 }
 
 impl Object for Sphere {
@@ -96,12 +1522,20 @@ impl Object for Sphere {
         let mut distance = f32::INFINITY;
         let from_ray_origin = vecmath::vec3_sub(self.position, ray.origin);
         let on_ray_midpoint = vecmath::vec3_dot(from_ray_origin, ray.direction);
-        if on_ray_midpoint > 0.0 {
+        // Rays leaving the surface may start a little inside by rounding.
+        let inside = vecmath::vec3_square_len(from_ray_origin)
+            < self.sq_radius * (1.0 - INSIDE_DEPTH).powi(2);
+        if on_ray_midpoint > 0.0 || inside {
             let c_center_to_midpoint =
                 vecmath::vec3_square_len(from_ray_origin) - (on_ray_midpoint * on_ray_midpoint);
             if c_center_to_midpoint < self.sq_radius {
                 let midpoint_to_intersect = (self.sq_radius - c_center_to_midpoint).sqrt();
-                distance = on_ray_midpoint - midpoint_to_intersect;
+                // Rays from inside leave through the far side.
+                distance = if inside {
+                    on_ray_midpoint + midpoint_to_intersect
+                } else {
+                    on_ray_midpoint - midpoint_to_intersect
+                };
             }
         }
         let hit_position = vec3_add(ray.origin, vec3_scale(ray.direction, distance));
@@ -113,19 +1547,56 @@ impl Object for Sphere {
     }
 
     fn get_color(&self) -> Color {
-        self.color
+        texture_color(self.texture.as_ref(), self.get_position())
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.texture.shade(hit, scene)
     }
 
     fn normal_to(&self, hit_ray: &Ray) -> Vecf {
-        vec3_normalized(vec3_sub(hit_ray.origin, self.position))
+        let normal = vec3_normalized(vec3_sub(hit_ray.origin, self.position));
+        match &self.normal_map {
+            // u grows eastwards around the vertical axis, v northwards.
+            Some(map) => {
+                let [x, _, z] = normal;
+                map.perturb(normal, [-z, 0.0, x], [0.0, 1.0, 0.0], Sphere::uv(normal))
+            }
+            None => normal,
+        }
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        Hit {
+            point,
+            normal: self.normal_to(&Ray::new(point, ray.direction)),
+            local_point: vec3_sub(point, self.position),
+            uv: Some(Sphere::uv(vec3_normalized(vec3_sub(point, self.position)))),
+            vertex_color: None,
+            tangent: Sphere::tangent(vec3_sub(point, self.position)),
+        }
+    }
+
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        // `Sphere::uv` backwards: u turns around the vertical axis from
+        // behind, v climbs from the bottom pole.
+        let around = (uv[0] - 0.5) * 2.0 * PI;
+        let up = (uv[1] - 0.5) * PI;
+        let normal = [around.sin() * up.cos(), up.sin(), -around.cos() * up.cos()];
+        let point = vec3_add(self.position, vec3_scale(normal, self.radius));
+        let towards = Ray {
+            origin: point,
+            direction: vecmath::vec3_neg(normal),
+        };
+        Some(self.hit_at(&towards, point))
     }
 
     fn get_lambert(&self) -> f32 {
-        self.lambert
+        self.material.get_lambert()
     }
 
     fn get_specular(&self) -> f32 {
-        self.specular
+        self.material.get_specular()
     }
 
     fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
@@ -135,34 +1606,122 @@ impl Object for Sphere {
         reflected_ray = vec3_sub(ray.direction, reflected_ray);
         Ray::new(point, reflected_ray)
     }
+
+    fn primitive(&self) -> Option<Primitive> {
+        if self.normal_map.is_some() {
+            return None;
+        }
+        Some(Primitive {
+            shape: self.shape()?,
+            color: self.texture.solid_color()?,
+            lambert: self.get_lambert(),
+            specular: self.get_specular(),
+        })
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        if self.material.get_cutout().is_some() {
+            return None;
+        }
+        Some(Shape::Sphere {
+            center: self.position,
+            radius: self.radius,
+        })
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    fn kind(&self) -> &str {
+        "sphere"
+    }
+
+    fn bounds(&self) -> Option<[Vecf; 2]> {
+        let extent = [self.radius; 3];
+        Some([
+            vec3_sub(self.position, extent),
+            vec3_add(self.position, extent),
+        ])
+    }
+
+    fn area(&self) -> Option<f32> {
+        Some(4.0 * PI * self.radius * self.radius)
+    }
+
+    fn point_on_surface(&self, u: f32, v: f32) -> Vecf {
+        let z = 1.0 - 2.0 * u;
+        let ring = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        vec3_add(
+            self.position,
+            vec3_scale([ring * phi.cos(), ring * phi.sin(), z], self.radius),
+        )
+    }
+
+    fn material(&self) -> Option<&dyn Material> {
+        Some(&*self.material)
+    }
+
+    fn enters(&self, ray: &Ray, point: Vecf) -> bool {
+        vec3_dot(
+            ray.direction,
+            self.normal_to(&Ray::new(point, ray.direction)),
+        ) < 0.0
+    }
+
+    fn refract_ray(&self, ray: &Ray, point: Vecf, outside: f32) -> Ray {
+        self.refract_through(ray, point, outside, self.get_ior())
+    }
+
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        self.refract_through(ray, point, outside, ior)
+    }
 }
 
 #[derive(Clone)]
 pub struct Plane {
     point: Vecf,
-    color: Color,
+    texture: Arc<dyn Texture>,
     normal: Vecf,
+    #[allow(dead_code)]
     width: f32,
+    #[allow(dead_code)]
     height: f32,
-    lambert: f32,
-    specular: f32,
+    material: Arc<dyn Material>,
+    /// Where texture coordinates are (0, 0), and the steps in world space
+    /// that take u and v from 0 to 1.
+    uv_origin: Vecf,
+    u_axis: Vecf,
+    v_axis: Vecf,
+    normal_map: Option<Arc<NormalMap>>,
 }
 
 impl Plane {
+    /// An endless plane through `point`. Texture coordinates start at
+    /// `point` and count world units along two directions in the plane.
     pub fn new(color: Color, normal: Vecf, point: Vecf, lambert: f32, specular: f32) -> Plane {
         let height = f32::INFINITY;
         let width = f32::INFINITY;
         let normal = vec3_normalized(normal);
+        let (u_axis, v_axis) = tangent_frame(normal);
         Plane {
-            color,
+            texture: Arc::new(color),
             normal,
             width,
             height,
             point,
-            lambert,
-            specular,
+            material: Arc::new(Standard::new(lambert, specular)),
+            uv_origin: point,
+            u_axis,
+            v_axis,
+            normal_map: None,
         }
     }
+
+    /// The plane of a rectangle given by three corners. Texture coordinates
+    /// run from (0, 0) at `bottom_left` to (1, 1) at `top_right`, so a
+    /// texture covers the rectangle once, right way up, and repeats beyond.
     pub fn from_points(
         color: Color,
         top_right: Vecf,
@@ -173,22 +1732,161 @@ impl Plane {
     ) -> Plane {
         let height_vec = vec3_sub(top_right, bottom_right);
         let width_vec = vec3_sub(bottom_right, bottom_left);
-        let center_position = vec3_scale(vec3_add(top_right, bottom_left), 0.5);
         let point = top_right;
         let normal = vec3_normalized(vec3_cross(width_vec, height_vec));
         let height = vec3_len(height_vec);
         let width = vec3_len(width_vec);
 
         Plane {
-            color,
+            texture: Arc::new(color),
             normal,
             width,
             height,
             point,
-            lambert,
-            specular,
+            material: Arc::new(Standard::new(lambert, specular)),
+            uv_origin: bottom_left,
+            u_axis: width_vec,
+            v_axis: height_vec,
+            normal_map: None,
         }
     }
+
+    pub fn with_texture<T: Texture + 'static>(mut self, texture: T) -> Plane {
+        self.texture = Arc::new(texture);
+        self
+    }
+
+    /// Bends the plane's shading normals by `normal_map`, laid out by the
+    /// same texture coordinates as `with_texture`.
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Plane {
+        self.normal_map = Some(Arc::new(normal_map));
+        self
+    }
+
+    /// Texture coordinates at `point`.
+    fn uv(&self, point: Vecf) -> [f32; 2] {
+        let from_origin = vec3_sub(point, self.uv_origin);
+        let along = |axis: Vecf| vec3_dot(from_origin, axis) / vec3_dot(axis, axis);
+        [along(self.u_axis), along(self.v_axis)]
+    }
+
+    /// Shades the plane with `material`, which other objects may share.
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Plane {
+        self.material = material;
+        self
+    }
+
+    /// Gives the plane a `Standard` material changed by `change`, as
+    /// `Sphere` does.
+    fn with_standard<F: FnOnce(Standard) -> Standard>(self, change: F) -> Plane {
+        let material = change(Standard::from_material(&*self.material));
+        let material: Arc<dyn Material> = match self.material.get_clearcoat() {
+            Some(coat) => Arc::new(Layered::new(Arc::new(material), coat)),
+            None => Arc::new(material),
+        };
+        self.with_material(material)
+    }
+
+    /// Makes the plane the surface of a material, such as water, with
+    /// index of refraction `ior` filling the side its normal points to,
+    /// letting `transparency` of the light into it. Planes are only hit
+    /// from the other side, so rays never leave the material again.
+    pub fn with_refraction(self, transparency: f32, ior: f32) -> Plane {
+        self.with_standard(|m| m.with_refraction(transparency, ior))
+    }
+
+    /// Makes the material behind the plane bend each color channel by its
+    /// own index of refraction, as `Standard::with_dispersion` does.
+    pub fn with_dispersion(self, iors: [f32; 3]) -> Plane {
+        self.with_standard(|m| m.with_dispersion(iors))
+    }
+
+    /// Makes the material behind the plane absorb light as
+    /// `Standard::with_absorption` does, so that deep water gets darker.
+    pub fn with_absorption(self, color: Color, density: f32) -> Plane {
+        self.with_standard(|m| m.with_absorption(color, density))
+    }
+
+    /// Makes the plane a metal, as `Standard::with_metal` does.
+    pub fn with_metal(self, roughness: f32) -> Plane {
+        self.with_standard(|m| m.with_metal(roughness))
+    }
+
+    /// Blurs reflections and refractions, as `Standard::with_roughness`
+    /// does.
+    pub fn with_roughness(self, roughness: f32) -> Plane {
+        self.with_standard(|m| m.with_roughness(roughness))
+    }
+
+    /// Cuts away the parts of the plane `mask` leaves uncovered, as
+    /// `Standard::with_cutout` does.
+    pub fn with_cutout(self, mask: AlphaMask, mode: AlphaMode) -> Plane {
+        self.with_standard(|m| m.with_cutout(mask, mode))
+    }
+
+    /// Splits rays meeting the rough plane into `samples`, as
+    /// `Standard::with_glossy_samples` does.
+    pub fn with_glossy_samples(self, samples: u32) -> Plane {
+        self.with_standard(|m| m.with_glossy_samples(samples))
+    }
+
+    /// Adds highlights from lights, as `Standard::with_shininess` does.
+    pub fn with_shininess(self, shininess: f32) -> Plane {
+        self.with_standard(|m| m.with_shininess(shininess))
+    }
+
+    /// Shades the plane as a physically based material, as
+    /// `Standard::with_pbr` does.
+    pub fn with_pbr(self, metallic: f32, roughness: f32) -> Plane {
+        self.with_standard(|m| m.with_pbr(metallic, roughness))
+    }
+
+    /// Gives the plane's physically based material different roughness
+    /// along its u and v texture directions, as `Standard::with_anisotropy`
+    /// does.
+    pub fn with_anisotropy(self, roughness_u: f32, roughness_v: f32) -> Plane {
+        self.with_standard(|m| m.with_anisotropy(roughness_u, roughness_v))
+    }
+
+    /// Makes the plane give off light, as `Standard::with_emission` does. Only
+    /// bounded objects light the scene with it, so a plane just glows.
+    pub fn with_emission(self, color: Color, strength: f32) -> Plane {
+        self.with_standard(|m| m.with_emission(color, strength))
+    }
+
+    /// Lays a clear coat over the plane's material, as
+    /// `Sphere::with_clearcoat` does, e.g. for a varnished floor.
+    pub fn with_clearcoat(self, strength: f32, roughness: f32) -> Plane {
+        let coat = Clearcoat {
+            strength,
+            roughness,
+        };
+        let material = Layered::new(self.material.clone(), coat);
+        self.with_material(Arc::new(material))
+    }
+
+    /// Repeats textures every `width` and `height` world units along the
+    /// plane's u and v directions, so tiles and checks keep their real size
+    /// however large the plane is.
+    pub fn with_tile_size(mut self, width: f32, height: f32) -> Plane {
+        self.u_axis = vec3_scale(vec3_normalized(self.u_axis), width);
+        self.v_axis = vec3_scale(vec3_normalized(self.v_axis), height);
+        self
+    }
+
+    /// `Object::refract_ray` into a material of index of refraction `ior`.
+    fn refract_through(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        // Rays only meet the plane going the way its normal points.
+        let direction = refract(ray.direction, vecmath::vec3_neg(self.normal), outside, ior)
+            .unwrap_or_else(|| self.reflect_ray(ray, point).direction);
+        let side = if vec3_dot(direction, self.normal) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let origin = vec3_add(point, vec3_scale(self.normal, side * PLANE_OFFSET));
+        Ray::new(origin, direction)
+    }
 }
 //TODO: FIX!
 impl Object for Plane {
@@ -214,29 +1912,215 @@ impl Object for Plane {
     }
 
     fn get_color(&self) -> Color {
-        self.color
+        texture_color(self.texture.as_ref(), self.get_position())
+    }
+
+    fn get_color_at(&self, hit: &Hit, scene: &Scene) -> Color {
+        self.texture.shade(hit, scene)
     }
 
     fn normal_to(&self, hit_ray: &Ray) -> Vecf {
-        if vec3_dot(hit_ray.direction, self.normal) < 0.0 {
+        let normal = if vec3_dot(hit_ray.direction, self.normal) < 0.0 {
             self.normal
         } else {
             vecmath::vec3_neg(self.normal)
+        };
+        match &self.normal_map {
+            Some(map) => map.perturb(normal, self.u_axis, self.v_axis, self.uv(hit_ray.origin)),
+            None => normal,
+        }
+    }
+
+    fn hit_at(&self, ray: &Ray, point: Vecf) -> Hit {
+        Hit {
+            point,
+            normal: self.normal_to(&Ray::new(point, ray.direction)),
+            local_point: vec3_sub(point, self.point),
+            uv: Some(self.uv(point)),
+            vertex_color: None,
+            tangent: Some(self.u_axis),
         }
     }
 
+    fn hit_at_uv(&self, uv: [f32; 2]) -> Option<Hit> {
+        let along = vec3_add(
+            vec3_scale(self.u_axis, uv[0]),
+            vec3_scale(self.v_axis, uv[1]),
+        );
+        let point = vec3_add(self.uv_origin, along);
+        // Rays only meet the plane going the way its normal points.
+        let towards = Ray {
+            origin: point,
+            direction: self.normal,
+        };
+        Some(self.hit_at(&towards, point))
+    }
+
     fn get_lambert(&self) -> f32 {
-        self.lambert
+        self.material.get_lambert()
     }
 
     fn get_specular(&self) -> f32 {
-        self.specular
+        self.material.get_specular()
     }
 
     fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
-        let reflection = 2.0 * vec3_dot(ray.direction, self.normal_to(ray));
-        let mut reflected_ray = vec3_scale(self.normal_to(ray), reflection);
+        let normal = self.normal_to(&Ray::new(point, ray.direction));
+        let reflection = 2.0 * vec3_dot(ray.direction, normal);
+        let mut reflected_ray = vec3_scale(normal, reflection);
         reflected_ray = vec3_sub(ray.direction, reflected_ray);
         Ray::new(point, reflected_ray)
     }
+
+    fn primitive(&self) -> Option<Primitive> {
+        if self.normal_map.is_some() || self.material.get_cutout().is_some() {
+            return None;
+        }
+        Some(Primitive {
+            shape: Shape::Plane {
+                point: self.point,
+                normal: self.normal,
+            },
+            color: self.texture.solid_color()?,
+            lambert: self.get_lambert(),
+            specular: self.get_specular(),
+        })
+    }
+
+    /// Only for unbounded planes, since the shape has no edges.
+    fn shape(&self) -> Option<Shape> {
+        if self.material.get_cutout().is_some() || self.width.is_finite() || self.height.is_finite()
+        {
+            return None;
+        }
+        Some(Shape::Plane {
+            point: self.point,
+            normal: self.normal,
+        })
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    fn kind(&self) -> &str {
+        "plane"
+    }
+
+    fn material(&self) -> Option<&dyn Material> {
+        Some(&*self.material)
+    }
+
+    fn refract_ray(&self, ray: &Ray, point: Vecf, outside: f32) -> Ray {
+        self.refract_through(ray, point, outside, self.get_ior())
+    }
+
+    fn disperse_ray(&self, ray: &Ray, point: Vecf, outside: f32, ior: f32) -> Ray {
+        self.refract_through(ray, point, outside, ior)
+    }
+}
+
+/// `direction` bent by Snell's law passing through a surface with
+/// `normal` facing where it comes from, from a material with index of
+/// refraction `from` into one with `to`. `None` on total internal
+/// reflection.
+pub(crate) fn refract(direction: Vecf, normal: Vecf, from: f32, to: f32) -> Option<Vecf> {
+    let eta = from / to;
+    let cos_in = -vec3_dot(direction, normal);
+    let sin2_out = eta * eta * (1.0 - cos_in * cos_in);
+    if sin2_out > 1.0 {
+        return None;
+    }
+    let cos_out = (1.0 - sin2_out).sqrt();
+    Some(vec3_normalized(vec3_add(
+        vec3_scale(direction, eta),
+        vec3_scale(normal, eta * cos_in - cos_out),
+    )))
+}
+
+/// Schlick's approximation of the share of light a surface reflects when
+/// the cosine of the angle between ray and normal is `cos`, given the share
+/// `f0` it reflects head on: `f0` rising to 1 at grazing angles.
+pub(crate) fn schlick(cos: f32, f0: f32) -> f32 {
+    f0 + (1.0 - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+/// Share of light left after travelling `distance` through a material
+/// absorbing `absorption` per unit of distance, by the Beer–Lambert law.
+pub(crate) fn transmittance(absorption: [f32; 3], distance: f32) -> [f32; 3] {
+    absorption.map(|a| {
+        let t = (-a * distance).exp();
+        // No absorption over any distance, or any absorption over none.
+        if t.is_nan() {
+            1.0
+        } else {
+            t
+        }
+    })
+}
+
+/// Transparent objects a ray is inside, innermost last, so that where
+/// objects nest, such as water in a glass or a bubble in glass, rays are
+/// bent and absorbed by the medium they actually travel through.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Media(Vec<Medium>);
+
+#[derive(Clone, Copy, Debug)]
+struct Medium {
+    /// Address of the object, which tells it apart from equal ones.
+    object: *const (),
+    ior: f32,
+    absorption: [f32; 3],
+}
+
+impl Media {
+    /// `Object::get_absorption` of the innermost object; none in air.
+    pub(crate) fn absorption(&self) -> [f32; 3] {
+        self.0.last().map_or([0.0; 3], |medium| medium.absorption)
+    }
+
+    /// Index of refraction of what surrounds `object` where `ray` meets it
+    /// at `point`, for `Object::refract_ray`.
+    pub(crate) fn around(&self, object: &dyn Object, ray: &Ray, point: Vecf) -> f32 {
+        let address = address(object);
+        let outer = if object.enters(ray, point) {
+            self.0.last()
+        } else {
+            self.0.iter().rev().find(|medium| medium.object != address)
+        };
+        outer.map_or(1.0, |medium| medium.ior)
+    }
+
+    /// The media along `refracted`, the ray `ray` continues as through
+    /// `object` at `point`: with the object added when the ray went in and
+    /// removed when it came out, unchanged on total internal reflection.
+    pub(crate) fn crossed(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        refracted: &Ray,
+    ) -> Media {
+        let mut media = self.clone();
+        let normal = object.normal_to(&Ray::new(point, ray.direction));
+        let through = vec3_dot(ray.direction, normal) * vec3_dot(refracted.direction, normal) > 0.0;
+        if !through {
+            return media;
+        }
+        let address = address(object);
+        if object.enters(ray, point) {
+            media.0.push(Medium {
+                object: address,
+                ior: object.get_ior(),
+                absorption: object.get_absorption(),
+            });
+        } else if let Some(i) = media.0.iter().rposition(|m| m.object == address) {
+            media.0.remove(i);
+        }
+        media
+    }
+}
+
+fn address(object: &dyn Object) -> *const () {
+    object as *const dyn Object as *const ()
 }
@@ -0,0 +1,200 @@
+//! Building scenes from Rhai scripts, for procedural scenes without
+//! compiling Rust. Scripts make objects and lights with functions named
+//! after their Rust constructors and add them to the scene with `add`:
+//!
+//! ```text
+//! let rng = rng(7);
+//! for i in 0..20 {
+//!     let angle = i * 0.6;
+//!     add(sphere([angle.cos(), -0.8, 4.0 + angle.sin()], [255, 180, 60], 0.1, 0.9, 0.1)
+//!         .with_shininess(40.0 * rng.next()));
+//! }
+//! add(plane([200, 200, 200], [0, -1, 0], [0, -1, 0], 0.8, 0.0));
+//! add(light([0, 2, 3], 80.0));
+//! ```
+//!
+//! Vectors and colors are arrays of three numbers, colors from 0 to 255;
+//! other numbers need a decimal point. The functions are:
+//!
+//! - `sphere(position, color, radius, lambert, specular)` and
+//!   `plane(color, normal, point, lambert, specular)`, with the methods
+//!   `with_refraction`, `with_metal`, `with_roughness`, `with_shininess`,
+//!   `with_pbr` and `with_emission` of `Sphere` and `Plane`;
+//! - `light(position, intensity)`, with `with_radius(radius, samples)`;
+//! - `add(object_or_light)` and `fog(color, density, falloff)`;
+//! - `rng(seed)`, whose `next()` gives random numbers from 0 to 1.
+
+use rhai::{Array, Engine, EvalAltResult, INT};
+use std::{cell::RefCell, convert::TryFrom, fmt, io, rc::Rc};
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
+
+use crate::{
+    atmosphere::HeightFog,
+    sampler::Rng,
+    scene::{Light, Plane, Scene, Sphere},
+    Color, Vecf,
+};
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    /// The script did not parse, or failed while running.
+    Script(Box<EvalAltResult>),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "could not read script: {}", err),
+            ScriptError::Script(err) => write!(f, "script failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<io::Error> for ScriptError {
+    fn from(err: io::Error) -> ScriptError {
+        ScriptError::Io(err)
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(err: Box<EvalAltResult>) -> ScriptError {
+        ScriptError::Script(err)
+    }
+}
+
+/// The scene the script at `path` builds.
+#[cfg(feature = "fs")]
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, ScriptError> {
+    run(&fs::read_to_string(path)?)
+}
+
+/// The scene the script `source` builds.
+pub fn run(source: &str) -> Result<Scene, ScriptError> {
+    let scene = Rc::new(RefCell::new(Scene::default()));
+    let mut engine = Engine::new();
+    engine
+        .register_type_with_name::<Sphere>("Sphere")
+        .register_type_with_name::<Plane>("Plane")
+        .register_type_with_name::<Light>("Light")
+        .register_type_with_name::<Rng>("Rng");
+
+    engine.register_fn(
+        "sphere",
+        |position: Array, color: Array, radius: f64, lambert: f64, specular: f64| {
+            Ok::<_, Box<EvalAltResult>>(Sphere::new(
+                vector(position)?,
+                self::color(color)?,
+                radius as f32,
+                lambert as f32,
+                specular as f32,
+            ))
+        },
+    );
+    engine
+        .register_fn("with_refraction", |s: Sphere, t: f64, ior: f64| {
+            s.with_refraction(t as f32, ior as f32)
+        })
+        .register_fn("with_metal", |s: Sphere, r: f64| s.with_metal(r as f32))
+        .register_fn("with_roughness", |s: Sphere, r: f64| {
+            s.with_roughness(r as f32)
+        })
+        .register_fn("with_shininess", |s: Sphere, n: f64| {
+            s.with_shininess(n as f32)
+        })
+        .register_fn("with_pbr", |s: Sphere, m: f64, r: f64| {
+            s.with_pbr(m as f32, r as f32)
+        })
+        .register_fn("with_emission", |s: Sphere, c: Array, strength: f64| {
+            Ok::<_, Box<EvalAltResult>>(s.with_emission(color(c)?, strength as f32))
+        });
+
+    engine.register_fn(
+        "plane",
+        |color: Array, normal: Array, point: Array, lambert: f64, specular: f64| {
+            Ok::<_, Box<EvalAltResult>>(Plane::new(
+                self::color(color)?,
+                vector(normal)?,
+                vector(point)?,
+                lambert as f32,
+                specular as f32,
+            ))
+        },
+    );
+    engine
+        .register_fn("with_refraction", |p: Plane, t: f64, ior: f64| {
+            p.with_refraction(t as f32, ior as f32)
+        })
+        .register_fn("with_metal", |p: Plane, r: f64| p.with_metal(r as f32))
+        .register_fn("with_roughness", |p: Plane, r: f64| {
+            p.with_roughness(r as f32)
+        })
+        .register_fn("with_shininess", |p: Plane, n: f64| {
+            p.with_shininess(n as f32)
+        })
+        .register_fn("with_pbr", |p: Plane, m: f64, r: f64| {
+            p.with_pbr(m as f32, r as f32)
+        })
+        .register_fn("with_emission", |p: Plane, c: Array, strength: f64| {
+            Ok::<_, Box<EvalAltResult>>(p.with_emission(color(c)?, strength as f32))
+        });
+
+    engine
+        .register_fn("light", |position: Array, intensity: f64| {
+            Ok::<_, Box<EvalAltResult>>(Light::new(vector(position)?, intensity as f32))
+        })
+        .register_fn("with_radius", |l: Light, radius: f64, samples: INT| {
+            l.with_radius(radius as f32, samples.clamp(0, u32::MAX as INT) as u32)
+        });
+
+    engine
+        .register_fn("rng", |seed: INT| Rng::new(0, 0, 0, seed as u32))
+        .register_fn("next", |rng: &mut Rng| rng.next_f32() as f64);
+
+    let target = scene.clone();
+    engine.register_fn("add", move |s: Sphere| target.borrow_mut().add_object(s));
+    let target = scene.clone();
+    engine.register_fn("add", move |p: Plane| target.borrow_mut().add_object(p));
+    let target = scene.clone();
+    engine.register_fn("add", move |l: Light| target.borrow_mut().add_light(l));
+    let target = scene.clone();
+    engine.register_fn(
+        "fog",
+        move |color: Array, density: f64, falloff: f64| -> Result<(), Box<EvalAltResult>> {
+            let fog = HeightFog::new(self::color(color)?, density as f32, falloff as f32);
+            target.borrow_mut().set_fog(fog);
+            Ok(())
+        },
+    );
+
+    engine.run(source)?;
+    Ok(scene.take())
+}
+
+/// The three numbers of `array`, integer or not.
+fn numbers(array: Array) -> Result<[f64; 3], Box<EvalAltResult>> {
+    let values: Vec<f64> = array
+        .iter()
+        .map(|value| {
+            value
+                .as_float()
+                .or_else(|_| value.as_int().map(|i| i as f64))
+                .map_err(|kind| format!("expected a number, found {}", kind).into())
+        })
+        .collect::<Result<_, Box<EvalAltResult>>>()?;
+    <[f64; 3]>::try_from(values.as_slice())
+        .map_err(|_| format!("expected 3 numbers, found {}", values.len()).into())
+}
+
+fn vector(array: Array) -> Result<Vecf, Box<EvalAltResult>> {
+    Ok(numbers(array)?.map(|value| value as f32))
+}
+
+fn color(array: Array) -> Result<Color, Box<EvalAltResult>> {
+    Ok(image::Rgb(
+        numbers(array)?.map(|value| value.round().clamp(0.0, 255.0) as u8),
+    ))
+}
@@ -0,0 +1,120 @@
+//! Shadow queries remembered between frames, for animations where only the
+//! camera moves. Visibility of a light is stored per object, light and
+//! cell of a grid over the scene, so points of a surface seen again from a
+//! new angle skip their shadow rays. Set one up with
+//! `Scene::set_shadow_cache`.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use crate::{
+    scene::{Light, Object, Scene},
+    Vecf,
+};
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// `fingerprint` of the scene the entries were found in.
+    scene: u64,
+    visibility: HashMap<(usize, u64, [i32; 3]), f32>,
+}
+
+/// Light visibility by object, light and cell. Points in the same cell of
+/// the same object share one result, so cells should be small next to the
+/// shadows' detail: shadow edges come out blocky at the size of a cell.
+#[derive(Debug)]
+pub struct ShadowCache {
+    cell_size: f32,
+    entries: Mutex<Entries>,
+}
+
+impl ShadowCache {
+    pub fn new(cell_size: f32) -> ShadowCache {
+        ShadowCache {
+            cell_size,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Number of results stored.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().visibility.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets every result.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().visibility.clear();
+    }
+
+    /// Forgets every result if the scene's objects or the view's `seed` or
+    /// `shadow_bias` changed since they were found. Run before each render.
+    pub(crate) fn revalidate(&self, scene: &Scene, seed: u32, shadow_bias: f32) {
+        let fingerprint = fingerprint(scene, seed, shadow_bias);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.scene != fingerprint {
+            entries.scene = fingerprint;
+            entries.visibility.clear();
+        }
+    }
+
+    /// Visibility of `light` from `point` on `object`, from `find` unless
+    /// it is already known for the cell around `point`.
+    pub(crate) fn visibility(
+        &self,
+        object: &dyn Object,
+        light: &Light,
+        point: Vecf,
+        find: impl FnOnce() -> f32,
+    ) -> f32 {
+        let key = (
+            object as *const dyn Object as *const () as usize,
+            light_hash(light),
+            point.map(|coordinate| (coordinate / self.cell_size).floor() as i32),
+        );
+        if let Some(&visibility) = self.entries.lock().unwrap().visibility.get(&key) {
+            return visibility;
+        }
+        let visibility = find();
+        self.entries
+            .lock()
+            .unwrap()
+            .visibility
+            .insert(key, visibility);
+        visibility
+    }
+}
+
+/// Everything about a light its shadows depend on. Moving or resizing a
+/// light needs no invalidation: it only stops matching its old results.
+fn light_hash(light: &Light) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    light.position.map(f32::to_bits).hash(&mut hasher);
+    light.radius.to_bits().hash(&mut hasher);
+    light.shadow_samples.hash(&mut hasher);
+    light.shadow_mask.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Everything about the scene's objects that can cast or catch a shadow,
+/// the seed soft shadows are jittered with and the shadow rays' offset.
+fn fingerprint(scene: &Scene, seed: u32, shadow_bias: f32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    shadow_bias.to_bits().hash(&mut hasher);
+    for object in &scene.objects {
+        (&**object as *const dyn Object as *const () as usize).hash(&mut hasher);
+        match object.primitive() {
+            Some(primitive) => format!("{:?}", primitive.shape).hash(&mut hasher),
+            None => format!("{:?} {:?}", object.get_position(), object.bounds()).hash(&mut hasher),
+        }
+        object.visibility().hash(&mut hasher);
+    }
+    hasher.finish()
+}
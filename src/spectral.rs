@@ -0,0 +1,95 @@
+//! Spectral rendering of dispersion, see `View::with_spectral`: light bent
+//! apart by dispersive objects is followed at single wavelengths, whose
+//! colors the CIE 1931 observer turns back into RGB.
+
+use std::sync::OnceLock;
+
+/// The visible wavelengths followed, in nanometers.
+pub const WAVELENGTHS: [f32; 2] = [380.0, 720.0];
+
+/// Wavelengths followed together from each hero wavelength, evenly spaced
+/// across the visible range.
+pub(crate) const COMPANIONS: usize = 4;
+
+/// Wavelengths, in nanometers, the red, green and blue indices of
+/// refraction of `Standard::with_dispersion` are taken to be measured at.
+const CHANNEL_WAVELENGTHS: [f32; 3] = [610.0, 550.0, 465.0];
+
+/// The CIE 1931 standard observer's color matching functions at
+/// `wavelength` in nanometers, from the multi-lobe Gaussian fit of Wyman,
+/// Sloan and Shirley.
+pub fn xyz(wavelength: f32) -> [f32; 3] {
+    let lobe = |mean: f32, below: f32, above: f32| {
+        let offset = wavelength - mean;
+        let width = if offset < 0.0 { below } else { above };
+        (-0.5 * (offset / width).powi(2)).exp()
+    };
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
+}
+
+/// CIE XYZ as linear sRGB, with the D65 white point.
+pub fn xyz_to_rgb([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ]
+}
+
+/// Linear RGB of light at `wavelength` alone, scaled so that light of
+/// every wavelength in `WAVELENGTHS` at once averages out to white.
+/// Colors beyond the sRGB gamut lose their negative parts.
+pub fn rgb(wavelength: f32) -> [f32; 3] {
+    static MEAN: OnceLock<[f32; 3]> = OnceLock::new();
+    let mean = MEAN.get_or_init(|| {
+        let steps = (WAVELENGTHS[1] - WAVELENGTHS[0]) as u32;
+        let mut sum = [0.0; 3];
+        for step in 0..steps {
+            let color = in_gamut(WAVELENGTHS[0] + step as f32 + 0.5);
+            for (s, c) in sum.iter_mut().zip(color) {
+                *s += c / steps as f32;
+            }
+        }
+        sum
+    });
+    let color = in_gamut(wavelength);
+    [0, 1, 2].map(|i| color[i] / mean[i])
+}
+
+fn in_gamut(wavelength: f32) -> [f32; 3] {
+    xyz_to_rgb(xyz(wavelength)).map(|c| c.max(0.0))
+}
+
+/// Index of refraction at `wavelength` of a material with red, green and
+/// blue indices `iors`, from Cauchy's equation fitted through them.
+pub fn ior_at(iors: [f32; 3], wavelength: f32) -> f32 {
+    // n = a + b / λ², with λ in micrometers, by least squares.
+    let inverse_square = |nm: f32| 1.0 / (nm / 1000.0).powi(2);
+    let xs = CHANNEL_WAVELENGTHS.map(inverse_square);
+    let mean_x = xs.iter().sum::<f32>() / 3.0;
+    let mean_n = iors.iter().sum::<f32>() / 3.0;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, n) in xs.iter().zip(iors) {
+        covariance += (x - mean_x) * (n - mean_n);
+        variance += (x - mean_x).powi(2);
+    }
+    let b = covariance / variance;
+    mean_n + b * (inverse_square(wavelength) - mean_x)
+}
+
+/// The hero wavelength at `u`, from 0 to 1, and its companions, spaced
+/// evenly across `WAVELENGTHS` and wrapping around at its end.
+pub(crate) fn hero_wavelengths(u: f32) -> [f32; COMPANIONS] {
+    let range = WAVELENGTHS[1] - WAVELENGTHS[0];
+    let mut wavelengths = [0.0; COMPANIONS];
+    for (i, wavelength) in wavelengths.iter_mut().enumerate() {
+        let offset = (u + i as f32 / COMPANIONS as f32).fract();
+        *wavelength = WAVELENGTHS[0] + range * offset;
+    }
+    wavelengths
+}
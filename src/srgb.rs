@@ -0,0 +1,27 @@
+//! The sRGB transfer function, which 8-bit images and color pickers use
+//! to spend more of their 256 levels on dark tones, where eyes are most
+//! sensitive. Light adds up linearly, so colors are decoded before
+//! shading and encoded again for display.
+
+/// The linear intensity, on the 0-255 scale, of 8-bit sRGB value `c`.
+pub fn decode(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    let linear = if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    };
+    linear * 255.0
+}
+
+/// The sRGB value, on the 0-255 scale, of linear intensity `c`; the
+/// inverse of `decode`. Values above 255 stay above it.
+pub fn encode(c: f32) -> f32 {
+    let c = c / 255.0;
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    encoded * 255.0
+}
@@ -0,0 +1,197 @@
+//! What a scene contains, printed before rendering so that a scene that
+//! is much bigger or smaller than intended shows up before a long render.
+
+use std::{fmt, mem};
+
+use crate::{
+    material::Clearcoat,
+    pbr::Pbr,
+    scene::{Light, Object, Scene},
+    Color,
+};
+
+/// Shading coefficients and color shared by objects of a scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// `None` for textured objects.
+    pub color: Option<Color>,
+    pub lambert: f32,
+    pub specular: f32,
+    pub transparency: f32,
+    pub ior: f32,
+    /// See `Sphere::with_dielectric`.
+    pub dielectric: bool,
+    /// See `Sphere::with_absorption`.
+    pub absorption: [f32; 3],
+    /// See `Sphere::with_metal`.
+    pub metal: bool,
+    /// See `Sphere::with_roughness`.
+    pub roughness: f32,
+    /// See `Sphere::with_shininess`.
+    pub shininess: f32,
+    pub pbr: Option<Pbr>,
+    /// See `Sphere::with_clearcoat`.
+    pub clearcoat: Option<Clearcoat>,
+}
+
+impl Material {
+    /// The material `object` is shaded with.
+    pub(crate) fn of(object: &dyn Object) -> Material {
+        Material {
+            color: object.solid_color(),
+            lambert: object.get_lambert(),
+            specular: object.get_specular(),
+            transparency: object.get_transparency(),
+            ior: object.get_ior(),
+            dielectric: object.is_dielectric(),
+            absorption: object.get_absorption(),
+            metal: object.is_metal(),
+            roughness: object.get_roughness(),
+            shininess: object.get_shininess(),
+            pbr: object.get_pbr(),
+            clearcoat: object.get_clearcoat(),
+        }
+    }
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.color {
+            Some(color) => write!(f, "rgb({}, {}, {})", color.0[0], color.0[1], color.0[2])?,
+            None => write!(f, "textured")?,
+        }
+        write!(f, ", lambert {}, specular {}", self.lambert, self.specular)?;
+        if self.transparency != 0.0 {
+            write!(f, ", transparency {}, ior {}", self.transparency, self.ior)?;
+        }
+        if self.dielectric {
+            write!(f, ", dielectric")?;
+        }
+        if self.absorption != [0.0; 3] {
+            let [r, g, b] = self.absorption;
+            write!(f, ", absorption ({}, {}, {})", r, g, b)?;
+        }
+        if self.metal {
+            write!(f, ", metal")?;
+        }
+        if self.roughness != 0.0 {
+            write!(f, ", roughness {}", self.roughness)?;
+        }
+        if self.shininess > 0.0 {
+            write!(f, ", shininess {}", self.shininess)?;
+        }
+        if let Some(pbr) = self.pbr {
+            write!(f, ", metallic {}", pbr.metallic)?;
+        }
+        if let Some(coat) = self.clearcoat {
+            write!(f, ", clearcoat {}", coat.strength)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned by `Scene::summary`; `Display` lays it out for the terminal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneSummary {
+    /// Number of objects of each `Object::kind`, in the order the kinds
+    /// first appear.
+    pub objects: Vec<(String, usize)>,
+    pub triangles: usize,
+    pub lights: usize,
+    /// Lights with a radius, which trace `shadow_samples` shadow rays each.
+    pub area_lights: usize,
+    /// Height fog, cloud layers and volumes.
+    pub media: usize,
+    /// Photons in the caustics map.
+    pub photons: usize,
+    /// Distinct materials, in the order they first appear.
+    pub materials: Vec<Material>,
+    /// Rough number of bytes the objects, lights and photons take.
+    pub memory: usize,
+}
+
+impl SceneSummary {
+    pub(crate) fn new(scene: &Scene) -> SceneSummary {
+        let mut objects: Vec<(String, usize)> = Vec::new();
+        let mut materials = Vec::new();
+        for object in &scene.objects {
+            match objects.iter_mut().find(|(kind, _)| kind == object.kind()) {
+                Some((_, count)) => *count += 1,
+                None => objects.push((object.kind().to_string(), 1)),
+            }
+            let material = Material::of(object.as_ref());
+            if !materials.contains(&material) {
+                materials.push(material);
+            }
+        }
+        let memory = scene
+            .objects
+            .iter()
+            .map(|object| object.memory_size())
+            .sum::<usize>()
+            + scene.lights.len() * mem::size_of::<Light>()
+            + scene
+                .caustics
+                .as_ref()
+                .map_or(0, |caustics| caustics.memory_size());
+        SceneSummary {
+            objects,
+            triangles: scene
+                .objects
+                .iter()
+                .map(|object| object.triangle_count())
+                .sum(),
+            lights: scene.lights.len(),
+            area_lights: scene
+                .lights
+                .iter()
+                .filter(|light| light.radius > 0.0)
+                .count(),
+            media: scene.fog.iter().count() + scene.clouds.len() + scene.volumes.len(),
+            photons: scene.caustics.as_ref().map_or(0, |caustics| caustics.len()),
+            materials,
+            memory,
+        }
+    }
+}
+
+impl fmt::Display for SceneSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: usize = self.objects.iter().map(|(_, count)| count).sum();
+        let kinds: Vec<String> = self
+            .objects
+            .iter()
+            .map(|(kind, count)| format!("{} {}", kind, count))
+            .collect();
+        writeln!(f, "objects    {} ({})", total, kinds.join(", "))?;
+        writeln!(f, "triangles  {}", self.triangles)?;
+        writeln!(f, "lights     {} ({} area)", self.lights, self.area_lights)?;
+        writeln!(f, "media      {}", self.media)?;
+        writeln!(f, "photons    {}", self.photons)?;
+        writeln!(f, "materials  {}", self.materials.len())?;
+        for material in &self.materials {
+            writeln!(f, "  {}", material)?;
+        }
+        write!(f, "memory     {}", Bytes(self.memory))
+    }
+}
+
+/// A byte count in the largest binary unit that keeps it at least 1.
+struct Bytes(usize);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut size = self.0 as f64;
+        for unit in ["B", "KiB", "MiB"] {
+            if size < 1024.0 {
+                return if unit == "B" {
+                    write!(f, "{} {}", self.0, unit)
+                } else {
+                    write!(f, "{:.1} {}", size, unit)
+                };
+            }
+            size /= 1024.0;
+        }
+        write!(f, "{:.1} GiB", size)
+    }
+}
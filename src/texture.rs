@@ -0,0 +1,653 @@
+#[cfg(feature = "fs")]
+use image::ImageResult;
+use image::{GrayImage, Rgb, RgbImage};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::{f32::consts::PI, fmt, sync::Arc};
+use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_normalized, vec3_scale, vec3_sub};
+
+use crate::{
+    noise,
+    scene::{Hit, Scene},
+    view::Ray,
+    warning::RenderWarning,
+    Color, Vecf,
+};
+
+/// Anything that can give an object its color at a given hit.
+pub trait Texture: Send + Sync {
+    fn color_at(&self, hit: &Hit) -> Color;
+
+    /// Color at `hit` for textures that need to query the rest of the scene.
+    fn shade(&self, hit: &Hit, _scene: &Scene) -> Color {
+        self.color_at(hit)
+    }
+
+    /// The color everywhere, if the texture does not vary.
+    fn solid_color(&self) -> Option<Color> {
+        None
+    }
+}
+
+impl Texture for Color {
+    fn color_at(&self, _hit: &Hit) -> Color {
+        *self
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        Some(*self)
+    }
+}
+
+/// Closures over the hit work as procedural textures, e.g.
+/// `|hit: &Hit| if hit.point[1] > 0.5 { moss } else { stone }`.
+impl<F> Texture for F
+where
+    F: Fn(&Hit) -> Color + Send + Sync,
+{
+    fn color_at(&self, hit: &Hit) -> Color {
+        self(hit)
+    }
+}
+
+/// Blends linearly between two colors by world-space height.
+#[derive(Clone)]
+pub struct HeightGradient {
+    low: Color,
+    high: Color,
+    bottom: f32,
+    top: f32,
+}
+
+impl HeightGradient {
+    pub fn new(low: Color, high: Color, bottom: f32, top: f32) -> HeightGradient {
+        HeightGradient {
+            low,
+            high,
+            bottom,
+            top,
+        }
+    }
+}
+
+impl Texture for HeightGradient {
+    fn color_at(&self, hit: &Hit) -> Color {
+        let t = ((hit.point[1] - self.bottom) / (self.top - self.bottom)).clamp(0.0, 1.0);
+        mix(self.low, self.high, t)
+    }
+}
+
+/// Uses a mesh's interpolated vertex color as albedo, falling back to
+/// `fallback` where the hit has none and recording a
+/// `RenderWarning::TextureFallback`.
+#[derive(Clone)]
+pub struct VertexColor {
+    fallback: Color,
+}
+
+impl VertexColor {
+    pub fn new(fallback: Color) -> VertexColor {
+        VertexColor { fallback }
+    }
+}
+
+impl Texture for VertexColor {
+    fn color_at(&self, hit: &Hit) -> Color {
+        hit.vertex_color.unwrap_or(self.fallback)
+    }
+
+    fn shade(&self, hit: &Hit, scene: &Scene) -> Color {
+        if hit.vertex_color.is_none() {
+            scene.warnings.record(RenderWarning::TextureFallback);
+        }
+        self.color_at(hit)
+    }
+}
+
+/// How `ImageTexture` reads colors between pixel centers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// The pixel the point falls in, which shows blocky pixels up close.
+    #[default]
+    Nearest,
+    /// A blend of the four pixels around the point, weighted by distance,
+    /// which smooths magnified textures.
+    Bilinear,
+}
+
+/// Samples an image at the hit's texture coordinates, repeating outside
+/// [0, 1]. Hits without coordinates take the image's bottom-left pixel and
+/// record a `RenderWarning::TextureFallback`. Empty images are black.
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<RgbImage>,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: RgbImage) -> ImageTexture {
+        ImageTexture {
+            image: Arc::new(image),
+            filter: Filter::Nearest,
+        }
+    }
+
+    /// Reads the image with `filter` rather than `Filter::Nearest`.
+    pub fn with_filter(mut self, filter: Filter) -> ImageTexture {
+        self.filter = filter;
+        self
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<ImageTexture> {
+        Ok(ImageTexture::new(image::open(path)?.to_rgb()))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color_at(&self, hit: &Hit) -> Color {
+        let [u, v] = hit.uv.unwrap_or([0.0, 0.0]);
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return Rgb([0; 3]);
+        }
+        let x = u.rem_euclid(1.0) * width as f32;
+        let y = (1.0 - v.rem_euclid(1.0)) * height as f32;
+        match self.filter {
+            Filter::Nearest => *self
+                .image
+                .get_pixel((x as u32).min(width - 1), (y as u32).min(height - 1)),
+            Filter::Bilinear => {
+                // Pixel centers lie half a pixel in; blend between the
+                // ones around the point, wrapping at the edges.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let wrap = |t: f32, size: u32| (t.rem_euclid(size as f32) as u32).min(size - 1);
+                let (x0, x1) = (wrap(x0, width), wrap(x0 + 1.0, width));
+                let (y0, y1) = (wrap(y0, height), wrap(y0 + 1.0, height));
+                let texel = |x, y| self.image.get_pixel(x, y).0.map(f32::from);
+                let lerp =
+                    |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+                let top = lerp(texel(x0, y0), texel(x1, y0), fx);
+                let bottom = lerp(texel(x0, y1), texel(x1, y1), fx);
+                Rgb(lerp(top, bottom, fy).map(|c| c.round().clamp(0.0, 255.0) as u8))
+            }
+        }
+    }
+
+    fn shade(&self, hit: &Hit, scene: &Scene) -> Color {
+        if hit.uv.is_none() {
+            scene.warnings.record(RenderWarning::TextureFallback);
+        }
+        self.color_at(hit)
+    }
+}
+
+/// A tangent-space normal map: an image whose red, green and blue give the
+/// surface's normal along the directions u and v grow in and straight out,
+/// as bake tools write them, with flat surface as (128, 128, 255). Objects
+/// given one with `with_normal_map` bend their shading normals by it, for
+/// bumps and grooves that catch the light without extra geometry.
+#[derive(Clone)]
+pub struct NormalMap {
+    image: ImageTexture,
+    strength: f32,
+}
+
+impl NormalMap {
+    pub fn new(image: RgbImage) -> NormalMap {
+        NormalMap {
+            image: ImageTexture::new(image).with_filter(Filter::Bilinear),
+            strength: 1.0,
+        }
+    }
+
+    /// Scales the bumps' slopes, e.g. 0.5 to flatten them or 0 to switch
+    /// the map off; 1 by default.
+    pub fn with_strength(mut self, strength: f32) -> NormalMap {
+        self.strength = strength;
+        self
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<NormalMap> {
+        Ok(NormalMap::new(image::open(path)?.to_rgb()))
+    }
+
+    /// A bump map: the normal map of the grayscale height map `heights`,
+    /// whose white parts stand `depth` pixels above its black ones, for
+    /// when no normal map was baked. Slopes are found once, here, so it
+    /// renders as fast as any normal map. The image repeats, as textures
+    /// do.
+    pub fn from_heights(heights: &GrayImage, depth: f32) -> NormalMap {
+        let (width, height) = heights.dimensions();
+        let at = |x: i64, y: i64| {
+            let x = x.rem_euclid(width as i64) as u32;
+            let y = y.rem_euclid(height as i64) as u32;
+            heights.get_pixel(x, y).0[0] as f32 / 255.0 * depth
+        };
+        let normals = RgbImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            // Central differences; v grows up the image, against y.
+            let slope_u = (at(x + 1, y) - at(x - 1, y)) / 2.0;
+            let slope_v = (at(x, y - 1) - at(x, y + 1)) / 2.0;
+            let normal = vec3_normalized([-slope_u, -slope_v, 1.0]);
+            Rgb(normal.map(|c| ((c * 0.5 + 0.5) * 255.0).round() as u8))
+        });
+        NormalMap::new(normals)
+    }
+
+    /// `from_heights` for the image at `path`, as grayscale.
+    #[cfg(feature = "fs")]
+    pub fn open_heights<P: AsRef<Path>>(path: P, depth: f32) -> ImageResult<NormalMap> {
+        Ok(NormalMap::from_heights(
+            &image::open(path)?.to_luma(),
+            depth,
+        ))
+    }
+
+    /// `normal` bent by the map at `uv`, where u grows along `tangent` and
+    /// v along `bitangent`. Falls back to `normal` where the directions do
+    /// not span the surface, as at a sphere's poles, and for empty images.
+    pub fn perturb(&self, normal: Vecf, tangent: Vecf, bitangent: Vecf, uv: [f32; 2]) -> Vecf {
+        let (width, height) = self.image.image.dimensions();
+        if width == 0 || height == 0 {
+            return normal;
+        }
+        let hit = Hit {
+            point: [0.0; 3],
+            normal,
+            local_point: [0.0; 3],
+            uv: Some(uv),
+            vertex_color: None,
+            tangent: None,
+        };
+        let [x, y, z] = self
+            .image
+            .color_at(&hit)
+            .0
+            .map(|c| c as f32 / 255.0 * 2.0 - 1.0);
+        // Make the directions perpendicular to the normal and each other.
+        let tangent = vec3_normalized(vec3_sub(
+            tangent,
+            vec3_scale(normal, vec3_dot(normal, tangent)),
+        ));
+        let bitangent = vec3_sub(bitangent, vec3_scale(normal, vec3_dot(normal, bitangent)));
+        let bitangent = vec3_normalized(vec3_sub(
+            bitangent,
+            vec3_scale(tangent, vec3_dot(tangent, bitangent)),
+        ));
+        let bent = vec3_normalized(vec3_add(
+            vec3_add(
+                vec3_scale(tangent, x * self.strength),
+                vec3_scale(bitangent, y * self.strength),
+            ),
+            vec3_scale(normal, z.max(0.0)),
+        ));
+        if bent.iter().all(|c| c.is_finite()) {
+            bent
+        } else {
+            normal
+        }
+    }
+}
+
+/// Which parts of a surface are there, for cutouts such as leaves drawn on
+/// a plane: a grayscale image, white where the surface is solid and black
+/// where it is cut away, repeating outside [0, 1] as textures do. Set with
+/// `Standard::with_cutout`. Hits without texture coordinates and empty
+/// images are solid.
+#[derive(Clone, PartialEq)]
+pub struct AlphaMask {
+    image: Arc<GrayImage>,
+}
+
+impl AlphaMask {
+    pub fn new(image: GrayImage) -> AlphaMask {
+        AlphaMask {
+            image: Arc::new(image),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<AlphaMask> {
+        Ok(AlphaMask::new(image::open(path)?.to_luma()))
+    }
+
+    /// Share of the surface there at texture coordinates `uv`, from 0 to 1.
+    pub fn coverage(&self, uv: Option<[f32; 2]>) -> f32 {
+        let (width, height) = self.image.dimensions();
+        let [u, v] = match uv {
+            Some(uv) if width > 0 && height > 0 => uv,
+            _ => return 1.0,
+        };
+        let x = (u.rem_euclid(1.0) * width as f32) as u32;
+        let y = ((1.0 - v.rem_euclid(1.0)) * height as f32) as u32;
+        let pixel = self.image.get_pixel(x.min(width - 1), y.min(height - 1));
+        pixel.0[0] as f32 / 255.0
+    }
+}
+
+/// Only the size, since the pixels would drown out the rest of a material.
+impl fmt::Debug for AlphaMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlphaMask")
+            .field("dimensions", &self.image.dimensions())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Alternates two colors in squares `size` texture units across, e.g. a
+/// chessboard on a `Plane::from_points` with a size of 1/8. Hits without
+/// coordinates take `a` and record a `RenderWarning::TextureFallback`.
+#[derive(Clone)]
+pub struct Checker {
+    a: Arc<dyn Texture>,
+    b: Arc<dyn Texture>,
+    size: f32,
+}
+
+impl Checker {
+    pub fn new(a: Color, b: Color, size: f32) -> Checker {
+        Checker::from_textures(a, b, size)
+    }
+
+    /// Alternates two textures instead of colors, e.g. two wood grains.
+    pub fn from_textures<A, B>(a: A, b: B, size: f32) -> Checker
+    where
+        A: Texture + 'static,
+        B: Texture + 'static,
+    {
+        Checker {
+            a: Arc::new(a),
+            b: Arc::new(b),
+            size,
+        }
+    }
+
+    fn square(&self, hit: &Hit) -> &dyn Texture {
+        let [u, v] = hit.uv.unwrap_or([0.0, 0.0]);
+        let square = (u / self.size).floor() + (v / self.size).floor();
+        if square.rem_euclid(2.0) == 1.0 {
+            &*self.b
+        } else {
+            &*self.a
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn color_at(&self, hit: &Hit) -> Color {
+        self.square(hit).color_at(hit)
+    }
+
+    fn shade(&self, hit: &Hit, scene: &Scene) -> Color {
+        if hit.uv.is_none() {
+            scene.warnings.record(RenderWarning::TextureFallback);
+        }
+        self.square(hit).shade(hit, scene)
+    }
+}
+
+/// Square tiles one texture unit across, set in grout lines `grout_width`
+/// tiles wide, e.g. a grid of lines to check texture coordinates by; on a
+/// plane, `Plane::with_tile_size` sets the tile size in world units. Hits
+/// without coordinates take the tile color and record a
+/// `RenderWarning::TextureFallback`.
+#[derive(Clone)]
+pub struct Tiles {
+    tile: Arc<dyn Texture>,
+    grout: Arc<dyn Texture>,
+    grout_width: f32,
+    size: f32,
+}
+
+impl Tiles {
+    pub fn new(tile: Color, grout: Color, grout_width: f32) -> Tiles {
+        Tiles::from_textures(tile, grout, grout_width)
+    }
+
+    /// Tiles and grout of textures instead of colors, e.g. marble tiles.
+    pub fn from_textures<T, G>(tile: T, grout: G, grout_width: f32) -> Tiles
+    where
+        T: Texture + 'static,
+        G: Texture + 'static,
+    {
+        Tiles {
+            tile: Arc::new(tile),
+            grout: Arc::new(grout),
+            grout_width,
+            size: 1.0,
+        }
+    }
+
+    /// Makes the tiles `size` texture units across rather than 1.
+    pub fn with_size(mut self, size: f32) -> Tiles {
+        self.size = size;
+        self
+    }
+
+    fn part(&self, hit: &Hit) -> &dyn Texture {
+        let [u, v] = match hit.uv {
+            Some(uv) => uv,
+            None => return &*self.tile,
+        };
+        // Half the grout lies on either side of every tile edge.
+        let half = self.grout_width / 2.0;
+        let in_grout = |t: f32| {
+            let t = (t / self.size).rem_euclid(1.0);
+            t < half || t > 1.0 - half
+        };
+        if in_grout(u) || in_grout(v) {
+            &*self.grout
+        } else {
+            &*self.tile
+        }
+    }
+}
+
+impl Texture for Tiles {
+    fn color_at(&self, hit: &Hit) -> Color {
+        self.part(hit).color_at(hit)
+    }
+
+    fn shade(&self, hit: &Hit, scene: &Scene) -> Color {
+        if hit.uv.is_none() {
+            scene.warnings.record(RenderWarning::TextureFallback);
+        }
+        self.part(hit).shade(hit, scene)
+    }
+}
+
+/// Veined stone: bands of `vein` through `base`, bent by turbulence, with
+/// features about `size` units across. Like the other noise textures, it
+/// is a solid texture of the hit's position relative to the object, so it
+/// moves with the object and needs no texture coordinates.
+#[derive(Clone)]
+pub struct Marble {
+    base: Color,
+    vein: Color,
+    size: f32,
+    turbulence: f32,
+}
+
+impl Marble {
+    pub fn new(base: Color, vein: Color, size: f32) -> Marble {
+        Marble {
+            base,
+            vein,
+            size,
+            turbulence: 5.0,
+        }
+    }
+
+    /// How far the veins wander from straight bands; 5 by default, 0 for
+    /// straight stripes.
+    pub fn with_turbulence(mut self, turbulence: f32) -> Marble {
+        self.turbulence = turbulence;
+        self
+    }
+}
+
+impl Texture for Marble {
+    fn color_at(&self, hit: &Hit) -> Color {
+        let point = vec3_scale(hit.local_point, 1.0 / self.size);
+        let phase = point[0] + self.turbulence * noise::turbulence(point, 6);
+        // Narrow veins where the sine peaks.
+        let t = (0.5 + 0.5 * (PI * phase).sin()).powi(4);
+        mix(self.base, self.vein, t)
+    }
+}
+
+/// Tree rings of `light` and `dark` wood around the object's vertical
+/// axis, `spacing` units apart, with a noisy grain.
+#[derive(Clone)]
+pub struct Wood {
+    light: Color,
+    dark: Color,
+    spacing: f32,
+}
+
+impl Wood {
+    pub fn new(light: Color, dark: Color, spacing: f32) -> Wood {
+        Wood {
+            light,
+            dark,
+            spacing,
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn color_at(&self, hit: &Hit) -> Color {
+        let [x, y, z] = vec3_scale(hit.local_point, 1.0 / self.spacing);
+        let radius = (x * x + z * z).sqrt() + 0.3 * noise::fbm([x, y * 0.2, z], 3);
+        mix(self.light, self.dark, radius.rem_euclid(1.0).powi(3))
+    }
+}
+
+/// Soft patches of `cloud` over `sky`, about `size` units across, from
+/// fractal noise.
+#[derive(Clone)]
+pub struct Clouds {
+    sky: Color,
+    cloud: Color,
+    size: f32,
+}
+
+impl Clouds {
+    pub fn new(sky: Color, cloud: Color, size: f32) -> Clouds {
+        Clouds { sky, cloud, size }
+    }
+}
+
+impl Texture for Clouds {
+    fn color_at(&self, hit: &Hit) -> Color {
+        let point = vec3_scale(hit.local_point, 1.0 / self.size);
+        let t = (0.5 + noise::fbm(point, 6)).clamp(0.0, 1.0);
+        mix(self.sky, self.cloud, t)
+    }
+}
+
+/// Darkens crevices of a base texture by shooting short occlusion rays
+/// around the normal at shade time.
+#[derive(Clone)]
+pub struct Cavity {
+    base: Arc<dyn Texture>,
+    dirt: Color,
+    distance: f32,
+    samples: u32,
+    strength: f32,
+    bias: f32,
+}
+
+impl Cavity {
+    pub fn new<T: Texture + 'static>(
+        base: T,
+        dirt: Color,
+        distance: f32,
+        samples: u32,
+        strength: f32,
+    ) -> Cavity {
+        Cavity {
+            base: Arc::new(base),
+            dirt,
+            distance,
+            samples,
+            strength,
+            bias: 1e-3,
+        }
+    }
+
+    /// Fraction of the hemisphere around the normal blocked within `distance`.
+    pub fn occlusion(&self, hit: &Hit, scene: &Scene) -> f32 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        let origin = vec3_add(hit.point, vec3_scale(hit.normal, self.bias));
+        let mut blocked = 0;
+        for i in 0..self.samples {
+            let direction = hemisphere_direction(hit.normal, i, self.samples);
+            if scene.occluded(&Ray::new(origin, direction), self.distance) {
+                blocked += 1;
+            }
+        }
+        blocked as f32 / self.samples as f32
+    }
+}
+
+impl Texture for Cavity {
+    fn color_at(&self, hit: &Hit) -> Color {
+        self.base.color_at(hit)
+    }
+
+    fn shade(&self, hit: &Hit, scene: &Scene) -> Color {
+        let color = self.base.shade(hit, scene);
+        let t = (self.occlusion(hit, scene) * self.strength).clamp(0.0, 1.0);
+        mix(color, self.dirt, t)
+    }
+}
+
+/// Linear blend from `a` (t = 0) to `b` (t = 1).
+pub fn mix(a: Color, b: Color, t: f32) -> Color {
+    let mut color = a;
+    for c in 0..3 {
+        color.0[c] = (a.0[c] as f32 * (1.0 - t) + b.0[c] as f32 * t) as u8;
+    }
+    color
+}
+
+/// The `i`th of `count` cosine-weighted directions spread over the
+/// hemisphere around `normal` on a Fibonacci spiral.
+fn hemisphere_direction(normal: Vecf, i: u32, count: u32) -> Vecf {
+    let r = ((i as f32 + 0.5) / count as f32).sqrt();
+    let phi = i as f32 * PI * (3.0 - 5f32.sqrt());
+    disk_to_hemisphere(normal, r, phi)
+}
+
+/// Lifts the point at radius `r` and angle `phi` on the unit disk
+/// perpendicular to `normal` onto the hemisphere around it. Uniform points
+/// on the disk give cosine-weighted directions.
+pub(crate) fn disk_to_hemisphere(normal: Vecf, r: f32, phi: f32) -> Vecf {
+    let (tangent, bitangent) = tangent_frame(normal);
+    let z = (1.0 - r * r).max(0.0).sqrt();
+    vec3_add(
+        vec3_add(
+            vec3_scale(tangent, r * phi.cos()),
+            vec3_scale(bitangent, r * phi.sin()),
+        ),
+        vec3_scale(normal, z),
+    )
+}
+
+/// Two unit vectors perpendicular to `normal` and to each other.
+pub(crate) fn tangent_frame(normal: Vecf) -> (Vecf, Vecf) {
+    let helper = if normal[0].abs() > 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let tangent = vec3_normalized(vec3_cross(helper, normal));
+    (tangent, vec3_cross(normal, tangent))
+}
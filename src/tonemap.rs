@@ -0,0 +1,104 @@
+//! Operators that compress the unbounded brightness of a render into the
+//! range of an 8-bit image.
+
+use crate::framebuffer::Framebuffer;
+
+/// How colors above white are brought into range when a render is
+/// converted to 8 bits, set with `View::with_tonemap`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Tonemap {
+    /// Clips every channel at white, so highlights burn out. Matches
+    /// renders from before tone mapping.
+    #[default]
+    Clip,
+    /// `1 - e^(-exposure * c)`, as film responds to light: linear in the
+    /// shadows and approaching white smoothly. Higher `exposure` is
+    /// brighter.
+    Exposure(f32),
+    /// `c / (1 + c)`, which keeps all detail in the highlights but
+    /// darkens midtones.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, with more
+    /// contrast than `Reinhard` and a soft shoulder.
+    Aces,
+}
+
+impl Tonemap {
+    /// Maps a linear color on the 0-255 scale of `Framebuffer`, with
+    /// any brightness, into 0-255. `Clip` returns colors unchanged and
+    /// leaves clipping to the conversion to 8 bits.
+    pub fn apply(self, color: [f32; 3]) -> [f32; 3] {
+        if self == Tonemap::Clip {
+            return color;
+        }
+        color.map(|c| self.curve(c / 255.0) * 255.0)
+    }
+
+    /// The operator on a scale with 1.0 as white.
+    fn curve(self, c: f32) -> f32 {
+        match self {
+            Tonemap::Clip => c.min(1.0),
+            Tonemap::Exposure(exposure) => 1.0 - (-exposure * c).exp(),
+            Tonemap::Reinhard => c / (1.0 + c),
+            // The fit rises past white for very bright colors.
+            Tonemap::Aces => ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).min(1.0),
+        }
+    }
+}
+
+/// Picks an exposure value for `View::with_exposure_value` from a render,
+/// so that its log-average luminance, which follows the overall
+/// brightness without being thrown off by a few bright lights, comes out
+/// as middle gray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposure {
+    key: f32,
+    range: Option<(f32, f32)>,
+}
+
+impl Default for AutoExposure {
+    fn default() -> AutoExposure {
+        AutoExposure {
+            key: 0.18,
+            range: None,
+        }
+    }
+}
+
+impl AutoExposure {
+    pub fn new() -> AutoExposure {
+        AutoExposure::default()
+    }
+
+    /// Brightness, with 1.0 as white, the log-average luminance is
+    /// exposed to; 0.18 by default. Higher keys suit bright scenes such
+    /// as snow, lower ones night scenes.
+    pub fn with_key(mut self, key: f32) -> AutoExposure {
+        self.key = key;
+        self
+    }
+
+    /// Keeps the exposure value between `min` and `max`, e.g. so a nearly
+    /// black render is not brightened until its noise shows.
+    pub fn with_range(mut self, min: f32, max: f32) -> AutoExposure {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// The exposure value for `framebuffer`, or 0 if it has no finite
+    /// pixels.
+    pub fn exposure_value(&self, framebuffer: &Framebuffer) -> f32 {
+        let mut ev = match framebuffer.log_average_luminance() {
+            Some(average) => (average / self.key).log2(),
+            None => 0.0,
+        };
+        if let Some((min, max)) = self.range {
+            ev = ev.max(min).min(max);
+        }
+        if ev.is_finite() {
+            ev
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Lengths scenes are measured in, so that assets authored at different
+//! scales can be mixed and physical quantities keep their meaning. Lights
+//! and fog are specified per meter whatever the scene's unit, set with
+//! `Scene::set_unit`; meshes are scaled into it with `Mesh::with_unit`.
+
+/// The length one scene unit stands for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Unit {
+    #[default]
+    Meter,
+    Centimeter,
+    Millimeter,
+    Inch,
+    Foot,
+    /// Any other length, in meters.
+    Custom(f32),
+}
+
+impl Unit {
+    /// Meters in one unit.
+    pub fn meters(self) -> f32 {
+        match self {
+            Unit::Meter => 1.0,
+            Unit::Centimeter => 0.01,
+            Unit::Millimeter => 0.001,
+            Unit::Inch => 0.0254,
+            Unit::Foot => 0.3048,
+            Unit::Custom(meters) => meters,
+        }
+    }
+
+    /// Units of `target` in one of this unit, e.g. 100 from meters to
+    /// centimeters.
+    pub fn scale_to(self, target: Unit) -> f32 {
+        self.meters() / target.meters()
+    }
+}
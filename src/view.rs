@@ -1,6 +1,31 @@
-use crate::{scene::Object, scene::Scene, Color, Vecf};
-use image::{Pixel, Rgb, RgbImage};
-use std::f32::consts::PI;
+#[cfg(feature = "spectral")]
+use crate::spectral::{self, COMPANIONS};
+use crate::{
+    axes::{Axes, Handedness},
+    checkpoint::Checkpoint,
+    cone::DistanceField,
+    dither::Dither,
+    framebuffer::Framebuffer,
+    inspect::{BounceKind, PixelTrace, Recorder},
+    integrator::{Integrator, Whitted},
+    material::{AlphaMode, Scatter},
+    pbr::Pbr,
+    sampler::{hash, unit, Rng, Sampler, Uniform},
+    scene::{same_object, schlick, transmittance, Hit, Light, Object, Part, RayMask, Scene},
+    srgb,
+    texture::{disk_to_hemisphere, mix, tangent_frame},
+    tonemap::Tonemap,
+    warning::RenderWarning,
+    Color, Vecf,
+};
+use image::{Rgb, RgbImage};
+use std::{
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use vecmath::{
     vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_neg, vec3_normalized, vec3_scale, vec3_sub,
 };
@@ -18,18 +43,166 @@ impl Ray {
     }
 }
 
+/// Block size, in pixels, of the first pass of `View::render_progressive`.
+pub const PROGRESSIVE_START_BLOCK: u32 = 16;
+
+/// Exposure values, in stops, of a standard -2/0/+2 EV bracket for
+/// `View::render_bracketed`.
+pub const BRACKET_STOPS: [f32; 3] = [-2.0, 0.0, 2.0];
+
+/// Samples `View::with_adaptive_sampling` takes before it first checks a
+/// pixel's noise, since fewer give too rough a variance estimate.
+pub const ADAPTIVE_MIN_SAMPLES: u32 = 4;
+
+/// Edge length, in pixels, of the square tiles renders are split into.
+pub const TILE_SIZE: u32 = 32;
+
+/// Half the width, in pixels, of the cross `View::render_layout` marks
+/// lights with.
+const LAYOUT_LIGHT_SIZE: f32 = 4.0;
+
+/// Cut-away surfaces a ray passes at most before counting as a miss, so
+/// rays cannot keep passing the same surface where the shadow bias is 0.
+pub(crate) const MAX_CUTOUT_LAYERS: u32 = 64;
+
+/// Distance in front of the camera `View::render_layout` cuts edges at,
+/// so edges passing behind the camera still show their visible part.
+const LAYOUT_NEAR: f32 = 1e-3;
+
+/// Shared flag for stopping a `View::render_cancellable` from another
+/// thread. Clones refer to the same flag.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Colors `View::render_light_debug` tells lights and intensity bands apart
+/// by; light `i` gets entry `i % 8`.
+pub const LIGHT_PALETTE: [Color; 8] = [
+    Rgb([230, 25, 75]),
+    Rgb([60, 180, 75]),
+    Rgb([0, 130, 200]),
+    Rgb([255, 225, 25]),
+    Rgb([145, 30, 180]),
+    Rgb([70, 240, 240]),
+    Rgb([245, 130, 48]),
+    Rgb([240, 50, 230]),
+];
+
+/// What `View::render_light_debug` shows at each hit.
+#[derive(Clone, Copy, Debug)]
+pub enum LightDebug {
+    /// The palette color of the light contributing most, scaled by its share
+    /// of the total, so boundaries between lights show up darker.
+    DominantLight,
+    /// Bands of total light intensity `step` wide, cycling through the
+    /// palette, like iso-intensity shells around the lights.
+    IsoIntensity { step: f32 },
+}
+
+#[derive(Clone)]
 pub struct View {
-    image_width: u32,
-    image_height: u32,
-    cam_position: Vecf,
-    fov_rad: f32,
-    direction: Vecf,
-    max_depth: u32,
-    background: Color,
-    shadow_bias: f32,
+    pub(crate) image_width: u32,
+    pub(crate) image_height: u32,
+    pub(crate) cam_position: Vecf,
+    pub(crate) fov_rad: f32,
+    /// Width of the view set with `with_orthographic`.
+    pub(crate) orthographic: Option<f32>,
+    pub(crate) direction: Vecf,
+    pub(crate) max_depth: u32,
+    pub(crate) background: Color,
+    pub(crate) backplate: Option<RgbImage>,
+    pub(crate) shadow_bias: f32,
+    pub(crate) region: Option<Region>,
+    pub(crate) samples: u32,
+    pub(crate) sampler: Arc<dyn Sampler>,
+    pub(crate) adaptive: Option<Adaptive>,
+    pub(crate) seed: u32,
+    /// Frame set with `with_frame`.
+    pub(crate) frame: u32,
+    pub(crate) fixed_noise: bool,
+    /// `None` for the default `Whitted`.
+    pub(crate) integrator: Option<Arc<dyn Integrator>>,
+    pub(crate) tonemap: Tonemap,
+    pub(crate) srgb: bool,
+    /// EV100 set with `with_exposure_value`.
+    pub(crate) exposure_value: f32,
+    pub(crate) dither: Option<Dither>,
+    pub(crate) min_contribution: f32,
+    pub(crate) light_cutoff: f32,
+    pub(crate) max_glossy_samples: u32,
+    pub(crate) cone_tracing: bool,
+    pub(crate) transparent_shadows: bool,
+    pub(crate) fresnel: bool,
+    #[cfg(feature = "spectral")]
+    pub(crate) spectral: bool,
+    pub(crate) axes: Axes,
+}
+
+/// Shares of light a surface reflects and refracts, from `View::split`.
+pub(crate) struct Split {
+    pub(crate) reflect: f32,
+    pub(crate) refract: f32,
+    /// Share its clear coat mirrors, along `View::coat_reflect`; the
+    /// others are of the light the coat lets through.
+    pub(crate) coat: f32,
+    /// The ray refracted light arrives along, for transparent objects.
+    pub(crate) refracted: Option<Ray>,
+}
+
+/// The light a ray carries alone once a dispersive object bent it apart
+/// from the rest, see `View::disperse`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Band {
+    /// One color channel.
+    Channel(usize),
+    /// One wavelength, in nanometers, with `View::with_spectral`.
+    #[cfg(feature = "spectral")]
+    Wavelength(f32),
+}
+
+impl Band {
+    /// Index of refraction for the band of a material with red, green and
+    /// blue indices `iors`.
+    fn ior(self, iors: [f32; 3]) -> f32 {
+        match self {
+            Band::Channel(channel) => iors[channel],
+            #[cfg(feature = "spectral")]
+            Band::Wavelength(wavelength) => spectral::ior_at(iors, wavelength),
+        }
+    }
+}
+
+/// Sub-rectangle of the image set with `View::set_region`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// Stopping rule set with `View::with_adaptive_sampling`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Adaptive {
+    pub(crate) threshold: f32,
+    pub(crate) max_samples: u32,
 }
 
 impl View {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image_width: u32,
         image_height: u32,
@@ -47,126 +220,2052 @@ impl View {
             image_height,
             cam_position,
             fov_rad,
+            orthographic: None,
             direction,
             max_depth,
             background,
+            backplate: None,
             shadow_bias,
+            region: None,
+            samples: 1,
+            sampler: Arc::new(Uniform),
+            adaptive: None,
+            seed: 0,
+            frame: 0,
+            fixed_noise: false,
+            integrator: None,
+            tonemap: Tonemap::Clip,
+            srgb: false,
+            exposure_value: 0.0,
+            dither: None,
+            min_contribution: 0.0,
+            light_cutoff: 0.0,
+            max_glossy_samples: u32::MAX,
+            cone_tracing: false,
+            transparent_shadows: false,
+            fresnel: false,
+            #[cfg(feature = "spectral")]
+            spectral: false,
+            axes: Axes::default(),
+        }
+    }
+
+    /// Shows `image`, stretched over the frame, wherever camera rays miss or
+    /// hit a `ShadowCatcher`, in place of the background color. An empty
+    /// image leaves the background color.
+    pub fn with_backplate(mut self, image: RgbImage) -> View {
+        self.backplate = Some(image);
+        self
+    }
+
+    /// Projects in parallel instead of in perspective, as technical
+    /// drawings do, showing `width` across the image; the field of view is
+    /// ignored. Rays start on the plane through the camera position.
+    pub fn with_orthographic(mut self, width: f32) -> View {
+        self.orthographic = Some(width);
+        self
+    }
+
+    /// Takes the world to follow the `axes` convention, so the camera
+    /// keeps that convention's up axis at the top of the image and its
+    /// handedness from mirroring the view. Pass meshes the same convention
+    /// with `Mesh::with_axes`.
+    pub fn with_axes(mut self, axes: Axes) -> View {
+        self.axes = axes;
+        self
+    }
+
+    /// Stops following reflected and refracted rays once at most
+    /// `contribution` of their light, e.g. 0.01, would reach the camera:
+    /// what the surfaces on the way reflect or let through, times what
+    /// fog and absorbing glass let through in the brightest channel. A ray
+    /// is then followed no deeper than it can change its pixel by about
+    /// `contribution` of full brightness, so dim paths stop well short of
+    /// the maximum depth. Where surfaces both reflect and refract, rays
+    /// split at every hit, so pruning faint ones saves most of the work at
+    /// a small loss of light. With the default of 0, rays are followed up
+    /// to the maximum depth unless nothing of their light would arrive.
+    pub fn with_min_contribution(mut self, contribution: f32) -> View {
+        self.min_contribution = contribution;
+        self
+    }
+
+    /// Skips lights too far from a surface to light it by more than
+    /// `cutoff` of full light, e.g. 0.001, before casting shadow rays to
+    /// them: each light only reaches as far as the sphere its intensity
+    /// falls to `cutoff` at. Scenes with dozens of weak lights spend most of
+    /// their time on shadow rays to lights too far to matter; this skips
+    /// them at a small loss of light. The default of 0 culls no lights.
+    /// `Whitted` and `PathTracer` follow it.
+    pub fn with_light_cutoff(mut self, cutoff: f32) -> View {
+        self.light_cutoff = cutoff;
+        self
+    }
+
+    /// Splits rays at rough surfaces into at most `samples`, however many
+    /// their materials ask for with `Standard::with_glossy_samples`, e.g. 1
+    /// for quick previews. Unlimited by default.
+    pub fn with_max_glossy_samples(mut self, samples: u32) -> View {
+        self.max_glossy_samples = samples;
+        self
+    }
+
+    /// Finds the soft shadows of lights with a radius, and the occlusion of
+    /// `AmbientOcclusion`, by tracing one cone against the spheres and
+    /// unbounded planes of the scene instead of sampling many rays: fast
+    /// and noiseless, but only approximate, as for quick previews. Other
+    /// objects cast hard shadows and are sampled for occlusion as before.
+    pub fn with_cone_tracing(mut self, cone_tracing: bool) -> View {
+        self.cone_tracing = cone_tracing;
+        self
+    }
+
+    /// Lets light through transparent objects to the shadows they cast,
+    /// for the Lambert shading and highlights of the default `Whitted`
+    /// integrator: dimmed by their transparency at every surface crossed
+    /// and tinted by what they absorb inside, so a red glass ball casts a
+    /// pale red shadow. By default transparent objects block light as
+    /// opaque ones do. Neither refraction nor the shadow cache apply to
+    /// the light let through.
+    pub fn with_transparent_shadows(mut self) -> View {
+        self.transparent_shadows = true;
+        self
+    }
+
+    /// Follows light that dispersive objects bend apart, see
+    /// `Standard::with_dispersion`, at single wavelengths instead of color
+    /// channels: each sample picks a hero wavelength, spread evenly over
+    /// the pixel's samples, and the default `Whitted` integrator follows
+    /// it with three companions spaced across the visible range, while
+    /// the `PathTracer` follows one of the four. Indices of refraction
+    /// between the red, green and blue ones come from Cauchy's equation,
+    /// and the wavelengths' colors from the CIE 1931 observer, so
+    /// rainbows blend smoothly instead of in three separate images.
+    #[cfg(feature = "spectral")]
+    pub fn with_spectral(mut self) -> View {
+        self.spectral = true;
+        self
+    }
+
+    /// Makes surfaces reflect more at grazing angles, as real ones do, by
+    /// Schlick's approximation of the Fresnel equations: `specular` is
+    /// then the share reflected head on, rising to all of it along the
+    /// surface. Transparent objects split the light they let through
+    /// between reflection and refraction by their index of refraction.
+    /// Without it, reflection is the same at every angle. The caustics
+    /// photon map is unaffected.
+    pub fn with_fresnel(mut self) -> View {
+        self.fresnel = true;
+        self
+    }
+
+    /// Averages `samples` rays per pixel, spread across its area, to
+    /// smooth out jagged edges. With the default of one, each pixel traces
+    /// the single ray through its corner.
+    pub fn with_samples(mut self, samples: u32) -> View {
+        assert!(samples > 0, "a pixel needs at least one sample");
+        self.samples = samples;
+        self
+    }
+
+    /// Places the samples of `with_samples` within each pixel, and the
+    /// shadow rays of lights with a radius over them, by `sampler` instead
+    /// of the default `Uniform` random offsets.
+    pub fn with_sampler<S: Sampler + 'static>(mut self, sampler: S) -> View {
+        self.sampler = Arc::new(sampler);
+        self
+    }
+
+    /// Computes each sample's color with `integrator` instead of the
+    /// default `Whitted` ray tracer.
+    pub fn with_integrator<I: Integrator + 'static>(mut self, integrator: I) -> View {
+        self.integrator = Some(Arc::new(integrator));
+        self
+    }
+
+    /// Converts renders to 8 bits with `tonemap` instead of clipping
+    /// colors above white. `render_hdr`, `render_bracketed` and
+    /// `Checkpoint::image` are not tone mapped; use `develop` on
+    /// `Checkpoint::framebuffer` instead.
+    pub fn with_tonemap(mut self, tonemap: Tonemap) -> View {
+        self.tonemap = tonemap;
+        self
+    }
+
+    /// Treats 8-bit colors, of objects, textures, the background and the
+    /// backplate, as sRGB, as image files and color pickers give them:
+    /// they are decoded to linear light for shading, and the render is
+    /// encoded back to sRGB when converted to 8 bits. Without it, 8-bit
+    /// values are used as linear light and written as they are, which
+    /// looks too dark and contrasty. `render_hdr` stays linear.
+    pub fn with_srgb(mut self) -> View {
+        self.srgb = true;
+        self
+    }
+
+    /// Exposes renders as a camera would at exposure value `ev` at ISO 100,
+    /// so lights can keep physical intensities: each step up halves the
+    /// brightness, for brighter scenes. The default of 0, e.g. f/1 for one
+    /// second, leaves colors as they are. Applies when converting to 8
+    /// bits; `render_hdr` keeps the light as it reaches the camera.
+    pub fn with_exposure_value(mut self, ev: f32) -> View {
+        self.exposure_value = ev;
+        self
+    }
+
+    /// Sets the exposure value from a shutter open for `shutter` seconds,
+    /// at `iso` film speed, with aperture `f_number`, e.g. 1/125 s at
+    /// ISO 400 and f/8.
+    pub fn with_camera_exposure(self, shutter: f32, iso: f32, f_number: f32) -> View {
+        let ev = (f_number * f_number / shutter).log2() - (iso / 100.0).log2();
+        self.with_exposure_value(ev)
+    }
+
+    /// Rounds colors to 8 bits up or down by `dither` instead of rounding
+    /// all of them the same way, which breaks up the bands smooth
+    /// gradients such as the sky and soft shadows show into fine noise.
+    pub fn with_dither(mut self, dither: Dither) -> View {
+        self.dither = Some(dither);
+        self
+    }
+
+    /// Seeds the pseudo-random sample placement. Renders of the same view
+    /// and scene with the same seed are bit-identical; changing it gives
+    /// an independent noise pattern. 0 by default.
+    pub fn with_seed(mut self, seed: u32) -> View {
+        self.seed = seed;
+        self
+    }
+
+    /// Marks the render as frame `frame` of an animation, mixing the frame
+    /// number into the seed so each frame gets its own noise pattern
+    /// instead of noise that stays frozen while the picture moves. Frame 0
+    /// renders as without it.
+    pub fn with_frame(mut self, frame: u32) -> View {
+        self.frame = frame;
+        self
+    }
+
+    /// Keeps the noise pattern of frame 0 on every frame, e.g. for
+    /// comparing frames pixel by pixel. Off by default.
+    pub fn with_fixed_noise(mut self, fixed_noise: bool) -> View {
+        self.fixed_noise = fixed_noise;
+        self
+    }
+
+    /// The seed samples are drawn with: `seed`, mixed with the frame
+    /// number unless the noise is fixed.
+    pub(crate) fn frame_seed(&self) -> u32 {
+        if self.fixed_noise || self.frame == 0 {
+            self.seed
+        } else {
+            hash(self.seed ^ hash(self.frame))
         }
     }
+
+    /// Keeps adding rounds of `with_samples` samples to a pixel until the
+    /// standard error of its mean brightness, on the 0-255 scale, is at
+    /// most `threshold`, or it has `max_samples`. Flat regions then stop
+    /// after the first round while edges and noisy shading get more.
+    pub fn with_adaptive_sampling(mut self, threshold: f32, max_samples: u32) -> View {
+        self.adaptive = Some(Adaptive {
+            threshold,
+            max_samples,
+        });
+        self
+    }
+
+    /// Restricts `render`, `render_cancellable`, `render_with_progress`,
+    /// `render_into`, `render_cropped` and `render_checkpointed` to the
+    /// `width` by `height` rectangle whose top left corner is pixel
+    /// (`x`, `y`). Panics if it does not fit in the image.
+    pub fn set_region(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        assert!(
+            x.checked_add(width)
+                .is_some_and(|end| end <= self.image_width)
+                && y.checked_add(height)
+                    .is_some_and(|end| end <= self.image_height),
+            "region does not fit in the image"
+        );
+        self.region = Some(Region {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    /// Goes back to rendering the whole image.
+    pub fn clear_region(&mut self) {
+        self.region = None;
+    }
+
+    fn region(&self) -> Region {
+        self.region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.image_width,
+            height: self.image_height,
+        })
+    }
+
+    /// Renders the full-sized image; with a region set, pixels outside it
+    /// are left black. Malformed scenes, with NaN positions, zero radii or
+    /// no objects or lights, still render: rays that hit nothing show the
+    /// background and NaN samples are black, as counted in `Scene::warnings`.
     pub fn render(&self, scene: &Scene) -> RgbImage {
-        let mut img_buffer = RgbImage::new(self.image_width, self.image_height);
+        self.develop(&self.render_hdr(scene))
+    }
+
+    /// Renders like `render`, keeping the unclamped linear color `render`
+    /// develops, e.g. to save as HDR or develop at other exposures.
+    pub fn render_hdr(&self, scene: &Scene) -> Framebuffer {
+        self.render_full(scene, &CancelToken::new(), |_, _| {})
+    }
+
+    /// Renders the image `render_hdr` would with each of `integrators`
+    /// instead of the view's own, e.g. a path traced beauty image with a
+    /// quick ambient occlusion pass and a `Whitted` preview, all from the
+    /// same camera rays. What each ray hits first is traced once and
+    /// shared; see `Integrator::li_from_hit`. With adaptive sampling, the
+    /// first integrator decides how many samples each pixel takes.
+    pub fn render_outputs(
+        &self,
+        scene: &Scene,
+        integrators: &[&dyn Integrator],
+    ) -> Vec<Framebuffer> {
+        let mut outputs =
+            vec![Framebuffer::new(self.image_width, self.image_height); integrators.len()];
+        let region = self.region();
+        let frame = self.begin_render(scene);
+        let mut colors = vec![[0.0; 3]; integrators.len()];
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                self.render_pixel_outputs(scene, &frame, x, y, integrators, &mut colors);
+                for (output, &color) in outputs.iter_mut().zip(&colors) {
+                    output.put_pixel(x, y, color);
+                }
+            }
+        }
+        outputs
+    }
+
+    /// Everything that goes into pixel `(x, y)`: its color and what the
+    /// rays of its first sample hit, how the surfaces there are lit and
+    /// shaded and the rays they send on; see `inspect::PixelTrace`.
+    pub fn debug_pixel(&self, scene: &Scene, x: u32, y: u32) -> PixelTrace {
+        let frame = self.begin_render(scene);
+        let offset = match self.adaptive {
+            None if self.samples == 1 => [0.0; 2],
+            _ => self
+                .sampler
+                .sample(x, y, 0, self.samples, self.frame_seed()),
+        };
+        let ray = self.camera_ray(&frame, x as f32 + offset[0], y as f32 + offset[1]);
+        let mut rng = Rng::new(x, y, 0, self.frame_seed());
+        let background = self.linear(self.background_at(x, y));
+        let integrator = self.integrator.as_deref().unwrap_or(&Whitted);
+        let mut recorder = Recorder::default();
+        integrator.li(self, scene, &ray, background, &mut rng, Some(&mut recorder));
+        if recorder.is_empty() {
+            let index = recorder.ray(None, BounceKind::Camera, 0, &ray, [1.0; 3]);
+            if let Some((point, distance, object, part)) =
+                self.trace(scene, &ray, None, RayMask::CAMERA, true)
+            {
+                recorder.hit(index, self, scene, object, &ray, point, distance, part);
+            }
+        }
+        PixelTrace {
+            x,
+            y,
+            color: self.render_pixel_hdr(scene, &frame, x, y),
+            bounces: recorder.into_bounces(),
+        }
+    }
+
+    /// Renders tile by tile, checking `cancel` before each one. Once it is
+    /// cancelled the image is returned as is, with unrendered tiles black.
+    pub fn render_cancellable(&self, scene: &Scene, cancel: &CancelToken) -> RgbImage {
+        self.develop(&self.render_full(scene, cancel, |_, _| {}))
+    }
+
+    /// Renders tile by tile, calling `progress(done_tiles, total_tiles)`
+    /// after each one, e.g. to drive a progress bar.
+    pub fn render_with_progress<F: FnMut(u32, u32)>(&self, scene: &Scene, progress: F) -> RgbImage {
+        self.develop(&self.render_hdr_with_progress(scene, progress))
+    }
+
+    /// `render_hdr` with `progress` called as for `render_with_progress`.
+    pub fn render_hdr_with_progress<F: FnMut(u32, u32)>(
+        &self,
+        scene: &Scene,
+        progress: F,
+    ) -> Framebuffer {
+        self.render_full(scene, &CancelToken::new(), progress)
+    }
+
+    /// Renders the region into `img_buffer`, a full-sized image, leaving
+    /// its other pixels untouched, e.g. to patch a few pixels of an earlier
+    /// render.
+    pub fn render_into(&self, scene: &Scene, img_buffer: &mut RgbImage) {
+        assert_eq!(
+            img_buffer.dimensions(),
+            (self.image_width, self.image_height),
+            "image does not match the view size"
+        );
+        let region = self.region();
+        let cropped = self.develop(&self.render_cropped_hdr(scene));
+        for (x, y, &pixel) in cropped.enumerate_pixels() {
+            img_buffer.put_pixel(region.x + x, region.y + y, pixel);
+        }
+    }
+
+    /// Renders only the region, returning an image of just its size.
+    pub fn render_cropped(&self, scene: &Scene) -> RgbImage {
+        self.develop(&self.render_cropped_hdr(scene))
+    }
+
+    fn render_cropped_hdr(&self, scene: &Scene) -> Framebuffer {
+        let region = self.region();
+        let mut img_buffer = Framebuffer::new(region.width, region.height);
+        self.render_tiles(
+            scene,
+            &CancelToken::new(),
+            |_, _| {},
+            &mut img_buffer,
+            region.x,
+            region.y,
+        );
+        img_buffer
+    }
+
+    /// Traces the samples of `with_samples` that `checkpoint` does not
+    /// have yet for each pixel of the region, adding each to it as it is
+    /// traced. Samples are taken in passes of one per pixel, tile by tile,
+    /// checking `cancel` before each tile. A cancelled render can be saved
+    /// with `Checkpoint::save` and resumed by calling this again with the
+    /// loaded checkpoint, or continued with more samples by a view with a
+    /// higher count; `Checkpoint::image` gives the result. Resuming gives
+    /// the image an uninterrupted call would. Adaptive sampling is not
+    /// applied.
+    pub fn render_checkpointed(
+        &self,
+        scene: &Scene,
+        checkpoint: &mut Checkpoint,
+        cancel: &CancelToken,
+    ) {
+        assert_eq!(
+            checkpoint.dimensions(),
+            (self.image_width, self.image_height),
+            "checkpoint does not match the view size"
+        );
+        let region = self.region();
+        let (end_x, end_y) = (region.x + region.width, region.y + region.height);
+        let frame = self.begin_render(scene);
+        let integrator = self.integrator.as_deref().unwrap_or(&Whitted);
+        let mut color = [[0.0; 3]];
+        for pass in 0..self.samples.max(1) {
+            for tile_y in (region.y..end_y).step_by(TILE_SIZE as usize) {
+                for tile_x in (region.x..end_x).step_by(TILE_SIZE as usize) {
+                    if cancel.is_cancelled() {
+                        return;
+                    }
+                    for x in tile_x..(tile_x + TILE_SIZE).min(end_x) {
+                        for y in tile_y..(tile_y + TILE_SIZE).min(end_y) {
+                            if checkpoint.samples(x, y) != pass {
+                                continue;
+                            }
+                            // A single sample goes through the pixel's
+                            // corner, as in `render_pixel_outputs`.
+                            let offset = match self.samples {
+                                1 => [0.0; 2],
+                                _ => {
+                                    self.sampler
+                                        .sample(x, y, pass, self.samples, self.frame_seed())
+                                }
+                            };
+                            self.render_sample(
+                                scene,
+                                &frame,
+                                x,
+                                y,
+                                pass,
+                                offset,
+                                &[integrator],
+                                &mut color,
+                            );
+                            checkpoint.add_sample(x, y, color[0]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn render_full<F: FnMut(u32, u32)>(
+        &self,
+        scene: &Scene,
+        cancel: &CancelToken,
+        progress: F,
+    ) -> Framebuffer {
+        let mut img_buffer = Framebuffer::new(self.image_width, self.image_height);
+        self.render_tiles(scene, cancel, progress, &mut img_buffer, 0, 0);
+        img_buffer
+    }
+
+    /// Renders the region tile by tile, writing pixel (`x`, `y`) of the view
+    /// to (`x - offset_x`, `y - offset_y`) of `img_buffer`.
+    fn render_tiles<F: FnMut(u32, u32)>(
+        &self,
+        scene: &Scene,
+        cancel: &CancelToken,
+        mut progress: F,
+        img_buffer: &mut Framebuffer,
+        offset_x: u32,
+        offset_y: u32,
+    ) {
+        let region = self.region();
+        let (end_x, end_y) = (region.x + region.width, region.y + region.height);
+        let frame = self.begin_render(scene);
+        let total_tiles = region.width.div_ceil(TILE_SIZE) * region.height.div_ceil(TILE_SIZE);
+        let mut done_tiles = 0;
+        for tile_y in (region.y..end_y).step_by(TILE_SIZE as usize) {
+            for tile_x in (region.x..end_x).step_by(TILE_SIZE as usize) {
+                if cancel.is_cancelled() {
+                    return;
+                }
+                for x in tile_x..(tile_x + TILE_SIZE).min(end_x) {
+                    for y in tile_y..(tile_y + TILE_SIZE).min(end_y) {
+                        let color = self.render_pixel_hdr(scene, &frame, x, y);
+                        img_buffer.put_pixel(x - offset_x, y - offset_y, color);
+                    }
+                }
+                done_tiles += 1;
+                progress(done_tiles, total_tiles);
+            }
+        }
+    }
+
+    /// Renders into `buffer` as tightly packed 8-bit RGBA rows, the layout of
+    /// a browser canvas' `ImageData`. `buffer` must hold exactly
+    /// `image_width * image_height * 4` bytes.
+    pub fn render_rgba(&self, scene: &Scene, buffer: &mut [u8]) {
+        assert_eq!(
+            buffer.len(),
+            self.image_width as usize * self.image_height as usize * 4,
+            "RGBA buffer does not match the view size"
+        );
+        let frame = self.begin_render(scene);
+        for (i, pixel) in buffer.chunks_exact_mut(4).enumerate() {
+            let x = (i % self.image_width as usize) as u32;
+            let y = (i / self.image_width as usize) as u32;
+            let color = self.render_pixel(scene, &frame, x, y);
+            pixel.copy_from_slice(&[color.0[0], color.0[1], color.0[2], 255]);
+        }
+    }
+
+    /// Renders once and develops the result at each of `stops` exposure
+    /// values, e.g. `&BRACKET_STOPS`. Stop 0 matches `render`; each stop up
+    /// doubles the brightness, recovering shadows and clipping highlights.
+    pub fn render_bracketed(&self, scene: &Scene, stops: &[f32]) -> Vec<RgbImage> {
+        let hdr = self.render_hdr(scene);
+        stops
+            .iter()
+            .map(|&stop| {
+                let scale = 2f32.powf(stop);
+                let (width, height) = hdr.dimensions();
+                RgbImage::from_fn(width, height, |x, y| {
+                    let color = hdr.get_pixel(x, y).map(|c| c * scale);
+                    self.develop_pixel(color, Tonemap::Clip, x, y)
+                })
+            })
+            .collect()
+    }
+
+    /// Converts `framebuffer`, e.g. from `render_hdr`, to 8 bits as
+    /// `render` does, by the view's exposure, tone map and encoding.
+    pub fn develop(&self, framebuffer: &Framebuffer) -> RgbImage {
+        let (width, height) = framebuffer.dimensions();
+        RgbImage::from_fn(width, height, |x, y| {
+            self.develop_pixel(framebuffer.get_pixel(x, y), self.tonemap, x, y)
+        })
+    }
+
+    /// Converts the color of pixel `(x, y)`, where the dither pattern is
+    /// looked up, to 8 bits.
+    fn develop_pixel(&self, color: [f32; 3], tonemap: Tonemap, x: u32, y: u32) -> Color {
+        let exposure = 2f32.powf(-self.exposure_value);
+        let color = tonemap.apply(color.map(|c| c * exposure));
+        let color = if self.srgb {
+            color.map(srgb::encode)
+        } else {
+            color
+        };
+        match self.dither {
+            Some(dither) => Rgb(color.map(|c| dither.quantize(c, x, y))),
+            None if self.srgb => Rgb(color.map(|c| c.round() as u8)),
+            None => Rgb(color.map(|c| c as u8)),
+        }
+    }
+
+    /// `color` as linear light on the 0-255 scale, decoded if the view
+    /// treats colors as sRGB.
+    pub(crate) fn linear(&self, color: Color) -> [f32; 3] {
+        if self.srgb {
+            color.0.map(srgb::decode)
+        } else {
+            color.0.map(f32::from)
+        }
+    }
+
+    /// Renders a 2x2 sheet documenting the scene: orthographic views of the
+    /// box `extent` wide around `center` from the top, front (looking
+    /// forward, along +z by default) and right, laid out as in technical
+    /// drawings, with this view in the top right corner. The orthographic
+    /// views start their rays `extent` from `center`. Each cell is the size
+    /// of the view.
+    pub fn render_sheet(&self, scene: &Scene, center: Vecf, extent: f32) -> RgbImage {
+        let (width, height) = (self.image_width, self.image_height);
+        let mut perspective = self.clone();
+        perspective.region = None;
+        let orthographic = |direction: Vecf| {
+            let mut view = perspective.clone().with_orthographic(extent);
+            view.direction = direction;
+            view.cam_position = vec3_sub(center, vec3_scale(direction, extent));
+            view
+        };
+        let cells = [
+            (orthographic(vec3_neg(self.axes.up_vector())), 0, 0),
+            (perspective.clone(), width, 0),
+            (orthographic(self.axes.forward_vector()), 0, height),
+            (orthographic([-1.0, 0.0, 0.0]), width, height),
+        ];
+        let mut sheet = RgbImage::new(width * 2, height * 2);
+        for (view, x, y) in cells {
+            image::imageops::replace(&mut sheet, &view.render(scene), x, y);
+        }
+        sheet
+    }
+
+    /// Draws where things are instead of tracing them, for checking the
+    /// camera and object placement in milliseconds: the box around each
+    /// bounded object, colored by its index from `LIGHT_PALETTE`, and a
+    /// white cross at each light, over the background color. Unbounded
+    /// objects such as planes are left out.
+    pub fn render_layout(&self, scene: &Scene) -> RgbImage {
+        let mut image = RgbImage::from_pixel(self.image_width, self.image_height, self.background);
+        for (index, object) in scene.objects.iter().enumerate() {
+            let [min, max] = match object.bounds() {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            let color = LIGHT_PALETTE[index % LIGHT_PALETTE.len()];
+            let corner = |i: usize| {
+                [0, 1, 2].map(|axis| {
+                    if i >> axis & 1 == 0 {
+                        min[axis]
+                    } else {
+                        max[axis]
+                    }
+                })
+            };
+            for i in 0..8 {
+                for axis in (0..3).filter(|axis| i >> axis & 1 == 0) {
+                    self.draw_edge(&mut image, corner(i), corner(i | 1 << axis), color);
+                }
+            }
+        }
+        for light in &scene.lights {
+            if let Some([x, y]) = self.project(light.position) {
+                let white = Rgb([255; 3]);
+                draw_line(
+                    &mut image,
+                    [x - LAYOUT_LIGHT_SIZE, y],
+                    [x + LAYOUT_LIGHT_SIZE, y],
+                    white,
+                );
+                draw_line(
+                    &mut image,
+                    [x, y - LAYOUT_LIGHT_SIZE],
+                    [x, y + LAYOUT_LIGHT_SIZE],
+                    white,
+                );
+            }
+        }
+        image
+    }
+
+    /// Draws the part of the edge from `a` to `b` in front of the camera.
+    fn draw_edge(&self, image: &mut RgbImage, a: Vecf, b: Vecf, color: Color) {
+        let depth = |point: Vecf| {
+            vec3_dot(vec3_sub(point, self.cam_position), self.direction) - LAYOUT_NEAR
+        };
+        let (depth_a, depth_b) = (depth(a), depth(b));
+        if depth_a < 0.0 && depth_b < 0.0 {
+            return;
+        }
+        let cut = |from: Vecf, to: Vecf, t: f32| vec3_add(from, vec3_scale(vec3_sub(to, from), t));
+        let t = depth_a / (depth_a - depth_b);
+        let a = if depth_a < 0.0 { cut(a, b, t) } else { a };
+        let b = if depth_b < 0.0 { cut(a, b, t) } else { b };
+        if let (Some(a), Some(b)) = (self.project(a), self.project(b)) {
+            draw_line(image, a, b, color);
+        }
+    }
+
+    /// Renders in successively finer passes, starting with one ray per
+    /// `PROGRESSIVE_START_BLOCK`² block of pixels and halving the block size
+    /// each pass. The last image yielded equals the output of `render`.
+    pub fn render_progressive<'a>(&'a self, scene: &'a Scene) -> Progressive<'a> {
+        Progressive {
+            view: self,
+            scene,
+            frame: self.begin_render(scene),
+            block: PROGRESSIVE_START_BLOCK,
+            img_buffer: RgbImage::new(self.image_width, self.image_height),
+        }
+    }
+
+    /// Readies `scene` for a render with this view and sets up its camera.
+    pub(crate) fn begin_render(&self, scene: &Scene) -> CameraFrame {
+        if let Some(cache) = &scene.shadow_cache {
+            cache.revalidate(scene, self.seed, self.shadow_bias);
+        }
+        self.camera_frame()
+    }
+
+    pub(crate) fn camera_frame(&self) -> CameraFrame {
         let img_height = self.image_height as f32;
         let img_width = self.image_width as f32;
-        let cam_right = vec3_normalized(vec3_cross([0.0, 1.0, 0.0], self.direction));
-        let cam_up = vec3_normalized(vec3_cross(cam_right, self.direction));
-        let cam_half_width = (self.fov_rad / 2.0).tan() as f32;
+        // The same turn is the other way round in right-handed worlds.
+        let cross = |a: Vecf, b: Vecf| match self.axes.handedness {
+            Handedness::Left => vec3_cross(a, b),
+            Handedness::Right => vec3_cross(b, a),
+        };
+        let mut cam_right = cross(self.axes.up_vector(), self.direction);
+        if vec3_len(cam_right) < 1e-6 {
+            // Looking straight up or down, forward is at the top of the image.
+            cam_right = cross(self.axes.forward_vector(), self.direction);
+        }
+        let cam_right = vec3_normalized(cam_right);
+        let cam_up = vec3_normalized(cross(cam_right, self.direction));
+        let cam_half_width = match self.orthographic {
+            Some(width) => width / 2.0,
+            None => (self.fov_rad / 2.0).tan(),
+        };
         let cam_half_height = cam_half_width * (img_height / img_width);
-        let pixel_width = cam_half_width * 2.0 / img_width;
-        let pixel_height = cam_half_height * 2.0 / img_height;
-
-        for x in 0..self.image_width {
-            for y in 0..self.image_height {
-                let vec_x_pixel = vec3_scale(cam_right, pixel_width * x as f32 - cam_half_width);
-                let vec_y_pixel = vec3_scale(cam_up, pixel_height * y as f32 - cam_half_height);
-                let vec_translate = vec3_add(vec_x_pixel, vec_y_pixel);
-                let mut ray = Ray::new(
-                    self.cam_position,
-                    vec3_normalized(vec3_add(self.direction, vec_translate)),
-                );
-                let mut pixel_color: [f32; 3] = [0.0; 3];
-                let mut depth = 0;
-                let mut reflection_coef = 1.0;
-                while depth < self.max_depth && reflection_coef > 0.0 {
-                    if !self.color_trace(scene, &mut reflection_coef, &mut ray, &mut pixel_color) {
-                        break;
-                    }
-                    depth += 1;
+        CameraFrame {
+            cam_right,
+            cam_up,
+            cam_half_width,
+            cam_half_height,
+            pixel_width: cam_half_width * 2.0 / img_width,
+            pixel_height: cam_half_height * 2.0 / img_height,
+        }
+    }
+
+    /// The primary ray through image position `(x, y)`, in pixels from the
+    /// top left corner.
+    fn camera_ray(&self, frame: &CameraFrame, x: f32, y: f32) -> Ray {
+        let vec_x_pixel = vec3_scale(
+            frame.cam_right,
+            frame.pixel_width * x - frame.cam_half_width,
+        );
+        let vec_y_pixel = vec3_scale(frame.cam_up, frame.pixel_height * y - frame.cam_half_height);
+        let vec_translate = vec3_add(vec_x_pixel, vec_y_pixel);
+        if self.orthographic.is_some() {
+            return Ray::new(vec3_add(self.cam_position, vec_translate), self.direction);
+        }
+        Ray::new(
+            self.cam_position,
+            vec3_normalized(vec3_add(self.direction, vec_translate)),
+        )
+    }
+
+    /// Image position, in pixels from the top left corner, that `point`
+    /// appears at; `None` if it is behind the camera. The position may lie
+    /// outside the image.
+    pub fn project(&self, point: Vecf) -> Option<[f32; 2]> {
+        let frame = self.camera_frame();
+        let to_point = vec3_sub(point, self.cam_position);
+        let forward = vec3_dot(to_point, self.direction);
+        if forward <= 0.0 {
+            return None;
+        }
+        let scale = if self.orthographic.is_some() {
+            1.0
+        } else {
+            forward
+        };
+        let right = vec3_dot(to_point, frame.cam_right) / scale;
+        let up = vec3_dot(to_point, frame.cam_up) / scale;
+        Some([
+            (right + frame.cam_half_width) / frame.pixel_width,
+            (up + frame.cam_half_height) / frame.pixel_height,
+        ])
+    }
+
+    pub(crate) fn render_pixel(&self, scene: &Scene, frame: &CameraFrame, x: u32, y: u32) -> Color {
+        let color = self.render_pixel_hdr(scene, frame, x, y);
+        self.develop_pixel(color, self.tonemap, x, y)
+    }
+
+    /// Pixel value before quantizing: on the 0-255 scale of `render_pixel`,
+    /// but unclamped, so overexposed highlights keep their detail.
+    fn render_pixel_hdr(&self, scene: &Scene, frame: &CameraFrame, x: u32, y: u32) -> [f32; 3] {
+        let integrator = self.integrator.as_deref().unwrap_or(&Whitted);
+        let mut color = [[0.0; 3]];
+        self.render_pixel_outputs(scene, frame, x, y, &[integrator], &mut color);
+        color[0]
+    }
+
+    /// `render_pixel_hdr` of each of `integrators` into `colors`, from the
+    /// same samples. The first integrator decides when adaptive sampling
+    /// stops.
+    fn render_pixel_outputs(
+        &self,
+        scene: &Scene,
+        frame: &CameraFrame,
+        x: u32,
+        y: u32,
+        integrators: &[&dyn Integrator],
+        colors: &mut [[f32; 3]],
+    ) {
+        let (threshold, max_samples) = match self.adaptive {
+            Some(adaptive) => (adaptive.threshold, adaptive.max_samples.max(self.samples)),
+            None if self.samples == 1 => {
+                self.render_sample(scene, frame, x, y, 0, [0.0; 2], integrators, colors);
+                return;
+            }
+            None => (0.0, self.samples),
+        };
+        let mut sums = vec![[0.0; 3]; integrators.len()];
+        let mut sample = vec![[0.0; 3]; integrators.len()];
+        // Running mean and sum of squared deviations of the luminance.
+        let (mut mean, mut squares) = (0.0, 0.0);
+        let mut count = 0;
+        while count < max_samples {
+            let offset = self
+                .sampler
+                .sample(x, y, count, self.samples, self.frame_seed());
+            self.render_sample(scene, frame, x, y, count, offset, integrators, &mut sample);
+            for (sum, color) in sums.iter_mut().zip(&sample) {
+                for (s, c) in sum.iter_mut().zip(color) {
+                    *s += c;
                 }
-                let mut color = [0; 3];
-                for c in 0..color.len() {
-                    color[c] = (pixel_color[c] * 255.0) as u8;
+            }
+            count += 1;
+            let color = sample[0];
+            let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+            let delta = luminance - mean;
+            mean += delta / count as f32;
+            squares += delta * (luminance - mean);
+            if count % self.samples == 0 && count >= ADAPTIVE_MIN_SAMPLES {
+                let std_error = (squares / ((count - 1) * count) as f32).sqrt();
+                if std_error <= threshold {
+                    break;
                 }
-                img_buffer.put_pixel(x, y, Rgb(color));
             }
         }
-        img_buffer
+        for (color, sum) in colors.iter_mut().zip(sums) {
+            *color = sum.map(|c| c / count as f32);
+        }
     }
 
-    fn color_trace(
+    /// Sample `index` of pixel `(x, y)`, `offset` pixels right and down
+    /// from its corner, of each of `integrators` into `colors`. Several
+    /// integrators share the camera ray's first hit.
+    #[allow(clippy::too_many_arguments)]
+    fn render_sample(
         &self,
         scene: &Scene,
-        reflection_coef: &mut f32,
-        ray: &mut Ray,
-        current_color: &mut [f32; 3],
-    ) -> bool {
-        if let Some((hit_point, dist, hit_object)) = self.trace(scene, &ray) {
-            let object_color = hit_object.get_color().0;
-            let light = self.lambert_shade(scene, hit_object.as_ref(), hit_point);
-            *ray = hit_object.reflect_ray(ray, hit_point);
-
-            for i in 0..current_color.len() {
-                current_color[i] += (object_color[i] as f32 / 255.0)
-                    * light
-                    * hit_object.get_lambert()
-                    * *reflection_coef;
-            }
-            *reflection_coef *= hit_object.get_specular();
-            true
+        frame: &CameraFrame,
+        x: u32,
+        y: u32,
+        index: u32,
+        offset: [f32; 2],
+        integrators: &[&dyn Integrator],
+        colors: &mut [[f32; 3]],
+    ) {
+        let ray = self.camera_ray(frame, x as f32 + offset[0], y as f32 + offset[1]);
+        let background = self.linear(self.background_at(x, y));
+        let hit = match integrators.len() {
+            1 => None,
+            _ => Some(self.trace(scene, &ray, None, RayMask::CAMERA, true)),
+        };
+        for (integrator, color) in integrators.iter().zip(colors.iter_mut()) {
+            // Each integrator draws the random numbers it would alone.
+            let mut rng = Rng::new(x, y, index, self.frame_seed());
+            let sample = match hit {
+                Some(hit) => integrator.li_from_hit(self, scene, &ray, hit, background, &mut rng),
+                None => integrator.li(self, scene, &ray, background, &mut rng, None),
+            };
+            *color = if sample.iter().all(|c| c.is_finite()) {
+                sample
+            } else {
+                scene.warnings.record(RenderWarning::NanSample);
+                [0.0; 3]
+            };
+        }
+    }
+
+    /// `surface`, the light leaving the end of `segment` weighted by `coef`,
+    /// dimmed by `medium_coef` and seen through `distance` of the scene's
+    /// media, plus the light they scatter in along the way. Dims
+    /// `medium_coef` by what they absorb.
+    pub(crate) fn medium_segment(
+        &self,
+        scene: &Scene,
+        segment: &Ray,
+        distance: f32,
+        coef: f32,
+        medium_coef: &mut [f32; 3],
+        surface: [f32; 3],
+    ) -> [f32; 3] {
+        let scattering = match scene.scattering(segment, distance) {
+            Some(scattering) => scattering,
+            None => return [0, 1, 2].map(|i| medium_coef[i] * surface[i]),
+        };
+        let mut seen = [0.0; 3];
+        for i in 0..3 {
+            seen[i] = medium_coef[i]
+                * (scattering.transmittance[i] * surface[i] + coef * scattering.inscatter[i]);
+            medium_coef[i] *= scattering.transmittance[i];
+        }
+        seen
+    }
+
+    /// The background color, or the backplate pixel under `(x, y)`.
+    fn background_at(&self, x: u32, y: u32) -> Color {
+        match &self.backplate {
+            Some(image) if image.width() > 0 && image.height() > 0 => {
+                let (width, height) = image.dimensions();
+                let px = x as u64 * width as u64 / self.image_width as u64;
+                let py = y as u64 * height as u64 / self.image_height as u64;
+                *image.get_pixel(px as u32, py as u32)
+            }
+            _ => self.background,
+        }
+    }
+
+    /// Light `hit_object` sends back along `ray` from `hit_point` on `part`
+    /// by Lambert shading and highlights, weighted by `coef`; reflection
+    /// and refraction are left to the integrator.
+    pub(crate) fn color_trace(
+        &self,
+        scene: &Scene,
+        hit_point: Vecf,
+        hit_object: &dyn Object,
+        part: Option<Part>,
+        ray: &Ray,
+        coef: f32,
+    ) -> [f32; 3] {
+        let hit = self.hit_at(scene, hit_object, ray, hit_point, part);
+        let object_color = self.linear(hit_object.get_color_at(&hit, scene));
+        let caustics_only = scene
+            .caustics
+            .as_ref()
+            .is_some_and(|caustics| caustics.caustics_only());
+        let mut light = if caustics_only {
+            [0.0; 3]
         } else {
-            false
+            self.lambert_shade(scene, hit_object, hit_point, part)
+        };
+        if let Some(caustics) = &scene.caustics {
+            let irradiance = caustics.irradiance(hit_point, hit.normal);
+            light = light.map(|l| (l + irradiance).min(1.0));
         }
+        let base = object_color.map(|c| c / 255.0);
+        let highlight = if caustics_only {
+            [0.0; 3]
+        } else {
+            self.highlight(scene, hit_object, hit_point, part, ray, base)
+        };
+        let lambert = hit_object.get_lambert() * (1.0 - self.coat(hit_object, ray, hit_point));
+        let mut color = [0.0; 3];
+        for i in 0..3 {
+            color[i] = (base[i] * light[i] * lambert + highlight[i]) * coef;
+        }
+        color
     }
 
-    fn trace(&self, scene: &Scene, ray: &Ray) -> Option<(Vecf, f32, Box<dyn Object>)> {
-        let mut min_dist = f32::INFINITY;
-        let mut closest_object: Option<(Vecf, f32, Box<dyn Object>)> = None;
-        for object in &scene.objects {
-            let (distance, hit_point) = object.intersect(&ray);
-            if distance < min_dist && distance > 0.0 {
-                min_dist = distance;
-                closest_object = Some((hit_point, min_dist, object.clone())); //OK??????
+    /// How much of the light arriving along `ray` at `point` `object`
+    /// reflects and refracts, by its specular and transparency coefficients
+    /// and, with `with_fresnel` or for dielectrics, the angle of incidence.
+    /// `outside` is passed on to `Object::refract_ray`. A clear coat takes
+    /// its share off the top.
+    pub(crate) fn split(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        part: Option<Part>,
+        outside: f32,
+    ) -> Split {
+        let coat = self.coat(object, ray, point);
+        let specular = object.get_specular() * (1.0 - coat);
+        let transparency = object.get_transparency() * (1.0 - coat);
+        let refracted = if transparency > 0.0 {
+            Some(object.refract_ray(ray, point, outside))
+        } else {
+            None
+        };
+        if !self.fresnel && !object.is_dielectric() {
+            return Split {
+                reflect: specular,
+                refract: transparency,
+                coat,
+                refracted,
+            };
+        }
+        let normal = normal_on(object, point, ray.direction, part);
+        let cos_in = vec3_dot(ray.direction, normal);
+        let reflect = if specular > 0.0 {
+            schlick(cos_in.abs(), specular)
+        } else {
+            specular
+        };
+        let reflectance = match &refracted {
+            Some(refracted) => {
+                let cos_out = vec3_dot(refracted.direction, normal);
+                if cos_in * cos_out < 0.0 {
+                    // Total internal reflection.
+                    1.0
+                } else {
+                    // The angle on the side of the thinner material counts.
+                    let ior = object.get_ior();
+                    let f0 = ((ior - outside) / (ior + outside)).powi(2);
+                    schlick(cos_in.abs().min(cos_out.abs()), f0)
+                }
             }
+            None => 0.0,
+        };
+        Split {
+            reflect: reflect + transparency * reflectance,
+            refract: transparency * (1.0 - reflectance),
+            coat,
+            refracted,
         }
-        closest_object
     }
 
-    fn all_intersects(&self, scene: &Scene, ray: &Ray) -> Vec<f32> {
-        let mut intersects = Vec::new();
-        for object in &scene.objects {
-            let (distance, hit_point) = object.intersect(&ray);
-            if distance > 0.0 && distance != f32::INFINITY {
-                intersects.push(distance);
+    /// Share of the light arriving along `ray` at `point` the clear coat
+    /// of `object` mirrors; 0 for objects without one.
+    fn coat(&self, object: &dyn Object, ray: &Ray, point: Vecf) -> f32 {
+        match object.get_clearcoat() {
+            Some(coat) => {
+                let normal = object.normal_to(&Ray::new(point, ray.direction));
+                coat.reflectance(vec3_dot(ray.direction, normal).abs())
+            }
+            None => 0.0,
+        }
+    }
+
+    /// The ray light mirrored by the clear coat of `object` towards `ray`'s
+    /// origin arrives along, blurred by the coat's own roughness rather
+    /// than the surface's.
+    pub(crate) fn coat_reflect(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        rng: &mut Rng,
+    ) -> Ray {
+        let mirrored = object.reflect_ray(ray, point);
+        match object.get_clearcoat() {
+            Some(coat) if coat.roughness > 0.0 => {
+                self.microfacet_reflect(object, ray, point, mirrored, coat.pbr(), rng)
+            }
+            _ => mirrored,
+        }
+    }
+
+    /// The ray light reflected by `object` at `point` towards `ray`'s origin
+    /// arrives along, picked at random around the mirror direction for rough
+    /// objects, and the share of each color channel the reflection keeps:
+    /// the object's color for metals, the Fresnel reflectance for PBR
+    /// materials, all of it otherwise.
+    pub(crate) fn reflect(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        part: Option<Part>,
+        rng: &mut Rng,
+    ) -> (Ray, [f32; 3]) {
+        let pbr = object.get_pbr();
+        let mirrored = match part {
+            // Mirrored off the part hit rather than one found again from
+            // the point.
+            Some(_) => {
+                let normal = normal_on(object, point, ray.direction, part);
+                let reflection = 2.0 * vec3_dot(ray.direction, normal);
+                Ray::new(
+                    point,
+                    vec3_sub(ray.direction, vec3_scale(normal, reflection)),
+                )
+            }
+            None => object.reflect_ray(ray, point),
+        };
+        let reflected = match pbr {
+            Some(pbr) if pbr.roughness > 0.0 => {
+                self.microfacet_reflect(object, ray, point, mirrored, pbr, rng)
+            }
+            _ => self.roughen(object, ray, point, mirrored, rng),
+        };
+        let tint = if pbr.is_some() || object.is_metal() {
+            let hit = self.hit_at(scene, object, ray, point, part);
+            let base = self
+                .linear(object.get_color_at(&hit, scene))
+                .map(|c| c / 255.0);
+            match pbr {
+                Some(pbr) => {
+                    let cos = vec3_dot(ray.direction, hit.normal).abs();
+                    pbr.f0(base).map(|f0| schlick(cos, f0))
+                }
+                None => base,
+            }
+        } else {
+            [1.0; 3]
+        };
+        (reflected, tint)
+    }
+
+    /// Rays a ray meeting `object` splits into: its glossy samples, capped
+    /// by the view, where it is rough, and 1 elsewhere.
+    pub(crate) fn glossy_samples(&self, object: &dyn Object) -> u32 {
+        if object.get_roughness() > 0.0 {
+            object
+                .get_glossy_samples()
+                .min(self.max_glossy_samples)
+                .max(1)
+        } else {
+            1
+        }
+    }
+
+    /// The rays `refracted`, the ray `ray` continues as through `object`
+    /// at `point`, splits into where `object` is dispersive, each bent for
+    /// the band of light it carries alone: the ray's own `band` if it
+    /// already carries one, otherwise the color channel whose turn the
+    /// sample is, see `Rng::stratum`, or with `with_spectral` a hero
+    /// wavelength and its companions. Each comes with its band and the
+    /// weight of each channel of the light along it, which makes up for
+    /// the light not followed. Other objects pass `refracted` on as it is.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn disperse(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        outside: f32,
+        refracted: Ray,
+        band: Option<Band>,
+        rng: &mut Rng,
+    ) -> Vec<(Ray, Option<Band>, [f32; 3])> {
+        let iors = match object.get_dispersion() {
+            Some(iors) => iors,
+            None => return vec![(refracted, band, [1.0; 3])],
+        };
+        let bent = |band: Band| object.disperse_ray(ray, point, outside, band.ior(iors));
+        if let Some(band) = band {
+            return vec![(bent(band), Some(band), [1.0; 3])];
+        }
+        #[cfg(feature = "spectral")]
+        if self.spectral {
+            // The companions repeat every quarter of the range, so heroes
+            // only need the first quarter, spread over the pixel's samples.
+            let turn = rng.stratum(self.samples) as f32 + rng.next_f32();
+            let hero = turn / self.samples as f32 / COMPANIONS as f32;
+            let share = 1.0 / COMPANIONS as f32;
+            return spectral::hero_wavelengths(hero)
+                .iter()
+                .map(|&wavelength| {
+                    let band = Band::Wavelength(wavelength);
+                    let weight = spectral::rgb(wavelength).map(|c| c * share);
+                    (bent(band), Some(band), weight)
+                })
+                .collect();
+        }
+        let channel = rng.stratum(3) as usize;
+        let mut weight = [0.0; 3];
+        weight[channel] = 3.0;
+        let band = Band::Channel(channel);
+        vec![(bent(band), Some(band), weight)]
+    }
+
+    /// `scattered`, a ray reflected or refracted by `object` where `ray`
+    /// meets it at `point`, turned in a random direction around its own
+    /// for rough objects.
+    pub(crate) fn roughen(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        mut scattered: Ray,
+        rng: &mut Rng,
+    ) -> Ray {
+        let roughness = object.get_roughness();
+        if roughness > 0.0 {
+            // Roughness 1 spreads rays over the whole hemisphere,
+            // cosine-weighted around their direction.
+            let r = roughness.min(1.0) * rng.next_f32().sqrt();
+            let phi = 2.0 * PI * rng.next_f32();
+            let direction = disk_to_hemisphere(scattered.direction, r, phi);
+            // Directions across the surface fall back to the ray's own.
+            let normal = object.normal_to(&Ray::new(point, ray.direction));
+            if vec3_dot(direction, normal) * vec3_dot(scattered.direction, normal) > 0.0 {
+                scattered.direction = vec3_normalized(direction);
+            }
+        }
+        scattered
+    }
+
+    /// `mirrored`, the mirror reflection of `ray` where it meets the PBR
+    /// object `object` at `point`, turned to reflect off a microfacet
+    /// picked from the GGX distribution its `pbr` highlights are shaded
+    /// with, so rough reflections spread as wide as the highlights.
+    fn microfacet_reflect(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        mut mirrored: Ray,
+        pbr: Pbr,
+        rng: &mut Rng,
+    ) -> Ray {
+        let normal = object.normal_to(&Ray::new(point, ray.direction));
+        let facing = if vec3_dot(normal, ray.direction) > 0.0 {
+            vec3_neg(normal)
+        } else {
+            normal
+        };
+        let tangent = pbr
+            .anisotropy
+            .and_then(|_| object.hit_at(ray, point).tangent);
+        let (u, v) = (rng.next_f32(), rng.next_f32());
+        let microfacet = pbr.sample_microfacet(facing, tangent, u, v);
+        let direction = vec3_sub(
+            ray.direction,
+            vec3_scale(microfacet, 2.0 * vec3_dot(ray.direction, microfacet)),
+        );
+        // Directions into the surface fall back to the mirror's own.
+        if vec3_dot(direction, facing) > 0.0 {
+            mirrored.direction = vec3_normalized(direction);
+        }
+        mirrored
+    }
+
+    /// What the material of `object` makes of `ray` meeting it at `point`,
+    /// see `Material::scatter`, with continued rays moved off the surface
+    /// by the shadow bias, and the light it gives off there, see
+    /// `Material::emitted`. Objects without a material get
+    /// `Scatter::Standard` and give off none.
+    pub(crate) fn scatter(
+        &self,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        rng: &mut Rng,
+    ) -> (Scatter, [f32; 3]) {
+        let material = match object.material() {
+            Some(material) => material,
+            None => return (Scatter::Standard, [0.0; 3]),
+        };
+        // The shading that follows records degenerate normals.
+        let mut hit = object.hit_at(ray, point);
+        repair_normal(&mut hit, ray);
+        let scatter = match material.scatter(ray, &hit, rng) {
+            Scatter::Continue(next, share) => {
+                let side = if vec3_dot(next.direction, hit.normal) < 0.0 {
+                    -self.shadow_bias
+                } else {
+                    self.shadow_bias
+                };
+                let origin = vec3_add(next.origin, vec3_scale(hit.normal, side));
+                Scatter::Continue(Ray::new(origin, next.direction), share)
             }
+            other => other,
+        };
+        (scatter, material.emitted(&hit))
+    }
+
+    /// `object.hit_on`, with a normal that is zero or not finite replaced
+    /// by one facing back along `ray` and recorded in the scene's warnings.
+    pub(crate) fn hit_at(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        ray: &Ray,
+        point: Vecf,
+        part: Option<Part>,
+    ) -> Hit {
+        let mut hit = object.hit_on(ray, point, part);
+        if repair_normal(&mut hit, ray) {
+            scene.warnings.record(RenderWarning::DegenerateNormal);
         }
-        intersects.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        intersects
+        hit
+    }
+
+    /// Closest object visible to `mask` that `ray` hits, and the part of
+    /// it, passing the cut-away parts of cutouts at random by their
+    /// coverage. A ray leaving a surface passes over the part `from` it
+    /// starts on, see `Scene::raycast_from`.
+    pub(crate) fn trace<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        from: Option<(&dyn Object, Part)>,
+        mask: RayMask,
+        shadow_catchers: bool,
+    ) -> Option<(Vecf, f32, &'a dyn Object, Option<Part>)> {
+        self.trace_cutouts(scene, ray, from, mask, shadow_catchers, false)
+    }
+
+    /// Like `trace`, but stopping at cutouts in `AlphaMode::Blend` wherever
+    /// they are hit, for integrators that blend through them.
+    pub(crate) fn trace_layers<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        from: Option<(&dyn Object, Part)>,
+        mask: RayMask,
+        shadow_catchers: bool,
+    ) -> Option<(Vecf, f32, &'a dyn Object, Option<Part>)> {
+        self.trace_cutouts(scene, ray, from, mask, shadow_catchers, true)
     }
 
-    fn lambert_shade(&self, scene: &Scene, object: &dyn Object, point: Vecf) -> f32 {
-        let mut lambert_amount = 0.0;
+    fn trace_cutouts<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        from: Option<(&dyn Object, Part)>,
+        mask: RayMask,
+        shadow_catchers: bool,
+        blend: bool,
+    ) -> Option<(Vecf, f32, &'a dyn Object, Option<Part>)> {
+        let mut from: Option<(&dyn Object, Part)> = from;
+        let mut ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction,
+        };
+        let mut travelled = 0.0;
+        for _ in 0..MAX_CUTOUT_LAYERS {
+            let hit = scene.raycast_from(&ray, from, |_, object| {
+                object.visibility().intersects(mask)
+                    && (shadow_catchers || !object.is_shadow_catcher())
+            })?;
+            let object = scene.objects[hit.index].as_ref();
+            let solid = match object.get_cutout() {
+                Some(cutout) if !blend || cutout.mode == AlphaMode::Stochastic => {
+                    self.covers(object, &ray, hit.point)
+                }
+                _ => true,
+            };
+            if solid {
+                return Some((hit.point, travelled + hit.distance, object, hit.part));
+            }
+            from = hit.part.map(|part| (object, part));
+            travelled += hit.distance + self.shadow_bias;
+            ray = Ray::new(
+                vec3_add(hit.point, vec3_scale(ray.direction, self.shadow_bias)),
+                ray.direction,
+            );
+        }
+        None
+    }
+
+    /// Whether `ray` stops at the cutout `object` at `point` rather than
+    /// passing it, picked at random by the coverage there. The pick depends
+    /// on the point and the ray's direction, so the many rays of a pixel's
+    /// samples or of soft shadows pick differently.
+    fn covers(&self, object: &dyn Object, ray: &Ray, point: Vecf) -> bool {
+        let cutout = match object.get_cutout() {
+            Some(cutout) => cutout,
+            None => return true,
+        };
+        let coverage = cutout.mask.coverage(object.hit_at(ray, point).uv);
+        let bits = point
+            .iter()
+            .chain(ray.direction.iter())
+            .fold(self.frame_seed(), |bits, c| hash(bits ^ c.to_bits()));
+        unit(bits) < coverage
+    }
+
+    fn lambert_shade(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+    ) -> [f32; 3] {
+        self.light_amount(scene, object, point, part, true)
+            .map(|light| light.min(1.0))
+    }
+
+    /// Highlights the lights leave at `point` on `object`, seen along `ray`:
+    /// Cook-Torrance ones tinted by the `base` color for PBR materials,
+    /// otherwise white Blinn-Phong ones weighted by the specular
+    /// coefficient and scaled so that, like Lambert shading, a highlight
+    /// sends back about as much light as falls on the surface, whatever
+    /// its shininess. A clear coat dims them and adds its own.
+    fn highlight(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        ray: &Ray,
+        base: [f32; 3],
+    ) -> [f32; 3] {
+        let shininess = object.get_shininess();
+        let pbr = object.get_pbr();
+        let clearcoat = object.get_clearcoat();
+        let shiny = shininess > 0.0 || pbr.is_some();
+        if !shiny && clearcoat.is_none() {
+            return [0.0; 3];
+        }
+        let through = 1.0 - self.coat(object, ray, point);
+        let normal = normal_on(object, point, ray.direction, part);
+        let normal = if vec3_dot(normal, ray.direction) > 0.0 {
+            vec3_neg(normal)
+        } else {
+            normal
+        };
+        // Only anisotropic materials need the tangent.
+        let tangent = pbr
+            .and_then(|pbr| pbr.anisotropy)
+            .and_then(|_| object.hit_on(ray, point, part).tangent);
+        let mut highlight = [0.0; 3];
         for light in &scene.lights {
-            let dist_to_light = vec3_sub(light.position, point);
-            let dir_to_light = vec3_normalized(dist_to_light);
-            let dist_to_light = vec3_len(dist_to_light);
-            let shadow_point = vec3_add(point, vec3_scale(dir_to_light, self.shadow_bias));
-            let mut blocked = false;
-            for intersect in self.all_intersects(scene, &Ray::new(shadow_point, dir_to_light)) {
-                if intersect < dist_to_light {
-                    blocked = true;
-                    break;
+            if self.out_of_reach(scene, light, point) {
+                continue;
+            }
+            let to_light = vec3_sub(light.position, point);
+            let dir_to_light = vec3_normalized(to_light);
+            let cos = vec3_dot(dir_to_light, normal);
+            if cos <= 0.0 {
+                continue;
+            }
+            let visibility = self.light_visibility(scene, object, point, part, light, dir_to_light);
+            let intensity = light.intensity * scene.intensity_scale();
+            let arriving = cos * (intensity / (4.0 * PI * vec3_len(to_light).powi(2))) * visibility;
+            let filter = if self.transparent_shadows && visibility > 0.0 {
+                self.transparent_filter(scene, object, point, light)
+            } else {
+                [1.0; 3]
+            };
+            // Lambert shading leaves out the 1 / pi of a diffuse surface's
+            // reflectance, so the specular one is scaled up to match.
+            let mut reflected = match pbr {
+                Some(pbr) => pbr
+                    .specular_along(base, normal, tangent, dir_to_light, vec3_neg(ray.direction))
+                    .map(|s| s * PI),
+                None if shiny => {
+                    let halfway = vec3_normalized(vec3_sub(dir_to_light, ray.direction));
+                    let phong = (shininess + 8.0) / 8.0
+                        * vec3_dot(normal, halfway).max(0.0).powf(shininess)
+                        * object.get_specular();
+                    [phong; 3]
+                }
+                None => [0.0; 3],
+            };
+            if let Some(coat) = clearcoat {
+                let [coated, ..] =
+                    coat.pbr()
+                        .specular(base, normal, dir_to_light, vec3_neg(ray.direction));
+                for r in &mut reflected {
+                    *r = *r * through + coated * PI * coat.strength;
                 }
             }
+            for ((h, r), f) in highlight.iter_mut().zip(reflected).zip(filter) {
+                *h += r * arriving * f;
+            }
+        }
+        highlight.map(|h| h.min(1.0))
+    }
+
+    /// Unclamped light reaching `point` on `part` in each channel, with or
+    /// without shadows.
+    pub(crate) fn light_amount(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        shadows: bool,
+    ) -> [f32; 3] {
+        let mut amount = [0.0; 3];
+        for light in &scene.lights {
+            let contribution = self.light_contribution(scene, object, point, part, light, shadows);
+            for (a, c) in amount.iter_mut().zip(contribution) {
+                *a += c;
+            }
+        }
+        amount
+    }
+
+    /// Light reaching `point` on `part` from `light` alone, in each
+    /// channel.
+    pub(crate) fn light_contribution(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        light: &Light,
+        shadows: bool,
+    ) -> [f32; 3] {
+        if self.out_of_reach(scene, light, point) {
+            return [0.0; 3];
+        }
+        let dist_to_light = vec3_sub(light.position, point);
+        let dir_to_light = vec3_normalized(dist_to_light);
+        let dist_to_light = vec3_len(dist_to_light);
+        let visibility = if shadows {
+            self.light_visibility(scene, object, point, part, light, dir_to_light)
+        } else {
+            1.0
+        };
+        if visibility == 0.0 {
+            return [0.0; 3];
+        }
+        let contribution = vec3_dot(
+            dir_to_light,
+            normal_on(object, point, vec3_neg(dir_to_light), part),
+        );
+        if contribution <= 0.0 {
+            return [0.0; 3];
+        }
+        let intensity = light.intensity * scene.intensity_scale();
+        let arriving = contribution * (intensity / (4.0 * PI * dist_to_light.powi(2))) * visibility;
+        if shadows && self.transparent_shadows {
+            self.transparent_filter(scene, object, point, light)
+                .map(|f| arriving * f)
+        } else {
+            [arriving; 3]
+        }
+    }
+
+    /// Whether `point` lies outside the sphere within which `light` lights
+    /// surfaces by more than the cutoff of `with_light_cutoff`.
+    pub(crate) fn out_of_reach(&self, scene: &Scene, light: &Light, point: Vecf) -> bool {
+        if self.light_cutoff <= 0.0 {
+            return false;
+        }
+        let to_light = vec3_sub(light.position, point);
+        let reach_squared =
+            light.intensity * scene.intensity_scale() / (4.0 * PI * self.light_cutoff);
+        vec3_dot(to_light, to_light) > reach_squared
+    }
+
+    /// Share of `light` visible from `point`, from the scene's shadow cache
+    /// if it has one. Cone traced shadows are quick enough to skip it.
+    fn light_visibility(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        light: &Light,
+        dir_to_light: Vecf,
+    ) -> f32 {
+        match &scene.shadow_cache {
+            Some(cache) if !self.cone_tracing => cache.visibility(object, light, point, || {
+                self.trace_light_visibility(scene, object, point, part, light, dir_to_light)
+            }),
+            _ => self.trace_light_visibility(scene, object, point, part, light, dir_to_light),
+        }
+    }
+
+    /// Share of `light` visible from `point`. Lights with a radius are
+    /// sampled over the disk they show towards `point` by the view's
+    /// sampler, seeded differently for every point.
+    fn trace_light_visibility(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        light: &Light,
+        dir_to_light: Vecf,
+    ) -> f32 {
+        if self.cone_tracing && light.radius > 0.0 {
+            return self.cone_visibility(scene, object, point, part, light);
+        }
+        if light.radius <= 0.0 || light.shadow_samples <= 1 {
+            return if self.blocked_by(
+                scene,
+                point,
+                light.position,
+                Some(object),
+                part,
+                light.shadow_mask,
+                |caster| self.casts_shadow(caster),
+            ) {
+                0.0
+            } else {
+                1.0
+            };
+        }
+        let (tangent, bitangent) = tangent_frame(dir_to_light);
+        let [px, py, pz] = point.map(f32::to_bits);
+        let mut visible = 0;
+        for i in 0..light.shadow_samples {
+            let [u, v] = self.sampler.sample(
+                px ^ pz.rotate_left(16),
+                py,
+                i,
+                light.shadow_samples,
+                self.frame_seed(),
+            );
+            let (r, phi) = (light.radius * u.sqrt(), 2.0 * PI * v);
+            let target = vec3_add(
+                light.position,
+                vec3_add(
+                    vec3_scale(tangent, r * phi.cos()),
+                    vec3_scale(bitangent, r * phi.sin()),
+                ),
+            );
+            let blocked = self.blocked_by(
+                scene,
+                point,
+                target,
+                Some(object),
+                part,
+                light.shadow_mask,
+                |caster| self.casts_shadow(caster),
+            );
             if !blocked {
-                let contribution = vec3_dot(
-                    dir_to_light,
-                    object.normal_to(&Ray::new(point, vec3_neg(dir_to_light))),
-                );
-                if contribution > 0.0 {
-                    lambert_amount +=
-                        contribution * (light.intensity / (4.0 * PI * dist_to_light.powi(2)));
+                visible += 1;
+            }
+        }
+        visible as f32 / light.shadow_samples as f32
+    }
+
+    /// Share of `light` visible from `point` by a cone traced towards it,
+    /// see `with_cone_tracing`.
+    fn cone_visibility(
+        &self,
+        scene: &Scene,
+        object: &dyn Object,
+        point: Vecf,
+        part: Option<Part>,
+        light: &Light,
+    ) -> f32 {
+        let casters = scene
+            .objects
+            .iter()
+            .map(|caster| caster.as_ref())
+            .filter(|caster| {
+                caster.visibility().intersects(light.shadow_mask)
+                    && !caster.is_shadow_catcher()
+                    && !same_object(*caster, object)
+                    && self.casts_shadow(*caster)
+            });
+        let field = DistanceField::around(casters, point);
+        let blocked = self.blocked_by(
+            scene,
+            point,
+            light.position,
+            Some(object),
+            part,
+            light.shadow_mask,
+            |caster| caster.shape().is_none() && self.casts_shadow(caster),
+        );
+        if blocked {
+            return 0.0;
+        }
+        field.visibility(point, light.position, light.radius, self.shadow_bias)
+    }
+
+    /// Whether anything visible to `mask` but a shadow catcher lies
+    /// between `point` and `target`. `receiver`, the object `point` lies on,
+    /// is skipped where it is convex and `target` lies outside it, and
+    /// otherwise its `part` the point lies on is.
+    pub(crate) fn blocked(
+        &self,
+        scene: &Scene,
+        point: Vecf,
+        target: Vecf,
+        receiver: Option<&dyn Object>,
+        part: Option<Part>,
+        mask: RayMask,
+    ) -> bool {
+        self.blocked_by(scene, point, target, receiver, part, mask, |_| true)
+    }
+
+    /// Like `blocked`, but only counting objects `include` picks.
+    #[allow(clippy::too_many_arguments)]
+    fn blocked_by(
+        &self,
+        scene: &Scene,
+        point: Vecf,
+        target: Vecf,
+        receiver: Option<&dyn Object>,
+        part: Option<Part>,
+        mask: RayMask,
+        include: impl Fn(&dyn Object) -> bool,
+    ) -> bool {
+        let to_target = vec3_sub(target, point);
+        let direction = vec3_normalized(to_target);
+        let distance = vec3_len(to_target);
+        let shadow_point = vec3_add(point, vec3_scale(direction, self.shadow_bias));
+        let skip = shadow_skip(point, direction, receiver, part);
+        let casts = |object: &dyn Object| {
+            object.visibility().intersects(mask)
+                && skip.is_none_or(|skip| !same_object(skip, object))
+                && include(object)
+        };
+        let shadow_ray = Ray::new(shadow_point, direction);
+        let from = receiver.zip(part);
+        scene.occluded_from(&shadow_ray, distance, from, |_, object| {
+            casts(object) && object.get_cutout().is_none()
+        }) || scene.objects.iter().any(|object| {
+            object.get_cutout().is_some()
+                && !object.is_shadow_catcher()
+                && casts(object.as_ref())
+                && self.cutout_blocks(object.as_ref(), &shadow_ray, distance)
+        })
+    }
+
+    /// Whether `caster` blocks light rather than letting it through to
+    /// `transparent_filter`.
+    fn casts_shadow(&self, caster: &dyn Object) -> bool {
+        !self.transparent_shadows || caster.get_transparency() <= 0.0
+    }
+
+    /// Share of each channel of `light` that the transparent objects
+    /// between `point` on `receiver` and the light let through, see
+    /// `with_transparent_shadows`.
+    fn transparent_filter(
+        &self,
+        scene: &Scene,
+        receiver: &dyn Object,
+        point: Vecf,
+        light: &Light,
+    ) -> [f32; 3] {
+        let to_light = vec3_sub(light.position, point);
+        let direction = vec3_normalized(to_light);
+        let distance = vec3_len(to_light);
+        let skip = shadow_skip(point, direction, Some(receiver), None);
+        let mut filter = [1.0; 3];
+        for object in &scene.objects {
+            let object = object.as_ref();
+            let transparency = object.get_transparency();
+            if transparency <= 0.0
+                || !object.visibility().intersects(light.shadow_mask)
+                || object.is_shadow_catcher()
+                || skip.is_some_and(|skip| same_object(skip, object))
+            {
+                continue;
+            }
+            let absorption = object.get_absorption();
+            let mut ray = Ray {
+                origin: vec3_add(point, vec3_scale(direction, self.shadow_bias)),
+                direction,
+            };
+            let mut travelled = self.shadow_bias;
+            // Where the ray last went inside the object, while it is.
+            let mut entered: Option<f32> = None;
+            for _ in 0..MAX_CUTOUT_LAYERS {
+                let (hit_distance, hit_point) = object.intersect(&ray);
+                if !(hit_distance > 0.0 && travelled + hit_distance < distance) {
+                    break;
+                }
+                travelled += hit_distance;
+                for f in filter.iter_mut() {
+                    *f *= transparency;
+                }
+                if object.enters(&ray, hit_point) {
+                    entered = Some(travelled);
+                } else {
+                    let inside = travelled - entered.take().unwrap_or(0.0);
+                    for (f, t) in filter.iter_mut().zip(transmittance(absorption, inside)) {
+                        *f *= t;
+                    }
                 }
+                ray.origin = vec3_add(hit_point, vec3_scale(direction, self.shadow_bias));
+                travelled += self.shadow_bias;
+            }
+            if let Some(entered) = entered {
+                for (f, t) in filter
+                    .iter_mut()
+                    .zip(transmittance(absorption, distance - entered))
+                {
+                    *f *= t;
+                }
+            }
+        }
+        filter
+    }
+
+    /// Whether `ray` meets a covered part of the cutout `object` within
+    /// `distance`.
+    fn cutout_blocks(&self, object: &dyn Object, ray: &Ray, distance: f32) -> bool {
+        let mut ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction,
+        };
+        let mut remaining = distance;
+        for _ in 0..MAX_CUTOUT_LAYERS {
+            let (hit_distance, point) = object.intersect(&ray);
+            if !(hit_distance > 0.0 && hit_distance < remaining) {
+                return false;
+            }
+            if self.covers(object, &ray, point) {
+                return true;
+            }
+            remaining -= hit_distance + self.shadow_bias;
+            ray = Ray::new(
+                vec3_add(point, vec3_scale(ray.direction, self.shadow_bias)),
+                ray.direction,
+            );
+        }
+        false
+    }
+
+    /// Distance from the camera to the first surface at each pixel, row by
+    /// row from the top left; infinite where rays hit nothing. Shadow
+    /// catchers count as surfaces.
+    pub fn render_depth(&self, scene: &Scene) -> Vec<f32> {
+        self.first_hits(scene, |ray, hit| match hit {
+            Some((point, _, _, _)) => vec3_len(vec3_sub(point, ray.origin)),
+            None => f32::INFINITY,
+        })
+    }
+
+    /// World-space normal of the first surface at each pixel, facing the
+    /// camera, row by row from the top left; zero where rays hit nothing.
+    pub fn render_normals(&self, scene: &Scene) -> Vec<Vecf> {
+        self.first_hits(scene, |ray, hit| match hit {
+            Some((point, _, object, part)) => self.hit_at(scene, object, ray, point, part).normal,
+            None => [0.0; 3],
+        })
+    }
+
+    /// Renders how densely the scene's caustics photons landed around the
+    /// first surface at each pixel instead of the shaded image, from black
+    /// for none to white for the densest pixel, for choosing a photon count
+    /// and gather radius. Black without a `Scene::set_caustics` map.
+    pub fn render_photon_density(&self, scene: &Scene) -> RgbImage {
+        let densities = self.first_hits(scene, |_, hit| match (hit, &scene.caustics) {
+            (Some((point, _, _, _)), Some(caustics)) => caustics.density(point),
+            _ => 0.0,
+        });
+        let densest = densities.iter().fold(0.0, |a: f32, &b| a.max(b));
+        RgbImage::from_fn(self.image_width, self.image_height, |x, y| {
+            let density = densities[y as usize * self.image_width as usize + x as usize];
+            let level = if densest > 0.0 {
+                (density / densest * 255.0).round() as u8
+            } else {
+                0
+            };
+            Rgb([level; 3])
+        })
+    }
+
+    /// `value` of every pixel's camera ray and what it hits first.
+    fn first_hits<T>(
+        &self,
+        scene: &Scene,
+        value: impl Fn(&Ray, Option<(Vecf, f32, &dyn Object, Option<Part>)>) -> T,
+    ) -> Vec<T> {
+        let frame = self.camera_frame();
+        let mut values = Vec::with_capacity((self.image_width * self.image_height) as usize);
+        for y in 0..self.image_height {
+            for x in 0..self.image_width {
+                let ray = self.camera_ray(&frame, x as f32, y as f32);
+                values.push(value(
+                    &ray,
+                    self.trace(scene, &ray, None, RayMask::CAMERA, true),
+                ));
+            }
+        }
+        values
+    }
+
+    /// Renders which lights drive each camera-ray hit instead of the
+    /// shaded image, for checking light placement. Misses are black.
+    pub fn render_light_debug(&self, scene: &Scene, mode: LightDebug) -> RgbImage {
+        let frame = self.begin_render(scene);
+        RgbImage::from_fn(self.image_width, self.image_height, |x, y| {
+            let ray = self.camera_ray(&frame, x as f32, y as f32);
+            let (hit_point, _dist, hit_object, part) =
+                match self.trace(scene, &ray, None, RayMask::CAMERA, true) {
+                    Some(hit) => hit,
+                    None => return Rgb([0; 3]),
+                };
+            let contributions: Vec<f32> = scene
+                .lights
+                .iter()
+                .map(|light| {
+                    // The brightest channel, for light tinted by colored
+                    // shadows.
+                    self.light_contribution(scene, hit_object, hit_point, part, light, true)
+                        .iter()
+                        .fold(0.0, |a: f32, &b| a.max(b))
+                })
+                .collect();
+            let total: f32 = contributions.iter().sum();
+            if total <= 0.0 {
+                return Rgb([0; 3]);
+            }
+            match mode {
+                LightDebug::DominantLight => {
+                    let (index, strongest) =
+                        contributions
+                            .iter()
+                            .enumerate()
+                            .fold(
+                                (0, 0.0),
+                                |best, (i, &c)| if c > best.1 { (i, c) } else { best },
+                            );
+                    let color = LIGHT_PALETTE[index % LIGHT_PALETTE.len()];
+                    mix(Rgb([0; 3]), color, strongest / total)
+                }
+                LightDebug::IsoIntensity { step } => {
+                    let band = (total / step) as usize;
+                    LIGHT_PALETTE[band % LIGHT_PALETTE.len()]
+                }
+            }
+        })
+    }
+}
+
+/// The object shadow rays from `point` on `receiver` towards `direction`
+/// can skip: the receiver itself where it is convex and the ray leaves it.
+/// Sphere normals point outwards; planes are never hit twice anyway.
+fn shadow_skip(
+    point: Vecf,
+    direction: Vecf,
+    receiver: Option<&dyn Object>,
+    part: Option<Part>,
+) -> Option<&dyn Object> {
+    receiver.filter(|receiver| {
+        receiver.is_convex()
+            && vec3_dot(
+                direction,
+                normal_on(*receiver, point, vec3_neg(direction), part),
+            ) > 0.0
+    })
+}
+
+/// Normal of `object` at `point` facing against `direction`, taken from
+/// the `part` hit where the object is built from several.
+fn normal_on(object: &dyn Object, point: Vecf, direction: Vecf, part: Option<Part>) -> Vecf {
+    let ray = Ray::new(point, direction);
+    match part {
+        Some(_) => object.hit_on(&ray, point, part).normal,
+        None => object.normal_to(&ray),
+    }
+}
+
+/// Replaces a normal that is zero or not finite by one facing back along
+/// `ray`; whether it had to.
+fn repair_normal(hit: &mut Hit, ray: &Ray) -> bool {
+    let length = vec3_len(hit.normal);
+    let degenerate = !(length > 0.0 && length.is_finite());
+    if degenerate {
+        hit.normal = vec3_neg(ray.direction);
+    }
+    degenerate
+}
+
+/// Draws the line from `a` to `b`, in pixels from the top left corner,
+/// clipped to the image.
+fn draw_line(image: &mut RgbImage, a: [f32; 2], b: [f32; 2], color: Color) {
+    let (width, height) = image.dimensions();
+    if !a.iter().chain(&b).all(|v| v.is_finite()) {
+        return;
+    }
+    let delta = [b[0] - a[0], b[1] - a[1]];
+    // Liang-Barsky: narrow the line's parameter range to each image edge.
+    let (mut start, mut end) = (0.0f32, 1.0f32);
+    for (rate, room) in [
+        (-delta[0], a[0] + 0.5),
+        (delta[0], width as f32 - 0.5 - a[0]),
+        (-delta[1], a[1] + 0.5),
+        (delta[1], height as f32 - 0.5 - a[1]),
+    ] {
+        if rate == 0.0 {
+            if room < 0.0 {
+                return;
+            }
+        } else if rate < 0.0 {
+            start = start.max(room / rate);
+        } else {
+            end = end.min(room / rate);
+        }
+    }
+    if start > end {
+        return;
+    }
+    let length = (end - start) * delta[0].abs().max(delta[1].abs());
+    // The clipped line fits in the image; the cap only guards against
+    // rounding for lines reaching far outside it.
+    let steps = length.ceil().clamp(1.0, (width + height) as f32) as u32;
+    for step in 0..=steps {
+        let t = start + (end - start) * step as f32 / steps as f32;
+        let x = (a[0] + delta[0] * t).round() as u32;
+        let y = (a[1] + delta[1] * t).round() as u32;
+        if x < width && y < height {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+/// Camera basis and pixel footprint shared by every ray of a render.
+pub(crate) struct CameraFrame {
+    pub(crate) cam_right: Vecf,
+    pub(crate) cam_up: Vecf,
+    pub(crate) cam_half_width: f32,
+    pub(crate) cam_half_height: f32,
+    pub(crate) pixel_width: f32,
+    pub(crate) pixel_height: f32,
+}
+
+/// Iterator returned by `View::render_progressive`.
+pub struct Progressive<'a> {
+    view: &'a View,
+    scene: &'a Scene,
+    frame: CameraFrame,
+    block: u32,
+    img_buffer: RgbImage,
+}
+
+impl<'a> Iterator for Progressive<'a> {
+    type Item = RgbImage;
+
+    fn next(&mut self) -> Option<RgbImage> {
+        if self.block == 0 {
+            return None;
+        }
+        let block = self.block;
+        let (width, height) = self.img_buffer.dimensions();
+        for x in (0..width).step_by(block as usize) {
+            for y in (0..height).step_by(block as usize) {
+                // Pixels on the coarser grid were traced by an earlier pass.
+                let coarse = block * 2;
+                if block < PROGRESSIVE_START_BLOCK && x % coarse == 0 && y % coarse == 0 {
+                    continue;
+                }
+                let color = self.view.render_pixel(self.scene, &self.frame, x, y);
+                self.fill_block(x, y, block, color);
+            }
+        }
+        self.block /= 2;
+        Some(self.img_buffer.clone())
+    }
+}
+
+impl<'a> Progressive<'a> {
+    fn fill_block(&mut self, x: u32, y: u32, block: u32, color: Color) {
+        let (width, height) = self.img_buffer.dimensions();
+        for bx in x..(x + block).min(width) {
+            for by in y..(y + block).min(height) {
+                self.img_buffer.put_pixel(bx, by, color);
             }
         }
-        lambert_amount.min(1.0)
     }
 }
@@ -0,0 +1,100 @@
+//! Problems the renderer works around while rendering, counted so they can
+//! be reported instead of only showing up as artifacts in the image.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderWarning {
+    /// A hit's normal was zero or not finite, as on zero-area triangles or
+    /// zero-radius spheres, and was replaced by one facing the ray.
+    DegenerateNormal,
+    /// A sample came out NaN or infinite and was rendered black.
+    NanSample,
+    /// A texture's fallback color was used for a hit without texture
+    /// coordinates or vertex colors.
+    TextureFallback,
+    /// A material with its own `Material::scatter` was rendered black, or
+    /// one that gives off light was rendered without it, by the
+    /// `Bidirectional` integrator or the caustics, which cannot follow
+    /// them.
+    UnsupportedMaterial,
+}
+
+impl RenderWarning {
+    pub const ALL: [RenderWarning; 4] = [
+        RenderWarning::DegenerateNormal,
+        RenderWarning::NanSample,
+        RenderWarning::TextureFallback,
+        RenderWarning::UnsupportedMaterial,
+    ];
+
+    /// Short plural name, e.g. for statistics.
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderWarning::DegenerateNormal => "degenerate normals",
+            RenderWarning::NanSample => "NaN samples",
+            RenderWarning::TextureFallback => "texture fallbacks",
+            RenderWarning::UnsupportedMaterial => "unsupported materials",
+        }
+    }
+}
+
+/// What happened, to follow a count, e.g. "3 samples were NaN or infinite
+/// and rendered black".
+impl fmt::Display for RenderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RenderWarning::DegenerateNormal => {
+                "degenerate normals were replaced by ones facing the ray"
+            }
+            RenderWarning::NanSample => "samples were NaN or infinite and rendered black",
+            RenderWarning::TextureFallback => {
+                "hits had no texture coordinates or vertex colors and used a fallback color"
+            }
+            RenderWarning::UnsupportedMaterial => {
+                "hits on materials with their own scattering or light were rendered without it"
+            }
+        })
+    }
+}
+
+/// How often each `RenderWarning` came up. Recording only needs a shared
+/// reference, so textures and integrators can record from anywhere.
+#[derive(Debug, Default)]
+pub struct RenderWarnings {
+    counts: [AtomicU64; 4],
+}
+
+impl RenderWarnings {
+    pub fn record(&self, warning: RenderWarning) {
+        self.counts[warning as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, warning: RenderWarning) -> u64 {
+        self.counts[warning as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        RenderWarning::ALL
+            .iter()
+            .all(|&warning| self.count(warning) == 0)
+    }
+
+    /// Warnings recorded at least once, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (RenderWarning, u64)> + '_ {
+        RenderWarning::ALL
+            .iter()
+            .map(move |&warning| (warning, self.count(warning)))
+            .filter(|&(_, count)| count > 0)
+    }
+
+    /// Sets every count back to zero, e.g. between frames.
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+}
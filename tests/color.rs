@@ -0,0 +1,155 @@
+//! Developing renders to 8 bits follows the curves, exposures and
+//! encodings views are set up with.
+
+use raytracer::{dither::Dither, image::Rgb, scene::*, srgb, tonemap::*, view::*};
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-3 * b.abs().max(1.0), "{} {}", a, b);
+}
+
+/// A black wall facing the camera, giving off `strength` times white.
+fn glowing_wall(strength: f32) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 1.0, 0.0)
+            .with_emission(Rgb([255; 3]), strength),
+    );
+    scene
+}
+
+fn view() -> View {
+    View::new(4, 4, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3)
+}
+
+#[test]
+fn tonemaps_follow_their_curves() {
+    let white = [255.0; 3];
+    assert_eq!(
+        Tonemap::Clip.apply([1000.0, 10.0, -1.0]),
+        [1000.0, 10.0, -1.0]
+    );
+    assert_close(Tonemap::Reinhard.apply(white)[0], 127.5);
+    assert_close(
+        Tonemap::Exposure(2.0).apply(white)[0],
+        255.0 * (1.0 - (-2.0f32).exp()),
+    );
+    assert_close(Tonemap::Aces.apply(white)[0], 255.0 * 2.54 / 3.16);
+    for tonemap in [Tonemap::Exposure(1.0), Tonemap::Reinhard, Tonemap::Aces] {
+        let mut last = 0.0;
+        for c in [0.0, 10.0, 100.0, 255.0, 1e3, 1e5] {
+            let mapped = tonemap.apply([c; 3])[0];
+            assert!(mapped >= last && mapped <= 255.0, "{:?} {}", tonemap, c);
+            last = mapped;
+        }
+        assert!(tonemap.apply([1e5; 3])[0] > 250.0, "{:?}", tonemap);
+    }
+
+    // Views develop renders through their tone map.
+    let scene = glowing_wall(3.0);
+    let pixel = view().with_tonemap(Tonemap::Reinhard).render(&scene);
+    assert_eq!(pixel.get_pixel(2, 2).0, [191; 3]);
+    assert_eq!(view().render(&scene).get_pixel(2, 2).0, [255; 3]);
+}
+
+#[test]
+fn srgb_round_trips_every_level() {
+    for c in 0..=255u8 {
+        assert_eq!(srgb::encode(srgb::decode(c)).round() as u8, c);
+    }
+    assert_close(srgb::decode(128), 0.2158605 * 255.0);
+    assert_close(srgb::decode(255), 255.0);
+    assert!(srgb::encode(510.0) > 255.0);
+
+    // The background goes through linear light and back unchanged, while
+    // the linear render holds it decoded.
+    let background = Rgb([128, 30, 240]);
+    let view = View::new(3, 2, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, background, 1e-3).with_srgb();
+    let scene = Scene::default();
+    assert!(view
+        .render(&scene)
+        .pixels()
+        .all(|&pixel| pixel == background));
+    let linear = view.render_hdr(&scene).get_pixel(1, 1);
+    for (l, c) in linear.iter().zip(background.0) {
+        assert_close(*l, srgb::decode(c));
+    }
+}
+
+#[test]
+fn exposure_halves_brightness_per_stop() {
+    let scene = glowing_wall(0.5);
+    let center = |view: View| view.render(&scene).get_pixel(2, 2).0[0];
+    assert_eq!(center(view()), 127);
+    assert_eq!(center(view().with_exposure_value(1.0)), 63);
+    assert_eq!(center(view().with_exposure_value(-1.0)), 255);
+    // One second at f/1 and ISO 100 is exposure value 0; faster shutters
+    // and smaller apertures darken, faster film brightens.
+    assert_eq!(center(view().with_camera_exposure(1.0, 100.0, 1.0)), 127);
+    assert_eq!(center(view().with_camera_exposure(0.5, 100.0, 1.0)), 63);
+    assert_eq!(center(view().with_camera_exposure(1.0, 100.0, 2.0)), 31);
+    assert_eq!(center(view().with_camera_exposure(0.5, 200.0, 1.0)), 127);
+    // Exposure is left to developing; the linear render is unchanged.
+    let hdr = view().with_exposure_value(3.0).render_hdr(&scene);
+    assert_close(hdr.get_pixel(2, 2)[0], 127.5);
+}
+
+#[test]
+fn auto_exposure_brings_the_average_to_middle_gray() {
+    let scene = glowing_wall(0.72);
+    let hdr = view().render_hdr(&scene);
+    let ev = AutoExposure::new().exposure_value(&hdr);
+    assert!((ev - 2.0).abs() < 1e-3, "{}", ev);
+    let developed = view().with_exposure_value(ev).develop(&hdr);
+    assert_eq!(developed.get_pixel(2, 2).0, [45; 3]);
+    let ev = AutoExposure::new().with_key(0.72).exposure_value(&hdr);
+    assert!(ev.abs() < 1e-3, "{}", ev);
+    let ev = AutoExposure::new()
+        .with_range(-1.0, 1.0)
+        .exposure_value(&hdr);
+    assert_eq!(ev, 1.0);
+    // Black pixels count as faint gray rather than pulling the average to
+    // nothing.
+    let dark = view().render_hdr(&Scene::default());
+    let ev = AutoExposure::new().exposure_value(&dark);
+    assert!((ev - (1e-4f32 / 0.18).log2()).abs() < 1e-3, "{}", ev);
+}
+
+#[test]
+fn dithering_keeps_the_average_level() {
+    for (dither, size) in [(Dither::Ordered, 8), (Dither::BlueNoise, 32)] {
+        // Every threshold of the tile is used once.
+        let mut ranks: Vec<u32> = (0..size * size)
+            .map(|i| (dither.threshold(i % size, i / size) * (size * size) as f32) as u32)
+            .collect();
+        ranks.sort_unstable();
+        assert!(ranks.iter().copied().eq(0..size * size), "{:?}", dither);
+        assert_eq!(dither.threshold(3, 5), dither.threshold(3 + size, 5 + size));
+        for c in [0.0, 17.3, 100.5, 254.9] {
+            let sum: u32 = (0..size * size)
+                .map(|i| dither.quantize(c, i % size, i / size) as u32)
+                .sum();
+            let mean = sum as f32 / (size * size) as f32;
+            assert!(
+                (mean - c).abs() <= 1.0 / (size * size) as f32,
+                "{:?} {} {}",
+                dither,
+                c,
+                mean
+            );
+        }
+    }
+
+    // A flat level between two steps comes out as a mix of both.
+    let scene = glowing_wall(100.25 / 255.0);
+    let view = View::new(8, 8, [0.0; 3], 10.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3);
+    assert!(view
+        .render(&scene)
+        .pixels()
+        .all(|pixel| pixel.0 == [100; 3]));
+    let dithered = view.with_dither(Dither::Ordered).render(&scene);
+    let high = dithered
+        .pixels()
+        .filter(|pixel| pixel.0 == [101; 3])
+        .count();
+    assert_eq!(high, 16);
+}
@@ -0,0 +1,125 @@
+//! Scalar fields show where and how their values lie.
+
+use raytracer::{
+    atmosphere::{Scattering, Volume},
+    image::Rgb,
+    isosurface::*,
+    scene::*,
+    view::Ray,
+    Vecf,
+};
+
+fn assert_near(a: Vecf, b: Vecf, tolerance: f32) {
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() <= tolerance, "{:?} {:?}", a, b);
+    }
+}
+
+#[test]
+fn isosurfaces_lie_where_fields_take_the_value() {
+    // The unit ball, as the squared distance from the origin.
+    let ball = Isosurface::new(
+        |p: Vecf| p[0] * p[0] + p[1] * p[1] + p[2] * p[2],
+        1.0,
+        [-2.0; 3],
+        [2.0; 3],
+        Rgb([255; 3]),
+        1.0,
+        0.0,
+    )
+    .with_step(0.01);
+    let ray = Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]);
+    let (distance, point) = ball.intersect(&ray);
+    assert!((distance - 4.0).abs() < 1e-3, "{}", distance);
+    assert_near(ball.hit_at(&ray, point).normal, [0.0, 0.0, -1.0], 1e-2);
+    let (distance, _) = ball.intersect(&Ray::new([0.0, 1.5, -5.0], [0.0, 0.0, 1.0]));
+    assert!(!(distance > 0.0 && distance.is_finite()), "{}", distance);
+
+    // A grid rising along x crosses 0.25 a quarter of the way across.
+    let values = (0..8).map(|i| (i % 2) as f32).collect();
+    let grid = Grid::new([0.0; 3], [2.0; 3], [2, 2, 2], values);
+    assert!((grid.value([0.5, 1.7, 0.3]) - 0.25).abs() < 1e-6);
+    let wall = Isosurface::from_grid(grid, 0.25, Rgb([255; 3]), 1.0, 0.0).with_step(0.01);
+    let ray = Ray::new([-1.0, 1.0, 1.0], [1.0, 0.0, 0.0]);
+    let (distance, point) = wall.intersect(&ray);
+    assert!((distance - 1.5).abs() < 1e-3, "{}", distance);
+    assert_near(wall.hit_at(&ray, point).normal, [-1.0, 0.0, 0.0], 1e-3);
+}
+
+#[test]
+fn slices_color_by_value_and_clip_planes_open_isosurfaces() {
+    let scene = Scene::default();
+    let ramp = ColorRamp::new()
+        .with_stop(0.0, Rgb([0, 0, 0]))
+        .with_stop(1.0, Rgb([200, 100, 0]));
+    let slice = Slice::new(
+        |p: Vecf| p[0],
+        ramp,
+        [0.0; 3],
+        [0.0, 0.0, 1.0],
+        [-1.0; 3],
+        [1.0; 3],
+    );
+    // Seen from both sides, colored by the field's value where it is hit.
+    for (origin, direction) in [([0.5, 0.0, -2.0], 1.0), ([0.5, 0.0, 2.0], -1.0)] {
+        let ray = Ray::new(origin, [0.0, 0.0, direction]);
+        let (distance, point) = slice.intersect(&ray);
+        assert!((distance - 2.0).abs() < 1e-4, "{}", distance);
+        let hit = slice.hit_at(&ray, point);
+        assert_near(hit.normal, [0.0, 0.0, -direction], 1e-6);
+        assert_eq!(slice.get_color_at(&hit, &scene), Rgb([100, 50, 0]));
+    }
+    let (distance, _) = slice.intersect(&Ray::new([1.5, 0.0, -2.0], [0.0, 0.0, 1.0]));
+    assert_eq!(distance, f32::INFINITY);
+
+    // Clipping the near half of a ball shows its far half from inside.
+    let ball = Isosurface::new(
+        |p: Vecf| p[0] * p[0] + p[1] * p[1] + p[2] * p[2],
+        1.0,
+        [-2.0; 3],
+        [2.0; 3],
+        Rgb([255; 3]),
+        1.0,
+        0.0,
+    )
+    .with_step(0.01)
+    .with_clip([0.0; 3], [0.0, 0.0, -1.0]);
+    let (distance, _) = ball.intersect(&Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]));
+    assert!((distance - 6.0).abs() < 1e-3, "{}", distance);
+}
+
+#[test]
+fn volumes_glow_and_absorb_by_their_transfer_function() {
+    let transfer = TransferFunction::new()
+        .with_stop(0.0, Rgb([0; 3]), 0.0)
+        .with_stop(1.0, Rgb([255, 0, 0]), 0.5);
+    assert_eq!(transfer.color_at(0.5), Rgb([127, 0, 0]));
+    assert!((transfer.opacity_at(0.5) - 0.25).abs() < 1e-6);
+    let volume = Volume::new(|_: Vecf| 1.0, transfer.clone(), [-1.0; 3], [1.0; 3]);
+    let ray = Ray::new([0.0, 0.0, -3.0], [0.0, 0.0, 1.0]);
+    // Two units of opacity 0.5 let through e^-1 and glow with the rest.
+    let through = volume.scattering(&ray, f32::INFINITY);
+    let kept = (-1.0f32).exp();
+    assert!(
+        (through.transmittance[0] - kept).abs() < 1e-4,
+        "{:?}",
+        through
+    );
+    assert!(
+        (through.inscatter[0] - (1.0 - kept)).abs() < 1e-4,
+        "{:?}",
+        through
+    );
+    assert_eq!(through.inscatter[1], 0.0);
+    // Surfaces inside the box hide the rest of it.
+    let half = volume.scattering(&ray, 3.0);
+    assert!(
+        (half.transmittance[0] - (-0.5f32).exp()).abs() < 1e-4,
+        "{:?}",
+        half
+    );
+    let clear = Volume::new(|_: Vecf| 0.0, transfer, [-1.0; 3], [1.0; 3]);
+    assert_eq!(clear.scattering(&ray, f32::INFINITY), Scattering::CLEAR);
+    let missed = volume.scattering(&Ray::new([0.0, 2.0, -3.0], [0.0, 0.0, 1.0]), f32::INFINITY);
+    assert_eq!(missed, Scattering::CLEAR);
+}
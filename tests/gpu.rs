@@ -0,0 +1,307 @@
+#![cfg(feature = "gpu")]
+
+use std::sync::Arc;
+
+use raytracer::{
+    gpu::{GpuError, GpuRenderer},
+    image::{Rgb, RgbImage},
+    integrator::PathTracer,
+    material::{Material, Scatter},
+    mesh::{Mesh, Triangle},
+    sampler::Rng,
+    scene::*,
+    view::*,
+};
+
+#[test]
+fn gpu_matches_cpu() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(GpuError::NoAdapter) => {
+            eprintln!("no GPU adapter, skipping");
+            return;
+        }
+        Err(err) => panic!("{}", err),
+    };
+    let view = View::new(
+        160,
+        90,
+        [0.0, 0.0, 0.0],
+        90.0,
+        [0.0, 0.0, 1.0],
+        12,
+        Rgb([50, 100, 200]),
+        1e-3,
+    );
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 1.0, 7.0], 20.0));
+    scene.add_light(Light::new([2.0, 0.5, 2.0], 40.0));
+    scene.add_object(Sphere::new(
+        [0.0, -0.3, 3.0],
+        Rgb([255, 0, 0]),
+        0.2,
+        0.9,
+        0.0,
+    ));
+    scene.add_object(Sphere::new(
+        [1.0, -0.3, 5.0],
+        Rgb([0, 0, 255]),
+        0.3,
+        0.9,
+        0.3,
+    ));
+    scene.add_object(Plane::new(
+        Rgb([0, 255, 0]),
+        [0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        0.6,
+        0.0,
+    ));
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 8.0],
+        0.05,
+        1.0,
+    ));
+
+    let cpu = view.render(&scene);
+    let gpu = renderer.render(&view, &scene).unwrap();
+
+    // Float rounding differs between the two, which can flip the odd pixel
+    // on a shadow edge; everything else must agree to within a step or two.
+    let mismatched = cpu
+        .pixels()
+        .zip(gpu.pixels())
+        .filter(|(a, b)| (0..3).any(|c| (a.0[c] as i32 - b.0[c] as i32).abs() > 2))
+        .count();
+    assert!(
+        mismatched * 100 <= cpu.pixels().len(),
+        "{} of {} pixels differ",
+        mismatched,
+        cpu.pixels().len()
+    );
+}
+
+#[test]
+fn procedural_textures_are_rejected() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let view = View::new(8, 8, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 1, Rgb([0; 3]), 1e-3);
+    let mut scene = Scene::default();
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0)
+            .with_texture(|_: &Hit| Rgb([0; 3])),
+    );
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedObject(0)) => {}
+        other => panic!("expected UnsupportedObject(0), got {:?}", other.map(|_| ())),
+    }
+
+    // Glowing objects are not solid colors either.
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 4.0],
+        1.0,
+        0.0,
+    ));
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0)
+            .with_emission(Rgb([255; 3]), 1.0),
+    );
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedObject(1)) => {}
+        other => panic!("expected UnsupportedObject(1), got {:?}", other.map(|_| ())),
+    }
+}
+
+/// A custom material soaking up every ray that meets it.
+struct Black;
+
+impl Material for Black {
+    fn get_lambert(&self) -> f32 {
+        1.0
+    }
+
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut Rng) -> Scatter {
+        Scatter::Absorb
+    }
+}
+
+#[test]
+fn custom_materials_are_rejected() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let view = View::new(8, 8, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 1, Rgb([0; 3]), 1e-3);
+    let mut scene = Scene::default();
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0).with_material(Arc::new(Black)),
+    );
+    scene.add_object(
+        Plane::new(Rgb([255; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 4.0], 1.0, 0.0)
+            .with_material(Arc::new(Black)),
+    );
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedObject(0)) => {}
+        other => panic!("expected UnsupportedObject(0), got {:?}", other.map(|_| ())),
+    }
+    scene.objects.remove(0);
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedObject(0)) => {}
+        other => panic!("expected UnsupportedObject(0), got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn regions_are_rejected() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let mut view = View::new(8, 8, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 1, Rgb([0; 3]), 1e-3);
+    view.set_region(2, 2, 4, 4);
+    let mut scene = Scene::default();
+    scene.add_object(Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0));
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedRegion) => {}
+        other => panic!("expected UnsupportedRegion, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn light_cutoffs_are_rejected() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let view = View::new(8, 8, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 1, Rgb([0; 3]), 1e-3)
+        .with_light_cutoff(0.01);
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 0.0, 1.0], 20.0));
+    scene.add_object(Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0));
+    match renderer.render(&view, &scene) {
+        Err(GpuError::UnsupportedLightCutoff) => {}
+        other => panic!(
+            "expected UnsupportedLightCutoff, got {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
+
+/// A ball, a pyramid and a floor under two lights.
+fn ball_and_pyramid() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 2.0, 1.0], 60.0));
+    scene.add_light(Light::new([-2.0, 0.5, 2.0], 30.0));
+    scene.add_object(Sphere::new(
+        [0.6, -0.4, 3.0],
+        Rgb([255, 60, 60]),
+        0.6,
+        0.8,
+        0.2,
+    ));
+    scene.add_object(Plane::new(
+        Rgb([200; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        0.9,
+        0.0,
+    ));
+    let apex = [-0.7, 0.3, 3.2];
+    let base = [[-1.3, -1.0, 2.6], [-0.1, -1.0, 2.8], [-0.8, -1.0, 3.8]];
+    let triangles = (0..3)
+        .map(|i| Triangle {
+            vertices: [apex, base[i], base[(i + 1) % 3]],
+            uvs: None,
+            colors: None,
+        })
+        .collect();
+    scene.add_object(Mesh::new(triangles, Rgb([60, 200, 60]), 0.9, 0.0));
+    scene
+}
+
+fn small_view() -> View {
+    View::new(
+        32,
+        24,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([40, 60, 90]),
+        1e-3,
+    )
+}
+
+/// Mean of each channel over each 8 by 8 block of `image`.
+fn block_means(image: &RgbImage) -> Vec<[f32; 3]> {
+    let (width, height) = image.dimensions();
+    let mut means = Vec::new();
+    for by in (0..height).step_by(8) {
+        for bx in (0..width).step_by(8) {
+            let mut sum = [0.0; 3];
+            for y in by..by + 8 {
+                for x in bx..bx + 8 {
+                    for (s, c) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+                        *s += c as f32 / 64.0;
+                    }
+                }
+            }
+            means.push(sum);
+        }
+    }
+    means
+}
+
+#[test]
+fn gpu_meshes_match_cpu() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let scene = ball_and_pyramid();
+    let cpu = small_view().render(&scene);
+    let gpu = renderer.render(&small_view(), &scene).unwrap();
+    let mismatched = cpu
+        .pixels()
+        .zip(gpu.pixels())
+        .filter(|(a, b)| (0..3).any(|c| (a.0[c] as i32 - b.0[c] as i32).abs() > 2))
+        .count();
+    assert!(mismatched * 100 <= cpu.pixels().len(), "{}", mismatched);
+    // The pyramid shows, in its own green.
+    assert!(gpu
+        .pixels()
+        .any(|pixel| pixel.0[1] > 2 * pixel.0[0].max(pixel.0[2])));
+}
+
+#[test]
+fn gpu_path_tracing_converges_to_cpu() {
+    let renderer = match GpuRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(_) => return,
+    };
+    let scene = ball_and_pyramid();
+    let view = small_view().with_integrator(PathTracer).with_samples(256);
+    let cpu = block_means(&view.render(&scene));
+    let gpu = block_means(&renderer.render(&view, &scene).unwrap());
+    for (a, b) in cpu.iter().zip(&gpu) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 3.0, "{:?} {:?}", cpu, gpu);
+        }
+    }
+    // Bounced light reaches what the lights leave in shadow.
+    let whitted = block_means(&small_view().render(&scene));
+    assert!(cpu.iter().zip(&whitted).any(|(a, b)| a[1] > b[1] + 3.0));
+
+    let adaptive = view.with_adaptive_sampling(1.0, 512);
+    match renderer.render(&adaptive, &scene) {
+        Err(GpuError::UnsupportedSamples) => {}
+        other => panic!("expected UnsupportedSamples, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,55 @@
+//! Pixel traces record what the rays of a pixel hit and how the surfaces
+//! there are lit.
+
+use raytracer::{image::Rgb, inspect::BounceKind, scene::*, view::View};
+
+#[test]
+fn traces_follow_the_rays_and_lights_of_a_pixel() {
+    // A half mirrored wall down the z axis, lit by a light at the camera
+    // and one whose light a ball keeps off the wall's middle.
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 4.0],
+        1.0,
+        0.5,
+    ));
+    scene.add_object(Sphere::new([1.5, 0.0, 2.0], Rgb([255; 3]), 0.5, 1.0, 0.0));
+    scene.add_light(Light::new([0.0, 0.0, 1.0], 20.0));
+    scene.add_light(Light::new([3.0, 0.0, 0.0], 20.0));
+    let view = View::new(4, 4, [0.0; 3], 30.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3);
+
+    let trace = view.debug_pixel(&scene, 2, 2);
+    let rendered = view.render_hdr(&scene).get_pixel(2, 2);
+    for (a, b) in trace.color.iter().zip(&rendered) {
+        assert!((a - b).abs() < 1e-3, "{:?} {:?}", trace.color, rendered);
+    }
+
+    let camera = &trace.bounces[0];
+    assert_eq!(
+        (camera.kind, camera.parent, camera.depth),
+        (BounceKind::Camera, None, 0)
+    );
+    let hit = camera.hit.as_ref().unwrap();
+    assert_eq!(hit.object, 0);
+    assert!((hit.distance - 4.0).abs() < 1e-4, "{:?}", hit.distance);
+    assert!((hit.reflect - 0.5).abs() < 1e-6, "{:?}", hit.reflect);
+    assert_eq!(hit.lights.len(), 2);
+    let [open, blocked] = [hit.lights[0], hit.lights[1]];
+    assert!(open.arriving[0] > 0.0, "{:?}", open);
+    assert_eq!(open.arriving, open.unshadowed);
+    assert_eq!(blocked.light, 1);
+    assert_eq!(blocked.arriving, [0.0; 3]);
+    assert!(blocked.unshadowed[0] > 0.0, "{:?}", blocked);
+
+    // The mirrored ray heads back past the camera, out of the scene.
+    let mirrored = trace
+        .bounces
+        .iter()
+        .find(|bounce| bounce.kind == BounceKind::Reflection)
+        .unwrap();
+    assert_eq!((mirrored.parent, mirrored.depth), (Some(0), 1));
+    assert!(mirrored.direction[2] < -0.99, "{:?}", mirrored.direction);
+    assert!(mirrored.hit.is_none());
+}
@@ -0,0 +1,349 @@
+//! The integrators other than the default one add up light the way they
+//! describe.
+
+use std::{f32::consts::PI, sync::Arc};
+
+use raytracer::{
+    framebuffer::Framebuffer,
+    image::Rgb,
+    integrator::*,
+    material::{Material, Scatter},
+    sampler::Rng,
+    scene::*,
+    view::*,
+};
+
+/// White walls facing the camera at each of `depths` along the z axis, lit
+/// from between the camera and the first.
+fn walls(depths: &[f32]) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 0.0, 1.0], 20.0));
+    for &depth in depths {
+        scene.add_object(Plane::new(
+            Rgb([255; 3]),
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, depth],
+            1.0,
+            0.0,
+        ));
+    }
+    scene
+}
+
+fn view(background: u8) -> View {
+    View::new(
+        5,
+        5,
+        [0.0; 3],
+        30.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([background; 3]),
+        1e-3,
+    )
+}
+
+fn center(view: &View, scene: &Scene) -> [f32; 3] {
+    view.render_hdr(scene).get_pixel(2, 2)
+}
+
+fn assert_close(a: [f32; 3], b: [f32; 3]) {
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-3 * b.abs().max(1.0), "{:?} {:?}", a, b);
+    }
+}
+
+#[test]
+fn depth_peeling_composites_layers_front_to_back() {
+    let view = |opacity, layers| view(100).with_integrator(DepthPeeling::new(opacity, layers));
+    let both = walls(&[2.0, 4.0]);
+    let front = center(&view(1.0, 4), &both);
+    let back = center(&view(1.0, 4), &walls(&[4.0]));
+    assert!(
+        front[0] > back[0] && back[0] > 0.0,
+        "{:?} {:?}",
+        front,
+        back
+    );
+    let one = center(&view(0.5, 1), &both);
+    assert_close(one, [0, 1, 2].map(|i| 0.5 * front[i] + 0.5 * 100.0));
+    let two = center(&view(0.5, 2), &both);
+    assert_close(
+        two,
+        [0, 1, 2].map(|i| 0.5 * front[i] + 0.25 * back[i] + 0.25 * 100.0),
+    );
+}
+
+#[test]
+fn path_traced_emissive_spheres_light_like_lamps() {
+    // A small ball two units above a white floor, seen from straight above.
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        1.0,
+        0.0,
+    ));
+    scene.add_object(
+        Sphere::new([0.0, 1.0, 0.0], Rgb([0; 3]), 0.2, 1.0, 0.0).with_emission(Rgb([255; 3]), 10.0),
+    );
+    let view = View::new(
+        2,
+        2,
+        [0.0; 3],
+        10.0,
+        [0.0, -1.0, 1e-3],
+        2,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    // The default integrator does not light surfaces by glowing ones.
+    assert_eq!(view.render_hdr(&scene).get_pixel(1, 1), [0.0; 3]);
+    // A ball of radiance L subtending sin² θ = (r / d)² sends L (r / d)²
+    // back off a white surface under it.
+    let lit = view
+        .with_integrator(PathTracer)
+        .with_samples(4096)
+        .render_hdr(&scene)
+        .get_pixel(1, 1);
+    let expected = 10.0 * 255.0 * (0.2f32 / 2.0).powi(2);
+    assert!((lit[0] - expected).abs() < 0.05 * expected, "{:?}", lit);
+}
+
+#[test]
+fn ambient_occlusion_darkens_by_the_share_of_sky_blocked_nearby() {
+    // A floor under a ceiling one unit up, seen from between them.
+    let mut scene = Scene::default();
+    for (height, facing) in [(0.0, -1.0), (1.0, 1.0)] {
+        scene.add_object(Plane::new(
+            Rgb([255; 3]),
+            [0.0, facing, 0.0],
+            [0.0, height, 0.0],
+            1.0,
+            0.0,
+        ));
+    }
+    let view = View::new(
+        3,
+        3,
+        [0.0, 0.5, 0.0],
+        30.0,
+        [0.0, -1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let occlusion = |distance, samples| {
+        view.clone()
+            .with_integrator(AmbientOcclusion::new(distance, samples))
+            .render_hdr(&scene)
+            .get_pixel(1, 1)
+    };
+    // Only rays leaving at cos θ below h / d, a share of (h / d)² of
+    // cosine weighted ones, get further than d before the ceiling.
+    let open = occlusion(2.0, 4096)[0];
+    assert!((open - 255.0 * 0.25).abs() < 4.0, "{}", open);
+    let open = occlusion(4.0, 4096)[0];
+    assert!((open - 255.0 / 16.0).abs() < 2.0, "{}", open);
+    // Nothing within reach, nothing blocked.
+    assert_eq!(occlusion(0.9, 64), [255.0; 3]);
+    // Looking up at the sky, there is nothing to occlude.
+    let up = View::new(
+        3,
+        3,
+        [0.0, 2.0, 0.0],
+        30.0,
+        [0.0, 1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let sky = up
+        .with_integrator(AmbientOcclusion::new(2.0, 16))
+        .render_hdr(&scene);
+    assert_eq!(sky.get_pixel(1, 1), [255.0; 3]);
+}
+
+#[test]
+fn outputs_match_renders_with_each_integrator_alone() {
+    let scene = walls(&[2.0, 4.0]);
+    let depth = Depth::new(8.0);
+    let peeling = DepthPeeling::new(0.5, 2);
+    let outputs = view(100).render_outputs(&scene, &[&Whitted, &depth, &peeling]);
+    assert_eq!(outputs.len(), 3);
+    let alone = [
+        view(100).render_hdr(&scene),
+        view(100).with_integrator(depth).render_hdr(&scene),
+        view(100).with_integrator(peeling).render_hdr(&scene),
+    ];
+    for (output, alone) in outputs.iter().zip(&alone) {
+        for (&a, &b) in output.pixels().iter().zip(alone.pixels()) {
+            assert_close(a, b);
+        }
+    }
+    assert!(outputs[1].get_pixel(2, 2) != outputs[0].get_pixel(2, 2));
+}
+
+/// A white diffuse surface that only ever bounces paths on, in a cosine
+/// weighted direction, so `PathTracer` finds lights on it by chance alone.
+struct BounceOnly;
+
+impl Material for BounceOnly {
+    fn get_lambert(&self) -> f32 {
+        1.0
+    }
+
+    fn scatter(&self, _ray: &Ray, hit: &Hit, rng: &mut Rng) -> Scatter {
+        let [nx, ny, nz] = hit.normal;
+        let tangent = if nx.abs() > 0.5 {
+            [ny, -nx, 0.0]
+        } else {
+            [0.0, nz, -ny]
+        };
+        let length = tangent.iter().map(|t| t * t).sum::<f32>().sqrt();
+        let tangent = tangent.map(|t| t / length);
+        let bitangent = [
+            ny * tangent[2] - nz * tangent[1],
+            nz * tangent[0] - nx * tangent[2],
+            nx * tangent[1] - ny * tangent[0],
+        ];
+        let (r, phi) = (rng.next_f32().sqrt(), 2.0 * PI * rng.next_f32());
+        let (a, b, up) = (r * phi.cos(), r * phi.sin(), (1.0 - r * r).sqrt());
+        let direction = [0, 1, 2].map(|i| a * tangent[i] + b * bitangent[i] + up * hit.normal[i]);
+        let origin = [0, 1, 2].map(|i| hit.point[i] + 1e-3 * hit.normal[i]);
+        Scatter::Continue(Ray::new(origin, direction), [1.0; 3])
+    }
+}
+
+/// A white floor under a light of `radius` one unit up, seen from just
+/// under the light; with `bounce_only`, it finds the light by chance.
+fn lamp_over_floor(radius: f32, bounce_only: bool) -> Scene {
+    let mut scene = Scene::default();
+    let floor = Plane::new(Rgb([255; 3]), [0.0, -1.0, 0.0], [0.0, 0.0, 0.0], 1.0, 0.0);
+    if bounce_only {
+        scene.add_object(floor.with_material(Arc::new(BounceOnly)));
+    } else {
+        scene.add_object(floor);
+    }
+    scene.add_light(Light::new([0.0, 1.0, 0.0], 20.0).with_radius(radius, 1));
+    scene
+}
+
+fn traced(samples: u32, scene: &Scene) -> Framebuffer {
+    View::new(
+        8,
+        8,
+        [0.0, 0.5, 0.0],
+        60.0,
+        [0.0, -1.0, 1e-3],
+        2,
+        Rgb([0; 3]),
+        1e-3,
+    )
+    .with_integrator(PathTracer)
+    .with_samples(samples)
+    .render_hdr(scene)
+}
+
+fn mean(image: &Framebuffer) -> f32 {
+    image.pixels().iter().map(|p| p[0]).sum::<f32>() / image.pixels().len() as f32
+}
+
+fn squared_error(image: &Framebuffer, reference: &Framebuffer) -> f32 {
+    let pixels = image.pixels().iter().zip(reference.pixels());
+    pixels.map(|(a, b)| (a[0] - b[0]).powi(2)).sum()
+}
+
+#[test]
+fn light_sampling_converges_to_bounce_sampling_with_less_noise() {
+    let (direct, bounced) = (lamp_over_floor(0.1, false), lamp_over_floor(0.1, true));
+    let reference = traced(4096, &bounced);
+    let sampled = traced(16, &direct);
+    assert!(
+        (mean(&sampled) - mean(&reference)).abs() < 0.05 * mean(&reference),
+        "{} {}",
+        mean(&sampled),
+        mean(&reference)
+    );
+    // A light a tenth of a unit across is rarely hit by chance.
+    let chanced = traced(16, &bounced);
+    assert!(
+        squared_error(&sampled, &reference) < 0.05 * squared_error(&chanced, &reference),
+        "{} {}",
+        squared_error(&sampled, &reference),
+        squared_error(&chanced, &reference)
+    );
+}
+
+#[test]
+fn combined_light_and_bounce_sampling_converge_to_bounce_sampling() {
+    // From lights far smaller than the floor's view of the sky to ones
+    // filling most of it, which bounced rays find more easily than shadow
+    // rays sample well.
+    for radius in [0.3, 1.0, 3.0] {
+        let (direct, bounced) = (
+            lamp_over_floor(radius, false),
+            lamp_over_floor(radius, true),
+        );
+        let reference = traced(4096, &bounced);
+        let combined = traced(16, &direct);
+        assert!(
+            (mean(&combined) - mean(&reference)).abs() < 0.03 * mean(&reference),
+            "{} {} {}",
+            radius,
+            mean(&combined),
+            mean(&reference)
+        );
+        let (error, chanced) = (
+            squared_error(&combined, &reference),
+            squared_error(&traced(16, &bounced), &reference),
+        );
+        assert!(error < 3.0 * chanced, "{} {} {}", radius, error, chanced);
+        if radius < 3.0 {
+            assert!(error < 0.2 * chanced, "{} {} {}", radius, error, chanced);
+        }
+    }
+}
+
+/// A ball in the corner of a floor and a wall, lit by a point light, so
+/// that much of what the camera sees is lit by light bounced off the
+/// others.
+fn ball_in_corner() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([-1.0, 1.5, 1.5], 30.0));
+    scene.add_object(Sphere::new(
+        [0.3, -0.5, 3.0],
+        Rgb([255, 120, 60]),
+        0.5,
+        0.9,
+        0.0,
+    ));
+    for (normal, point) in [
+        ([0.0, -1.0, 0.0], [0.0, -1.0, 0.0]),
+        ([0.0, 0.0, 1.0], [0.0, 0.0, 4.0]),
+    ] {
+        scene.add_object(Plane::new(Rgb([200; 3]), normal, point, 0.8, 0.0));
+    }
+    scene
+}
+
+#[test]
+fn bidirectional_matches_path_tracing() {
+    let scene = ball_in_corner();
+    let view =
+        View::new(12, 9, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3).with_samples(512);
+    let forward = view.clone().with_integrator(PathTracer).render_hdr(&scene);
+    let both = view
+        .clone()
+        .with_integrator(Bidirectional)
+        .render_hdr(&scene);
+    for (a, b) in forward.pixels().iter().zip(both.pixels()) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 3.0, "{} {}", a, b);
+        }
+    }
+    assert!((mean(&forward) - mean(&both)).abs() < 0.01 * mean(&forward));
+    // A fifth of it is bounced light, which `Whitted` leaves out.
+    assert!(mean(&view.render_hdr(&scene)) < 0.85 * mean(&both));
+}
@@ -0,0 +1,318 @@
+//! Lights reach surfaces as they are set up to, and only as far as they
+//! are meant to.
+
+use raytracer::{
+    animation::LightAnimation,
+    image::Rgb,
+    photon::PhotonMap,
+    sampler::{Halton, Stratified, Uniform},
+    scene::*,
+    view::*,
+};
+
+/// A white floor, seen from above at the center pixel of a 5 by 5 view.
+fn floor() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0; 3],
+        1.0,
+        0.0,
+    ));
+    scene
+}
+
+fn view_from_above() -> View {
+    View::new(
+        5,
+        5,
+        [0.0, 3.0, 0.0],
+        30.0,
+        [0.0, -1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+}
+
+fn center(view: &View, scene: &Scene) -> [f32; 3] {
+    view.render_hdr(scene).get_pixel(2, 2)
+}
+
+#[test]
+fn quad_lights_spread_over_their_corners() {
+    let mut scene = floor();
+    scene.add_area_light_from_quad(
+        [1.0, 4.0, 1.0],
+        [1.0, 4.0, -1.0],
+        [-1.0, 4.0, -1.0],
+        100.0,
+        2,
+        2,
+    );
+    assert_eq!(scene.lights.len(), 4);
+    for light in &scene.lights {
+        assert!((light.intensity - 25.0).abs() < 1e-4);
+        let [x, y, z] = light.position;
+        assert!((x.abs() - 0.5).abs() < 1e-4 && (y - 4.0).abs() < 1e-4);
+        assert!((z.abs() - 0.5).abs() < 1e-4, "{:?}", light.position);
+    }
+    let view = view_from_above();
+    let lit = center(&view, &scene);
+    assert!(lit[0] > 0.0);
+    // A ball hidden from the camera, just under one of the four lights,
+    // shadows the middle of the floor from that light alone.
+    let ball = Sphere::new([0.5, 3.5, 0.5], Rgb([255; 3]), 0.2, 1.0, 0.0);
+    scene.add_object(Visibility(ball, RayMask::SHADOW));
+    let penumbra = center(&view, &scene);
+    scene.objects.pop();
+    scene
+        .lights
+        .retain(|light| light.position[0] < 0.0 || light.position[2] < 0.0);
+    let three = center(&view, &scene);
+    assert!(penumbra[0] < lit[0] * 0.8, "{:?} {:?}", penumbra, lit);
+    assert!(
+        (penumbra[0] - three[0]).abs() < 1e-3,
+        "{:?} {:?}",
+        penumbra,
+        three
+    );
+}
+
+#[test]
+fn light_cutoff_skips_lights_too_far_to_matter() {
+    // Lights bringing half of full light from one unit above the middle of
+    // the floor, and a two-hundredth from ten units.
+    let half = 4.0 * std::f32::consts::PI * 0.5;
+    let mut near = floor();
+    near.add_light(Light::new([0.0, 1.0, 0.0], half));
+    let mut both = floor();
+    both.add_light(Light::new([0.0, 1.0, 0.0], half));
+    both.add_light(Light::new([0.0, 10.0, 0.0], half));
+    let view = view_from_above();
+    let alone = center(&view, &near);
+    // The middle pixel looks a little off the middle of the floor.
+    assert!(alone[0] > 110.0 && alone[0] < 127.5, "{:?}", alone);
+    let all = center(&view, &both);
+    assert!((all[0] - alone[0] - 255.0 / 200.0).abs() < 0.1, "{:?}", all);
+    assert_eq!(center(&view.clone().with_light_cutoff(0.01), &both), alone);
+    // Surfaces lit by more than the cutoff keep the light.
+    assert_eq!(center(&view.clone().with_light_cutoff(0.4), &near), alone);
+    assert_eq!(center(&view.with_light_cutoff(0.6), &near), [0.0; 3]);
+}
+
+#[test]
+fn shadow_caches_reuse_results_until_objects_change() {
+    let mut scene = floor();
+    scene.add_light(Light::new([0.0, 2.0, 0.0], 20.0));
+    let view = view_from_above();
+    let uncached = view.render_hdr(&scene);
+    scene.set_shadow_cache(1e-3);
+    assert_eq!(view.render_hdr(&scene), uncached);
+    let cache = scene.shadow_cache.as_ref().unwrap();
+    let found = cache.len();
+    assert!(found > 0);
+    // Seen again, the floor's points are already known.
+    view.render_hdr(&scene);
+    assert_eq!(scene.shadow_cache.as_ref().unwrap().len(), found);
+
+    // A ball hidden from the camera, between the light and the floor, is
+    // a new scene whose shadows are looked up anew.
+    let ball = Sphere::new([0.0, 1.0, 0.0], Rgb([255; 3]), 0.2, 1.0, 0.0);
+    scene.add_object(Visibility(ball.clone(), RayMask::SHADOW));
+    let shadowed = center(&view, &scene);
+    assert_eq!(shadowed, [0.0; 3]);
+    let mut uncached = floor();
+    uncached.add_light(Light::new([0.0, 2.0, 0.0], 20.0));
+    uncached.add_object(Visibility(ball, RayMask::SHADOW));
+    assert_eq!(view.render_hdr(&scene), view.render_hdr(&uncached));
+}
+
+#[test]
+fn cone_traced_shadows_soften_without_noise() {
+    // A ball hidden from the camera, under a light a unit across.
+    let scene_lit_by = |samples| {
+        let mut scene = floor();
+        scene.add_light(Light::new([0.0, 4.0, 0.0], 200.0).with_radius(1.0, samples));
+        scene
+    };
+    let shadowed = |samples| {
+        let mut scene = scene_lit_by(samples);
+        let ball = Sphere::new([0.0, 2.0, 0.0], Rgb([255; 3]), 0.3, 1.0, 0.0);
+        scene.add_object(Visibility(ball, RayMask::SHADOW));
+        scene
+    };
+    let view = View::new(
+        16,
+        3,
+        [0.0, 6.0, 0.0],
+        40.0,
+        [0.0, -1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let row = |view: &View, scene: &Scene| {
+        let image = view.render_hdr(scene);
+        (0..16)
+            .map(|x| image.get_pixel(x, 1)[0])
+            .collect::<Vec<_>>()
+    };
+    let cones = view.clone().with_cone_tracing(true);
+    // One cone for the whole light, whatever its sample count.
+    let soft = row(&cones, &shadowed(4));
+    assert_eq!(row(&cones, &shadowed(64)), soft);
+    // Nothing in the way, nothing shadowed.
+    assert_eq!(row(&cones, &scene_lit_by(4)), row(&view, &scene_lit_by(4)));
+    // The shadow deepens smoothly towards the middle, without going black.
+    let open = row(&view, &scene_lit_by(4));
+    for x in 1..8 {
+        let (darkening, inner) = (open[x] - soft[x], open[x + 1] - soft[x + 1]);
+        assert!(
+            darkening >= 0.0 && inner > darkening,
+            "{:?} {:?}",
+            soft,
+            open
+        );
+        assert!((soft[x] - soft[16 - x]).abs() < 1e-3, "{:?}", soft);
+    }
+    assert!(soft[8] > 0.1 * open[8], "{:?}", soft);
+}
+
+#[test]
+fn soft_shadows_are_sampled_by_the_view_sampler() {
+    // A ball hidden from the camera, under a light a unit across.
+    let shadowed = |samples| {
+        let mut scene = floor();
+        scene.add_light(Light::new([0.0, 4.0, 0.0], 200.0).with_radius(1.0, samples));
+        let ball = Sphere::new([0.0, 2.0, 0.0], Rgb([255; 3]), 0.3, 1.0, 0.0);
+        scene.add_object(Visibility(ball, RayMask::SHADOW));
+        scene
+    };
+    let view = View::new(
+        24,
+        24,
+        [0.0, 6.0, 0.0],
+        40.0,
+        [0.0, -1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let reference = view.render_hdr(&shadowed(4096));
+    let error = |view: View| {
+        let image = view.render_hdr(&shadowed(16));
+        let pixels = image.pixels().iter().zip(reference.pixels());
+        pixels.map(|(a, b)| (a[0] - b[0]).powi(2)).sum::<f32>()
+    };
+    let uniform = error(view.clone().with_sampler(Uniform));
+    let stratified = error(view.clone().with_sampler(Stratified));
+    let halton = error(view.with_sampler(Halton));
+    assert!(stratified < 0.8 * uniform, "{} {}", stratified, uniform);
+    assert!(halton < 0.8 * uniform, "{} {}", halton, uniform);
+}
+
+#[test]
+fn animated_lights_follow_their_keys_and_flicker_within_bounds() {
+    let keys = LightAnimation::Keyframes(vec![(10.0, 2.0), (20.0, 6.0), (30.0, 4.0)]);
+    let expected = [
+        (0.0, 2.0),
+        (10.0, 2.0),
+        (12.5, 3.0),
+        (20.0, 6.0),
+        (25.0, 5.0),
+        (40.0, 4.0),
+    ];
+    for (frame, intensity) in expected {
+        assert!(
+            (keys.intensity_at(frame) - intensity).abs() < 1e-5,
+            "{} {}",
+            frame,
+            keys.intensity_at(frame)
+        );
+    }
+
+    // The floor brightens with the light, frame by frame.
+    let mut scene = floor();
+    scene.add_light(Light::new([0.0, 1.0, 0.0], 1.0).with_animation(keys));
+    let view = view_from_above();
+    let first = center(&view, &scene);
+    scene.set_frame(12.5);
+    let later = center(&view, &scene);
+    assert!(
+        (later[0] - 1.5 * first[0]).abs() < 1e-2,
+        "{:?} {:?}",
+        later,
+        first
+    );
+
+    let flicker = |seed| LightAnimation::Flicker {
+        base: 10.0,
+        amount: 0.3,
+        speed: 0.5,
+        seed,
+    };
+    let frames: Vec<f32> = (0..200)
+        .map(|i| flicker(1).intensity_at(i as f32 * 0.1))
+        .collect();
+    assert!(
+        frames.iter().all(|i| (7.0..=13.0).contains(i)),
+        "{:?}",
+        frames
+    );
+    // Smooth from one tenth of a frame to the next, yet wavering over all.
+    assert!(
+        frames.windows(2).all(|w| (w[0] - w[1]).abs() < 0.5),
+        "{:?}",
+        frames
+    );
+    let spread = frames.iter().fold(0.0f32, |m, i| m.max((i - 10.0).abs()));
+    assert!(spread > 0.5, "{:?}", frames);
+    assert_eq!(flicker(1).intensity_at(33.0 * 0.1), frames[33]);
+    let other: Vec<f32> = (0..200)
+        .map(|i| flicker(2).intensity_at(i as f32 * 0.1))
+        .collect();
+    assert_ne!(other, frames);
+}
+
+#[test]
+fn photon_maps_light_what_mirrors_reflect_onto() {
+    // A mirror wall one unit right of a light two units up, and a ball
+    // hidden from the camera shading the middle of the floor from the
+    // light, but not from its reflection.
+    let mut scene = floor();
+    scene.add_light(Light::new([0.0, 2.0, 0.0], 40.0));
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        0.0,
+        1.0,
+    ));
+    let ball = Sphere::new([0.0, 1.0, 0.0], Rgb([255; 3]), 0.3, 1.0, 0.0);
+    scene.add_object(Visibility(ball, RayMask::SHADOW));
+    let view = view_from_above();
+    assert_eq!(center(&view, &scene), [0.0; 3]);
+
+    // The reflection lights the floor as the light's mirror image would.
+    let mut mirrored = floor();
+    mirrored.add_light(Light::new([2.0, 2.0, 0.0], 40.0));
+    let expected = center(&view, &mirrored);
+    scene.set_caustics(PhotonMap::build(&scene, 100_000, 0));
+    let caustic = center(&view, &scene);
+    assert!(
+        (caustic[0] - expected[0]).abs() < 0.1 * expected[0],
+        "{:?} {:?}",
+        caustic,
+        expected
+    );
+
+    // Without the ball, the caustics alone leave out the direct light.
+    scene.objects.pop();
+    let photons = PhotonMap::build(&scene, 100_000, 0);
+    scene.set_caustics(photons.clone());
+    assert!(center(&view, &scene)[0] > caustic[0] * 1.5);
+    scene.set_caustics(photons.with_caustics_only(true));
+    assert!((center(&view, &scene)[0] - caustic[0]).abs() < 1e-3);
+}
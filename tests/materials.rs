@@ -0,0 +1,488 @@
+//! Materials shade surfaces by the models they describe.
+
+use std::{f32::consts::PI, sync::Arc};
+
+use raytracer::{
+    framebuffer::Framebuffer,
+    image::{GrayImage, Luma, Rgb, RgbImage},
+    inspect::BounceKind,
+    integrator::PathTracer,
+    material::{AlphaMode, Clearcoat, Material, Scatter, Standard},
+    pbr::Pbr,
+    sampler::Rng,
+    scene::*,
+    texture::AlphaMask,
+    view::*,
+};
+
+/// `object` under a white sky, which lights everything it reflects.
+fn under_the_sky(object: impl Object + 'static) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(object);
+    scene.add_object(
+        Sphere::new([0.0; 3], Rgb([0; 3]), 100.0, 1.0, 0.0).with_emission(Rgb([255; 3]), 1.0),
+    );
+    scene
+}
+
+/// A view from the origin down the z axis.
+fn view(size: u32) -> View {
+    View::new(
+        size,
+        size,
+        [0.0; 3],
+        30.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+}
+
+fn lit_pixels(image: &RgbImage) -> usize {
+    image.pixels().filter(|pixel| pixel.0[0] > 0).count()
+}
+
+#[test]
+fn metals_tint_reflections_and_roughness_blurs_them() {
+    let copper = Rgb([255, 128, 0]);
+    let wall = |roughness| {
+        Plane::new(copper, [0.0, 0.0, 1.0], [0.0, 0.0, 3.0], 1.0, 0.0).with_metal(roughness)
+    };
+    // Everything reflected is white, so the metal shows its own color.
+    let tinted = view(4).render(&under_the_sky(wall(0.0)));
+    assert_eq!(tinted.get_pixel(2, 2).0, copper.0);
+
+    // A small ball behind the camera, seen in the metal.
+    let reflecting = |roughness| {
+        let mut scene = Scene::default();
+        scene.add_object(wall(roughness));
+        scene.add_object(
+            Sphere::new([0.0, 0.0, -3.0], Rgb([0; 3]), 0.3, 1.0, 0.0)
+                .with_emission(Rgb([255; 3]), 1.0),
+        );
+        view(32).with_samples(16).render(&scene)
+    };
+    let sharp = reflecting(0.0);
+    let blurred = reflecting(0.3);
+    assert_eq!(sharp.get_pixel(16, 16).0, copper.0);
+    assert!(
+        lit_pixels(&blurred) > 2 * lit_pixels(&sharp),
+        "{} {}",
+        lit_pixels(&blurred),
+        lit_pixels(&sharp)
+    );
+    assert!(blurred.get_pixel(16, 16).0[0] < 255);
+}
+
+#[test]
+fn highlights_narrow_as_shininess_rises() {
+    // A black ball, so that only highlights show, lit from the camera so
+    // that they show head on.
+    let shiny = |shininess| {
+        let mut scene = Scene::default();
+        scene.add_light(Light::new([0.0; 3], 0.1 * 4.0 * PI * 9.0));
+        let ball = Sphere::new([0.0, 0.0, 4.0], Rgb([0; 3]), 1.0, 1.0, 0.5);
+        scene.add_object(if shininess > 0.0 {
+            ball.with_shininess(shininess)
+        } else {
+            ball
+        });
+        scene
+    };
+    // A tenth of the light arrives three units away, and the peak gives it
+    // back scaled by (n + 8) / 8 and the specular coefficient.
+    let peak = view(4).render_hdr(&shiny(20.0)).get_pixel(2, 2)[0];
+    let expected = 0.1 * 28.0 / 8.0 * 0.5 * 255.0;
+    assert!((peak - expected).abs() < 1e-2 * expected, "{}", peak);
+    assert_eq!(lit_pixels(&view(32).render(&shiny(0.0))), 0);
+    let broad = lit_pixels(&view(32).render(&shiny(10.0)));
+    let narrow = lit_pixels(&view(32).render(&shiny(100.0)));
+    assert!(narrow > 0 && narrow < broad, "{} {}", narrow, broad);
+}
+
+/// Share of light arriving along `to_eye` that `pbr` reflects in all, the
+/// reflectance integrated over the hemisphere above the normal +z.
+fn reflected_energy(pbr: &Pbr, to_eye: [f32; 3]) -> f32 {
+    let (steps_theta, steps_phi) = (1000, 256);
+    let (d_theta, d_phi) = (PI / 2.0 / steps_theta as f32, 2.0 * PI / steps_phi as f32);
+    let mut energy = 0.0;
+    for i in 0..steps_theta {
+        let theta = (i as f32 + 0.5) * d_theta;
+        for j in 0..steps_phi {
+            let phi = (j as f32 + 0.5) * d_phi;
+            let to_light = [
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ];
+            let [r, ..] = pbr.specular([1.0; 3], [0.0, 0.0, 1.0], to_light, to_eye);
+            energy += r * theta.cos() * theta.sin() * d_theta * d_phi;
+        }
+    }
+    energy
+}
+
+#[test]
+fn ggx_reflects_no_more_light_than_arrives() {
+    let metal = |roughness| Pbr {
+        metallic: 1.0,
+        roughness,
+        anisotropy: None,
+    };
+    let head_on = [0.0, 0.0, 1.0];
+    let slanted = [(PI / 3.0).sin(), 0.0, (PI / 3.0).cos()];
+    // Smooth white metal reflects nearly everything; rougher metal loses
+    // some to light bouncing between microfacets, which single scattering
+    // leaves out.
+    let smooth = reflected_energy(&metal(0.3), head_on);
+    assert!((smooth - 1.0).abs() < 0.03, "{}", smooth);
+    let mut last = smooth;
+    for roughness in [0.5, 0.8, 1.0] {
+        let energy = reflected_energy(&metal(roughness), head_on);
+        assert!(energy > 0.25 && energy < last, "{} {}", roughness, energy);
+        last = energy;
+        let energy = reflected_energy(&metal(roughness), slanted);
+        assert!(energy > 0.25 && energy < 1.0, "{} {}", roughness, energy);
+    }
+    // Plastic reflects its 4% head on.
+    let plastic = Pbr {
+        metallic: 0.0,
+        ..metal(0.3)
+    };
+    let energy = reflected_energy(&plastic, head_on);
+    assert!((energy - 0.04).abs() < 0.005, "{}", energy);
+}
+
+#[test]
+fn objects_sharing_a_material_shade_alike() {
+    let glow: Arc<dyn Material> =
+        Arc::new(Standard::new(1.0, 0.0).with_emission(Rgb([255; 3]), 0.5));
+    let balls = |ball: &dyn Fn(f32) -> Sphere| {
+        let mut scene = Scene::default();
+        scene.add_object(ball(-1.5));
+        scene.add_object(ball(1.5));
+        scene
+    };
+    let at = |x| Sphere::new([x, 0.0, 6.0], Rgb([0; 3]), 1.0, 1.0, 0.0);
+    let shared = balls(&|x| at(x).with_material(glow.clone()));
+    let own = balls(&|x| at(x).with_emission(Rgb([255; 3]), 0.5));
+    assert_eq!(view(16).render(&shared), view(16).render(&own));
+    assert_eq!(Arc::strong_count(&glow), 3);
+    assert_eq!(shared.summary().materials.len(), 1);
+}
+
+/// A custom material letting rays straight through, keeping `filter` of
+/// their light, and glowing red left of x = 0.
+struct Filter([f32; 3]);
+
+impl Material for Filter {
+    fn scatter(&self, ray: &Ray, hit: &Hit, _rng: &mut Rng) -> Scatter {
+        Scatter::Continue(Ray::new(hit.point, ray.direction), self.0)
+    }
+
+    fn emitted(&self, hit: &Hit) -> [f32; 3] {
+        if hit.point[0] < 0.0 {
+            [0.5, 0.0, 0.0]
+        } else {
+            [0.0; 3]
+        }
+    }
+}
+
+#[test]
+fn custom_materials_scatter_and_emit_for_both_integrators() {
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 1.0, 0.0)
+            .with_material(Arc::new(Filter([1.0, 0.5, 0.0]))),
+    );
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 4.0], 1.0, 0.0)
+            .with_emission(Rgb([255; 3]), 1.0),
+    );
+    let whitted = view(4);
+    let path_tracer = view(4).with_integrator(PathTracer);
+    for view in [whitted, path_tracer] {
+        let image = view.render_hdr(&scene);
+        let right = image.get_pixel(2, 2);
+        let left = image.get_pixel(1, 2);
+        for (seen, expected) in [(right, [255.0, 127.5, 0.0]), (left, [382.5, 127.5, 0.0])] {
+            for (s, e) in seen.iter().zip(expected) {
+                assert!((s - e).abs() < 1e-2, "{:?} {:?}", seen, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn rough_materials_split_rays_into_their_own_glossy_samples() {
+    // A rough white metal wall reflecting a glowing ball behind the camera.
+    let render = |samples, cap| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Plane::new(Rgb([255; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 3.0], 1.0, 0.0)
+                .with_metal(0.3)
+                .with_glossy_samples(samples),
+        );
+        scene.add_object(
+            Sphere::new([0.0, 0.0, -3.0], Rgb([0; 3]), 0.5, 1.0, 0.0)
+                .with_emission(Rgb([255; 3]), 1.0),
+        );
+        let view = view(16).with_max_glossy_samples(cap);
+        let reflections = view
+            .debug_pixel(&scene, 8, 8)
+            .bounces
+            .iter()
+            .filter(|bounce| bounce.kind == BounceKind::Reflection)
+            .count();
+        (reflections, view.render_hdr(&scene))
+    };
+    let (reference, smooth) = render(256, u32::MAX);
+    assert_eq!(reference, 256);
+    let error = |image: &Framebuffer| {
+        let mut sum = 0.0;
+        for y in 0..16 {
+            for x in 0..16 {
+                sum += (image.get_pixel(x, y)[0] - smooth.get_pixel(x, y)[0]).powi(2);
+            }
+        }
+        sum
+    };
+    let (one, noisy) = render(1, u32::MAX);
+    let (sixteen, finer) = render(16, u32::MAX);
+    assert_eq!((one, sixteen), (1, 16));
+    assert!(
+        error(&finer) < 0.5 * error(&noisy),
+        "{} {}",
+        error(&finer),
+        error(&noisy)
+    );
+    // The view caps them, each carrying a quarter of the light.
+    let (capped, quarters) = render(16, 4);
+    assert_eq!(capped, 4);
+    for y in 0..16 {
+        for x in 0..16 {
+            let level = quarters.get_pixel(x, y)[0] / (255.0 / 4.0);
+            assert!(
+                (level - level.round()).abs() < 1e-3,
+                "{} {} {}",
+                x,
+                y,
+                level
+            );
+        }
+    }
+}
+
+#[test]
+fn cutouts_let_rays_through_by_coverage() {
+    // A blue glowing screen, cut away on the left and half covering the
+    // right, before a red glowing wall.
+    let screen = |mode| {
+        let mask = GrayImage::from_fn(16, 1, |x, _| Luma([if x < 8 { 0 } else { 128 }]));
+        let mut scene = Scene::default();
+        scene.add_object(
+            Plane::from_points(
+                Rgb([0; 3]),
+                [4.0, 4.0, 2.0],
+                [4.0, -4.0, 2.0],
+                [-4.0, -4.0, 2.0],
+                1.0,
+                0.0,
+            )
+            .with_emission(Rgb([0, 0, 255]), 1.0)
+            .with_cutout(AlphaMask::new(mask), mode),
+        );
+        scene.add_object(
+            Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 4.0], 1.0, 0.0)
+                .with_emission(Rgb([255, 0, 0]), 1.0),
+        );
+        scene
+    };
+    let coverage = 128.0 / 255.0;
+    let blended = view(8).render_hdr(&screen(AlphaMode::Blend));
+    for y in 0..8 {
+        for x in 0..8 {
+            let pixel = blended.get_pixel(x, y);
+            let expected = if x < 4 {
+                [255.0, 0.0, 0.0]
+            } else {
+                [255.0 * (1.0 - coverage), 0.0, 255.0 * coverage]
+            };
+            for (p, e) in pixel.iter().zip(expected) {
+                assert!((p - e).abs() < 0.5, "{} {} {:?}", x, y, pixel);
+            }
+        }
+    }
+    // Stochastic rays either stop or pass, about half of each on the right.
+    let stochastic = view(8).render_hdr(&screen(AlphaMode::Stochastic));
+    let mut stopped = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let pixel = stochastic.get_pixel(x, y);
+            if x < 4 {
+                assert_eq!(pixel, [255.0, 0.0, 0.0], "{} {}", x, y);
+            } else if pixel == [0.0, 0.0, 255.0] {
+                stopped += 1;
+            } else {
+                assert_eq!(pixel, [255.0, 0.0, 0.0], "{} {}", x, y);
+            }
+        }
+    }
+    assert!((8..=24).contains(&stopped), "{}", stopped);
+}
+
+#[test]
+fn pbr_reflections_blur_by_the_ggx_distribution() {
+    // Half of GGX's microfacets lie within atan(α) of the normal.
+    for roughness in [0.2, 0.5, 0.9] {
+        let pbr = Pbr {
+            metallic: 1.0,
+            roughness,
+            anisotropy: None,
+        };
+        let alpha: f32 = roughness * roughness;
+        let steps = 64;
+        let mut within = 0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let (u, v) = ((i as f32 + 0.5) / steps as f32, j as f32 / steps as f32);
+                let facet = pbr.sample_microfacet([0.0, 0.0, 1.0], None, u, v);
+                assert!(facet[2] > 0.0, "{:?}", facet);
+                if facet[2].acos() < alpha.atan() {
+                    within += 1;
+                }
+            }
+        }
+        let share = within as f32 / (steps * steps) as f32;
+        assert!((share - 0.5).abs() < 0.02, "{} {}", roughness, share);
+    }
+
+    // Rougher metal spreads a small ball's reflection wider: the mean
+    // distance of its light from the middle of the image grows.
+    let spread = |roughness| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Plane::new(Rgb([255; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 3.0], 1.0, 0.0)
+                .with_pbr(1.0, roughness),
+        );
+        scene.add_object(
+            Sphere::new([0.0, 0.0, -3.0], Rgb([0; 3]), 0.3, 1.0, 0.0)
+                .with_emission(Rgb([255; 3]), 1.0),
+        );
+        let image = view(32).with_samples(16).render_hdr(&scene);
+        let (mut light, mut distance) = (0.0, 0.0);
+        for y in 0..32 {
+            for x in 0..32 {
+                let seen = image.get_pixel(x, y)[0];
+                light += seen;
+                distance += seen * (x as f32 - 16.0).hypot(y as f32 - 16.0);
+            }
+        }
+        distance / light
+    };
+    let (sharp, rough, rougher) = (spread(0.0), spread(0.3), spread(0.5));
+    assert!(
+        sharp < rough && rough < rougher,
+        "{} {} {}",
+        sharp,
+        rough,
+        rougher
+    );
+}
+
+#[test]
+fn anisotropic_highlights_stretch_along_the_rougher_direction() {
+    let brushed = |roughness_u, roughness_v| Pbr {
+        metallic: 1.0,
+        roughness: 0.4,
+        anisotropy: Some([roughness_u, roughness_v]),
+    };
+    let normal = [0.0, 0.0, 1.0];
+    let tangent = Some([1.0, 0.0, 0.0]);
+    let eye = [0.0, 0.0, 1.0];
+    let angle = 20f32.to_radians();
+    let along = [angle.sin(), 0.0, angle.cos()];
+    let across = [0.0, angle.sin(), angle.cos()];
+    let highlight = |pbr: &Pbr, light| pbr.specular_along([1.0; 3], normal, tangent, light, eye)[0];
+    // Rough along the tangent, light off to that side still glints.
+    let along_u = brushed(0.6, 0.2);
+    assert!(
+        highlight(&along_u, along) > 10.0 * highlight(&along_u, across),
+        "{} {}",
+        highlight(&along_u, along),
+        highlight(&along_u, across)
+    );
+    let along_v = brushed(0.2, 0.6);
+    assert!((highlight(&along_v, across) - highlight(&along_u, along)).abs() < 1e-3);
+    // Equal roughness both ways, or no tangent, is the isotropic lobe.
+    for (pbr, tangent) in [(brushed(0.4, 0.4), tangent), (along_u, None)] {
+        for light in [along, across] {
+            let isotropic = Pbr {
+                anisotropy: None,
+                ..pbr
+            };
+            let seen = pbr.specular_along([1.0; 3], normal, tangent, light, eye)[0];
+            let expected = isotropic.specular([1.0; 3], normal, light, eye)[0];
+            assert!(
+                (seen - expected).abs() < 1e-4 * expected,
+                "{} {}",
+                seen,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn clear_coats_mirror_more_at_grazing_angles_over_the_surface_below() {
+    let coat = Clearcoat {
+        strength: 1.0,
+        roughness: 0.0,
+    };
+    assert!((coat.reflectance(1.0) - 0.04).abs() < 1e-4);
+    assert!((coat.reflectance(0.0) - 1.0).abs() < 1e-4);
+    let half = Clearcoat {
+        strength: 0.5,
+        ..coat
+    };
+    assert!((half.reflectance(0.3) - 0.5 * coat.reflectance(0.3)).abs() < 1e-6);
+
+    // A red ball lit from beside the camera under a white sky: only the
+    // coat mirrors the sky's green and blue.
+    let render = |coat: Option<f32>| {
+        let mut ball = Sphere::new([0.0, 0.0, 3.0], Rgb([255, 0, 0]), 1.0, 1.0, 0.0);
+        if let Some(roughness) = coat {
+            ball = ball.with_clearcoat(1.0, roughness);
+        }
+        let mut scene = under_the_sky(ball);
+        scene.add_light(Light::new([1.0, 1.0, 0.5], 20.0));
+        view(16).render_hdr(&scene)
+    };
+    let bare = render(None);
+    let coated = render(Some(0.0));
+    let center = coated.get_pixel(8, 8);
+    assert_eq!(bare.get_pixel(8, 8)[1], 0.0);
+    let mirrored = coat.reflectance(1.0) * 255.0;
+    assert!((center[1] - mirrored).abs() < 0.5, "{:?}", center);
+    let base = (1.0 - coat.reflectance(1.0)) * bare.get_pixel(8, 8)[0];
+    assert!((center[0] - base - center[1]).abs() < 0.5, "{:?}", center);
+    let edge = coated.get_pixel(8, 2);
+    assert!(edge[1] > center[1], "{:?} {:?}", edge, center);
+
+    // In the dark, only the light's highlight shows in the coat: a point
+    // for a smooth one, spread over the ball by a rough one.
+    let highlit = |roughness| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Sphere::new([0.0, 0.0, 3.0], Rgb([255, 0, 0]), 1.0, 1.0, 0.0)
+                .with_clearcoat(1.0, roughness),
+        );
+        scene.add_light(Light::new([1.0, 1.0, 0.5], 20.0));
+        let image = view(16).render_hdr(&scene);
+        image.pixels().iter().filter(|pixel| pixel[1] > 1.0).count()
+    };
+    assert_eq!(highlit(0.0), 0);
+    assert!(highlit(0.3) > 10, "{}", highlit(0.3));
+}
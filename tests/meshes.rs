@@ -0,0 +1,72 @@
+//! Meshes are closed where their triangles meet, and hits on them lie on
+//! their surface.
+
+use raytracer::{
+    image::Rgb,
+    mesh::{Mesh, Triangle},
+    scene::*,
+    view::Ray,
+    Vecf,
+};
+
+/// `sides` triangles fanned around `center` on the plane z = `depth`,
+/// their outer corners at irregular distances so that shared edges run at
+/// awkward angles.
+fn fan(center: [f32; 2], depth: f32, sides: usize) -> (Vec<Vecf>, Mesh) {
+    let ring: Vec<Vecf> = (0..sides)
+        .map(|i| {
+            let angle = i as f32 / sides as f32 * std::f32::consts::TAU + 0.1;
+            let radius = 1.0 + 0.37 * (i as f32 * 1.7).sin();
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+                depth,
+            ]
+        })
+        .collect();
+    let middle = [center[0], center[1], depth];
+    let triangles = (0..sides)
+        .map(|i| Triangle {
+            vertices: [middle, ring[i], ring[(i + 1) % sides]],
+            uvs: None,
+            colors: None,
+        })
+        .collect();
+    let mut points = vec![middle];
+    points.extend(ring);
+    (points, Mesh::new(triangles, Rgb([255; 3]), 1.0, 0.0))
+}
+
+#[test]
+fn rays_through_shared_edges_and_corners_never_slip_through() {
+    let depth = 2.7;
+    let (points, mesh) = fan([0.13, -0.29], depth, 7);
+    let middle = points[0];
+    let origins = [[0.0; 3], [0.31, 0.77, -1.3], [-2.1, 0.4, 0.9]];
+    for origin in origins {
+        // The middle corner, shared by every triangle, and points along
+        // each shared edge.
+        let mut targets = vec![middle];
+        for &corner in &points[1..] {
+            for step in 1..40 {
+                let t = step as f32 / 40.0;
+                targets.push([0, 1, 2].map(|i| middle[i] + (corner[i] - middle[i]) * t));
+            }
+        }
+        for target in targets {
+            let direction = [0, 1, 2].map(|i| target[i] - origin[i]);
+            let ray = Ray::new(origin, direction);
+            let (distance, point) = mesh.intersect(&ray);
+            assert!(
+                distance > 0.0 && distance.is_finite(),
+                "{:?} {:?} {}",
+                origin,
+                target,
+                distance
+            );
+            // Rebuilt on the triangle rather than stepped along the ray.
+            let hit = mesh.hit_at(&ray, point);
+            assert!((hit.point[2] - depth).abs() < 1e-6, "{:?}", hit.point);
+        }
+    }
+}
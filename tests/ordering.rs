@@ -0,0 +1,89 @@
+//! Renders must not depend on the order objects were added in, so that
+//! procedurally generated scenes are reproducible.
+
+use raytracer::{
+    image::{Rgb, RgbImage},
+    integrator::PathTracer,
+    sampler::Rng,
+    scene::*,
+    view::*,
+};
+
+/// Disjoint spheres of random sizes and colors on a grid, in a box of
+/// planes, with a pair of coincident planes and a pair of coincident
+/// spheres colored differently, and a pair of coincident spheres alike but
+/// for their materials.
+fn objects(seed: u32) -> Vec<Box<dyn Object>> {
+    let mut rng = Rng::new(seed, 0, 0, 0);
+    let mut objects: Vec<Box<dyn Object>> = Vec::new();
+    for i in 0..16 {
+        let center = [(i % 4) as f32 - 1.5, (i / 4) as f32 - 1.5, 5.0];
+        let color = Rgb([0; 3].map(|_: u8| (rng.next_f32() * 256.0) as u8));
+        let radius = 0.1 + rng.next_f32() * 0.35;
+        let specular = rng.next_f32();
+        objects.push(Box::new(Sphere::new(center, color, radius, 0.8, specular)));
+    }
+    for (normal, point) in [
+        ([0.0, -1.0, 0.0], [0.0, -2.0, 0.0]),
+        ([0.0, 0.0, 1.0], [0.0, 0.0, 7.0]),
+        ([1.0, 0.0, 0.0], [3.0, 0.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [-3.0, 0.0, 0.0]),
+    ] {
+        objects.push(Box::new(Plane::new(Rgb([200; 3]), normal, point, 0.6, 0.2)));
+    }
+    for color in [Rgb([255, 0, 0]), Rgb([0, 255, 0])] {
+        objects.push(Box::new(Plane::new(
+            color,
+            [0.0, 1.0, 0.0],
+            [0.0, 2.0, 0.0],
+            0.6,
+            0.0,
+        )));
+        objects.push(Box::new(Sphere::new([0.0, 0.0, 3.0], color, 0.3, 0.9, 0.0)));
+    }
+    let sphere = Sphere::new([1.0, 0.5, 3.0], Rgb([90, 160, 220]), 0.3, 0.7, 0.3);
+    objects.push(Box::new(sphere.clone().with_shininess(40.0)));
+    objects.push(Box::new(sphere.with_clearcoat(1.0, 0.0)));
+    objects
+}
+
+fn render(view: &View, objects: Vec<Box<dyn Object>>) -> RgbImage {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 1.5, 2.0], 20.0));
+    scene.add_light(Light::new([2.0, -1.0, 4.0], 10.0).with_radius(0.2, 4));
+    scene.objects = objects;
+    view.render(&scene)
+}
+
+#[test]
+fn object_order_does_not_change_renders() {
+    let view = View::new(
+        48,
+        32,
+        [0.0, 0.0, 0.0],
+        90.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([50, 100, 200]),
+        1e-3,
+    );
+    for view in [
+        view.clone(),
+        view.with_integrator(PathTracer).with_samples(2),
+    ] {
+        for seed in 0..4 {
+            let expected = render(&view, objects(seed));
+            let mut reversed = objects(seed);
+            reversed.reverse();
+            assert!(render(&view, reversed) == expected, "seed {}", seed);
+            // A shuffle by the scene's own random numbers.
+            let mut shuffled = objects(seed);
+            let mut rng = Rng::new(seed, 1, 0, 0);
+            for i in (1..shuffled.len()).rev() {
+                let j = (rng.next_f32() * (i + 1) as f32) as usize;
+                shuffled.swap(i, j.min(i));
+            }
+            assert!(render(&view, shuffled) == expected, "seed {}", seed);
+        }
+    }
+}
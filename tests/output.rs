@@ -0,0 +1,214 @@
+//! Renders keep light above white until they are developed to 8 bits,
+//! and the HDR and 16-bit formats store it as rendered.
+
+use std::{convert::TryInto, time::Duration};
+
+use raytracer::{
+    exr::ExrImage, framebuffer::Framebuffer, image::Rgb, integrator::PathTracer,
+    report::RenderReport, scene::*, view::*, warning::RenderWarning,
+};
+#[cfg(feature = "fs")]
+use raytracer::{
+    image::hdr::HdrDecoder,
+    metadata::{MetadataError, RenderInfo},
+};
+
+/// A black wall facing the camera, glowing white `strength` times
+/// brighter than white.
+fn glowing_wall(strength: f32) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 1.0, 0.0)
+            .with_emission(Rgb([255; 3]), strength),
+    );
+    scene
+}
+
+fn view() -> View {
+    View::new(8, 6, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3)
+}
+
+#[test]
+fn framebuffer_keeps_light_above_white() {
+    let view = view();
+    let scene = glowing_wall(10.0);
+    let hdr = view.render_hdr(&scene);
+    let center = hdr.get_pixel(4, 3);
+    assert!(center.iter().all(|&c| c > 255.0 * 4.0), "{:?}", center);
+    assert_eq!(view.render(&scene), hdr.to_rgb8());
+    assert_eq!(*hdr.to_rgb8().get_pixel(4, 3), Rgb([255; 3]));
+    // Developed 16 times darker, the center shows what clipping hid.
+    let dark = hdr.to_rgb8_exposed(-4.0);
+    let expected = center.map(|c| (c / 16.0) as u8);
+    assert_eq!(*dark.get_pixel(4, 3), Rgb(expected));
+    assert!(expected.iter().all(|&c| c < 255), "{:?}", expected);
+}
+
+#[test]
+fn exr_stores_channels_as_floats() {
+    let mut framebuffer = Framebuffer::new(2, 1);
+    framebuffer.put_pixel(0, 0, [510.0, 255.0, 0.0]);
+    framebuffer.put_pixel(1, 0, [25.5, 51.0, 2550.0]);
+    let image = ExrImage::new(&framebuffer).with_depth(vec![5.0, 6.5]);
+    let mut bytes = Vec::new();
+    image.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes[..4], [0x76, 0x2f, 0x31, 0x01]);
+    // One scanline of the channels in alphabetical order, each a row of
+    // floats with 1.0 as white, at the offset the table gives.
+    let floats = |bytes: &[u8]| -> Vec<f32> {
+        bytes
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    let line = 4 * 2 * 4;
+    let chunk = bytes.len() - line - 8;
+    let offset = u64::from_le_bytes(bytes[chunk - 8..chunk].try_into().unwrap());
+    assert_eq!(offset as usize, chunk);
+    assert_eq!(bytes[chunk..chunk + 4], 0i32.to_le_bytes());
+    assert_eq!(bytes[chunk + 4..chunk + 8], (line as i32).to_le_bytes());
+    assert_eq!(
+        floats(&bytes[chunk + 8..]),
+        [0.0, 10.0, 1.0, 0.2, 2.0, 0.1, 5.0, 6.5]
+    );
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn radiance_hdr_round_trips() {
+    let scene = glowing_wall(10.0);
+    let hdr = view().render_hdr(&scene);
+    let mut bytes = Vec::new();
+    hdr.write_hdr(&mut bytes).unwrap();
+    let decoder = HdrDecoder::new(&bytes[..]).unwrap();
+    let metadata = decoder.metadata();
+    assert_eq!((metadata.width, metadata.height), hdr.dimensions());
+    let pixels = decoder.read_image_hdr().unwrap();
+    // Radiance images keep 8 bits of mantissa per channel.
+    for (read, written) in pixels.iter().zip(hdr.pixels()) {
+        for (r, w) in read.0.iter().zip(written) {
+            assert!((r - w / 255.0).abs() <= w / 255.0 / 64.0, "{} {}", r, w);
+        }
+    }
+    assert!(pixels[3 * 8 + 4].0[0] > 4.0, "{:?}", pixels[3 * 8 + 4]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn png16_keeps_fractions_of_8_bit_steps() {
+    let mut framebuffer = Framebuffer::new(3, 1);
+    framebuffer.put_pixel(0, 0, [0.0, 255.0, 1000.0]);
+    framebuffer.put_pixel(1, 0, [100.25, 100.5, 100.75]);
+    framebuffer.put_pixel(2, 0, [-1.0, 1.0, 254.999]);
+    let mut bytes = Vec::new();
+    framebuffer.write_png16(&mut bytes).unwrap();
+    let image = raytracer::image::load_from_memory(&bytes).unwrap();
+    let image = image.as_rgb16().unwrap();
+    assert_eq!(image.dimensions(), (3, 1));
+    assert_eq!(image.get_pixel(0, 0).0, [0, 65535, 65535]);
+    assert_eq!(image.get_pixel(2, 0).0, [0, 257, 65534]);
+    // Shades 8 bits round to the same step stay apart.
+    assert_eq!(image.get_pixel(1, 0).0, [25764, 25828, 25892]);
+    assert_eq!(framebuffer.to_rgb8().get_pixel(1, 0).0, [100; 3]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn render_info_round_trips_through_png_files() {
+    let view = view().with_samples(16).with_seed(3);
+    let scene = glowing_wall(10.0);
+    let image = view.render(&scene);
+    let info = RenderInfo::new(&view, &scene).with_entry("Scene", "wall ≠ floor");
+    assert_eq!(info.get("Resolution"), Some("8x6"));
+    assert_eq!(info.get("Samples"), Some("16"));
+    assert_eq!(info.get("Seed"), Some("3"));
+    let mut bytes = Vec::new();
+    info.write_png(&image, &mut bytes).unwrap();
+
+    // Viewers still see the image as rendered.
+    let decoded = raytracer::image::load_from_memory(&bytes).unwrap();
+    assert_eq!(decoded.to_rgb(), image);
+    let read = RenderInfo::read_png(&bytes[..]).unwrap();
+    assert_eq!(
+        read.entries()[..read.entries().len() - 1],
+        info.entries()[..info.entries().len() - 1]
+    );
+    assert_eq!(read.get("Scene"), Some("wall ? floor"));
+    assert!(matches!(
+        RenderInfo::read_png(&b"GIF89a.."[..]),
+        Err(MetadataError::Invalid(_))
+    ));
+
+    // The scene hash tells apart scenes rendered with the same settings.
+    let hash = |scene: &Scene| {
+        RenderInfo::new(&view, scene)
+            .get("Scene hash")
+            .map(String::from)
+    };
+    assert_eq!(hash(&glowing_wall(10.0)), hash(&scene));
+    assert_ne!(hash(&glowing_wall(5.0)), hash(&scene));
+}
+
+#[test]
+fn render_reports_write_their_entries_as_json() {
+    let view = view().with_integrator(PathTracer);
+    let report = RenderReport::new(&view, &Scene::default())
+        .with_setting("Scene", "a \"quoted\"\\name")
+        .with_timing("render", Duration::from_millis(1500))
+        .with_stat("rays per second", f64::INFINITY)
+        .with_output("beauty", "out/beauty.png");
+    // Likely mistakes are pointed out.
+    assert_eq!(
+        report.warnings(),
+        [
+            "the scene has no objects",
+            "the scene has no lights",
+            "the path integrator with one sample per pixel is noisy",
+        ]
+    );
+    assert_eq!(report.settings().get("Integrator"), Some("path"));
+
+    let mut bytes = Vec::new();
+    report.write_json(&mut bytes).unwrap();
+    let json = String::from_utf8(bytes).unwrap();
+    // The entries of `RenderInfo::new`, then the one added, escaped.
+    let entries = report.settings().entries();
+    let plain: Vec<String> = entries[..entries.len() - 1]
+        .iter()
+        .map(|(key, value)| format!("    \"{}\": \"{}\",\n", key, value))
+        .collect();
+    let settings = format!(
+        "{{\n  \"settings\": {{\n{}{}\n  }},\n",
+        plain.concat(),
+        r#"    "Scene": "a \"quoted\"\\name""#
+    );
+    assert!(json.starts_with(&settings), "{}", json);
+    let rest = &json[json.find("  \"timings\"").unwrap()..];
+    let stats: String = RenderWarning::ALL
+        .iter()
+        .map(|warning| format!(",\n    \"{}\": 0", warning.name()))
+        .collect();
+    let expected = format!(
+        r#"  "timings": {{
+    "render": 1.5
+  }},
+  "stats": {{
+    "pixels": 48,
+    "objects": 0,
+    "lights": 0{},
+    "rays per second": null
+  }},
+  "warnings": [
+    "the scene has no objects",
+    "the scene has no lights",
+    "the path integrator with one sample per pixel is noisy"
+  ],
+  "outputs": {{
+    "beauty": "out/beauty.png"
+  }}
+}}
+"#,
+        stats
+    );
+    assert_eq!(rest, expected);
+}
@@ -0,0 +1,120 @@
+//! Effects applied to finished renders add what they describe, where
+//! they describe, and nothing else.
+
+use raytracer::{
+    image::{Rgb, RgbImage},
+    post::*,
+    scene::*,
+    view::*,
+};
+
+fn view() -> View {
+    View::new(
+        48,
+        32,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+}
+
+/// An empty scene with a light up and to the right of the middle of the
+/// view, with its flares added to a black image.
+fn flared(scene: &mut Scene, flare: &LensFlare) -> RgbImage {
+    scene.add_light(Light::new([0.6, 0.3, 3.0], 100.0));
+    let mut image = RgbImage::new(48, 32);
+    flare.apply(&view(), scene, &mut image);
+    image
+}
+
+fn brightness(image: &RgbImage, x: u32, y: u32) -> u32 {
+    image.get_pixel(x, y).0.iter().map(|&c| c as u32).sum()
+}
+
+#[test]
+fn lens_flares_glow_around_visible_lights() {
+    let flare = LensFlare::new();
+    let image = flared(&mut Scene::default(), &flare);
+    let [lx, ly] = view().project([0.6, 0.3, 3.0]).unwrap();
+    let (lx, ly) = (lx.round() as u32, ly.round() as u32);
+    assert!(lx > 24 && ly < 16, "{} {}", lx, ly);
+    // Brightest at the light, fading away from it.
+    let peak = brightness(&image, lx, ly);
+    assert!(peak > 0);
+    assert!(image
+        .enumerate_pixels()
+        .all(|(x, y, _)| brightness(&image, x, y) <= peak));
+    assert!(brightness(&image, lx, ly + 6) < peak);
+    // Ghosts sit across the middle, down and to the left.
+    let ghosted = image
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| *x < 24 && *y > 16 && pixel.0 != [0; 3])
+        .count();
+    assert!(ghosted > 0);
+    let plain = flared(&mut Scene::default(), &flare.with_ghosts(0));
+    assert!(plain
+        .enumerate_pixels()
+        .all(|(x, y, _)| brightness(&plain, x, y) <= brightness(&image, x, y)));
+    let faint = flared(&mut Scene::default(), &flare.with_strength(0.2));
+    assert!(brightness(&faint, lx, ly) < peak);
+
+    // A ball between the camera and the light hides it, flares and all.
+    let mut hidden = Scene::default();
+    hidden.add_object(Sphere::new([0.3, 0.15, 1.5], Rgb([255; 3]), 0.3, 1.0, 0.0));
+    assert!(flared(&mut hidden, &flare).pixels().all(|p| p.0 == [0; 3]));
+    // As do lights behind the camera.
+    let mut behind = Scene::default();
+    behind.add_light(Light::new([0.0, 0.0, -3.0], 100.0));
+    let mut image = RgbImage::new(48, 32);
+    flare.apply(&view(), &behind, &mut image);
+    assert!(image.pixels().all(|p| p.0 == [0; 3]));
+}
+
+/// `burn_in` drawn over a flat grey 64 by 40 image.
+fn burnt(burn_in: &BurnIn) -> RgbImage {
+    let mut image = RgbImage::from_pixel(64, 40, Rgb([100; 3]));
+    burn_in.apply(&mut image);
+    image
+}
+
+/// Checks that `image` is darkened grey with white text in the box with
+/// corners (`left`, `top`) and (`right`, `bottom`), exclusive, and
+/// untouched outside.
+fn assert_boxed(image: &RgbImage, left: u32, top: u32, right: u32, bottom: u32) {
+    let mut text = 0;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if (left..right).contains(&x) && (top..bottom).contains(&y) {
+            assert!(pixel.0 == [50; 3] || pixel.0 == [255; 3], "{} {}", x, y);
+            text += (pixel.0 == [255; 3]) as u32;
+        } else {
+            assert_eq!(pixel.0, [100; 3], "{} {}", x, y);
+        }
+    }
+    assert!(text > 0);
+}
+
+#[test]
+fn burn_ins_write_their_lines_in_a_corner_box() {
+    // Two lines of at most seven characters: a box of 7 six pixel wide
+    // cells and 2 eight pixel tall ones, with a pixel of padding, four
+    // pixels in from the corner.
+    let burn_in = BurnIn::new().with_line("ab").with_frame(3.0);
+    let image = burnt(&burn_in);
+    assert_boxed(&image, 4, 40 - 4 - 17, 4 + 43, 40 - 4);
+    // The font only has capitals.
+    assert_eq!(burnt(&BurnIn::new().with_line("AB").with_frame(3.0)), image);
+    assert_ne!(burnt(&BurnIn::new().with_line("AC").with_frame(3.0)), image);
+
+    let top_right = burnt(&burn_in.clone().with_corner(Corner::TopRight));
+    assert_boxed(&top_right, 64 - 4 - 43, 4, 64 - 4, 4 + 17);
+    let doubled = burnt(&burn_in.clone().with_corner(Corner::TopLeft).with_scale(2));
+    assert_boxed(&doubled, 8, 8, 8 + 86, 8 + 34);
+
+    assert_eq!(
+        burnt(&BurnIn::new()),
+        RgbImage::from_pixel(64, 40, Rgb([100; 3]))
+    );
+}
@@ -0,0 +1,57 @@
+//! Ray queries against a scene see only the objects they are asked about.
+
+use raytracer::{image::Rgb, scene::*, view::Ray};
+
+/// Two balls one behind the other along the z axis.
+fn balls() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0));
+    scene.add_object(Sphere::new([0.0, 0.0, 6.0], Rgb([255; 3]), 1.0, 1.0, 0.0));
+    scene
+}
+
+#[test]
+fn filtered_queries_skip_rejected_objects() {
+    let scene = balls();
+    let ray = Ray::new([0.0; 3], [0.0, 0.0, 1.0]);
+    let hit = scene.raycast(&ray).unwrap();
+    assert_eq!(hit.index, 0);
+    assert!((hit.distance - 2.0).abs() < 1e-4, "{:?}", hit);
+    let hit = scene.raycast_filtered(&ray, |index, _| index != 0).unwrap();
+    assert_eq!(hit.index, 1);
+    assert!((hit.distance - 5.0).abs() < 1e-4, "{:?}", hit);
+    assert!(scene.raycast_filtered(&ray, |_, _| false).is_none());
+
+    assert!(scene.occluded(&ray, 4.0));
+    assert!(!scene.occluded(&ray, 1.5));
+    assert!(!scene.occluded_filtered(&ray, 4.0, |index, _| index != 0));
+    assert!(scene.occluded_filtered(&ray, 6.0, |index, _| index != 0));
+}
+
+#[test]
+fn masked_objects_hide_from_other_rays() {
+    let mut scene = balls();
+    let ghost = Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0);
+    scene.objects[0] = Box::new(Visibility(ghost, RayMask::SHADOW));
+    let ray = Ray::new([0.0; 3], [0.0, 0.0, 1.0]);
+    assert_eq!(
+        scene.raycast_masked(&ray, RayMask::CAMERA).unwrap().index,
+        1
+    );
+    assert_eq!(
+        scene.raycast_masked(&ray, RayMask::SHADOW).unwrap().index,
+        0
+    );
+    assert!(scene.occluded(&ray, 4.0));
+
+    // Seen by the camera, but casting no shadow.
+    let ghost = Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0);
+    scene.objects[0] = Box::new(Visibility(ghost, RayMask::CAMERA | RayMask::user(0)));
+    assert_eq!(
+        scene.raycast_masked(&ray, RayMask::CAMERA).unwrap().index,
+        0
+    );
+    assert!(!scene.occluded(&ray, 4.0));
+    assert!(RayMask::user(0).intersects(scene.objects[0].visibility()));
+    assert!(!RayMask::user(1).intersects(scene.objects[0].visibility()));
+}
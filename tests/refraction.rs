@@ -0,0 +1,375 @@
+//! Light passing into and out of transparent objects bends, reflects and
+//! fades the way the physics of dielectrics says it does.
+
+use raytracer::{
+    image::{Rgb, RgbImage},
+    scene::*,
+    texture::Checker,
+    view::*,
+    Color, Vecf,
+};
+
+fn assert_near(a: Vecf, b: Vecf) {
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-4 * b.abs().max(1.0), "{:?} {:?}", a, b);
+    }
+}
+
+/// A unit ray in the xz plane at `degrees` from the z axis.
+fn tilted(degrees: f32) -> Vecf {
+    let angle = degrees.to_radians();
+    [angle.sin(), 0.0, angle.cos()]
+}
+
+/// A clear unit ball of glass at the origin.
+fn glass() -> Sphere {
+    Sphere::new([0.0; 3], Rgb([255; 3]), 1.0, 0.0, 0.0).with_refraction(1.0, 1.5)
+}
+
+#[test]
+fn refraction_follows_snells_law_up_to_total_internal_reflection() {
+    let ball = glass();
+    // Entering at the near pole, 30 degrees from the normal: sin 30 = 1.5
+    // sin θ.
+    let entering = Ray::new([0.0, 0.0, -2.0], tilted(30.0));
+    let bent = ball.refract_ray(&entering, [0.0, 0.0, -1.0], 1.0);
+    assert_near(bent.direction, [1.0 / 3.0, 0.0, (8.0f32 / 9.0).sqrt()]);
+    // Leaving at the far pole bends away from the normal by the same law.
+    let leaving = Ray::new([0.0; 3], tilted(30.0));
+    let out = ball.refract_ray(&leaving, [0.0, 0.0, 1.0], 1.0);
+    assert_near(out.direction, [0.75, 0.0, (1.0f32 - 0.75 * 0.75).sqrt()]);
+    assert!(out.origin[2] > 1.0, "{:?}", out.origin);
+    // Past the critical angle of asin(1 / 1.5), about 41.8 degrees, no
+    // light gets out and the ray reflects back inside.
+    let trapped = Ray::new([0.0; 3], tilted(45.0));
+    let reflected = ball.refract_ray(&trapped, [0.0, 0.0, 1.0], 1.0);
+    assert_near(reflected.direction, tilted(135.0));
+    assert!(reflected.origin[2] < 1.0, "{:?}", reflected.origin);
+    // Head on, nothing bends.
+    let straight = Ray::new([0.0, 0.0, -2.0], [0.0, 0.0, 1.0]);
+    assert_near(
+        ball.refract_ray(&straight, [0.0, 0.0, -1.0], 1.0).direction,
+        [0.0, 0.0, 1.0],
+    );
+}
+
+/// A black wall across the z axis at `depth`, facing the way `normal`
+/// points and giving off `color`.
+fn glowing_wall(color: Color, depth: f32, normal: f32) -> Plane {
+    Plane::new(Rgb([0; 3]), [0.0, 0.0, normal], [0.0, 0.0, depth], 1.0, 0.0)
+        .with_emission(color, 1.0)
+}
+
+fn view() -> View {
+    View::new(4, 4, [0.0; 3], 30.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3)
+}
+
+#[test]
+fn surfaces_both_reflect_and_let_light_through() {
+    // Half a mirror and half clear, between a red wall ahead and a blue one
+    // behind the camera.
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 0.0, 0.5)
+            .with_refraction(0.5, 1.0),
+    );
+    scene.add_object(glowing_wall(Rgb([255, 0, 0]), 4.0, 1.0));
+    scene.add_object(glowing_wall(Rgb([0, 0, 255]), -1.0, -1.0));
+    let seen = view().render_hdr(&scene).get_pixel(2, 2);
+    assert_near(seen, [127.5, 0.0, 127.5]);
+}
+
+#[test]
+fn fresnel_reflection_rises_at_grazing_angles() {
+    // A floor reflecting a fifth of the light head on, under a white sky.
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([0; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        0.0,
+        0.2,
+    ));
+    scene.add_object(
+        Sphere::new([0.0; 3], Rgb([0; 3]), 100.0, 1.0, 0.0).with_emission(Rgb([255; 3]), 1.0),
+    );
+    let reflected = |direction, fresnel| {
+        let view = View::new(4, 4, [0.0; 3], 1.0, direction, 4, Rgb([0; 3]), 1e-3);
+        let view = if fresnel { view.with_fresnel() } else { view };
+        view.render_hdr(&scene).get_pixel(2, 2)[0]
+    };
+    let down = [0.0, -1.0, 1e-3];
+    let along = [0.0, -0.05, 1.0];
+    for direction in [down, along] {
+        let plain = reflected(direction, false);
+        assert!((plain - 51.0).abs() < 0.1, "{:?} {}", direction, plain);
+    }
+    let head_on = reflected(down, true);
+    let grazing = reflected(along, true);
+    assert!((head_on - 51.0).abs() < 0.5, "{}", head_on);
+    // Schlick's 0.2 + 0.8 (1 - cos θ)^5 with cos θ of about 0.05.
+    assert!((grazing / 255.0 - 0.82).abs() < 0.01, "{}", grazing);
+}
+
+#[test]
+fn dielectrics_tint_light_by_how_far_it_travels_inside() {
+    // Balls of glass that turn white light orange over two units, seen
+    // head on against a white wall.
+    let through = |radius| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), radius, 1.0, 0.5).with_dielectric(
+                1.5,
+                Rgb([255, 128, 64]),
+                2.0,
+            ),
+        );
+        scene.add_object(glowing_wall(Rgb([255; 3]), 10.0, 1.0));
+        view().render_hdr(&scene).get_pixel(2, 2)
+    };
+    // Each of the two surfaces reflects 4 percent head on.
+    let thick = through(1.0);
+    let kept = 0.96 * 0.96;
+    assert!((thick[0] - kept * 255.0).abs() < 1.0, "{:?}", thick);
+    assert!(
+        (thick[1] / thick[0] - 128.0 / 255.0).abs() < 0.01,
+        "{:?}",
+        thick
+    );
+    assert!(
+        (thick[2] / thick[0] - 64.0 / 255.0).abs() < 0.01,
+        "{:?}",
+        thick
+    );
+    // Half as far inside lets through the square root.
+    let thin = through(0.5);
+    assert!((thin[0] - kept * 255.0).abs() < 1.0, "{:?}", thin);
+    let ratio = (128.0f32 / 255.0).sqrt();
+    assert!((thin[1] / thin[0] - ratio).abs() < 0.01, "{:?}", thin);
+}
+
+/// A clear ball of index of refraction 1.5 in front of a checkered wall,
+/// holding a smaller ball of index `inner` if any.
+fn ball_in_front_of_checks(inner: Option<f32>) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 0.0, 8.0], 40.0));
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 10.0], 1.0, 0.0)
+            .with_texture(Checker::new(Rgb([255; 3]), Rgb([0; 3]), 0.5)),
+    );
+    let ball = |radius, ior| {
+        Sphere::new([0.0, 0.0, 4.0], Rgb([255; 3]), radius, 0.0, 0.0).with_refraction(1.0, ior)
+    };
+    scene.add_object(ball(2.0, 1.5));
+    if let Some(ior) = inner {
+        scene.add_object(ball(1.0, ior));
+    }
+    scene
+}
+
+#[test]
+fn rays_bend_by_the_media_on_both_sides_of_nested_surfaces() {
+    let view = View::new(
+        16,
+        16,
+        [0.0; 3],
+        30.0,
+        [0.0, 0.0, 1.0],
+        6,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let solid = view.render(&ball_in_front_of_checks(None));
+    // Water-clear glass inside the glass leaves it looking solid, while a
+    // bubble of air bends rays at its surface.
+    let filled = view.render(&ball_in_front_of_checks(Some(1.5)));
+    let bubble = view.render(&ball_in_front_of_checks(Some(1.0)));
+    let differing = |image: &RgbImage| {
+        image
+            .pixels()
+            .zip(solid.pixels())
+            .filter(|(a, b)| a != b)
+            .count()
+    };
+    assert_eq!(differing(&filled), 0);
+    assert!(differing(&bubble) > 10, "{}", differing(&bubble));
+}
+
+#[test]
+fn absorption_deepens_with_distance_behind_planes() {
+    // Clear water from two units away, over a white floor at `depth`.
+    let seen = |depth| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 0.0, 0.0)
+                .with_refraction(1.0, 1.0)
+                .with_absorption(Rgb([255, 128, 64]), 0.5),
+        );
+        scene.add_object(glowing_wall(Rgb([255; 3]), depth, 1.0));
+        view().render_hdr(&scene).get_pixel(2, 2)
+    };
+    // White light comes out as the color after `1 / density` units.
+    assert_near(seen(4.0), [255.0, 128.0, 64.0]);
+    assert_near(
+        seen(6.0),
+        [255.0, 128.0 * 128.0 / 255.0, 64.0 * 64.0 / 255.0],
+    );
+}
+
+#[test]
+fn rough_refraction_frosts_what_is_seen_through() {
+    // A small glowing ball under the surface of a pane of glass.
+    let through = |roughness| {
+        let mut scene = Scene::default();
+        scene.add_object(
+            Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 0.0, 0.0)
+                .with_refraction(1.0, 1.5)
+                .with_roughness(roughness),
+        );
+        scene.add_object(
+            Sphere::new([0.0, 0.0, 6.0], Rgb([0; 3]), 0.3, 1.0, 0.0)
+                .with_emission(Rgb([255; 3]), 1.0),
+        );
+        let view = View::new(
+            32,
+            32,
+            [0.0; 3],
+            30.0,
+            [0.0, 0.0, 1.0],
+            4,
+            Rgb([0; 3]),
+            1e-3,
+        );
+        view.with_samples(16).render(&scene)
+    };
+    let lit = |image: &RgbImage| image.pixels().filter(|pixel| pixel.0[0] > 0).count();
+    let clear = through(0.0);
+    let frosted = through(0.3);
+    assert_eq!(clear.get_pixel(16, 16).0, [255; 3]);
+    assert!(frosted.get_pixel(16, 16).0[0] < 255);
+    assert!(
+        lit(&frosted) > 2 * lit(&clear),
+        "{} {}",
+        lit(&frosted),
+        lit(&clear)
+    );
+}
+
+#[test]
+fn rays_dimmed_by_absorption_stop_below_the_minimum_contribution() {
+    // Two units of water keeping a tenth of the light, then a clear pane
+    // the ray is split at, then a white wall.
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 0.0, 0.0)
+            .with_refraction(1.0, 1.0)
+            .with_absorption(Rgb([26; 3]), 0.5),
+    );
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 4.0], 0.0, 0.0)
+            .with_refraction(1.0, 1.0),
+    );
+    scene.add_object(glowing_wall(Rgb([255; 3]), 6.0, 1.0));
+    let seen = |contribution| {
+        view()
+            .with_min_contribution(contribution)
+            .render_hdr(&scene)
+            .get_pixel(2, 2)
+    };
+    let all = seen(0.0);
+    assert!((all[0] - 26.0).abs() < 0.01, "{:?}", all);
+    assert_eq!(seen(0.05), seen(0.0));
+    assert_eq!(seen(0.2), [0.0; 3]);
+}
+
+#[test]
+fn transparent_objects_cast_tinted_shadows() {
+    // A clear ball turning light across its diameter pale red, hidden from
+    // the camera, between a light and the floor under the camera.
+    let floor = |ball: Option<Sphere>| {
+        let mut scene = Scene::default();
+        scene.add_light(Light::new([0.0, 4.0, 0.0], 100.0));
+        scene.add_object(Plane::new(
+            Rgb([255; 3]),
+            [0.0, -1.0, 0.0],
+            [0.0; 3],
+            1.0,
+            0.0,
+        ));
+        if let Some(ball) = ball {
+            scene.add_object(Visibility(ball, RayMask::SHADOW));
+        }
+        scene
+    };
+    let ball = |transparency| {
+        Sphere::new([0.0, 2.0, 0.0], Rgb([255; 3]), 0.5, 0.0, 0.0)
+            .with_refraction(transparency, 1.0)
+            .with_absorption(Rgb([255, 64, 64]), 1.0)
+    };
+    let view = View::new(
+        4,
+        4,
+        [0.0, 3.0, 0.0],
+        30.0,
+        [0.0, -1.0, 1e-3],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    );
+    let lit = |scene: &Scene, view: &View| view.render_hdr(scene).get_pixel(2, 2);
+    let open = lit(&floor(None), &view);
+    assert!(open[0] > 100.0, "{:?}", open);
+    assert_eq!(lit(&floor(Some(ball(1.0))), &view), [0.0; 3]);
+    let view = view.with_transparent_shadows();
+    let clear = lit(&floor(Some(ball(1.0))), &view);
+    assert_near(
+        clear,
+        [open[0], open[1] * 64.0 / 255.0, open[2] * 64.0 / 255.0],
+    );
+    // Half transparent, each of the two surfaces lets through half.
+    let dim = lit(&floor(Some(ball(0.5))), &view);
+    assert_near(dim, clear.map(|c| c * 0.25));
+}
+
+/// The brightness-weighted mean column of each channel of a glowing ball
+/// seen at an angle through a glass plane with the given indices.
+fn channel_centroids(iors: [f32; 3]) -> [f32; 3] {
+    let mut scene = Scene::default();
+    scene.add_object(
+        Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 0.0, 0.0)
+            .with_refraction(1.0, 1.5)
+            .with_dispersion(iors),
+    );
+    scene.add_object(
+        Sphere::new([3.1, 0.0, 5.0], Rgb([0; 3]), 0.3, 1.0, 0.0).with_emission(Rgb([255; 3]), 1.0),
+    );
+    let view =
+        View::new(32, 32, [0.0; 3], 20.0, tilted(40.0), 4, Rgb([0; 3]), 1e-3).with_samples(64);
+    let image = view.render_hdr(&scene);
+    let mut sums = [[0.0; 2]; 3];
+    for (i, pixel) in image.pixels().iter().enumerate() {
+        let x = (i % 32) as f32;
+        for (sum, value) in sums.iter_mut().zip(pixel) {
+            sum[0] += value * x;
+            sum[1] += value;
+        }
+    }
+    [
+        sums[0][0] / sums[0][1],
+        sums[1][0] / sums[1][1],
+        sums[2][0] / sums[2][1],
+    ]
+}
+
+#[test]
+fn dispersion_bends_each_channel_by_its_own_index() {
+    let [red, green, blue] = channel_centroids([1.5; 3]);
+    assert!((red - blue).abs() < 0.2, "{:?}", [red, green, blue]);
+
+    let [red, green, blue] = channel_centroids([1.46, 1.5, 1.54]);
+    assert!(
+        red + 0.3 < green && green + 0.3 < blue,
+        "{:?}",
+        [red, green, blue]
+    );
+}
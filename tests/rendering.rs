@@ -0,0 +1,352 @@
+//! Renders can be watched as they go, stopped and picked up again, and
+//! come out as a plain render would.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use raytracer::{
+    checkpoint::Checkpoint,
+    framebuffer::Framebuffer,
+    image::{Rgb, RgbImage},
+    inspect::Recorder,
+    integrator::{Integrator, PathTracer, Whitted},
+    sampler::{Halton, Rng, Sampler, Sobol, Stratified, Uniform},
+    scene::*,
+    view::*,
+};
+
+/// A ball on a floor under one light, path traced so that every sample
+/// adds noise of its own.
+fn ball_on_floor() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([1.0, 3.0, 1.0], 40.0));
+    scene.add_object(Sphere::new(
+        [0.0, 0.0, 3.0],
+        Rgb([255, 128, 64]),
+        0.8,
+        1.0,
+        0.0,
+    ));
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0, -0.8, 0.0],
+        1.0,
+        0.0,
+    ));
+    scene
+}
+
+fn view(samples: u32) -> View {
+    View::new(
+        16,
+        12,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([40; 3]),
+        1e-3,
+    )
+    .with_integrator(PathTracer)
+    .with_samples(samples)
+}
+
+/// Shows every pixel white, cancelling `0` as soon as it traces a ray.
+struct Cancelling(CancelToken);
+
+impl Integrator for Cancelling {
+    fn li(
+        &self,
+        _view: &View,
+        _scene: &Scene,
+        _ray: &Ray,
+        _background: [f32; 3],
+        _rng: &mut Rng,
+        _recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        self.0.cancel();
+        [255.0; 3]
+    }
+}
+
+#[test]
+fn cancelled_renders_stop_before_the_next_tile() {
+    let cancel = CancelToken::new();
+    // Two by two tiles, the last ones cut short by the image edges.
+    let view = View::new(
+        48,
+        40,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+    .with_integrator(Cancelling(cancel.clone()));
+    let image = view.render_cancellable(&Scene::default(), &cancel);
+    assert!(cancel.is_cancelled());
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let first_tile = x < TILE_SIZE && y < TILE_SIZE;
+        assert_eq!(pixel.0 == [255; 3], first_tile, "{} {}", x, y);
+    }
+}
+
+#[test]
+fn checkpoints_resume_to_the_uninterrupted_image() {
+    let scene = ball_on_floor();
+    let mut checkpoint = Checkpoint::new(16, 12);
+    let cancelled = CancelToken::new();
+    cancelled.cancel();
+    view(8).render_checkpointed(&scene, &mut checkpoint, &cancelled);
+    assert_eq!(checkpoint.samples(0, 0), 0);
+
+    // A first session takes two samples a pixel and is saved.
+    view(2).render_checkpointed(&scene, &mut checkpoint, &CancelToken::new());
+    assert!((0..12).all(|y| (0..16).all(|x| checkpoint.samples(x, y) == 2)));
+    let mut saved = Vec::new();
+    checkpoint.write_to(&mut saved).unwrap();
+    let mut loaded = Checkpoint::read_from(&saved[..]).unwrap();
+    assert_eq!(loaded, checkpoint);
+
+    // A second one adds the rest.
+    view(8).render_checkpointed(&scene, &mut loaded, &CancelToken::new());
+    assert!((0..12).all(|y| (0..16).all(|x| loaded.samples(x, y) == 8)));
+    assert_eq!(loaded.framebuffer(), view(8).render_hdr(&scene));
+    assert_ne!(loaded.framebuffer(), checkpoint.framebuffer());
+}
+
+#[test]
+fn progressive_renders_refine_to_the_full_render() {
+    let scene = ball_on_floor();
+    let view = view(1);
+    let passes: Vec<_> = view.render_progressive(&scene).collect();
+    // Blocks of 16, 8, 4, 2 and finally single pixels.
+    assert_eq!(passes.len(), 5);
+    let first = &passes[0];
+    for (x, y, pixel) in first.enumerate_pixels() {
+        assert_eq!(
+            pixel,
+            first.get_pixel(x / 16 * 16, y / 16 * 16),
+            "{} {}",
+            x,
+            y
+        );
+    }
+    // Every pass keeps the pixels the coarser ones traced.
+    for (i, pass) in passes.iter().enumerate() {
+        let block = 16 >> i;
+        for later in &passes[i..] {
+            for y in (0..12).step_by(block) {
+                for x in (0..16).step_by(block) {
+                    assert_eq!(pass.get_pixel(x, y), later.get_pixel(x, y), "{} {}", x, y);
+                }
+            }
+        }
+    }
+    assert_eq!(passes.last(), Some(&view.render(&scene)));
+    assert_ne!(first, &view.render(&scene));
+}
+
+#[test]
+fn progress_counts_every_tile_up_to_the_total() {
+    let scene = ball_on_floor();
+    let mut view = View::new(
+        70,
+        40,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([40; 3]),
+        1e-3,
+    );
+    let mut calls = Vec::new();
+    let image = view.render_with_progress(&scene, |done, total| calls.push((done, total)));
+    // Three tiles across and two down.
+    assert_eq!(calls, (1..=6).map(|done| (done, 6)).collect::<Vec<_>>());
+    assert_eq!(image, view.render(&scene));
+
+    // A region counts its own tiles only.
+    view.set_region(30, 10, 40, 20);
+    let mut calls = Vec::new();
+    view.render_with_progress(&scene, |done, total| calls.push((done, total)));
+    assert_eq!(calls, [(1, 2), (2, 2)]);
+}
+
+#[test]
+fn regions_render_only_their_rectangle() {
+    let scene = ball_on_floor();
+    let full = view(1).render(&scene);
+    let mut view = view(1);
+    view.set_region(5, 3, 8, 6);
+    let inside = |x: u32, y: u32| (5..13).contains(&x) && (3..9).contains(&y);
+
+    // The rest of the image is left black.
+    for (x, y, pixel) in view.render(&scene).enumerate_pixels() {
+        let expected = if inside(x, y) {
+            *full.get_pixel(x, y)
+        } else {
+            Rgb([0; 3])
+        };
+        assert_eq!(*pixel, expected, "{} {}", x, y);
+    }
+    let cropped = view.render_cropped(&scene);
+    assert_eq!(cropped.dimensions(), (8, 6));
+    for (x, y, pixel) in cropped.enumerate_pixels() {
+        assert_eq!(pixel, full.get_pixel(x + 5, y + 3), "{} {}", x, y);
+    }
+    // Rendered into an earlier image, only the region changes.
+    let mut patched = RgbImage::from_pixel(16, 12, Rgb([1, 2, 3]));
+    view.render_into(&scene, &mut patched);
+    for (x, y, pixel) in patched.enumerate_pixels() {
+        let expected = if inside(x, y) {
+            *full.get_pixel(x, y)
+        } else {
+            Rgb([1, 2, 3])
+        };
+        assert_eq!(*pixel, expected, "{} {}", x, y);
+    }
+
+    view.clear_region();
+    assert_eq!(view.render(&scene), full);
+}
+
+#[test]
+fn animation_frames_get_noise_of_their_own() {
+    let scene = ball_on_floor();
+    let still = view(4).render_hdr(&scene);
+    assert_eq!(view(4).with_frame(0).render_hdr(&scene), still);
+    let second = view(4).with_frame(1).render_hdr(&scene);
+    assert_ne!(second, still);
+    assert_eq!(view(4).with_frame(1).render_hdr(&scene), second);
+    assert_ne!(view(4).with_frame(2).render_hdr(&scene), second);
+    // Fixed noise keeps the first frame's pattern.
+    let fixed = view(4).with_frame(1).with_fixed_noise(true);
+    assert_eq!(fixed.render_hdr(&scene), still);
+}
+
+/// A ball glowing white, so it shows without lights, filling the middle
+/// of a 16 by 16 view.
+fn glowing_ball() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([0; 3]), 1.1, 1.0, 0.0).with_emission(Rgb([255; 3]), 1.0),
+    );
+    scene
+}
+
+fn ball_view(samples: u32) -> View {
+    View::new(
+        16,
+        16,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+    .with_samples(samples)
+}
+
+/// Squared error of a 16 sample render of a glowing ball's edge against
+/// a converged one, with samples placed by `sampler`.
+fn edge_error<S: Sampler + 'static>(sampler: S) -> f32 {
+    let scene = glowing_ball();
+    let reference = ball_view(1024).render_hdr(&scene);
+    let image = ball_view(16).with_sampler(sampler).render_hdr(&scene);
+    let pixels = image.pixels().iter().zip(reference.pixels());
+    pixels.map(|(a, b)| (a[0] - b[0]).powi(2)).sum()
+}
+
+#[test]
+fn even_samplers_converge_faster_than_uniform() {
+    let uniform = edge_error(Uniform);
+    for (name, error) in [
+        ("stratified", edge_error(Stratified)),
+        ("halton", edge_error(Halton)),
+        ("sobol", edge_error(Sobol)),
+    ] {
+        assert!(error < 0.6 * uniform, "{} {} {}", name, error, uniform);
+    }
+}
+
+/// `Whitted`, counting the camera rays it is given.
+struct Counting(Arc<AtomicU32>);
+
+impl Integrator for Counting {
+    fn li(
+        &self,
+        view: &View,
+        scene: &Scene,
+        ray: &Ray,
+        background: [f32; 3],
+        rng: &mut Rng,
+        recorder: Option<&mut Recorder>,
+    ) -> [f32; 3] {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Whitted.li(view, scene, ray, background, rng, recorder)
+    }
+}
+
+#[test]
+fn adaptive_sampling_stops_early_on_flat_pixels() {
+    let scene = glowing_ball();
+    let rays = Arc::new(AtomicU32::new(0));
+    let view = ball_view(4)
+        .with_adaptive_sampling(2.0, 256)
+        .with_integrator(Counting(rays.clone()));
+    let adaptive = view.render_hdr(&scene);
+    let traced = rays.swap(0, Ordering::Relaxed);
+    // Pixels wholly on or off the ball take a single round of four; those
+    // on its edge go on.
+    view.render_hdr(&Scene::default());
+    assert_eq!(rays.load(Ordering::Relaxed), 16 * 16 * 4);
+    assert!(traced > 16 * 16 * 4 + 256, "{}", traced);
+    assert!(traced < 16 * 16 * 64, "{}", traced);
+
+    let reference = ball_view(1024).render_hdr(&scene);
+    let fixed = ball_view(4).render_hdr(&scene);
+    let error = |image: &Framebuffer| {
+        let pixels = image.pixels().iter().zip(reference.pixels());
+        pixels.map(|(a, b)| (a[0] - b[0]).powi(2)).sum::<f32>()
+    };
+    assert!(
+        error(&adaptive) < 0.5 * error(&fixed),
+        "{} {}",
+        error(&adaptive),
+        error(&fixed)
+    );
+}
+
+#[test]
+fn seeds_make_renders_reproducible() {
+    let scene = ball_on_floor();
+    let seeded = view(4).with_seed(7).render_hdr(&scene);
+    assert_eq!(view(4).with_seed(7).render_hdr(&scene), seeded);
+    assert_eq!(
+        view(4).with_seed(0).render_hdr(&scene),
+        view(4).render_hdr(&scene)
+    );
+
+    // Another seed is other noise around the same picture.
+    let other = view(4).with_seed(8).render_hdr(&scene);
+    let differing = seeded
+        .pixels()
+        .iter()
+        .zip(other.pixels())
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(differing > 16 * 12 / 2, "{}", differing);
+    let mean = |image: &Framebuffer| image.pixels().iter().map(|p| p[0]).sum::<f32>() / 192.0;
+    assert!(
+        (mean(&seeded) - mean(&other)).abs() < 0.05 * mean(&seeded),
+        "{} {}",
+        mean(&seeded),
+        mean(&other)
+    );
+}
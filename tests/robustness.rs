@@ -0,0 +1,580 @@
+//! Malformed scenes must render a defined image rather than panic.
+
+use std::sync::Arc;
+
+use raytracer::{
+    atmosphere::{CloudLayer, HeightFog, Volume},
+    axes::Axes,
+    bake::bake,
+    dither::Dither,
+    image::{GrayImage, Luma, Rgb, RgbImage},
+    integrator::*,
+    isosurface::{ColorRamp, Grid, Isosurface, Slice, TransferFunction},
+    material::{AlphaMode, Material, Scatter, Standard},
+    mesh::{Mesh, Triangle},
+    photon::PhotonMap,
+    placement::{jitter, Region},
+    sampler::{Halton, Rng, Sobol, Stratified},
+    scene::*,
+    texture::{
+        AlphaMask, Checker, Clouds, Filter, ImageTexture, Marble, NormalMap, Tiles, VertexColor,
+        Wood,
+    },
+    tonemap::{AutoExposure, Tonemap},
+    units::Unit,
+    view::*,
+    warning::RenderWarning,
+};
+
+/// Mostly ordinary numbers, often NaN, infinite, zero, negative or huge.
+fn value(rng: &mut Rng) -> f32 {
+    const AWKWARD: [f32; 8] = [
+        f32::NAN,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        0.0,
+        -1.0,
+        1e30,
+        1e-30,
+        f32::MAX,
+    ];
+    if rng.next_f32() < 0.3 {
+        AWKWARD[(rng.next_f32() * AWKWARD.len() as f32) as usize]
+    } else {
+        rng.next_f32() * 10.0 - 5.0
+    }
+}
+
+fn vector(rng: &mut Rng) -> [f32; 3] {
+    [value(rng), value(rng), value(rng)]
+}
+
+fn color(rng: &mut Rng) -> Rgb<u8> {
+    Rgb([0; 3].map(|_: u8| (rng.next_f32() * 256.0) as u8))
+}
+
+fn count(rng: &mut Rng, max: u32) -> u32 {
+    (rng.next_f32() * (max + 1) as f32) as u32
+}
+
+/// A custom material that scatters and glows by malformed amounts.
+struct Wild(f32);
+
+impl Material for Wild {
+    fn scatter(&self, _ray: &Ray, hit: &Hit, rng: &mut Rng) -> Scatter {
+        match count(rng, 2) {
+            0 => Scatter::Standard,
+            1 => Scatter::Absorb,
+            _ => Scatter::Continue(Ray::new(hit.point, vector(rng)), [self.0; 3]),
+        }
+    }
+
+    fn emitted(&self, _hit: &Hit) -> [f32; 3] {
+        [self.0; 3]
+    }
+}
+
+fn axes(rng: &mut Rng) -> Axes {
+    [
+        Axes::Y_UP_LEFT,
+        Axes::Y_UP_RIGHT,
+        Axes::Z_UP_RIGHT,
+        Axes::Z_UP_LEFT,
+    ][count(rng, 3) as usize]
+}
+
+fn unit(rng: &mut Rng) -> Unit {
+    match count(rng, 3) {
+        0 => Unit::Meter,
+        1 => Unit::Centimeter,
+        2 => Unit::Inch,
+        _ => Unit::Custom(value(rng)),
+    }
+}
+
+fn random_scene(seed: u32) -> Scene {
+    let mut rng = Rng::new(seed, 0, 0, 0);
+    let mut scene = Scene::default();
+    let shared: Arc<dyn Material> = Arc::new(
+        Standard::new(value(&mut rng), value(&mut rng))
+            .with_refraction(value(&mut rng), value(&mut rng))
+            .with_metal(value(&mut rng))
+            .with_emission(color(&mut rng), value(&mut rng))
+            .with_glossy_samples(count(&mut rng, 4)),
+    );
+    for _ in 0..count(&mut rng, 4) {
+        let (position, color, radius) = (vector(&mut rng), color(&mut rng), value(&mut rng));
+        let (lambert, specular) = (value(&mut rng), value(&mut rng));
+        let sphere =
+            Sphere::new(position, color, radius, lambert, specular).with_shininess(value(&mut rng));
+        let sphere = match count(&mut rng, 6) {
+            0 => sphere
+                .with_refraction(value(&mut rng), value(&mut rng))
+                .with_absorption(self::color(&mut rng), value(&mut rng))
+                .with_roughness(value(&mut rng)),
+            1 => sphere
+                .with_dielectric(value(&mut rng), self::color(&mut rng), value(&mut rng))
+                .with_dispersion([value(&mut rng), value(&mut rng), value(&mut rng)]),
+            2 => sphere.with_metal(value(&mut rng)),
+            3 => sphere
+                .with_pbr(value(&mut rng), value(&mut rng))
+                .with_clearcoat(value(&mut rng), value(&mut rng)),
+            4 => sphere.with_material(shared.clone()),
+            5 => sphere.with_material(Arc::new(Wild(value(&mut rng)))),
+            6 => sphere.with_emission(self::color(&mut rng), value(&mut rng)),
+            _ => sphere,
+        };
+        match count(&mut rng, 6) {
+            0 => scene.add_object(ShadowCatcher(sphere)),
+            1 => scene.add_object(DepthBias(sphere, value(&mut rng))),
+            2 => scene.add_object(Visibility(sphere, RayMask(count(&mut rng, 7)))),
+            3 => {
+                let (width, height) = (count(&mut rng, 2), count(&mut rng, 2));
+                let pixel = self::color(&mut rng);
+                let image = RgbImage::from_fn(width, height, |x, y| {
+                    if (x + y) % 2 == 0 {
+                        pixel
+                    } else {
+                        Rgb([0; 3])
+                    }
+                });
+                let filter = [Filter::Nearest, Filter::Bilinear][count(&mut rng, 1) as usize];
+                scene.add_object(sphere.with_texture(ImageTexture::new(image).with_filter(filter)))
+            }
+            4 => {
+                let (a, b, size) = (
+                    self::color(&mut rng),
+                    self::color(&mut rng),
+                    value(&mut rng),
+                );
+                match count(&mut rng, 2) {
+                    0 => scene
+                        .add_object(sphere.with_texture(
+                            Marble::new(a, b, size).with_turbulence(value(&mut rng)),
+                        )),
+                    1 => scene.add_object(sphere.with_texture(Wood::new(a, b, size))),
+                    _ => scene.add_object(sphere.with_texture(Clouds::new(a, b, size))),
+                }
+            }
+            5 => {
+                let (width, height) = (count(&mut rng, 3), count(&mut rng, 3));
+                let bumps = RgbImage::from_fn(width, height, |_, _| self::color(&mut rng));
+                let map = NormalMap::new(bumps).with_strength(value(&mut rng));
+                scene.add_object(sphere.with_normal_map(map))
+            }
+            _ => scene.add_object(sphere),
+        }
+    }
+    for _ in 0..count(&mut rng, 3) {
+        let (normal, point) = (vector(&mut rng), vector(&mut rng));
+        let (lambert, specular) = (value(&mut rng), value(&mut rng));
+        let plane = Plane::new(color(&mut rng), normal, point, lambert, specular);
+        let plane = match count(&mut rng, 5) {
+            0 => plane
+                .with_refraction(value(&mut rng), value(&mut rng))
+                .with_absorption(color(&mut rng), value(&mut rng))
+                .with_roughness(value(&mut rng)),
+            1 => plane
+                .with_clearcoat(value(&mut rng), value(&mut rng))
+                .with_metal(value(&mut rng)),
+            2 => plane
+                .with_pbr(value(&mut rng), value(&mut rng))
+                .with_anisotropy(value(&mut rng), value(&mut rng)),
+            3 => plane
+                .with_material(shared.clone())
+                .with_shininess(value(&mut rng)),
+            _ => plane,
+        };
+        match count(&mut rng, 6) {
+            0 => scene.add_object(
+                plane
+                    .with_tile_size(value(&mut rng), value(&mut rng))
+                    .with_texture(Checker::new(
+                        color(&mut rng),
+                        color(&mut rng),
+                        value(&mut rng),
+                    )),
+            ),
+            1 => scene.add_object(plane.with_texture(Tiles::new(
+                color(&mut rng),
+                color(&mut rng),
+                value(&mut rng),
+            ))),
+            2 => {
+                let inner = Checker::new(color(&mut rng), color(&mut rng), value(&mut rng));
+                let tiles = Tiles::from_textures(inner, color(&mut rng), value(&mut rng))
+                    .with_size(value(&mut rng));
+                let checker = Checker::from_textures(tiles, color(&mut rng), value(&mut rng));
+                scene.add_object(plane.with_texture(checker))
+            }
+            3 => {
+                let bumps = RgbImage::from_fn(count(&mut rng, 3), 1, |_, _| color(&mut rng));
+                scene.add_object(plane.with_normal_map(NormalMap::new(bumps)))
+            }
+            4 => {
+                let heights = GrayImage::from_fn(count(&mut rng, 3), count(&mut rng, 3), |_, _| {
+                    Luma([color(&mut rng).0[0]])
+                });
+                let bumps = NormalMap::from_heights(&heights, value(&mut rng));
+                scene.add_object(plane.with_normal_map(bumps))
+            }
+            5 => {
+                let mask = GrayImage::from_fn(count(&mut rng, 3), count(&mut rng, 3), |_, _| {
+                    Luma([color(&mut rng).0[0]])
+                });
+                let mode = [AlphaMode::Blend, AlphaMode::Stochastic][count(&mut rng, 1) as usize];
+                scene.add_object(plane.with_cutout(AlphaMask::new(mask), mode))
+            }
+            _ => scene.add_object(plane),
+        }
+    }
+    if count(&mut rng, 1) == 1 {
+        let triangles = (0..count(&mut rng, 3))
+            .map(|_| Triangle {
+                vertices: [vector(&mut rng), vector(&mut rng), vector(&mut rng)],
+                uvs: if count(&mut rng, 1) == 0 {
+                    None
+                } else {
+                    Some([0, 1, 2].map(|_| [value(&mut rng), value(&mut rng)]))
+                },
+                colors: None,
+            })
+            .collect();
+        let (lambert, specular) = (value(&mut rng), value(&mut rng));
+        scene.add_object(
+            Mesh::new(triangles, color(&mut rng), lambert, specular)
+                .with_texture(VertexColor::new(color(&mut rng)))
+                .with_axes(axes(&mut rng), axes(&mut rng))
+                .with_unit(unit(&mut rng), unit(&mut rng))
+                .with_normal_map(NormalMap::new(RgbImage::new(1, 1))),
+        );
+    }
+    if count(&mut rng, 4) == 0 {
+        let (iso_value, lambert, specular) = (value(&mut rng), value(&mut rng), value(&mut rng));
+        let (min, max) = (vector(&mut rng), vector(&mut rng));
+        let surface = if count(&mut rng, 1) == 1 {
+            let values = (0..8).map(|_| value(&mut rng)).collect();
+            Isosurface::from_grid(
+                Grid::new(min, max, [2; 3], values),
+                iso_value,
+                color(&mut rng),
+                lambert,
+                specular,
+            )
+        } else {
+            let center = vector(&mut rng);
+            let field = move |p: [f32; 3]| (0..3).map(|i| (p[i] - center[i]).powi(2)).sum::<f32>();
+            Isosurface::new(
+                field,
+                iso_value,
+                min,
+                max,
+                color(&mut rng),
+                lambert,
+                specular,
+            )
+        };
+        let surface = if count(&mut rng, 4) == 0 {
+            surface.with_step(value(&mut rng))
+        } else {
+            surface
+        };
+        scene.add_object(if count(&mut rng, 1) == 1 {
+            surface.with_clip(vector(&mut rng), vector(&mut rng))
+        } else {
+            surface
+        });
+    }
+    if count(&mut rng, 4) == 0 {
+        let mut ramp = ColorRamp::new();
+        for _ in 0..count(&mut rng, 3) {
+            ramp = ramp.with_stop(value(&mut rng), color(&mut rng));
+        }
+        let values = (0..8).map(|_| value(&mut rng)).collect();
+        let grid = Grid::new(vector(&mut rng), vector(&mut rng), [2; 3], values);
+        scene.add_object(Slice::from_grid(
+            grid,
+            ramp,
+            vector(&mut rng),
+            vector(&mut rng),
+        ));
+    }
+    for _ in 0..count(&mut rng, 3) {
+        let light = Light::new(vector(&mut rng), value(&mut rng));
+        scene.add_light(if count(&mut rng, 1) == 1 {
+            light.with_radius(value(&mut rng), count(&mut rng, 4))
+        } else {
+            light
+        });
+    }
+    if count(&mut rng, 3) == 0 {
+        let corners = [vector(&mut rng), vector(&mut rng), vector(&mut rng)];
+        scene.add_area_light_from_quad(
+            corners[0],
+            corners[1],
+            corners[2],
+            value(&mut rng),
+            count(&mut rng, 3),
+            count(&mut rng, 3),
+        );
+    }
+    if count(&mut rng, 2) == 0 {
+        scene.set_fog(
+            HeightFog::new(color(&mut rng), value(&mut rng), value(&mut rng))
+                .with_base_height(value(&mut rng)),
+        );
+    }
+    if count(&mut rng, 2) == 0 {
+        scene.add_clouds(
+            CloudLayer::new(
+                value(&mut rng),
+                value(&mut rng),
+                color(&mut rng),
+                color(&mut rng),
+            )
+            .with_density(value(&mut rng))
+            .with_coverage(value(&mut rng))
+            .with_quality(count(&mut rng, 8), value(&mut rng)),
+        );
+    }
+    if count(&mut rng, 3) == 0 {
+        let mut transfer = TransferFunction::new();
+        for _ in 0..count(&mut rng, 3) {
+            transfer = transfer.with_stop(value(&mut rng), color(&mut rng), value(&mut rng));
+        }
+        let values = (0..8).map(|_| value(&mut rng)).collect();
+        let grid = Grid::new(vector(&mut rng), vector(&mut rng), [2; 3], values);
+        scene.add_volume(Volume::from_grid(grid, transfer).with_steps(count(&mut rng, 8)));
+    }
+    if count(&mut rng, 3) == 0 {
+        let region = Region::new(vector(&mut rng), vector(&mut rng), vector(&mut rng));
+        let mut points = if count(&mut rng, 1) == 0 {
+            region.grid(count(&mut rng, 3), count(&mut rng, 3))
+        } else {
+            region.scatter(count(&mut rng, 6), seed)
+        };
+        jitter(&mut points, value(&mut rng), seed);
+        let (radius, color) = (value(&mut rng), color(&mut rng));
+        scene.add_at(&points, |point, index| {
+            Sphere::new(point, color, radius * index as f32, 0.5, 0.5)
+        });
+    }
+    if count(&mut rng, 2) == 0 {
+        scene.set_unit(unit(&mut rng));
+    }
+    if count(&mut rng, 3) == 0 {
+        scene.set_shadow_cache(value(&mut rng));
+    }
+    if count(&mut rng, 3) == 0 {
+        let caustics = PhotonMap::build(&scene, count(&mut rng, 200), seed)
+            .with_gather(count(&mut rng, 10).max(1) as usize, value(&mut rng));
+        scene.set_caustics(caustics);
+    }
+    scene
+}
+
+fn random_view(seed: u32, integrator: u32) -> View {
+    let mut rng = Rng::new(seed, 1, 0, 0);
+    let view = View::new(
+        6,
+        4,
+        vector(&mut rng),
+        value(&mut rng),
+        vector(&mut rng),
+        count(&mut rng, 4),
+        color(&mut rng),
+        value(&mut rng),
+    )
+    .with_samples(count(&mut rng, 2).max(1))
+    .with_seed(seed);
+    let view = match count(&mut rng, 3) {
+        0 => view.with_sampler(Stratified),
+        1 => view.with_sampler(Halton),
+        2 => view.with_sampler(Sobol),
+        _ => view,
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_backplate(RgbImage::new(count(&mut rng, 2), count(&mut rng, 2)))
+    } else {
+        view
+    };
+    let view = match count(&mut rng, 4) {
+        0 => view.with_tonemap(Tonemap::Exposure(value(&mut rng))),
+        1 => view.with_tonemap(Tonemap::Reinhard),
+        2 => view.with_tonemap(Tonemap::Aces),
+        _ => view,
+    };
+    let view = match count(&mut rng, 3) {
+        0 => view.with_dither(Dither::Ordered),
+        1 => view.with_dither(Dither::BlueNoise),
+        _ => view,
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_fresnel()
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_min_contribution(value(&mut rng))
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_light_cutoff(value(&mut rng))
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_max_glossy_samples(count(&mut rng, 4))
+    } else {
+        view
+    };
+    let view = view.with_cone_tracing(count(&mut rng, 3) == 0);
+    let view = if count(&mut rng, 2) == 0 {
+        view.with_transparent_shadows()
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_orthographic(value(&mut rng))
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_camera_exposure(value(&mut rng), value(&mut rng), value(&mut rng))
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 1) == 1 {
+        view.with_srgb()
+    } else {
+        view
+    };
+    let view = if count(&mut rng, 3) == 0 {
+        view.with_adaptive_sampling(value(&mut rng), count(&mut rng, 8))
+    } else {
+        view
+    };
+    let view = view.with_axes(axes(&mut rng));
+    match integrator {
+        0 => view,
+        1 => view.with_integrator(PathTracer),
+        2 => view.with_integrator(Bidirectional),
+        3 => view.with_integrator(AmbientOcclusion::new(value(&mut rng), count(&mut rng, 4))),
+        4 => view.with_integrator(Normals),
+        5 => view.with_integrator(DepthPeeling::new(value(&mut rng), count(&mut rng, 4))),
+        _ => view.with_integrator(Depth::new(value(&mut rng))),
+    }
+}
+
+#[test]
+fn random_malformed_scenes_render() {
+    for seed in 0..2000 {
+        let scene = random_scene(seed);
+        let summary = scene.summary();
+        let total: usize = summary.objects.iter().map(|(_, count)| count).sum();
+        assert_eq!(total, scene.objects.len(), "seed {}", seed);
+        assert!(!summary.to_string().is_empty(), "seed {}", seed);
+        for object in scene.objects.iter() {
+            let baked = bake(object.as_ref(), &scene, 3, 2);
+            assert_eq!(baked.dimensions(), (3, 2), "seed {}", seed);
+        }
+        for integrator in 0..7 {
+            let view = random_view(seed, integrator);
+            assert_eq!(view.render(&scene).dimensions(), (6, 4), "seed {}", seed);
+            if integrator == 0 {
+                let last = view.render_progressive(&scene).last().unwrap();
+                assert_eq!(last.dimensions(), (6, 4), "seed {}", seed);
+                let stops = [f32::NAN, f32::INFINITY, -1e30, 0.0];
+                assert_eq!(
+                    view.render_bracketed(&scene, &stops).len(),
+                    4,
+                    "seed {}",
+                    seed
+                );
+                let mut rng = Rng::new(seed, 2, 0, 0);
+                let sheet = view.render_sheet(&scene, vector(&mut rng), value(&mut rng));
+                assert_eq!(sheet.dimensions(), (12, 8), "seed {}", seed);
+                let layout = view.render_layout(&scene);
+                assert_eq!(layout.dimensions(), (6, 4), "seed {}", seed);
+                let ao = AmbientOcclusion::new(value(&mut rng), count(&mut rng, 4));
+                let outputs = view.render_outputs(&scene, &[&PathTracer, &ao, &Normals]);
+                assert_eq!(outputs.len(), 3, "seed {}", seed);
+                let trace = view.debug_pixel(&scene, count(&mut rng, 5), count(&mut rng, 3));
+                for (index, bounce) in trace.bounces.iter().enumerate() {
+                    assert!(bounce.parent.is_none_or(|p| p < index), "seed {}", seed);
+                }
+                assert!(!trace.to_string().is_empty(), "seed {}", seed);
+                let hdr = view.render_hdr(&scene);
+                let auto = AutoExposure::new().with_range(-1e30, f32::NAN);
+                assert!(auto.exposure_value(&hdr).is_finite(), "seed {}", seed);
+                for mode in [
+                    LightDebug::DominantLight,
+                    LightDebug::IsoIntensity { step: 0.0 },
+                ] {
+                    view.render_light_debug(&scene, mode);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn empty_scene_renders_background() {
+    let scene = Scene::default();
+    let background = Rgb([50, 100, 200]);
+    for integrator in 0..3 {
+        let view = View::new(8, 6, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 4, background, 1e-3);
+        let view = match integrator {
+            0 => view,
+            1 => view.with_integrator(PathTracer),
+            _ => view.with_integrator(Bidirectional),
+        };
+        let image = view.render(&scene);
+        assert!(
+            image.pixels().all(|&pixel| pixel == background),
+            "{} {:?}",
+            integrator,
+            image.get_pixel(0, 0)
+        );
+    }
+}
+
+#[test]
+fn unlit_scene_renders_black() {
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 2.0],
+        1.0,
+        0.0,
+    ));
+    let view = View::new(8, 6, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 4, Rgb([50; 3]), 1e-3);
+    let image = view.render(&scene);
+    assert!(image.pixels().all(|&pixel| pixel == Rgb([0; 3])));
+}
+
+#[test]
+fn nan_samples_render_black() {
+    let mut scene = Scene::default();
+    scene.add_object(Plane::new(
+        Rgb([255; 3]),
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 2.0],
+        1.0,
+        0.0,
+    ));
+    scene.add_light(Light::new([0.0, 0.0, 1.0], f32::NAN));
+    let view = View::new(8, 6, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 4, Rgb([50; 3]), 1e-3)
+        .with_integrator(PathTracer);
+    let image = view.render(&scene);
+    assert!(image.pixels().all(|&pixel| pixel == Rgb([0; 3])));
+    assert_eq!(scene.warnings.count(RenderWarning::NanSample), 8 * 6);
+}
+
+#[test]
+fn empty_image_renders() {
+    let view = View::new(0, 0, [0.0; 3], 90.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3);
+    assert_eq!(view.render(&random_scene(0)).dimensions(), (0, 0));
+}
@@ -0,0 +1,218 @@
+//! Scenes account for what they hold and place it as asked.
+
+use raytracer::{
+    atmosphere::HeightFog,
+    image::Rgb,
+    mesh::{Mesh, Triangle},
+    placement::{jitter, Region},
+    scene::*,
+    units::Unit,
+    view::{Ray, View},
+    Color, Vecf,
+};
+
+fn triangle(z: f32) -> Triangle {
+    Triangle {
+        vertices: [[0.0, 0.0, z], [1.0, 0.0, z], [0.0, 1.0, z]],
+        uvs: None,
+        colors: None,
+    }
+}
+
+#[test]
+fn summaries_count_what_scenes_hold() {
+    let mut scene = Scene::default();
+    let red = Rgb([255, 0, 0]);
+    scene.add_object(Sphere::new([0.0; 3], red, 1.0, 0.5, 0.5));
+    scene.add_object(Plane::new(red, [0.0, 1.0, 0.0], [0.0; 3], 0.5, 0.5));
+    scene.add_object(Sphere::new([3.0, 0.0, 0.0], red, 2.0, 0.5, 0.5));
+    scene.add_object(Mesh::new(vec![triangle(0.0), triangle(1.0)], red, 1.0, 0.0));
+    scene.add_light(Light::new([0.0; 3], 1.0));
+    scene.add_light(Light::new([0.0; 3], 1.0).with_radius(0.5, 4));
+    let summary = scene.summary();
+    assert_eq!(
+        summary.objects,
+        [
+            ("sphere".to_string(), 2),
+            ("plane".to_string(), 1),
+            ("mesh".to_string(), 1)
+        ]
+    );
+    assert_eq!(summary.triangles, 2);
+    assert_eq!((summary.lights, summary.area_lights), (2, 1));
+    assert_eq!((summary.media, summary.photons), (0, 0));
+    // The balls and the plane share a material; the mesh has its own.
+    assert_eq!(summary.materials.len(), 2);
+    assert_eq!(summary.materials[0].color, Some(red));
+    assert_eq!(
+        (summary.materials[1].lambert, summary.materials[1].specular),
+        (1.0, 0.0)
+    );
+    assert!(summary.memory > 0);
+    let text = summary.to_string();
+    assert!(
+        text.contains("objects    4 (sphere 2, plane 1, mesh 1)"),
+        "{}",
+        text
+    );
+    assert!(text.contains("lights     2 (1 area)"), "{}", text);
+
+    let memory = summary.memory;
+    scene.set_fog(HeightFog::new(Rgb([255; 3]), 0.1, 1.0));
+    scene.add_object(Mesh::new(vec![triangle(2.0); 100], red, 1.0, 0.0));
+    let summary = scene.summary();
+    assert_eq!((summary.media, summary.triangles), (1, 102));
+    assert!(summary.memory > memory);
+}
+
+#[test]
+fn scenes_in_centimeters_light_like_scenes_in_meters() {
+    // A wall two meters away lit from one meter, in fog.
+    let render = |unit: Unit| {
+        let scale = Unit::Meter.scale_to(unit);
+        let corner = |x: f32, y: f32| [x, y, 2.0];
+        let wall = Mesh::new(
+            vec![
+                Triangle {
+                    vertices: [corner(-5.0, -5.0), corner(5.0, -5.0), corner(-5.0, 5.0)],
+                    uvs: None,
+                    colors: None,
+                },
+                Triangle {
+                    vertices: [corner(5.0, -5.0), corner(5.0, 5.0), corner(-5.0, 5.0)],
+                    uvs: None,
+                    colors: None,
+                },
+            ],
+            Rgb([255; 3]),
+            1.0,
+            0.0,
+        )
+        .with_unit(Unit::Meter, unit);
+        let mut scene = Scene::default();
+        scene.set_unit(unit);
+        scene.add_object(wall);
+        scene.add_light(Light::new([0.0, 0.0, scale], 20.0));
+        scene.set_fog(HeightFog::new(Rgb([255; 3]), 0.2, 0.5));
+        View::new(8, 8, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3).render_hdr(&scene)
+    };
+    let meters = render(Unit::Meter);
+    let centimeters = render(Unit::Centimeter);
+    for y in 0..8 {
+        for x in 0..8 {
+            let (m, c) = (meters.get_pixel(x, y), centimeters.get_pixel(x, y));
+            assert!(m[0] > 0.0, "{:?}", m);
+            for (m, c) in m.iter().zip(c) {
+                assert!(
+                    (m - c).abs() < 1e-3 * m.abs().max(1.0),
+                    "{} {} {} {}",
+                    x,
+                    y,
+                    m,
+                    c
+                );
+            }
+        }
+    }
+    assert_eq!(Unit::Inch.scale_to(Unit::Centimeter), 2.54);
+}
+
+#[test]
+fn placement_spreads_objects_over_regions() {
+    // Four wide along x and two deep along z.
+    let region = Region::new([4.0, 0.0, 2.0], [4.0, 0.0, 0.0], [0.0; 3]);
+    assert_eq!(
+        region.grid(2, 2),
+        [
+            [1.0, 0.0, 0.5],
+            [3.0, 0.0, 0.5],
+            [1.0, 0.0, 1.5],
+            [3.0, 0.0, 1.5]
+        ]
+    );
+    let scattered = region.scatter(200, 7);
+    assert_eq!(scattered, region.scatter(200, 7));
+    assert_ne!(scattered, region.scatter(200, 8));
+    for point in &scattered {
+        assert!((0.0..=4.0).contains(&point[0]), "{:?}", point);
+        assert!((0.0..=2.0).contains(&point[2]), "{:?}", point);
+        assert_eq!(point[1], 0.0);
+    }
+    // Both halves get some.
+    assert!(scattered.iter().any(|point| point[0] < 2.0));
+    assert!(scattered.iter().any(|point| point[0] > 2.0));
+
+    let mut jittered = region.grid(10, 10);
+    jitter(&mut jittered, 0.1, 3);
+    for (moved, point) in jittered.iter().zip(region.grid(10, 10)) {
+        for (m, p) in moved.iter().zip(point) {
+            assert!((m - p).abs() <= 0.1, "{:?} {:?}", moved, point);
+        }
+    }
+    assert_ne!(jittered, region.grid(10, 10));
+
+    let mut scene = Scene::default();
+    scene.add_at(&region.grid(3, 4), |point, _| {
+        Sphere::new(point, Rgb([255; 3]), 0.1, 1.0, 0.0)
+    });
+    assert_eq!(scene.objects.len(), 12);
+    assert_eq!(scene.summary().objects, [("sphere".to_string(), 12)]);
+}
+
+/// A wall facing the camera at z = 2, implementing only what objects had
+/// to before hits carried texture inputs.
+#[derive(Clone)]
+struct OldWall(Color);
+
+impl Object for OldWall {
+    fn intersect(&self, ray: &Ray) -> (f32, Vecf) {
+        let distance = (2.0 - ray.origin[2]) / ray.direction[2];
+        let point = [0, 1, 2].map(|i| ray.origin[i] + distance * ray.direction[i]);
+        match distance > 0.0 {
+            true => (distance, point),
+            false => (f32::INFINITY, point),
+        }
+    }
+
+    fn get_position(&self) -> Vecf {
+        [0.0, 0.0, 2.0]
+    }
+
+    fn get_color(&self) -> Color {
+        self.0
+    }
+
+    fn normal_to(&self, _hit_ray: &Ray) -> Vecf {
+        [0.0, 0.0, -1.0]
+    }
+
+    fn get_lambert(&self) -> f32 {
+        1.0
+    }
+
+    fn get_specular(&self) -> f32 {
+        0.0
+    }
+
+    fn reflect_ray(&self, ray: &Ray, point: Vecf) -> Ray {
+        Ray::new(
+            point,
+            [ray.direction[0], ray.direction[1], -ray.direction[2]],
+        )
+    }
+}
+
+#[test]
+fn objects_written_before_hit_aware_colors_still_render() {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 0.0, 1.0], 20.0));
+    scene.add_object(OldWall(Rgb([200, 100, 0])));
+    let view = View::new(4, 4, [0.0; 3], 30.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3);
+    let pixel = view.render(&scene).get_pixel(2, 2).0;
+    assert!(pixel[0] > 0 && pixel[2] == 0, "{:?}", pixel);
+    assert!(
+        (pixel[0] as i32 - 2 * pixel[1] as i32).abs() <= 1,
+        "{:?}",
+        pixel
+    );
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "script")]
+
+use raytracer::script::{self, ScriptError};
+
+#[test]
+fn script_builds_scene() {
+    let scene = script::run(
+        r#"
+        let rng = rng(3);
+        for i in 0..10 {
+            add(sphere([i, rng.next(), 5], [255, 0, 0], 0.2, 0.9, 0.1).with_metal(0.5));
+        }
+        add(plane([200, 200, 200], [0, -1, 0], [0, -1, 0], 0.8, 0.0)
+            .with_emission([255, 255, 255], 0.5));
+        add(light([0, 2, 3], 30.0).with_radius(0.1, 4));
+        fog([200, 200, 200], 0.1, 0.5);
+        "#,
+    )
+    .unwrap();
+    assert_eq!(scene.objects.len(), 11);
+    assert_eq!(scene.lights.len(), 1);
+    assert!(scene.fog.is_some());
+
+    for bad in [
+        "add(sphere([0, 0], [255, 0, 0], 0.2, 0.9, 0.1));",
+        "add(light([0, \"up\", 0], 1.0));",
+        "add(",
+    ] {
+        assert!(matches!(script::run(bad), Err(ScriptError::Script(_))));
+    }
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "spectral")]
+
+use raytracer::{
+    image::Rgb,
+    integrator::PathTracer,
+    scene::*,
+    spectral::{self, WAVELENGTHS},
+    view::*,
+};
+
+#[test]
+fn spectral_dispersion_keeps_white_white() {
+    let steps = 340;
+    let mut mean = [0.0; 3];
+    for step in 0..steps {
+        let wavelength = WAVELENGTHS[0] + (step as f32 + 0.5) / steps as f32 * 340.0;
+        for (m, c) in mean.iter_mut().zip(spectral::rgb(wavelength)) {
+            *m += c / steps as f32;
+        }
+    }
+    for m in mean {
+        assert!((m - 1.0).abs() < 1e-3, "{:?}", mean);
+    }
+    let iors = [1.5, 1.51, 1.53];
+    assert!(spectral::ior_at(iors, 700.0) < spectral::ior_at(iors, 400.0));
+    assert!((spectral::ior_at([1.5; 3], 430.0) - 1.5).abs() < 1e-5);
+
+    // A white wall seen through a ball of glass that bends every
+    // wavelength alike looks, on average, as it does through one that does
+    // not split them at all: the spectral weights add up to white.
+    let scene = |ball: Sphere| {
+        let mut scene = Scene::default();
+        scene.add_light(Light::new([0.0, 0.0, -1.0], 400.0));
+        scene.add_object(Plane::new(
+            Rgb([255; 3]),
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 6.0],
+            1.0,
+            0.0,
+        ));
+        scene.add_object(ball);
+        scene
+    };
+    let ball = Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 0.0, 0.0).with_refraction(1.0, 1.5);
+    let view = View::new(
+        16,
+        12,
+        [0.0; 3],
+        60.0,
+        [0.0, 0.0, 1.0],
+        6,
+        Rgb([0; 3]),
+        1e-3,
+    )
+    .with_samples(32)
+    .with_spectral();
+    let (plain, dispersive) = (scene(ball.clone()), scene(ball.with_dispersion([1.5; 3])));
+    for view in [view.clone(), view.with_integrator(PathTracer)] {
+        let mean = |scene: &Scene| {
+            let image = view.render_hdr(scene);
+            let mut mean = [0.0; 3];
+            for y in 0..12 {
+                for x in 0..16 {
+                    for (m, c) in mean.iter_mut().zip(image.get_pixel(x, y)) {
+                        *m += c / 192.0;
+                    }
+                }
+            }
+            mean
+        };
+        let (expected, spectral) = (mean(&plain), mean(&dispersive));
+        for (e, s) in expected.iter().zip(spectral) {
+            assert!((e - s).abs() <= 0.02 * e, "{:?} {:?}", expected, spectral);
+        }
+    }
+}
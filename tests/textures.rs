@@ -0,0 +1,329 @@
+//! Textures follow the coordinates objects give their hits.
+
+use raytracer::{
+    bake::bake,
+    image::{GrayImage, Luma, Rgb, RgbImage},
+    noise,
+    scene::*,
+    texture::*,
+    view::{Ray, View},
+    Color, Vecf,
+};
+
+const RED: Color = Rgb([255, 0, 0]);
+const BLUE: Color = Rgb([0, 0, 255]);
+
+/// The hit on `object` of a ray from the origin through `point`.
+fn hit_towards(object: &dyn Object, point: Vecf) -> Hit {
+    let ray = Ray::new([0.0; 3], point);
+    let (distance, point) = object.intersect(&ray);
+    assert!(distance > 0.0 && distance.is_finite(), "{}", distance);
+    object.hit_at(&ray, point)
+}
+
+fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+    (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+}
+
+#[test]
+fn planes_map_checks_over_their_corners() {
+    let scene = Scene::default();
+    // A 2 by 2 square facing the camera, in checks of a quarter of it.
+    let square = Plane::from_points(
+        Rgb([0; 3]),
+        [1.0, 1.0, 2.0],
+        [1.0, -1.0, 2.0],
+        [-1.0, -1.0, 2.0],
+        1.0,
+        0.0,
+    )
+    .with_texture(Checker::new(RED, BLUE, 0.5));
+    for (point, uv, color) in [
+        ([-0.5, -0.5, 2.0], [0.25, 0.25], RED),
+        ([0.5, -0.5, 2.0], [0.75, 0.25], BLUE),
+        ([-0.5, 0.5, 2.0], [0.25, 0.75], BLUE),
+        ([0.5, 0.5, 2.0], [0.75, 0.75], RED),
+    ] {
+        let hit = hit_towards(&square, point);
+        assert!(close(hit.uv.unwrap(), uv), "{:?} {:?}", point, hit.uv);
+        assert_eq!(square.get_color_at(&hit, &scene), color, "{:?}", point);
+    }
+
+    // Tile sizes count world units from the plane's point.
+    let wall = Plane::new(Rgb([0; 3]), [0.0, 0.0, 1.0], [0.0, 0.0, 2.0], 1.0, 0.0)
+        .with_tile_size(4.0, 4.0)
+        .with_texture(Tiles::new(RED, BLUE, 0.1));
+    let origin = hit_towards(&wall, [0.0, 0.0, 2.0]);
+    assert!(close(origin.uv.unwrap(), [0.0, 0.0]), "{:?}", origin.uv);
+    assert_eq!(wall.get_color_at(&origin, &scene), BLUE);
+    let uv = hit_towards(&wall, [1.0, 1.0, 2.0]).uv.unwrap();
+    assert!(close(uv.map(f32::abs), [0.25, 0.25]), "{:?}", uv);
+    let inside = hit_towards(&wall, [2.0, 2.0, 2.0]);
+    assert_eq!(wall.get_color_at(&inside, &scene), RED);
+}
+
+#[test]
+fn spheres_map_longitude_and_latitude() {
+    let scene = Scene::default();
+    let center = [0.0, 0.0, 5.0];
+    let ball =
+        Sphere::new(center, Rgb([0; 3]), 1.0, 1.0, 0.0).with_texture(Checker::new(RED, BLUE, 0.5));
+    // The hit where the outward normal is `normal`, seen from outside.
+    let hit_facing = |normal: Vecf| {
+        let from = [0, 1, 2].map(|i| center[i] + 2.0 * normal[i]);
+        let ray = Ray::new(from, normal.map(|n| -n));
+        let (distance, point) = ball.intersect(&ray);
+        assert!((distance - 1.0).abs() < 1e-4, "{:?} {}", normal, distance);
+        ball.hit_at(&ray, point)
+    };
+    let up = (0.5f32).sqrt();
+    for (normal, uv) in [
+        ([0.0, 0.0, -1.0], [0.5, 0.5]),
+        ([1.0, 0.0, 0.0], [0.75, 0.5]),
+        ([-1.0, 0.0, 0.0], [0.25, 0.5]),
+        ([0.0, up, -up], [0.5, 0.75]),
+        ([0.0, -up, -up], [0.5, 0.25]),
+    ] {
+        let hit = hit_facing(normal);
+        assert!(close(hit.uv.unwrap(), uv), "{:?} {:?}", normal, hit.uv);
+    }
+    // Checks of a quarter of u and v alternate around the lower half, and
+    // v reaches 0 and 1 at the poles.
+    let below = |x: f32, z: f32| [x * up, -up, z * up];
+    assert_eq!(
+        ball.get_color_at(&hit_facing(below(-1.0, 0.0)), &scene),
+        RED
+    );
+    assert_eq!(
+        ball.get_color_at(&hit_facing(below(1.0, 0.0)), &scene),
+        BLUE
+    );
+    let top = hit_facing([0.0, 1.0, 0.0]).uv.unwrap();
+    assert!((top[1] - 1.0).abs() < 1e-4, "{:?}", top);
+    let bottom = hit_facing([0.0, -1.0, 0.0]).uv.unwrap();
+    assert!(bottom[1].abs() < 1e-4, "{:?}", bottom);
+}
+
+/// A hit at texture coordinates `uv`, as texture lookups see it.
+fn hit_at_uv(uv: Option<[f32; 2]>) -> Hit {
+    Hit {
+        point: [0.0; 3],
+        normal: [0.0, 0.0, -1.0],
+        local_point: [0.0; 3],
+        uv,
+        vertex_color: None,
+        tangent: None,
+    }
+}
+
+#[test]
+fn image_textures_read_the_nearest_pixel_or_blend_four() {
+    // Black on the left and white on the right, over a red bottom row.
+    let image = RgbImage::from_fn(2, 2, |x, y| match (x, y) {
+        (_, 1) => RED,
+        (0, _) => Rgb([0; 3]),
+        _ => Rgb([255; 3]),
+    });
+    let nearest = ImageTexture::new(image.clone());
+    let bilinear = ImageTexture::new(image).with_filter(Filter::Bilinear);
+    let at = |texture: &ImageTexture, u, v| texture.color_at(&hit_at_uv(Some([u, v])));
+    assert_eq!(at(&nearest, 0.3, 0.7), Rgb([0; 3]));
+    assert_eq!(at(&nearest, 0.7, 0.7), Rgb([255; 3]));
+    assert_eq!(at(&nearest, 0.7, 0.3), RED);
+    // Repeating outside [0, 1].
+    assert_eq!(at(&nearest, 1.7, -0.3), Rgb([255; 3]));
+    // No coordinates read the bottom-left pixel.
+    assert_eq!(nearest.color_at(&hit_at_uv(None)), RED);
+
+    // Pixel centers keep their colors; between them colors blend, across
+    // the wrapped edges too.
+    assert_eq!(at(&bilinear, 0.25, 0.75), Rgb([0; 3]));
+    assert_eq!(at(&bilinear, 0.75, 0.75), Rgb([255; 3]));
+    assert_eq!(at(&bilinear, 0.5, 0.75), Rgb([128; 3]));
+    assert_eq!(at(&bilinear, 0.0, 0.75), Rgb([128; 3]));
+    assert_eq!(at(&bilinear, 0.25, 0.5), Rgb([128, 0, 0]));
+}
+
+#[test]
+fn checks_and_tiles_nest_other_textures() {
+    const GREEN: Color = Rgb([0, 255, 0]);
+    let at = |texture: &dyn Texture, u, v| texture.color_at(&hit_at_uv(Some([u, v])));
+    // Fine red and blue checks in every other coarse square.
+    let checks = Checker::from_textures(Checker::new(RED, BLUE, 0.125), GREEN, 0.5);
+    assert_eq!(at(&checks, 0.1, 0.1), RED);
+    assert_eq!(at(&checks, 0.2, 0.1), BLUE);
+    assert_eq!(at(&checks, 0.6, 0.1), GREEN);
+    assert_eq!(at(&checks, 0.6, 0.6), RED);
+    // Tiles two units across of red and blue checks, in green grout.
+    let tiles = Tiles::from_textures(Checker::new(RED, BLUE, 1.0), GREEN, 0.1).with_size(2.0);
+    assert_eq!(at(&tiles, 0.5, 0.5), RED);
+    assert_eq!(at(&tiles, 1.5, 0.5), BLUE);
+    assert_eq!(at(&tiles, 2.05, 0.5), GREEN);
+}
+
+/// A hit at `local_point` relative to the object, as solid textures see it.
+fn hit_at_local(local_point: Vecf) -> Hit {
+    Hit {
+        local_point,
+        ..hit_at_uv(None)
+    }
+}
+
+#[test]
+fn noise_is_smooth_and_textures_built_on_it_stay_between_their_colors() {
+    assert_eq!(noise::perlin([3.0, -2.0, 7.0]), 0.0);
+    let points: Vec<Vecf> = (0..500)
+        .map(|i| {
+            let i = i as f32;
+            [i * 0.137, i * 0.071 - 9.0, i * 0.029 + 4.0]
+        })
+        .collect();
+    let mut spread = (f32::INFINITY, f32::NEG_INFINITY);
+    for &point in &points {
+        let value = noise::perlin(point);
+        assert!(value.abs() <= 1.0, "{:?} {}", point, value);
+        assert_eq!(noise::perlin(point), value);
+        let nudged = noise::perlin([point[0] + 1e-3, point[1], point[2]]);
+        assert!((nudged - value).abs() < 1e-2, "{:?}", point);
+        assert!(noise::fbm(point, 5).abs() <= 1.0);
+        assert!((0.0..=1.0).contains(&noise::turbulence(point, 5)));
+        spread = (spread.0.min(value), spread.1.max(value));
+    }
+    assert!(spread.0 < -0.3 && spread.1 > 0.3, "{:?}", spread);
+
+    // Without turbulence, marble is straight veins along x.
+    let marble = Marble::new(BLUE, RED, 2.0).with_turbulence(0.0);
+    assert_eq!(marble.color_at(&hit_at_local([1.0, 5.0, -3.0])), RED);
+    assert_eq!(marble.color_at(&hit_at_local([-1.0, 0.0, 8.0])), BLUE);
+    // Wood is light on its axis, where the rings start.
+    let wood = Wood::new(RED, BLUE, 0.5);
+    assert_eq!(wood.color_at(&hit_at_local([0.0; 3])), RED);
+    // Every noise texture mixes its two colors, differently from place to
+    // place.
+    let textures: [&dyn Texture; 3] = [
+        &Marble::new(BLUE, RED, 2.0),
+        &wood,
+        &Clouds::new(BLUE, RED, 1.0),
+    ];
+    for texture in textures {
+        let colors: Vec<Color> = points
+            .iter()
+            .map(|&point| texture.color_at(&hit_at_local(point)))
+            .collect();
+        for color in &colors {
+            assert_eq!(color.0[1], 0, "{:?}", color);
+            let sum = color.0[0] as u32 + color.0[2] as u32;
+            assert!((254..=256).contains(&sum), "{:?}", color);
+        }
+        assert!(colors.iter().any(|color| color.0[0] > 200));
+        assert!(colors.iter().any(|color| color.0[2] > 200));
+    }
+}
+
+/// A 2 by 2 square facing the camera two units away, u growing along +x
+/// and v along +y.
+fn square() -> Plane {
+    Plane::from_points(
+        Rgb([0; 3]),
+        [1.0, 1.0, 2.0],
+        [1.0, -1.0, 2.0],
+        [-1.0, -1.0, 2.0],
+        1.0,
+        0.0,
+    )
+}
+
+fn assert_near(a: Vecf, b: Vecf) {
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-2, "{:?} {:?}", a, b);
+    }
+}
+
+#[test]
+fn normal_maps_bend_shading_normals_along_texture_directions() {
+    // Tilted 45 degrees towards +u everywhere.
+    let tilted = || NormalMap::new(RgbImage::from_pixel(1, 1, Rgb([218, 128, 218])));
+    let flat = NormalMap::new(RgbImage::from_pixel(1, 1, Rgb([128, 128, 255])));
+    let half = (0.5f32).sqrt();
+    let normal_at = |plane: Plane| hit_towards(&plane, [0.3, -0.2, 2.0]).normal;
+    assert_near(normal_at(square()), [0.0, 0.0, -1.0]);
+    assert_near(normal_at(square().with_normal_map(flat)), [0.0, 0.0, -1.0]);
+    assert_near(
+        normal_at(square().with_normal_map(tilted())),
+        [half, 0.0, -half],
+    );
+    assert_near(
+        normal_at(square().with_normal_map(tilted().with_strength(0.0))),
+        [0.0, 0.0, -1.0],
+    );
+    // Along any other frame too.
+    let bent = tilted().perturb([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.5; 2]);
+    assert_near(bent, [half, half, 0.0]);
+
+    // Bent towards a light off to the side, the surface catches more of it.
+    let lit = |plane: Plane| {
+        let mut scene = Scene::default();
+        scene.add_light(Light::new([3.0, 0.0, 0.0], 50.0));
+        scene.add_object(plane);
+        let view = View::new(4, 4, [0.0; 3], 30.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3);
+        view.render_hdr(&scene).get_pixel(2, 2)[0]
+    };
+    let white = || square().with_texture(Rgb([255; 3]));
+    assert!(lit(white().with_normal_map(tilted())) > 1.5 * lit(white()));
+}
+
+#[test]
+fn bump_maps_tilt_normals_down_their_slopes() {
+    let half = (0.5f32).sqrt();
+    let frame =
+        |map: &NormalMap| map.perturb([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.5; 2]);
+    // Rising 16 levels a pixel, with depth making that one pixel up per
+    // pixel across, along u and then up the image, along v.
+    let depth = 255.0 / 16.0;
+    let along_u = GrayImage::from_fn(16, 16, |x, _| Luma([x as u8 * 16]));
+    assert_near(
+        frame(&NormalMap::from_heights(&along_u, depth)),
+        [-half, 0.0, half],
+    );
+    let along_v = GrayImage::from_fn(16, 16, |_, y| Luma([(15 - y) as u8 * 16]));
+    assert_near(
+        frame(&NormalMap::from_heights(&along_v, depth)),
+        [0.0, -half, half],
+    );
+    // Half as deep, half as steep.
+    let shallow = frame(&NormalMap::from_heights(&along_u, depth / 2.0));
+    assert_near(shallow, [-1.0 / 5f32.sqrt(), 0.0, 2.0 / 5f32.sqrt()]);
+    // Level ground stays flat.
+    let level = GrayImage::from_pixel(4, 4, Luma([90]));
+    assert_near(
+        frame(&NormalMap::from_heights(&level, depth)),
+        [0.0, 0.0, 1.0],
+    );
+}
+
+#[test]
+fn baked_textures_stand_in_for_the_procedural_ones_they_came_from() {
+    let scene = Scene::default();
+    let marble =
+        Sphere::new([0.0; 3], Rgb([0; 3]), 1.0, 1.0, 0.0).with_texture(Marble::new(RED, BLUE, 0.3));
+    let (width, height) = (32, 16);
+    let image = bake(&marble, &scene, width, height);
+    assert_eq!(image.dimensions(), (width, height));
+    let baked = Sphere::new([0.0; 3], Rgb([0; 3]), 1.0, 1.0, 0.0)
+        .with_texture(ImageTexture::new(image.clone()));
+    let mut colors = std::collections::HashSet::new();
+    for y in 0..height {
+        for x in 0..width {
+            let uv = [
+                (x as f32 + 0.5) / width as f32,
+                1.0 - (y as f32 + 0.5) / height as f32,
+            ];
+            let color = marble.get_color_at(&marble.hit_at_uv(uv).unwrap(), &scene);
+            assert_eq!(*image.get_pixel(x, y), color, "{:?}", uv);
+            let hit = baked.hit_at_uv(uv).unwrap();
+            assert_eq!(baked.get_color_at(&hit, &scene), color, "{:?}", uv);
+            colors.insert(color);
+        }
+    }
+    assert!(colors.len() > 10, "{}", colors.len());
+}
@@ -0,0 +1,170 @@
+//! Views show the scene from where and how they are set up to, and lay
+//! out what they show as documented.
+
+use raytracer::{
+    axes::Axes,
+    image::{Rgb, RgbImage},
+    scene::*,
+    view::*,
+};
+
+/// A ball of `radius` at `position` glowing white, so it shows without
+/// lights.
+fn glowing_ball(position: [f32; 3], radius: f32) -> Sphere {
+    Sphere::new(position, Rgb([0; 3]), radius, 1.0, 0.0).with_emission(Rgb([255; 3]), 1.0)
+}
+
+fn scene_of(object: impl Object + 'static) -> Scene {
+    let mut scene = Scene::default();
+    scene.add_object(object);
+    scene
+}
+
+fn view(width: u32, height: u32, position: [f32; 3], direction: [f32; 3]) -> View {
+    View::new(
+        width,
+        height,
+        position,
+        60.0,
+        direction,
+        4,
+        Rgb([0; 3]),
+        1e-3,
+    )
+}
+
+fn lit_pixels(image: &RgbImage) -> usize {
+    image.pixels().filter(|pixel| pixel.0[0] > 0).count()
+}
+
+#[test]
+fn orthographic_views_keep_sizes_at_any_distance() {
+    let forward = [0.0, 0.0, 1.0];
+    let ortho = view(16, 16, [0.0; 3], forward).with_orthographic(4.0);
+    let perspective = view(16, 16, [0.0; 3], forward);
+    let near = scene_of(glowing_ball([0.0, 0.0, 3.0], 1.0));
+    let far = scene_of(glowing_ball([0.0, 0.0, 30.0], 1.0));
+    // Two units of the four across are eight pixels wide.
+    let lit = lit_pixels(&ortho.render(&near));
+    assert!(
+        (lit as f32 - 16.0 * std::f32::consts::PI).abs() < 8.0,
+        "{}",
+        lit
+    );
+    assert_eq!(ortho.render(&far), ortho.render(&near));
+    assert!(lit_pixels(&perspective.render(&far)) < lit_pixels(&perspective.render(&near)));
+
+    // The sheet has the view itself top right, and the ball in the middle
+    // of the top, front and side views around it.
+    let ball = scene_of(glowing_ball([0.0; 3], 1.0));
+    let camera = view(16, 16, [0.0, 0.0, -5.0], forward);
+    let sheet = camera.render_sheet(&ball, [0.0; 3], 4.0);
+    assert_eq!(sheet.dimensions(), (32, 32));
+    let own = camera.render(&ball);
+    for (x, y, pixel) in own.enumerate_pixels() {
+        assert_eq!(sheet.get_pixel(16 + x, y), pixel);
+    }
+    for (x, y) in [(0, 0), (0, 16), (16, 16)] {
+        assert_eq!(sheet.get_pixel(x + 8, y + 8).0, [255; 3], "{} {}", x, y);
+        assert_eq!(sheet.get_pixel(x, y).0, [0; 3], "{} {}", x, y);
+    }
+}
+
+#[test]
+fn layouts_box_objects_where_they_render() {
+    let mut scene = scene_of(glowing_ball([0.0, 0.0, 5.0], 1.0));
+    scene.add_light(Light::new([2.0, 0.0, 5.0], 1.0));
+    let camera = view(32, 32, [0.0; 3], [0.0, 0.0, 1.0]);
+    let layout = camera.render_layout(&scene);
+    let traced = camera.render(&scene);
+    let white = Rgb([255; 3]);
+    let boxed: Vec<(u32, u32)> = layout
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0 != [0; 3] && **pixel != white)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert!(!boxed.is_empty());
+    let (left, right) = (
+        boxed.iter().map(|p| p.0).min().unwrap(),
+        boxed.iter().map(|p| p.0).max().unwrap(),
+    );
+    let (top, bottom) = (
+        boxed.iter().map(|p| p.1).min().unwrap(),
+        boxed.iter().map(|p| p.1).max().unwrap(),
+    );
+    for (x, y, pixel) in traced.enumerate_pixels() {
+        if pixel.0[0] > 0 {
+            assert!(
+                (left..=right).contains(&x) && (top..=bottom).contains(&y),
+                "{} {}",
+                x,
+                y
+            );
+        }
+    }
+    // The light is two fifths of the way to the edge of a 60 degree view,
+    // right of the ball.
+    let across = 16.0 + 16.0 * 0.4 / 30f32.to_radians().tan();
+    let crossed: Vec<(u32, u32)> = layout
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| **pixel == white)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert!(!crossed.is_empty());
+    let count = crossed.len() as f32;
+    let x = crossed.iter().map(|p| p.0 as f32).sum::<f32>() / count;
+    let y = crossed.iter().map(|p| p.1 as f32).sum::<f32>() / count;
+    assert!(
+        (x - across).abs() < 1.0 && (y - 16.0).abs() < 1.0,
+        "{} {}",
+        x,
+        y
+    );
+
+    // Unbounded planes are left out.
+    let floor = scene_of(Plane::new(
+        Rgb([255; 3]),
+        [0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        1.0,
+        0.0,
+    ));
+    assert!(camera
+        .render_layout(&floor)
+        .pixels()
+        .all(|pixel| pixel.0 == [0; 3]));
+}
+
+#[test]
+fn axis_conventions_show_converted_scenes_alike() {
+    // A big ball right of and a small one above where the camera looks.
+    let balls = |axes: Axes| {
+        let mut scene = scene_of(glowing_ball(
+            Axes::Y_UP_LEFT.convert([1.5, 0.0, 6.0], axes),
+            1.0,
+        ));
+        scene.add_object(glowing_ball(
+            Axes::Y_UP_LEFT.convert([0.0, 1.5, 6.0], axes),
+            0.5,
+        ));
+        scene
+    };
+    let seen = |axes: Axes| {
+        let forward = Axes::Y_UP_LEFT.convert([0.0, 0.0, 1.0], axes);
+        view(24, 24, [0.0; 3], forward)
+            .with_axes(axes)
+            .render(&balls(axes))
+    };
+    let reference = seen(Axes::Y_UP_LEFT);
+    assert_eq!(reference.get_pixel(20, 12).0, [255; 3]);
+    assert_eq!(reference.get_pixel(12, 6).0, [255; 3]);
+    for axes in [Axes::Y_UP_RIGHT, Axes::Z_UP_RIGHT, Axes::Z_UP_LEFT] {
+        assert_eq!(seen(axes), reference, "{:?}", axes);
+        assert_eq!(
+            axes.up_vector(),
+            Axes::Y_UP_LEFT.convert([0.0, 1.0, 0.0], axes)
+        );
+    }
+    assert_eq!(Axes::Z_UP_RIGHT.up_vector(), [0.0, 0.0, 1.0]);
+    assert_eq!(Axes::Z_UP_RIGHT.forward_vector(), [0.0, 1.0, 0.0]);
+}
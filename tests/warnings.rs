@@ -0,0 +1,92 @@
+//! Problems worked around while rendering are counted, and reported as
+//! they came up.
+
+use std::sync::Arc;
+
+use raytracer::{
+    image::Rgb,
+    integrator::Bidirectional,
+    material::{Material, Scatter},
+    report::RenderReport,
+    sampler::Rng,
+    scene::*,
+    texture::VertexColor,
+    view::*,
+    warning::RenderWarning,
+};
+
+/// A ball colored by vertex colors it does not have, under a light.
+fn uncolored_ball() -> Scene {
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 2.0, 0.0], 20.0));
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0)
+            .with_texture(VertexColor::new(Rgb([0, 255, 0]))),
+    );
+    scene
+}
+
+fn view() -> View {
+    View::new(8, 8, [0.0; 3], 60.0, [0.0, 0.0, 1.0], 4, Rgb([0; 3]), 1e-3)
+}
+
+/// A custom material passing rays straight through.
+struct Clear;
+
+impl Material for Clear {
+    fn get_lambert(&self) -> f32 {
+        1.0
+    }
+
+    fn scatter(&self, ray: &Ray, hit: &Hit, _rng: &mut Rng) -> Scatter {
+        Scatter::Continue(Ray::new(hit.point, ray.direction), [1.0; 3])
+    }
+}
+
+#[test]
+fn warnings_count_each_workaround_until_reset() {
+    let scene = uncolored_ball();
+    assert!(scene.warnings.is_empty());
+    let image = view().render(&scene);
+    // Every pixel showing the ball fell back to the fallback color.
+    let hits = view()
+        .render_depth(&scene)
+        .iter()
+        .filter(|d| d.is_finite())
+        .count() as u64;
+    assert!(hits > 0);
+    assert_eq!(scene.warnings.count(RenderWarning::TextureFallback), hits);
+    assert!(image
+        .pixels()
+        .any(|pixel| pixel.0[1] > 0 && pixel.0[0] == 0 && pixel.0[2] == 0));
+    assert_eq!(
+        scene.warnings.iter().collect::<Vec<_>>(),
+        [(RenderWarning::TextureFallback, hits)]
+    );
+    view().render(&scene);
+    assert_eq!(
+        scene.warnings.count(RenderWarning::TextureFallback),
+        2 * hits
+    );
+    // Reports spell out what happened.
+    let report = RenderReport::new(&view(), &scene);
+    let expected = format!("{} {}", 2 * hits, RenderWarning::TextureFallback);
+    assert!(
+        report.warnings().contains(&expected),
+        "{:?}",
+        report.warnings()
+    );
+    scene.warnings.reset();
+    assert!(scene.warnings.is_empty());
+    assert_eq!(scene.warnings.iter().count(), 0);
+
+    // Integrators that cannot follow a material say so.
+    let mut scene = Scene::default();
+    scene.add_light(Light::new([0.0, 2.0, 0.0], 20.0));
+    scene.add_object(
+        Sphere::new([0.0, 0.0, 3.0], Rgb([255; 3]), 1.0, 1.0, 0.0).with_material(Arc::new(Clear)),
+    );
+    view().with_integrator(Bidirectional).render(&scene);
+    assert!(scene.warnings.count(RenderWarning::UnsupportedMaterial) > 0);
+    assert_eq!(scene.warnings.count(RenderWarning::NanSample), 0);
+}